@@ -1,9 +1,40 @@
+use std::process::Command;
+
 fn main() {
     // Tell cargo to invalidate the built crate whenever the wrapper changes
     println!("cargo:rerun-if-changed=src/lib.rs");
     println!("cargo:rerun-if-changed=src/cxx.rs");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+
+    // Build metadata for `get_library_info`, empty when it can't be determined
+    let git_describe = Command::new("git")
+        .args(["describe", "--tags", "--always", "--dirty"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .unwrap_or_default();
+    println!(
+        "cargo:rustc-env=BARK_CPP_GIT_DESCRIBE={}",
+        git_describe.trim()
+    );
+    println!(
+        "cargo:rustc-env=BARK_CPP_BARK_VERSION={}",
+        bark_dependency_version().unwrap_or_default()
+    );
 
     cxx_build::bridge("src/cxx.rs")
         .flag_if_supported("-std=c++17")
         .compile("arkcxxbridge");
 }
+
+/// Reads the tag of the bark-wallet git dependency from Cargo.toml
+fn bark_dependency_version() -> Option<String> {
+    let manifest = std::fs::read_to_string("Cargo.toml").ok()?;
+    let line = manifest
+        .lines()
+        .find(|line| line.trim_start().starts_with("bark-wallet"))?;
+    let tag = line.split("tag = \"").nth(1)?.split('"').next()?;
+    Some(tag.trim_start_matches("bark-").to_string())
+}
@@ -1,11 +1,10 @@
 use crate::cxx::ffi::{ArkoorPaymentResult, BarkMovement, BarkVtxo, OnchainPaymentResult};
-use crate::{TOKIO_RUNTIME, utils};
+use crate::{TOKIO_RUNTIME, units, utils};
 use anyhow::{Context, Ok, bail};
 use bark::ark::bitcoin::hex::DisplayHex;
-use bark::ark::bitcoin::{Address, address};
 use bark::ark::lightning::{self, PaymentHash};
+use bark::vtxo::{VtxoState, VtxoStateKind};
 use bdk_wallet::bitcoin::{self, FeeRate, network};
-use bip39::Mnemonic;
 use hex::ToHex;
 use logger::log::{self, info};
 
@@ -17,6 +16,7 @@ pub(crate) mod ffi {
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct BarkVtxo {
+        /// In sats
         amount: u64,
         expiry_height: u32,
         server_pubkey: String,
@@ -24,11 +24,47 @@ pub(crate) mod ffi {
         anchor_point: String,
         point: String,
         state: String,
+        /// Received out of round and not refreshed since
+        needs_refresh_for_trust: bool,
     }
 
-    pub struct BoardResult {
-        vtxos: Vec<String>,
-        funding_txid: String,
+    pub struct CxxBoardResult {
+        txid: String,
+        amount_sat: u64,
+        fee_sat: u64,
+        vtxo_ids: Vec<String>,
+    }
+
+    pub struct CxxExpectedPayment {
+        id: i64,
+        pubkey_index: u32,
+        user_pubkey: String,
+        /// 0 if any amount is accepted
+        amount_sat: u64,
+        memo: String,
+        /// open, fulfilled, expired or cancelled
+        status: String,
+        /// The VTXO that paid it, empty if none did
+        vtxo_id: String,
+        created_at: i64,
+        expires_at: i64,
+    }
+
+    pub struct CxxDatadirMoveReport {
+        files_copied: u32,
+        bytes_copied: u64,
+        wallet_migration_version: u32,
+        store_schema_version: u32,
+        tables_verified: u32,
+        rows_verified: u64,
+    }
+
+    pub struct CxxBoardRequirements {
+        min_board_sat: u64,
+        /// 0 if the server sets no maximum
+        max_board_sat: u64,
+        estimated_onchain_fee_sat: u64,
+        estimated_round_fee_sat: u64,
     }
 
     pub struct NewAddressResult {
@@ -43,9 +79,26 @@ pub(crate) mod ffi {
         payment_hash: String,
     }
 
+    pub struct CxxReceiveBundle {
+        pub ark_address: String,
+        pub onchain_address: String,
+        pub bolt11: String,
+        pub unified_uri: String,
+    }
+
+    pub struct CxxPendingPayment {
+        pub payment_hash: String,
+        pub bolt11: String,
+        pub amount_msat: u64,
+        pub destination: String,
+        pub started_at: i64,
+        pub status: String,
+    }
+
     pub struct LightningSend {
         pub invoice: String,
         pub payment_hash: String,
+        /// In sats
         pub amount: u64,
         pub htlc_vtxos: Vec<BarkVtxo>,
         pub movement_id: u32,
@@ -55,7 +108,57 @@ pub(crate) mod ffi {
     pub struct ArkoorPaymentResult {
         amount_sat: u64,
         destination_pubkey: String,
+        /// All outputs of the payment, sent and change
         vtxos: Vec<BarkVtxo>,
+        sent_vtxos: Vec<BarkVtxo>,
+        change_vtxos: Vec<BarkVtxo>,
+        change_amount_sat: u64,
+    }
+
+    pub struct ArkoorPaymentFromResult {
+        pub payment: ArkoorPaymentResult,
+        /// Selected VTXOs the payment spent
+        pub consumed_vtxo_ids: Vec<String>,
+        /// Empty if the payment left no change
+        pub change_vtxo_id: String,
+    }
+
+    pub struct CxxOnchainDescriptors {
+        pub external: String,
+        pub internal: String,
+        pub birthday_height: u32,
+        pub import_descriptors_json: String,
+    }
+
+    pub struct CxxRescanSummary {
+        pub from_height: u32,
+        pub to_height: u32,
+        pub blocks_scanned: u32,
+        pub transactions_found: u64,
+        pub balance_delta_sat: i64,
+        pub birthday_height: u32,
+        pub has_birthday: bool,
+    }
+
+    pub struct CxxAtRiskReceive {
+        pub payment_hash: String,
+        pub amount_msat: u64,
+        pub blocks_left: i64,
+        pub failed_attempts: u32,
+        pub last_error: String,
+    }
+
+    pub struct CxxSelfTransfer {
+        pub txid: String,
+        /// The wallet's own address the funds moved to
+        pub address: String,
+        pub amount_sat: u64,
+        pub fee_sat: u64,
+    }
+
+    pub struct CxxFeeBumpResult {
+        txid: String,
+        additional_fee_sat: u64,
     }
 
     pub struct OnchainPaymentResult {
@@ -64,6 +167,7 @@ pub(crate) mod ffi {
         destination_address: String,
     }
 
+    #[derive(Default)]
     pub struct CxxArkInfo {
         network: String,
         server_pubkey: String,
@@ -88,6 +192,27 @@ pub(crate) mod ffi {
         htlc_recv_claim_delta: u16,
         vtxo_exit_margin: u16,
         round_tx_required_confirmations: u32,
+        /// BIP32 purpose index of the Ark keys, 0 for the default of 350.
+        /// Other values are refused when creating a wallet, as bark always
+        /// derives the wallet's keys under 350.
+        ark_purpose_index: u32,
+    }
+
+    /// The effective wallet config. Unset optional values are empty strings
+    /// or zero, and the bitcoind password is never returned.
+    pub struct CxxConfig {
+        server_address: String,
+        esplora_address: String,
+        bitcoind_address: String,
+        bitcoind_cookiefile: String,
+        bitcoind_user: String,
+        has_password: bool,
+        vtxo_refresh_expiry_threshold: u32,
+        /// In sat/kvB, the same unit `ConfigOpts` takes
+        fallback_fee_rate: u64,
+        htlc_recv_claim_delta: u16,
+        vtxo_exit_margin: u16,
+        round_tx_required_confirmations: u32,
     }
 
     pub struct CreateOpts {
@@ -121,6 +246,19 @@ pub(crate) mod ffi {
         pub finished_at: *const u64,
     }
 
+    pub struct CxxLightningReceive {
+        pub payment_hash: String,
+        pub bolt11: String,
+        /// 0 for invoices without an amount
+        pub amount_msat: u64,
+        pub description: String,
+        /// "Pending", "Received", "Claimed" or "Expired"
+        pub status: String,
+        /// RFC 3339 creation time of the invoice
+        pub created_at: String,
+    }
+
+    #[derive(Default)]
     pub struct OffchainBalance {
         /// Coins that are spendable in the Ark, either in-round or out-of-round.
         pub spendable: u64,
@@ -132,6 +270,8 @@ pub(crate) mod ffi {
         pub pending_exit: u64,
         /// Coins that are pending sufficient confirmations from board transactions.
         pub pending_board: u64,
+        /// Coins received out of round that need a refresh to be trusted.
+        pub pending_trustless: u64,
     }
 
     pub struct OnChainBalance {
@@ -145,6 +285,29 @@ pub(crate) mod ffi {
         pub confirmed: u64,
     }
 
+    pub struct CxxOnchainBalance {
+        /// Confirmed and immediately spendable balance
+        pub confirmed_sat: u64,
+        /// Unconfirmed UTXOs generated by a wallet tx
+        pub trusted_pending_sat: u64,
+        /// Unconfirmed UTXOs received from an external wallet
+        pub untrusted_pending_sat: u64,
+        /// All coinbase outputs not yet matured
+        pub immature_sat: u64,
+        /// Sum of all of the above
+        pub total_sat: u64,
+    }
+
+    pub struct CxxUtxo {
+        pub txid: String,
+        pub vout: u32,
+        pub amount_sat: u64,
+        /// Hex encoded
+        pub script_pubkey: String,
+        /// 0 while unconfirmed
+        pub confirmations: u32,
+    }
+
     pub struct KeyPairResult {
         pub public_key: String,
         pub secret_key: String,
@@ -156,9 +319,86 @@ pub(crate) mod ffi {
         pub amount_sat: u64,
     }
 
+    pub enum MnemonicLanguage {
+        English,
+        SimplifiedChinese,
+        TraditionalChinese,
+        Czech,
+        French,
+        Italian,
+        Japanese,
+        Korean,
+        Portuguese,
+        Spanish,
+    }
+
+    pub enum CxxWalletState {
+        NotLoaded = 0,
+        Loaded = 1,
+        Loading = 2,
+        Error = 3,
+    }
+
+    pub enum CxxVtxoSortKey {
+        Expiry,
+        Amount,
+    }
+
+    /// Zero amounts, heights and limits mean no bound
+    pub struct VtxoFilter {
+        /// `Spendable`, `Locked` or `Spent`; all states when empty
+        pub states: Vec<String>,
+        pub min_amount_sat: u64,
+        pub max_amount_sat: u64,
+        pub max_expiry_height: u32,
+        pub sort_key: CxxVtxoSortKey,
+        pub descending: bool,
+        pub limit: u32,
+        pub offset: u32,
+    }
+
+    pub struct BarkVtxoPage {
+        pub vtxos: Vec<BarkVtxo>,
+        /// Number of matching VTXOs across all pages
+        pub total_count: u64,
+    }
+
+    pub enum CxxMovementKind {
+        Board,
+        Send,
+        Receive,
+        Offboard,
+        Exit,
+        Refresh,
+        Unknown,
+    }
+
+    pub struct CxxMovementKindStats {
+        pub kind: CxxMovementKind,
+        pub count: u64,
+        pub received_sat: u64,
+        pub sent_sat: u64,
+    }
+
+    pub struct CxxWalletStats {
+        pub movement_count: u64,
+        pub total_received_sat: u64,
+        pub total_sent_sat: u64,
+        pub per_kind: Vec<CxxMovementKindStats>,
+        /// Unix timestamp of the first movement, 0 if there is none
+        pub first_movement_at: i64,
+    }
+
+    pub struct CxxMovementPage {
+        pub movements: Vec<BarkMovement>,
+        /// Pass to the next call, empty once there are no more movements
+        pub next_cursor: String,
+    }
+
     pub struct BarkMovement {
         pub id: u32,
         pub status: String,
+        pub kind: CxxMovementKind,
         pub subsystem_name: String,
         pub subsystem_kind: String,
         pub metadata_json: String,
@@ -175,6 +415,18 @@ pub(crate) mod ffi {
         pub completed_at: String,
     }
 
+    pub struct CxxNextRoundEta {
+        /// Seconds until the next round, only valid when `eta_known`
+        pub eta_secs: u64,
+        pub eta_known: bool,
+        /// Funding txid of the last round we took part in, empty if none
+        pub last_round_txid: String,
+        pub last_round_at: i64,
+        /// How long we waited for the last round we took part in
+        pub last_round_wait_secs: u64,
+        pub round_interval_secs: u64,
+    }
+
     pub struct RoundStatus {
         pub status: String,
         pub funding_txid: String,
@@ -184,17 +436,241 @@ pub(crate) mod ffi {
         pub is_success: bool,
     }
 
+    pub struct BarkRecipient {
+        /// Address book name, empty when the destination is unnamed
+        pub name: String,
+        pub destination: String,
+        pub destination_type: String,
+        /// Unix timestamp of the last payment, 0 when never used
+        pub last_used_at: u64,
+        pub times_used: u32,
+    }
+
+    pub struct BarkBoardIntent {
+        pub id: i64,
+        /// 0 when the intent boards all onchain funds
+        pub amount_sat: u64,
+        pub funding_txid: String,
+        pub state: String,
+        pub error: String,
+        pub created_at: i64,
+        pub updated_at: i64,
+    }
+
+    pub struct BarkPendingBoard {
+        pub funding_txid: String,
+        pub vout: u32,
+        pub amount_sat: u64,
+        pub vtxo_ids: Vec<String>,
+        pub confirmations: u32,
+        pub required_confirmations: u32,
+        pub state: String,
+    }
+
+    pub struct BoardResumeReport {
+        pub resumed: Vec<BarkBoardIntent>,
+        pub abandoned: Vec<BarkBoardIntent>,
+        pub pending: Vec<BarkBoardIntent>,
+    }
+
+    pub struct BarkAutoRefreshPolicy {
+        pub enabled: bool,
+        pub threshold_blocks: u32,
+        /// Local hour (0-23) at which the quiet period starts
+        pub quiet_hours_start: u8,
+        /// Local hour (0-23) at which the quiet period ends, exclusive
+        pub quiet_hours_end: u8,
+        pub timezone_offset_minutes: i32,
+    }
+
+    /// Result of `get_wallet_snapshot`. Each field has a matching `_error`
+    /// which is non-empty when that component failed to load.
+    pub struct WalletSnapshot {
+        /// The fields that were requested, see the `SNAPSHOT_*` flags
+        pub fields_mask: u32,
+        pub balance: OffchainBalance,
+        pub balance_error: String,
+        pub ark_info: CxxArkInfo,
+        pub ark_info_error: String,
+        pub vtxos: Vec<BarkVtxo>,
+        pub vtxos_error: String,
+        pub movements: Vec<BarkMovement>,
+        pub movements_error: String,
+        /// JSON encoded, in the same format as `onchain_utxos`
+        pub onchain_utxos: String,
+        pub onchain_utxos_error: String,
+        pub expiring_vtxos: Vec<BarkVtxo>,
+        pub expiring_vtxos_error: String,
+        pub lightning_receives: Vec<LightningReceive>,
+        pub lightning_receives_error: String,
+    }
+
+    pub struct BarkQuarantinedVtxo {
+        pub vtxo_id: String,
+        pub amount_sat: u64,
+        pub reason: String,
+        pub quarantined_at: i64,
+        /// What to do with the funds, currently always `start_exit`
+        pub recommended_action: String,
+    }
+
+    pub struct OnchainOutputResult {
+        pub destination: String,
+        pub amount_sat: u64,
+        /// Empty if the output was not sent
+        pub txid: String,
+        /// Empty if the output was sent
+        pub error: String,
+    }
+
+    pub struct BarkVtxoExitCost {
+        pub vtxo_id: String,
+        pub fee_sat: u64,
+        pub tx_count: u32,
+    }
+
+    pub struct BarkExitCostEstimate {
+        pub per_vtxo: Vec<BarkVtxoExitCost>,
+        pub total_fee_sat: u64,
+        pub tx_count: u32,
+        /// Blocks to wait after the exit confirms before the funds can be claimed
+        pub claimable_after_blocks: u16,
+    }
+
+    pub struct CxxResolvedInvoice {
+        pub bolt11: String,
+        pub description: String,
+        pub amount_sat: u64,
+        pub expires_at: u64,
+    }
+
+    pub struct CxxExitStatus {
+        pub vtxo_id: String,
+        pub amount_sat: u64,
+        pub state: String,
+        pub claimable: bool,
+    }
+
+    pub struct BarkMetricEntry {
+        pub operation: String,
+        pub duration_ms: u64,
+        pub success: bool,
+        /// Empty on success
+        pub error_class: String,
+        pub recorded_at: i64,
+    }
+
+    pub struct BarkOperationStats {
+        pub operation: String,
+        pub count: u64,
+        pub failure_count: u64,
+        pub p50_ms: u64,
+        pub p95_ms: u64,
+        pub max_ms: u64,
+    }
+
+    pub struct BarkMetricsSnapshot {
+        pub entries: Vec<BarkMetricEntry>,
+        pub operations: Vec<BarkOperationStats>,
+    }
+
+    pub struct BarkLockRecord {
+        pub operation: String,
+        pub waited_ms: u64,
+        pub held_ms: u64,
+        pub acquired_at: i64,
+    }
+
+    pub struct BarkLockDiagnostics {
+        /// Oldest first
+        pub records: Vec<BarkLockRecord>,
+        /// Operation holding the wallet right now, empty if none
+        pub current_holder: String,
+        pub current_holder_since: i64,
+    }
+
+    pub struct ConfigApplyResult {
+        /// Changed fields that took effect right away
+        pub applied: Vec<String>,
+        /// Changed fields that take effect once the wallet is reloaded
+        pub requires_reload: Vec<String>,
+    }
+
+    pub struct LibraryInfo {
+        pub version: String,
+        pub git_describe: String,
+        pub bark_version: String,
+        pub persister_backend: String,
+        pub features: Vec<String>,
+        /// 0 when no wallet is loaded
+        pub store_schema_version: u32,
+    }
+
+    pub struct BarkLightningFeeCap {
+        /// Share of the payment amount in basis points, 100 is 1%
+        pub cap_bps: u32,
+        pub floor_sat: u64,
+    }
+
+    pub struct BarkEvent {
+        pub kind: String,
+        pub payload_json: String,
+        pub created_at: i64,
+    }
+
     extern "Rust" {
-        fn init_logger();
+        fn init_logger() -> Result<()>;
+        fn init_logger_with_file(path: &str, level: &str) -> Result<()>;
+        fn init_panic_handler() -> Result<()>;
+        fn get_last_panic_report() -> Result<String>;
         fn create_mnemonic() -> Result<String>;
-        fn is_wallet_loaded() -> bool;
+        fn create_mnemonic_with_options(
+            word_count: u32,
+            language: MnemonicLanguage,
+        ) -> Result<String>;
+        fn validate_mnemonic(mnemonic: &str) -> Result<MnemonicLanguage>;
+        fn verify_derivation_integrity() -> Result<bool>;
+        fn is_wallet_loaded() -> Result<bool>;
+        fn get_wallet_state() -> Result<CxxWalletState>;
+        fn get_wallet_id() -> Result<String>;
+        fn get_wallet_created_at() -> Result<String>;
+        fn backup_database(backup_path: &str) -> Result<String>;
+        fn get_database_size_bytes() -> Result<u64>;
+        fn get_database_table_stats() -> Result<String>;
+        fn set_offline_mode(offline: bool) -> Result<()>;
+        fn is_offline_mode() -> Result<bool>;
+        fn get_library_info() -> Result<LibraryInfo>;
+        fn get_required_app_version_hint(datadir: &str) -> Result<String>;
         fn close_wallet() -> Result<()>;
+        fn reset_wallet(datadir: &str, confirm: bool) -> Result<()>;
+        fn move_wallet_datadir(old_path: &str, new_path: &str) -> Result<CxxDatadirMoveReport>;
         fn get_ark_info() -> Result<CxxArkInfo>;
+        fn get_config() -> Result<CxxConfig>;
+        fn apply_config(opts: ConfigOpts) -> Result<ConfigApplyResult>;
+        fn get_default_config(network: &str) -> Result<CxxConfig>;
         fn offchain_balance() -> Result<OffchainBalance>;
+        fn get_balance(no_sync: bool) -> Result<OffchainBalance>;
+        fn offchain_balance_sat() -> Result<u64>;
+        fn onchain_balance_sat() -> Result<u64>;
+        fn pending_exit_balance_sat() -> Result<u64>;
+        unsafe fn register_expected_payment(
+            pubkey_index: u32,
+            amount_sat: *const u64,
+            memo: &str,
+            expiry_secs: u32,
+        ) -> Result<CxxExpectedPayment>;
+        fn list_expected_payments(status: &str) -> Result<Vec<CxxExpectedPayment>>;
+        fn cancel_expected_payment(id: i64) -> Result<bool>;
+        fn has_sufficient_offchain_balance(amount_sat: u64) -> Result<bool>;
         fn derive_store_next_keypair() -> Result<KeyPairResult>;
         fn peak_keypair(index: u32) -> Result<KeyPairResult>;
         fn new_address() -> Result<NewAddressResult>;
         fn peak_address(index: u32) -> Result<NewAddressResult>;
+        fn get_ark_receive_address_at_index(index: u32) -> Result<String>;
+        fn prefetch_ark_address() -> Result<()>;
+        fn get_pending_ark_address() -> Result<String>;
+        fn consume_pending_ark_address() -> Result<()>;
+        fn get_new_ark_receive_address() -> Result<String>;
         fn sign_message(message: &str, index: u32) -> Result<String>;
         fn sign_messsage_with_mnemonic(
             message: &str,
@@ -208,40 +684,130 @@ pub(crate) mod ffi {
             index: u32,
         ) -> Result<KeyPairResult>;
         fn verify_message(message: &str, signature: &str, public_key: &str) -> Result<bool>;
+        fn sign_data_schnorr(data_hex: &str, index: u32) -> Result<String>;
+        fn verify_data_schnorr(
+            data_hex: &str,
+            signature_hex: &str,
+            pubkey_hex: &str,
+        ) -> Result<bool>;
         fn history() -> Result<Vec<BarkMovement>>;
+        fn get_movement_count() -> Result<u64>;
+        fn get_movements_after(cursor: &str, limit: u32) -> Result<CxxMovementPage>;
+        fn export_movements_csv(dest_path: &str, from_ts: i64, to_ts: i64) -> Result<u64>;
+        fn get_wallet_stats() -> Result<CxxWalletStats>;
+        fn get_balance_history(days: u32) -> Result<String>;
+        fn get_total_received_sat() -> Result<u64>;
+        fn get_total_sent_sat() -> Result<u64>;
+        fn get_total_received_in_range(from_ts: i64, to_ts: i64) -> Result<u64>;
+        fn get_total_sent_in_range(from_ts: i64, to_ts: i64) -> Result<u64>;
+        fn get_wallet_snapshot(fields_mask: u32) -> Result<WalletSnapshot>;
+        fn reconcile_vtxos_with_server(
+            server_vtxo_ids: Vec<String>,
+        ) -> Result<Vec<BarkQuarantinedVtxo>>;
+        fn get_quarantined_vtxos() -> Result<Vec<BarkQuarantinedVtxo>>;
+        fn release_quarantined_vtxo(vtxo_id: &str) -> Result<bool>;
+        fn vtxo_id_to_string(vtxo_id: &str) -> Result<String>;
+        fn is_valid_vtxo_id(vtxo_id: &str) -> Result<bool>;
+        fn estimate_exit_cost(vtxo_ids: Vec<String>) -> Result<BarkExitCostEstimate>;
         fn vtxos() -> Result<Vec<BarkVtxo>>;
+        fn get_vtxos_filtered(filter: VtxoFilter) -> Result<BarkVtxoPage>;
+        fn get_vtxo_history(vtxo_id: &str) -> Result<String>;
+        fn get_spendable_vtxo_count() -> Result<u64>;
+        fn get_spent_vtxo_count() -> Result<u64>;
+        fn get_locked_vtxo_count() -> Result<u64>;
+        fn force_vtxo_state(vtxo_id: &str, state: &str) -> Result<()>;
+        fn get_total_vtxo_count() -> Result<u64>;
         fn get_expiring_vtxos(threshold: u32) -> Result<Vec<BarkVtxo>>;
         fn get_first_expiring_vtxo_blockheight() -> Result<*const u32>;
         fn get_next_required_refresh_blockheight() -> Result<*const u32>;
-        fn bolt11_invoice(amount_msat: u64) -> Result<Bolt11Invoice>;
+        fn bolt11_invoice(amount_sat: u64) -> Result<Bolt11Invoice>;
         fn lightning_receive_status(payment_hash: String) -> Result<*const LightningReceive>;
         fn check_lightning_payment(payment_hash: String, wait: bool) -> Result<String>;
+        fn cancel_expired_htlcs() -> Result<u32>;
+        fn prune_expired_receives() -> Result<Vec<String>>;
+        fn recreate_invoice(payment_hash: &str) -> Result<Bolt11Invoice>;
         fn sync_pending_boards() -> Result<()>;
         fn maintenance() -> Result<()>;
         fn maintenance_delegated() -> Result<()>;
         fn maintenance_with_onchain() -> Result<()>;
         fn maintenance_with_onchain_delegated() -> Result<()>;
         fn maintenance_refresh() -> Result<()>;
+        fn set_auto_refresh_policy(
+            enabled: bool,
+            threshold_blocks: u32,
+            quiet_hours_start: u8,
+            quiet_hours_end: u8,
+            timezone_offset_minutes: i32,
+        ) -> Result<()>;
+        fn get_auto_refresh_policy() -> Result<BarkAutoRefreshPolicy>;
+        fn set_lightning_fee_cap(cap_bps: u32, floor_sat: u64) -> Result<()>;
+        fn get_lightning_fee_cap() -> Result<BarkLightningFeeCap>;
+        fn set_allow_spending_unrefreshed_arkoor(allow: bool) -> Result<()>;
+        fn allow_spending_unrefreshed_arkoor() -> Result<bool>;
+        fn poll_events() -> Result<Vec<BarkEvent>>;
+        fn get_metrics_snapshot() -> Result<BarkMetricsSnapshot>;
+        fn export_event_journal(dest_path: &str, from_ts: i64, to_ts: i64) -> Result<u64>;
+        fn reset_metrics() -> Result<()>;
+        fn get_lock_diagnostics() -> Result<BarkLockDiagnostics>;
+        fn get_next_round_eta() -> Result<CxxNextRoundEta>;
         fn refresh_server() -> Result<()>;
         fn sync() -> Result<()>;
+        fn get_last_successful_sync_timestamp() -> Result<i64>;
         fn create_wallet(datadir: &str, opts: CreateOpts) -> Result<()>;
         fn load_wallet(datadir: &str, config: CreateOpts) -> Result<()>;
-        fn board_amount(amount_sat: u64) -> Result<BoardResult>;
-        fn board_all() -> Result<BoardResult>;
+        fn load_wallet_steal_lock(datadir: &str, config: CreateOpts) -> Result<()>;
+        fn get_board_requirements() -> Result<CxxBoardRequirements>;
+        fn board_amount(amount_sat: u64) -> Result<CxxBoardResult>;
+        fn board_all() -> Result<CxxBoardResult>;
+        fn resume_pending_boards() -> Result<BoardResumeReport>;
+        fn list_pending_boards() -> Result<Vec<BarkPendingBoard>>;
         fn validate_arkoor_address(address: &str) -> Result<()>;
+        fn parse_bip21_uri(uri: &str) -> Result<String>;
+        unsafe fn get_receive_bundle(amount_sat: *const u64) -> Result<CxxReceiveBundle>;
+        fn generate_bip21_uri(
+            address: &str,
+            amount_sat: u64,
+            label: &str,
+            bolt11: &str,
+        ) -> Result<String>;
         fn send_arkoor_payment(destination: &str, amount_sat: u64) -> Result<ArkoorPaymentResult>;
+        fn send_arkoor_payment_from(
+            destination: &str,
+            amount_sat: u64,
+            vtxo_ids: Vec<String>,
+        ) -> Result<ArkoorPaymentFromResult>;
+        fn get_pending_payments() -> Result<Vec<CxxPendingPayment>>;
+        fn retry_failed_payment(
+            payment_hash: &str,
+            max_fee_increase_pct: u32,
+            timeout_secs: u32,
+        ) -> Result<String>;
         unsafe fn pay_lightning_invoice(
             destination: &str,
             amount_sat: *const u64,
+            max_fee_sat: *const u64,
         ) -> Result<LightningSend>;
         unsafe fn pay_lightning_offer(offer: &str, amount_sat: *const u64)
         -> Result<LightningSend>;
-        fn pay_lightning_address(
+        fn resolve_lightning_address(
+            addr: &str,
+            amount_sat: u64,
+            comment: &str,
+        ) -> Result<CxxResolvedInvoice>;
+        unsafe fn pay_lightning_address(
             addr: &str,
             amount_sat: u64,
             comment: &str,
+            max_fee_sat: *const u64,
         ) -> Result<LightningSend>;
+        fn send_payment(
+            destination: &str,
+            amount_sat: u64,
+            comment: &str,
+            no_sync: bool,
+        ) -> Result<String>;
         fn send_onchain(destination: &str, amount_sat: u64) -> Result<String>;
+        fn send_onchain_many(outputs: Vec<SendManyOutput>) -> Result<Vec<OnchainOutputResult>>;
         fn offboard_specific(vtxo_ids: Vec<String>, destination_address: &str) -> Result<String>;
         fn offboard_all(destination_address: &str) -> Result<String>;
         unsafe fn try_claim_lightning_receive(
@@ -250,14 +816,32 @@ pub(crate) mod ffi {
             token: *const String,
         ) -> Result<LightningReceive>;
         fn try_claim_all_lightning_receives(wait: bool) -> Result<()>;
+        fn claim_all_lightning_receives_with_retry(max_wait_secs: u64) -> Result<()>;
+        fn cancel_claim_retries() -> Result<()>;
+        fn get_at_risk_receives() -> Result<Vec<CxxAtRiskReceive>>;
+        fn get_lightning_receive(payment_hash: &str) -> Result<CxxLightningReceive>;
+        fn get_all_lightning_receives() -> Result<Vec<CxxLightningReceive>>;
+        fn decode_lnurl(lnurl: &str) -> Result<String>;
+        fn process_lnurl_withdraw(lnurl: &str) -> Result<String>;
         fn sync_exits() -> Result<()>;
+        fn sync_exits_with_progress() -> Result<Vec<CxxExitStatus>>;
         fn sync_pending_rounds() -> Result<()>;
+        fn get_recent_recipients(
+            limit: u32,
+            payment_type_filter: &str,
+        ) -> Result<Vec<BarkRecipient>>;
+        fn get_address_book() -> Result<Vec<BarkRecipient>>;
+        fn add_address_book_entry(name: &str, destination: &str) -> Result<()>;
+        fn remove_address_book_entry(destination: &str) -> Result<bool>;
 
         // Onchain methods
         fn onchain_balance() -> Result<OnChainBalance>;
+        fn get_onchain_balance() -> Result<CxxOnchainBalance>;
+        fn has_sufficient_onchain_balance(amount_sat: u64, include_fees: bool) -> Result<bool>;
         fn onchain_sync() -> Result<()>;
         fn onchain_list_unspent() -> Result<String>;
         fn onchain_utxos() -> Result<String>;
+        fn get_onchain_utxos_v2() -> Result<Vec<CxxUtxo>>;
         fn onchain_address() -> Result<String>;
         unsafe fn onchain_send(
             destination: &str,
@@ -265,6 +849,16 @@ pub(crate) mod ffi {
             fee_rate: *const u64,
         ) -> Result<OnchainPaymentResult>;
         unsafe fn onchain_drain(destination: &str, fee_rate: *const u64) -> Result<String>;
+        fn drain_onchain(destination: &str, no_sync: bool) -> Result<String>;
+        fn onchain_bump_fee(txid: &str, new_fee_rate_sat_vb: u64) -> Result<CxxFeeBumpResult>;
+        unsafe fn consolidate_onchain(fee_rate: *const u64) -> Result<CxxSelfTransfer>;
+        unsafe fn send_to_self_onchain(
+            amount_sat: u64,
+            fee_rate: *const u64,
+        ) -> Result<CxxSelfTransfer>;
+        fn export_onchain_descriptors() -> Result<CxxOnchainDescriptors>;
+        fn onchain_rescan(from_height: u32) -> Result<CxxRescanSummary>;
+        fn cancel_onchain_rescan() -> Result<()>;
         unsafe fn onchain_send_many(
             outputs: Vec<SendManyOutput>,
             fee_rate: *const u64,
@@ -272,355 +866,1537 @@ pub(crate) mod ffi {
     }
 }
 
-pub(crate) fn init_logger() {
-    crate::init_logger()
+pub(crate) fn init_logger() -> anyhow::Result<()> {
+    crate::catch_panic(|| {
+        crate::init_logger();
+        Ok(())
+    })
 }
 
-pub(crate) fn create_mnemonic() -> anyhow::Result<String> {
-    crate::create_mnemonic()
+pub(crate) fn init_logger_with_file(path: &str, level: &str) -> anyhow::Result<()> {
+    crate::catch_panic(|| crate::init_logger_with_file(Path::new(path), level))
 }
 
-pub(crate) fn is_wallet_loaded() -> bool {
-    crate::TOKIO_RUNTIME.block_on(crate::is_wallet_loaded())
+pub(crate) fn init_panic_handler() -> anyhow::Result<()> {
+    crate::catch_panic(|| {
+        crate::init_panic_handler();
+        Ok(())
+    })
 }
 
-pub(crate) fn close_wallet() -> anyhow::Result<()> {
-    crate::TOKIO_RUNTIME.block_on(crate::close_wallet())
+/// Empty if nothing panicked
+pub(crate) fn get_last_panic_report() -> anyhow::Result<String> {
+    crate::catch_panic(|| Ok(crate::get_last_panic_report().unwrap_or_default()))
 }
 
-pub(crate) fn get_ark_info() -> anyhow::Result<ffi::CxxArkInfo> {
-    let info = crate::TOKIO_RUNTIME.block_on(crate::get_ark_info())?;
-    Ok(ffi::CxxArkInfo {
-        network: info.network.to_string(),
-        server_pubkey: info.server_pubkey.to_string(),
-        round_interval: info.round_interval.as_secs(),
-        nb_round_nonces: info.nb_round_nonces as u16,
-        vtxo_exit_delta: info.vtxo_exit_delta,
-        vtxo_expiry_delta: info.vtxo_expiry_delta,
-        htlc_send_expiry_delta: info.htlc_send_expiry_delta,
-        max_vtxo_amount: info.max_vtxo_amount.map_or(0, |a| a.to_sat()),
-        required_board_confirmations: info.required_board_confirmations as u8,
-    })
+/// Panics inside a bridge call, to test that the panic is caught.
+#[cfg(test)]
+pub(crate) fn panic_for_test(message: &str) -> anyhow::Result<()> {
+    crate::catch_panic(|| panic!("{}", message))
 }
 
-pub(crate) fn offchain_balance() -> anyhow::Result<ffi::OffchainBalance> {
-    let balance = crate::TOKIO_RUNTIME.block_on(crate::balance())?;
-    Ok(ffi::OffchainBalance {
-        spendable: balance.spendable.to_sat(),
-        pending_lightning_send: balance.pending_lightning_send.to_sat(),
+pub(crate) fn create_mnemonic() -> anyhow::Result<String> {
+    crate::catch_panic(crate::create_mnemonic)
+}
 
-        pending_in_round: balance.pending_in_round.to_sat(),
-        pending_exit: balance.pending_exit.map_or(0, |a| a.to_sat()),
-        pending_board: balance.pending_board.to_sat(),
+pub(crate) fn create_mnemonic_with_options(
+    word_count: u32,
+    language: ffi::MnemonicLanguage,
+) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let language = match language {
+            ffi::MnemonicLanguage::English => bip39::Language::English,
+            ffi::MnemonicLanguage::SimplifiedChinese => bip39::Language::SimplifiedChinese,
+            ffi::MnemonicLanguage::TraditionalChinese => bip39::Language::TraditionalChinese,
+            ffi::MnemonicLanguage::Czech => bip39::Language::Czech,
+            ffi::MnemonicLanguage::French => bip39::Language::French,
+            ffi::MnemonicLanguage::Italian => bip39::Language::Italian,
+            ffi::MnemonicLanguage::Japanese => bip39::Language::Japanese,
+            ffi::MnemonicLanguage::Korean => bip39::Language::Korean,
+            ffi::MnemonicLanguage::Portuguese => bip39::Language::Portuguese,
+            ffi::MnemonicLanguage::Spanish => bip39::Language::Spanish,
+            _ => bail!("Unknown mnemonic language"),
+        };
+        crate::create_mnemonic_with_options(word_count as usize, language)
     })
 }
 
-pub(crate) fn derive_store_next_keypair() -> anyhow::Result<ffi::KeyPairResult> {
-    let keypair = crate::TOKIO_RUNTIME.block_on(crate::derive_store_next_keypair())?;
-    Ok(ffi::KeyPairResult {
-        public_key: keypair.public_key().to_string(),
-        secret_key: keypair.secret_key().display_secret().to_string(),
+pub(crate) fn validate_mnemonic(mnemonic: &str) -> anyhow::Result<ffi::MnemonicLanguage> {
+    crate::catch_panic(|| {
+        Ok(match crate::validate_mnemonic(mnemonic)? {
+            bip39::Language::English => ffi::MnemonicLanguage::English,
+            bip39::Language::SimplifiedChinese => ffi::MnemonicLanguage::SimplifiedChinese,
+            bip39::Language::TraditionalChinese => ffi::MnemonicLanguage::TraditionalChinese,
+            bip39::Language::Czech => ffi::MnemonicLanguage::Czech,
+            bip39::Language::French => ffi::MnemonicLanguage::French,
+            bip39::Language::Italian => ffi::MnemonicLanguage::Italian,
+            bip39::Language::Japanese => ffi::MnemonicLanguage::Japanese,
+            bip39::Language::Korean => ffi::MnemonicLanguage::Korean,
+            bip39::Language::Portuguese => ffi::MnemonicLanguage::Portuguese,
+            bip39::Language::Spanish => ffi::MnemonicLanguage::Spanish,
+        })
     })
 }
 
-pub(crate) fn peak_keypair(index: u32) -> anyhow::Result<ffi::KeyPairResult> {
-    let keypair = crate::TOKIO_RUNTIME.block_on(crate::peak_keypair(index))?;
-    Ok(ffi::KeyPairResult {
-        public_key: keypair.public_key().to_string(),
-        secret_key: keypair.secret_key().display_secret().to_string(),
-    })
+pub(crate) fn verify_derivation_integrity() -> anyhow::Result<bool> {
+    crate::catch_panic(crate::verify_derivation_integrity)
 }
 
-pub(crate) fn new_address() -> anyhow::Result<ffi::NewAddressResult> {
-    let address = crate::TOKIO_RUNTIME.block_on(crate::new_address())?;
-    Ok(ffi::NewAddressResult {
-        user_pubkey: address.policy().user_pubkey().to_string(),
-        ark_id: address.ark_id().to_string(),
-        address: address.to_string(),
-    })
+pub(crate) fn is_wallet_loaded() -> anyhow::Result<bool> {
+    crate::catch_panic(|| Ok(crate::TOKIO_RUNTIME.block_on(crate::is_wallet_loaded())))
 }
 
-pub(crate) fn peak_address(index: u32) -> anyhow::Result<ffi::NewAddressResult> {
-    let address = crate::TOKIO_RUNTIME.block_on(crate::peak_address(index))?;
-    Ok(ffi::NewAddressResult {
-        user_pubkey: address.policy().user_pubkey().to_string(),
-        ark_id: address.ark_id().to_string(),
-        address: address.to_string(),
+pub(crate) fn get_wallet_state() -> anyhow::Result<ffi::CxxWalletState> {
+    crate::catch_panic(|| {
+        Ok(match crate::get_wallet_state() {
+            crate::WalletState::NotLoaded => ffi::CxxWalletState::NotLoaded,
+            crate::WalletState::Loaded => ffi::CxxWalletState::Loaded,
+            crate::WalletState::Loading => ffi::CxxWalletState::Loading,
+            crate::WalletState::Error => ffi::CxxWalletState::Error,
+        })
     })
 }
 
-pub(crate) fn sign_message(message: &str, index: u32) -> anyhow::Result<String> {
-    let message = crate::TOKIO_RUNTIME
-        .block_on(crate::sign_message(message, index))?
-        .to_string();
-    Ok(message)
+pub(crate) fn set_offline_mode(offline: bool) -> anyhow::Result<()> {
+    crate::catch_panic(|| {
+        crate::TOKIO_RUNTIME.block_on(crate::set_offline_mode(offline));
+        Ok(())
+    })
 }
 
-pub(crate) fn sign_messsage_with_mnemonic(
-    message: &str,
-    mnemonic: &str,
-    network: &str,
-    index: u32,
-) -> anyhow::Result<String> {
-    let mnemonic = Mnemonic::from_str(mnemonic)
-        .with_context(|| format!("Invalid mnemonic format: '{}'", mnemonic))?;
-
-    let network = match network {
-        "mainnet" => network::Network::Bitcoin,
-        "regtest" => network::Network::Regtest,
-        "signet" => network::Network::Signet,
-        _ => bail!("Invalid network format: '{}'", network),
-    };
+pub(crate) fn get_wallet_id() -> anyhow::Result<String> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::get_wallet_id()))
+}
 
-    let message = crate::TOKIO_RUNTIME
-        .block_on(crate::sign_messsage_with_mnemonic(
-            message, mnemonic, network, index,
-        ))?
-        .to_string();
-    Ok(message)
+pub(crate) fn get_wallet_created_at() -> anyhow::Result<String> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::get_wallet_created_at()))
 }
 
-pub(crate) fn derive_keypair_from_mnemonic(
-    mnemonic: &str,
-    network: &str,
-    index: u32,
-) -> anyhow::Result<ffi::KeyPairResult> {
-    let mnemonic = bip39::Mnemonic::from_str(mnemonic)
-        .with_context(|| format!("Invalid mnemonic format: '{}'", mnemonic))?;
-    let network = match network {
-        "mainnet" => network::Network::Bitcoin,
-        "regtest" => network::Network::Regtest,
-        "signet" => network::Network::Signet,
-        _ => bail!("Invalid network format: '{}'", network),
-    };
+/// Returns the path of the backup, which carries a timestamp prefix.
+pub(crate) fn backup_database(backup_path: &str) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let path =
+            crate::TOKIO_RUNTIME.block_on(crate::backup_wallet_database(Path::new(backup_path)))?;
+        Ok(path.display().to_string())
+    })
+}
 
-    let keypair = crate::TOKIO_RUNTIME.block_on(crate::derive_keypair_from_mnemonic(
-        mnemonic, network, index,
-    ))?;
+pub(crate) fn get_database_size_bytes() -> anyhow::Result<u64> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::get_database_size_bytes()))
+}
 
-    Ok(ffi::KeyPairResult {
-        public_key: keypair.public_key().to_string(),
-        secret_key: keypair.secret_key().display_secret().to_string(),
+/// JSON object with the row count of each table, under `wallet` for bark's
+/// database and `store` for the local store
+pub(crate) fn get_database_table_stats() -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let stats = crate::TOKIO_RUNTIME.block_on(crate::get_database_table_stats())?;
+        serde_json::to_string(&stats).context("Failed to serialize database table stats")
     })
 }
 
-pub(crate) fn verify_message(
-    message: &str,
-    signature: &str,
-    public_key: &str,
-) -> anyhow::Result<bool> {
-    let signature = bark::ark::bitcoin::secp256k1::ecdsa::Signature::from_str(signature)
-        .with_context(|| format!("Invalid signature format: '{}'", signature))?;
-    let public_key = bark::ark::bitcoin::secp256k1::PublicKey::from_str(public_key)
-        .with_context(|| format!("Invalid public key format: '{}'", public_key))?;
+pub(crate) fn is_offline_mode() -> anyhow::Result<bool> {
+    crate::catch_panic(|| Ok(crate::TOKIO_RUNTIME.block_on(crate::is_offline_mode())))
+}
 
-    crate::TOKIO_RUNTIME.block_on(crate::verify_message(message, signature, &public_key))
+/// Empty if the wallet data can be opened by this version of the app
+pub(crate) fn get_required_app_version_hint(datadir: &str) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        Ok(crate::get_required_app_version_hint(Path::new(datadir))?.unwrap_or_default())
+    })
 }
 
-pub(crate) fn history() -> anyhow::Result<Vec<BarkMovement>> {
-    let history = crate::TOKIO_RUNTIME.block_on(crate::history())?;
-    fn fun_name(m: &bark::movement::Movement) -> Result<BarkMovement, anyhow::Error> {
-        utils::movement_to_bark_movement(m)
-    }
+pub(crate) fn get_library_info() -> anyhow::Result<ffi::LibraryInfo> {
+    crate::catch_panic(|| {
+        let info = crate::TOKIO_RUNTIME.block_on(crate::get_library_info());
+        Ok(ffi::LibraryInfo {
+            version: info.version.to_string(),
+            git_describe: info.git_describe.to_string(),
+            bark_version: info.bark_version.to_string(),
+            persister_backend: info.persister_backend.to_string(),
+            features: info.features,
+            store_schema_version: info.store_schema_version.unwrap_or(0),
+        })
+    })
+}
 
-    history.iter().map(fun_name).collect()
+pub(crate) fn close_wallet() -> anyhow::Result<()> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::close_wallet()))
 }
 
-pub(crate) fn vtxos() -> anyhow::Result<Vec<BarkVtxo>> {
-    let vtxos = crate::TOKIO_RUNTIME.block_on(crate::vtxos())?;
-    Ok(vtxos
-        .into_iter()
-        .map(utils::wallet_vtxo_to_bark_vtxo)
-        .collect())
+pub(crate) fn reset_wallet(datadir: &str, confirm: bool) -> anyhow::Result<()> {
+    crate::catch_panic(|| {
+        crate::TOKIO_RUNTIME.block_on(crate::reset_wallet(Path::new(datadir), confirm))
+    })
 }
 
-pub(crate) fn get_expiring_vtxos(threshold: u32) -> anyhow::Result<Vec<BarkVtxo>> {
-    let expiring_vtxos = crate::TOKIO_RUNTIME.block_on(crate::get_expiring_vtxos(threshold))?;
-    Ok(expiring_vtxos
-        .into_iter()
-        .map(utils::wallet_vtxo_to_bark_vtxo)
-        .collect())
+pub(crate) fn move_wallet_datadir(
+    old_path: &str,
+    new_path: &str,
+) -> anyhow::Result<ffi::CxxDatadirMoveReport> {
+    crate::catch_panic(|| {
+        let report = crate::TOKIO_RUNTIME.block_on(crate::move_wallet_datadir(
+            Path::new(old_path),
+            Path::new(new_path),
+        ))?;
+        Ok(ffi::CxxDatadirMoveReport {
+            files_copied: report.files_copied,
+            bytes_copied: report.bytes_copied,
+            wallet_migration_version: report.wallet_migration_version,
+            store_schema_version: report.store_schema_version,
+            tables_verified: report.tables_verified,
+            rows_verified: report.rows_verified,
+        })
+    })
 }
 
-pub(crate) fn get_first_expiring_vtxo_blockheight() -> anyhow::Result<*const u32> {
-    let blockheight = crate::TOKIO_RUNTIME.block_on(crate::get_first_expiring_vtxo_blockheight())?;
-    match blockheight {
-        Some(height) => Ok(Box::into_raw(Box::new(height))),
-        None => Ok(std::ptr::null()),
-    }
+pub(crate) fn get_ark_info() -> anyhow::Result<ffi::CxxArkInfo> {
+    crate::catch_panic(|| {
+        let info = crate::TOKIO_RUNTIME.block_on(crate::get_ark_info())?;
+        Ok(ark_info_to_ffi(&info))
+    })
 }
 
-pub(crate) fn get_next_required_refresh_blockheight() -> anyhow::Result<*const u32> {
-    let blockheight =
-        crate::TOKIO_RUNTIME.block_on(crate::get_next_required_refresh_blockheight())?;
-    match blockheight {
-        Some(height) => Ok(Box::into_raw(Box::new(height))),
-        None => Ok(std::ptr::null()),
-    }
+pub(crate) fn get_config() -> anyhow::Result<ffi::CxxConfig> {
+    crate::catch_panic(|| {
+        let config = crate::TOKIO_RUNTIME.block_on(crate::get_config())?;
+        Ok(config_to_ffi(&config))
+    })
 }
 
-pub(crate) fn bolt11_invoice(amount_msat: u64) -> anyhow::Result<ffi::Bolt11Invoice> {
-    let invoice = crate::TOKIO_RUNTIME.block_on(crate::bolt11_invoice(amount_msat))?;
-    Ok(ffi::Bolt11Invoice {
-        bolt11_invoice: invoice.to_string(),
-        payment_secret: invoice.payment_secret().to_string(),
-        payment_hash: invoice.payment_hash().to_string(),
+pub(crate) fn apply_config(opts: ffi::ConfigOpts) -> anyhow::Result<ffi::ConfigApplyResult> {
+    crate::catch_panic(|| {
+        let opts = utils::ffi_config_opts_to_config_opts(opts)?;
+        let result = crate::TOKIO_RUNTIME.block_on(crate::apply_config(opts))?;
+        Ok(ffi::ConfigApplyResult {
+            applied: result.applied,
+            requires_reload: result.requires_reload,
+        })
     })
 }
 
-pub(crate) fn lightning_receive_status(
-    payment_hash: String,
-) -> anyhow::Result<*const ffi::LightningReceive> {
-    let payment = bark::ark::lightning::PaymentHash::from_str(&payment_hash)
-        .with_context(|| format!("Invalid payment hash format: '{}'", payment_hash))?;
-    let status = crate::TOKIO_RUNTIME.block_on(crate::lightning_receive_status(payment))?;
+pub(crate) fn get_default_config(network: &str) -> anyhow::Result<ffi::CxxConfig> {
+    crate::catch_panic(|| {
+        let network = match network {
+            "mainnet" => network::Network::Bitcoin,
+            "regtest" => network::Network::Regtest,
+            "signet" => network::Network::Signet,
+            _ => bail!("Invalid network format: '{}'", network),
+        };
+        Ok(config_to_ffi(&utils::default_config(network)))
+    })
+}
 
-    if status.is_none() {
-        return Ok(std::ptr::null());
+/// Mirrors `ConfigOpts::merge_into`, so feeding the result back as
+/// `ConfigOpts` yields the same config.
+fn config_to_ffi(config: &bark::Config) -> ffi::CxxConfig {
+    ffi::CxxConfig {
+        server_address: config.server_address.clone(),
+        esplora_address: config.esplora_address.clone().unwrap_or_default(),
+        bitcoind_address: config.bitcoind_address.clone().unwrap_or_default(),
+        bitcoind_cookiefile: config
+            .bitcoind_cookiefile
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default(),
+        bitcoind_user: config.bitcoind_user.clone().unwrap_or_default(),
+        has_password: config.bitcoind_pass.is_some(),
+        vtxo_refresh_expiry_threshold: config.vtxo_refresh_expiry_threshold,
+        fallback_fee_rate: config
+            .fallback_fee_rate
+            .map_or(0, |rate| rate.to_sat_per_kwu() * 4),
+        htlc_recv_claim_delta: config.htlc_recv_claim_delta,
+        vtxo_exit_margin: config.vtxo_exit_margin,
+        round_tx_required_confirmations: config.round_tx_required_confirmations,
     }
+}
 
-    let status = status.unwrap();
-    let status = Box::new(ffi::LightningReceive {
-        payment_hash: status.payment_hash.to_string(),
-        payment_preimage: status.payment_preimage.to_string(),
-        invoice: status.invoice.to_string(),
-        preimage_revealed_at: status.preimage_revealed_at.map_or(std::ptr::null(), |v| {
-            Box::into_raw(Box::new(v.timestamp() as u64))
-        }),
-        finished_at: status.finished_at.map_or(std::ptr::null(), |v| {
-            Box::into_raw(Box::new(v.timestamp() as u64))
-        }),
-    });
-    Ok(Box::into_raw(status))
+fn ark_info_to_ffi(info: &bark::ark::ArkInfo) -> ffi::CxxArkInfo {
+    ffi::CxxArkInfo {
+        network: info.network.to_string(),
+        server_pubkey: info.server_pubkey.to_string(),
+        round_interval: info.round_interval.as_secs(),
+        nb_round_nonces: info.nb_round_nonces as u16,
+        vtxo_exit_delta: info.vtxo_exit_delta,
+        vtxo_expiry_delta: info.vtxo_expiry_delta,
+        htlc_send_expiry_delta: info.htlc_send_expiry_delta,
+        max_vtxo_amount: info.max_vtxo_amount.map_or(0, |a| a.to_sat()),
+        required_board_confirmations: info.required_board_confirmations as u8,
+    }
 }
 
-pub(crate) fn sync_pending_boards() -> anyhow::Result<()> {
-    crate::TOKIO_RUNTIME.block_on(crate::sync_pending_boards())
+pub(crate) fn offchain_balance() -> anyhow::Result<ffi::OffchainBalance> {
+    crate::catch_panic(|| {
+        let balance = crate::TOKIO_RUNTIME.block_on(crate::balance())?;
+        Ok(offchain_balance_to_ffi(&balance))
+    })
 }
 
-pub(crate) fn maintenance() -> anyhow::Result<()> {
-    crate::TOKIO_RUNTIME.block_on(crate::maintenance())
+pub(crate) fn get_balance(no_sync: bool) -> anyhow::Result<ffi::OffchainBalance> {
+    crate::catch_panic(|| {
+        let balance = crate::TOKIO_RUNTIME.block_on(crate::get_balance(no_sync))?;
+        Ok(offchain_balance_to_ffi(&balance))
+    })
 }
 
-pub(crate) fn maintenance_delegated() -> anyhow::Result<()> {
-    crate::TOKIO_RUNTIME.block_on(crate::maintenance_delegated())
+pub(crate) fn offchain_balance_sat() -> anyhow::Result<u64> {
+    crate::catch_panic(|| {
+        Ok(crate::TOKIO_RUNTIME
+            .block_on(crate::offchain_balance())?
+            .to_sat())
+    })
 }
 
-pub(crate) fn maintenance_with_onchain() -> anyhow::Result<()> {
-    crate::TOKIO_RUNTIME.block_on(crate::maintenance_with_onchain())
+pub(crate) fn onchain_balance_sat() -> anyhow::Result<u64> {
+    crate::catch_panic(|| {
+        Ok(crate::TOKIO_RUNTIME
+            .block_on(crate::onchain_balance())?
+            .to_sat())
+    })
+}
+
+pub(crate) fn pending_exit_balance_sat() -> anyhow::Result<u64> {
+    crate::catch_panic(|| {
+        Ok(crate::TOKIO_RUNTIME
+            .block_on(crate::pending_exit_balance())?
+            .to_sat())
+    })
+}
+
+pub(crate) fn register_expected_payment(
+    pubkey_index: u32,
+    amount_sat: *const u64,
+    memo: &str,
+    expiry_secs: u32,
+) -> anyhow::Result<ffi::CxxExpectedPayment> {
+    crate::catch_panic(|| {
+        let amount = unsafe { amount_sat.as_ref() }.map(|sat| units::amount_from_sat(*sat));
+        let payment = crate::TOKIO_RUNTIME.block_on(crate::register_expected_payment(
+            pubkey_index,
+            amount,
+            memo,
+            expiry_secs,
+        ))?;
+        Ok(payment.into())
+    })
+}
+
+/// All expected payments if `status` is empty.
+pub(crate) fn list_expected_payments(status: &str) -> anyhow::Result<Vec<ffi::CxxExpectedPayment>> {
+    crate::catch_panic(|| {
+        let status = match status {
+            "" => None,
+            status => Some(status.parse::<crate::ExpectedPaymentStatus>()?),
+        };
+        let payments = crate::TOKIO_RUNTIME.block_on(crate::list_expected_payments(status))?;
+        Ok(payments.into_iter().map(Into::into).collect())
+    })
+}
+
+pub(crate) fn cancel_expected_payment(id: i64) -> anyhow::Result<bool> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::cancel_expected_payment(id)))
+}
+
+impl From<crate::ExpectedPayment> for ffi::CxxExpectedPayment {
+    fn from(payment: crate::ExpectedPayment) -> Self {
+        ffi::CxxExpectedPayment {
+            id: payment.id,
+            pubkey_index: payment.pubkey_index,
+            user_pubkey: payment.user_pubkey,
+            amount_sat: payment.amount_sat.unwrap_or(0),
+            memo: payment.memo,
+            status: payment.status.as_str().to_string(),
+            vtxo_id: payment.vtxo_id.unwrap_or_default(),
+            created_at: payment.created_at,
+            expires_at: payment.expires_at,
+        }
+    }
+}
+
+pub(crate) fn has_sufficient_offchain_balance(amount_sat: u64) -> anyhow::Result<bool> {
+    crate::catch_panic(|| {
+        crate::TOKIO_RUNTIME.block_on(crate::has_sufficient_offchain_balance(
+            units::amount_from_sat(amount_sat),
+        ))
+    })
+}
+
+pub(crate) fn has_sufficient_onchain_balance(
+    amount_sat: u64,
+    include_fees: bool,
+) -> anyhow::Result<bool> {
+    crate::catch_panic(|| {
+        crate::TOKIO_RUNTIME.block_on(crate::has_sufficient_onchain_balance(
+            units::amount_from_sat(amount_sat),
+            include_fees,
+        ))
+    })
+}
+
+fn offchain_balance_to_ffi(offchain: &crate::OffchainBalance) -> ffi::OffchainBalance {
+    let balance = &offchain.balance;
+    ffi::OffchainBalance {
+        spendable: balance.spendable.to_sat(),
+        pending_lightning_send: balance.pending_lightning_send.to_sat(),
+
+        pending_in_round: balance.pending_in_round.to_sat(),
+        pending_exit: balance.pending_exit.map_or(0, |a| a.to_sat()),
+        pending_board: balance.pending_board.to_sat(),
+        pending_trustless: offchain.pending_trustless.to_sat(),
+    }
+}
+
+pub(crate) fn derive_store_next_keypair() -> anyhow::Result<ffi::KeyPairResult> {
+    crate::catch_panic(|| {
+        let keypair = crate::TOKIO_RUNTIME.block_on(crate::derive_store_next_keypair())?;
+        Ok(ffi::KeyPairResult {
+            public_key: keypair.public_key().to_string(),
+            secret_key: keypair.secret_key().display_secret().to_string(),
+        })
+    })
+}
+
+pub(crate) fn peak_keypair(index: u32) -> anyhow::Result<ffi::KeyPairResult> {
+    crate::catch_panic(|| {
+        let keypair = crate::TOKIO_RUNTIME.block_on(crate::peak_keypair(index))?;
+        Ok(ffi::KeyPairResult {
+            public_key: keypair.public_key().to_string(),
+            secret_key: keypair.secret_key().display_secret().to_string(),
+        })
+    })
+}
+
+pub(crate) fn new_address() -> anyhow::Result<ffi::NewAddressResult> {
+    crate::catch_panic(|| {
+        let address = crate::TOKIO_RUNTIME.block_on(crate::new_address())?;
+        Ok(ffi::NewAddressResult {
+            user_pubkey: address.policy().user_pubkey().to_string(),
+            ark_id: address.ark_id().to_string(),
+            address: address.to_string(),
+        })
+    })
+}
+
+pub(crate) fn peak_address(index: u32) -> anyhow::Result<ffi::NewAddressResult> {
+    crate::catch_panic(|| {
+        let address = crate::TOKIO_RUNTIME.block_on(crate::peak_address(index))?;
+        Ok(ffi::NewAddressResult {
+            user_pubkey: address.policy().user_pubkey().to_string(),
+            ark_id: address.ark_id().to_string(),
+            address: address.to_string(),
+        })
+    })
+}
+
+pub(crate) fn get_ark_receive_address_at_index(index: u32) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        crate::TOKIO_RUNTIME.block_on(crate::get_ark_receive_address(Some(index)))
+    })
+}
+
+pub(crate) fn get_new_ark_receive_address() -> anyhow::Result<String> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::get_ark_receive_address(None)))
+}
+
+pub(crate) fn prefetch_ark_address() -> anyhow::Result<()> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::prefetch_address()))
+}
+
+/// Empty if no address is pending
+pub(crate) fn get_pending_ark_address() -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let address = crate::TOKIO_RUNTIME.block_on(crate::get_pending_address())?;
+        Ok(address.map(|a| a.to_string()).unwrap_or_default())
+    })
+}
+
+pub(crate) fn consume_pending_ark_address() -> anyhow::Result<()> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::consume_pending_address()))
+}
+
+pub(crate) fn sign_message(message: &str, index: u32) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let message = crate::TOKIO_RUNTIME
+            .block_on(crate::sign_message(message, index))?
+            .to_string();
+        Ok(message)
+    })
+}
+
+pub(crate) fn sign_messsage_with_mnemonic(
+    message: &str,
+    mnemonic: &str,
+    network: &str,
+    index: u32,
+) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let mnemonic = utils::parse_mnemonic(mnemonic)?;
+
+        let network = match network {
+            "mainnet" => network::Network::Bitcoin,
+            "regtest" => network::Network::Regtest,
+            "signet" => network::Network::Signet,
+            _ => bail!("Invalid network format: '{}'", network),
+        };
+
+        let message = crate::TOKIO_RUNTIME
+            .block_on(async {
+                let purpose_index = crate::ark_purpose_index().await?;
+                crate::sign_messsage_with_mnemonic(message, mnemonic, network, purpose_index, index)
+                    .await
+            })?
+            .to_string();
+        Ok(message)
+    })
+}
+
+pub(crate) fn derive_keypair_from_mnemonic(
+    mnemonic: &str,
+    network: &str,
+    index: u32,
+) -> anyhow::Result<ffi::KeyPairResult> {
+    crate::catch_panic(|| {
+        let mnemonic = utils::parse_mnemonic(mnemonic)?;
+        let network = match network {
+            "mainnet" => network::Network::Bitcoin,
+            "regtest" => network::Network::Regtest,
+            "signet" => network::Network::Signet,
+            _ => bail!("Invalid network format: '{}'", network),
+        };
+
+        let keypair = crate::TOKIO_RUNTIME.block_on(async {
+            let purpose_index = crate::ark_purpose_index().await?;
+            crate::derive_keypair_from_mnemonic(mnemonic, network, purpose_index, index).await
+        })?;
+
+        Ok(ffi::KeyPairResult {
+            public_key: keypair.public_key().to_string(),
+            secret_key: keypair.secret_key().display_secret().to_string(),
+        })
+    })
+}
+
+pub(crate) fn verify_message(
+    message: &str,
+    signature: &str,
+    public_key: &str,
+) -> anyhow::Result<bool> {
+    crate::catch_panic(|| {
+        let signature = bark::ark::bitcoin::secp256k1::ecdsa::Signature::from_str(signature)
+            .with_context(|| format!("Invalid signature format: '{}'", signature))?;
+        let public_key = bark::ark::bitcoin::secp256k1::PublicKey::from_str(public_key)
+            .with_context(|| format!("Invalid public key format: '{}'", public_key))?;
+
+        crate::TOKIO_RUNTIME.block_on(crate::verify_message(message, signature, &public_key))
+    })
+}
+
+pub(crate) fn sign_data_schnorr(data_hex: &str, index: u32) -> anyhow::Result<String> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::sign_data_schnorr(data_hex, index)))
+}
+
+pub(crate) fn verify_data_schnorr(
+    data_hex: &str,
+    signature_hex: &str,
+    pubkey_hex: &str,
+) -> anyhow::Result<bool> {
+    crate::catch_panic(|| crate::verify_data_schnorr(data_hex, signature_hex, pubkey_hex))
+}
+
+pub(crate) fn history() -> anyhow::Result<Vec<BarkMovement>> {
+    crate::catch_panic(|| {
+        let history = crate::TOKIO_RUNTIME.block_on(crate::history())?;
+        fn fun_name(m: &bark::movement::Movement) -> Result<BarkMovement, anyhow::Error> {
+            utils::movement_to_bark_movement(m)
+        }
+
+        history.iter().map(fun_name).collect()
+    })
+}
+
+pub(crate) fn get_movement_count() -> anyhow::Result<u64> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::get_movement_count()))
+}
+
+/// `cursor` is empty for the first page.
+pub(crate) fn get_movements_after(
+    cursor: &str,
+    limit: u32,
+) -> anyhow::Result<ffi::CxxMovementPage> {
+    crate::catch_panic(|| {
+        let cursor = match cursor.is_empty() {
+            true => None,
+            false => Some(utils::MovementCursor::decode(cursor)?),
+        };
+        let (movements, next) =
+            crate::TOKIO_RUNTIME.block_on(crate::get_movements_after(cursor, limit as usize))?;
+        Ok(ffi::CxxMovementPage {
+            movements: movements
+                .iter()
+                .map(utils::movement_to_bark_movement)
+                .collect::<anyhow::Result<_>>()?,
+            next_cursor: next.map(|c| c.encode()).unwrap_or_default(),
+        })
+    })
+}
+
+pub(crate) fn export_movements_csv(
+    dest_path: &str,
+    from_ts: i64,
+    to_ts: i64,
+) -> anyhow::Result<u64> {
+    crate::catch_panic(|| {
+        TOKIO_RUNTIME.block_on(crate::export_movements_csv(
+            Path::new(dest_path),
+            from_ts,
+            to_ts,
+        ))
+    })
+}
+
+/// Only runs when called with a server listing, see
+/// [`crate::reconcile_vtxos_with_server`]
+pub(crate) fn reconcile_vtxos_with_server(
+    server_vtxo_ids: Vec<String>,
+) -> anyhow::Result<Vec<ffi::BarkQuarantinedVtxo>> {
+    crate::catch_panic(|| {
+        let server_vtxo_ids = server_vtxo_ids
+            .iter()
+            .map(|id| {
+                bark::ark::VtxoId::from_str(id)
+                    .with_context(|| format!("Invalid vtxo id format: '{}'", id))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let quarantined =
+            crate::TOKIO_RUNTIME.block_on(crate::reconcile_vtxos_with_server(server_vtxo_ids))?;
+        Ok(quarantined
+            .into_iter()
+            .map(quarantined_vtxo_to_ffi)
+            .collect())
+    })
+}
+
+pub(crate) fn get_quarantined_vtxos() -> anyhow::Result<Vec<ffi::BarkQuarantinedVtxo>> {
+    crate::catch_panic(|| {
+        let quarantined = crate::TOKIO_RUNTIME.block_on(crate::get_quarantined_vtxos())?;
+        Ok(quarantined
+            .into_iter()
+            .map(quarantined_vtxo_to_ffi)
+            .collect())
+    })
+}
+
+pub(crate) fn release_quarantined_vtxo(vtxo_id: &str) -> anyhow::Result<bool> {
+    crate::catch_panic(|| {
+        let vtxo_id = utils::parse_vtxo_id(vtxo_id)?;
+        crate::TOKIO_RUNTIME.block_on(crate::release_quarantined_vtxo(vtxo_id))
+    })
+}
+
+pub(crate) fn vtxo_id_to_string(vtxo_id: &str) -> anyhow::Result<String> {
+    crate::catch_panic(|| Ok(utils::parse_vtxo_id(vtxo_id)?.to_string()))
+}
+
+pub(crate) fn is_valid_vtxo_id(vtxo_id: &str) -> anyhow::Result<bool> {
+    crate::catch_panic(|| Ok(utils::parse_vtxo_id(vtxo_id).is_ok()))
+}
+
+pub(crate) fn estimate_exit_cost(
+    vtxo_ids: Vec<String>,
+) -> anyhow::Result<ffi::BarkExitCostEstimate> {
+    crate::catch_panic(|| {
+        let vtxo_ids = vtxo_ids
+            .iter()
+            .map(|id| {
+                bark::ark::VtxoId::from_str(id)
+                    .with_context(|| format!("Invalid vtxo id format: '{}'", id))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let estimate = crate::TOKIO_RUNTIME.block_on(crate::estimate_exit_cost(vtxo_ids))?;
+        Ok(exit_cost_estimate_to_ffi(estimate))
+    })
+}
+
+fn exit_cost_estimate_to_ffi(estimate: crate::ExitCostEstimate) -> ffi::BarkExitCostEstimate {
+    ffi::BarkExitCostEstimate {
+        per_vtxo: estimate
+            .per_vtxo
+            .into_iter()
+            .map(|cost| ffi::BarkVtxoExitCost {
+                vtxo_id: cost.vtxo_id,
+                fee_sat: cost.fee_sat,
+                tx_count: cost.tx_count,
+            })
+            .collect(),
+        total_fee_sat: estimate.total_fee_sat,
+        tx_count: estimate.tx_count,
+        claimable_after_blocks: estimate.claimable_after_blocks,
+    }
+}
+
+fn quarantined_vtxo_to_ffi(vtxo: crate::QuarantinedVtxo) -> ffi::BarkQuarantinedVtxo {
+    ffi::BarkQuarantinedVtxo {
+        vtxo_id: vtxo.vtxo_id,
+        amount_sat: vtxo.amount_sat,
+        reason: vtxo.reason,
+        quarantined_at: vtxo.quarantined_at,
+        recommended_action: utils::QUARANTINE_RECOMMENDED_ACTION.to_string(),
+    }
+}
+
+pub(crate) fn get_wallet_snapshot(fields_mask: u32) -> anyhow::Result<ffi::WalletSnapshot> {
+    crate::catch_panic(|| {
+        let snapshot = crate::TOKIO_RUNTIME.block_on(crate::get_wallet_snapshot(fields_mask))?;
+
+        /// Splits an optional result into the value (or its default) and an error message
+        fn split<T, U: Default>(
+            field: Option<anyhow::Result<T>>,
+            convert: impl FnOnce(T) -> anyhow::Result<U>,
+        ) -> (U, String) {
+            match field.map(|res| res.and_then(convert)) {
+                Some(Ok(value)) => (value, String::new()),
+                Some(Err(err)) => (U::default(), format!("{:#}", err)),
+                None => (U::default(), String::new()),
+            }
+        }
+
+        let vtxos_to_ffi = |vtxos: Vec<bark::WalletVtxo>| -> anyhow::Result<Vec<BarkVtxo>> {
+            Ok(vtxos
+                .into_iter()
+                .map(utils::wallet_vtxo_to_bark_vtxo)
+                .collect())
+        };
+
+        let (balance, balance_error) = split(snapshot.balance, |b| Ok(offchain_balance_to_ffi(&b)));
+        let (ark_info, ark_info_error) = split(snapshot.ark_info, |i| Ok(ark_info_to_ffi(&i)));
+        let (vtxos, vtxos_error) = split(snapshot.vtxos, vtxos_to_ffi);
+        let (movements, movements_error) = split(snapshot.movements, |movements| {
+            movements
+                .iter()
+                .map(utils::movement_to_bark_movement)
+                .collect()
+        });
+        let (onchain_utxos, onchain_utxos_error) = split(snapshot.onchain_utxos, |utxos| {
+            crate::onchain::utxos_to_json(&utxos)
+        });
+        let (expiring_vtxos, expiring_vtxos_error) = split(snapshot.expiring_vtxos, vtxos_to_ffi);
+        let (lightning_receives, lightning_receives_error) =
+            split(snapshot.lightning_receives, |receives| {
+                Ok(receives.iter().map(lightning_receive_to_ffi).collect())
+            });
+
+        Ok(ffi::WalletSnapshot {
+            fields_mask,
+            balance,
+            balance_error,
+            ark_info,
+            ark_info_error,
+            vtxos,
+            vtxos_error,
+            movements,
+            movements_error,
+            onchain_utxos,
+            onchain_utxos_error,
+            expiring_vtxos,
+            expiring_vtxos_error,
+            lightning_receives,
+            lightning_receives_error,
+        })
+    })
+}
+
+pub(crate) fn get_balance_history(days: u32) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let history = TOKIO_RUNTIME.block_on(crate::get_balance_history(days))?;
+        serde_json::to_string(&history).map_err(Into::into)
+    })
+}
+
+pub(crate) fn get_total_received_sat() -> anyhow::Result<u64> {
+    crate::catch_panic(|| TOKIO_RUNTIME.block_on(crate::get_total_received_sat()))
+}
+
+pub(crate) fn get_total_sent_sat() -> anyhow::Result<u64> {
+    crate::catch_panic(|| TOKIO_RUNTIME.block_on(crate::get_total_sent_sat()))
+}
+
+pub(crate) fn get_total_received_in_range(from_ts: i64, to_ts: i64) -> anyhow::Result<u64> {
+    crate::catch_panic(|| {
+        TOKIO_RUNTIME.block_on(crate::get_total_received_in_range(from_ts, to_ts))
+    })
+}
+
+pub(crate) fn get_total_sent_in_range(from_ts: i64, to_ts: i64) -> anyhow::Result<u64> {
+    crate::catch_panic(|| TOKIO_RUNTIME.block_on(crate::get_total_sent_in_range(from_ts, to_ts)))
+}
+
+pub(crate) fn get_wallet_stats() -> anyhow::Result<ffi::CxxWalletStats> {
+    crate::catch_panic(|| {
+        let stats = TOKIO_RUNTIME.block_on(crate::get_wallet_stats())?;
+        Ok(ffi::CxxWalletStats {
+            movement_count: stats.movement_count,
+            total_received_sat: stats.total_received_sat,
+            total_sent_sat: stats.total_sent_sat,
+            per_kind: stats
+                .per_kind
+                .into_iter()
+                .map(|k| ffi::CxxMovementKindStats {
+                    kind: k.kind.into(),
+                    count: k.count,
+                    received_sat: k.received_sat,
+                    sent_sat: k.sent_sat,
+                })
+                .collect(),
+            first_movement_at: stats.first_movement_at.unwrap_or(0),
+        })
+    })
+}
+
+pub(crate) fn vtxos() -> anyhow::Result<Vec<BarkVtxo>> {
+    crate::catch_panic(|| {
+        let vtxos = crate::TOKIO_RUNTIME.block_on(crate::vtxos())?;
+        Ok(vtxos
+            .into_iter()
+            .map(utils::wallet_vtxo_to_bark_vtxo)
+            .collect())
+    })
+}
+
+fn vtxo_filter_to_query(filter: ffi::VtxoFilter) -> anyhow::Result<utils::VtxoQuery> {
+    let states = filter
+        .states
+        .iter()
+        .map(|state| match state.as_str() {
+            "Spendable" => Ok(VtxoStateKind::Spendable),
+            "Locked" => Ok(VtxoStateKind::Locked),
+            "Spent" => Ok(VtxoStateKind::Spent),
+            _ => bail!("Invalid vtxo state: '{}'", state),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let nonzero = |value: u64| (value != 0).then_some(value);
+    Ok(utils::VtxoQuery {
+        states,
+        min_amount_sat: nonzero(filter.min_amount_sat),
+        max_amount_sat: nonzero(filter.max_amount_sat),
+        max_expiry_height: (filter.max_expiry_height != 0).then_some(filter.max_expiry_height),
+        sort_key: match filter.sort_key {
+            ffi::CxxVtxoSortKey::Expiry => utils::VtxoSortKey::Expiry,
+            ffi::CxxVtxoSortKey::Amount => utils::VtxoSortKey::Amount,
+            _ => bail!("Invalid vtxo sort key"),
+        },
+        descending: filter.descending,
+        limit: (filter.limit != 0).then_some(filter.limit as usize),
+        offset: filter.offset as usize,
+    })
+}
+
+pub(crate) fn get_vtxos_filtered(filter: ffi::VtxoFilter) -> anyhow::Result<ffi::BarkVtxoPage> {
+    crate::catch_panic(|| {
+        let query = vtxo_filter_to_query(filter)?;
+        let (vtxos, total_count) = TOKIO_RUNTIME.block_on(crate::get_vtxos_filtered(query))?;
+        Ok(ffi::BarkVtxoPage {
+            vtxos: vtxos
+                .into_iter()
+                .map(utils::wallet_vtxo_to_bark_vtxo)
+                .collect(),
+            total_count,
+        })
+    })
+}
+
+pub(crate) fn get_vtxo_history(vtxo_id: &str) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let vtxo_id = utils::parse_vtxo_id(vtxo_id)?;
+        let history = crate::TOKIO_RUNTIME.block_on(crate::get_vtxo_history(vtxo_id))?;
+        serde_json::to_string(&history).map_err(Into::into)
+    })
+}
+
+pub(crate) fn get_spendable_vtxo_count() -> anyhow::Result<u64> {
+    crate::catch_panic(|| {
+        TOKIO_RUNTIME.block_on(crate::get_vtxo_count(&[VtxoStateKind::Spendable]))
+    })
+}
+
+pub(crate) fn get_spent_vtxo_count() -> anyhow::Result<u64> {
+    crate::catch_panic(|| TOKIO_RUNTIME.block_on(crate::get_vtxo_count(&[VtxoStateKind::Spent])))
+}
+
+pub(crate) fn get_locked_vtxo_count() -> anyhow::Result<u64> {
+    crate::catch_panic(|| TOKIO_RUNTIME.block_on(crate::get_vtxo_count(&[VtxoStateKind::Locked])))
+}
+
+pub(crate) fn get_total_vtxo_count() -> anyhow::Result<u64> {
+    crate::catch_panic(|| {
+        TOKIO_RUNTIME.block_on(crate::get_vtxo_count(&[
+            VtxoStateKind::Spendable,
+            VtxoStateKind::Spent,
+            VtxoStateKind::Locked,
+        ]))
+    })
+}
+
+pub(crate) fn force_vtxo_state(vtxo_id: &str, state: &str) -> anyhow::Result<()> {
+    crate::catch_panic(|| {
+        let vtxo_id = utils::parse_vtxo_id(vtxo_id)?;
+        let state = match state {
+            "Spendable" => VtxoState::Spendable,
+            "Locked" => VtxoState::Locked { movement_id: None },
+            "Spent" => VtxoState::Spent,
+            _ => bail!("Invalid vtxo state: '{}'", state),
+        };
+        TOKIO_RUNTIME.block_on(crate::force_vtxo_state(vtxo_id, state))
+    })
+}
+
+pub(crate) fn get_expiring_vtxos(threshold: u32) -> anyhow::Result<Vec<BarkVtxo>> {
+    crate::catch_panic(|| {
+        let expiring_vtxos = crate::TOKIO_RUNTIME.block_on(crate::get_expiring_vtxos(threshold))?;
+        Ok(expiring_vtxos
+            .into_iter()
+            .map(utils::wallet_vtxo_to_bark_vtxo)
+            .collect())
+    })
+}
+
+pub(crate) fn get_first_expiring_vtxo_blockheight() -> anyhow::Result<*const u32> {
+    crate::catch_panic(|| {
+        let blockheight =
+            crate::TOKIO_RUNTIME.block_on(crate::get_first_expiring_vtxo_blockheight())?;
+        match blockheight {
+            Some(height) => Ok(Box::into_raw(Box::new(height))),
+            None => Ok(std::ptr::null()),
+        }
+    })
+}
+
+pub(crate) fn get_next_required_refresh_blockheight() -> anyhow::Result<*const u32> {
+    crate::catch_panic(|| {
+        let blockheight =
+            crate::TOKIO_RUNTIME.block_on(crate::get_next_required_refresh_blockheight())?;
+        match blockheight {
+            Some(height) => Ok(Box::into_raw(Box::new(height))),
+            None => Ok(std::ptr::null()),
+        }
+    })
+}
+
+pub(crate) fn bolt11_invoice(amount_sat: u64) -> anyhow::Result<ffi::Bolt11Invoice> {
+    crate::catch_panic(|| {
+        let invoice = crate::TOKIO_RUNTIME
+            .block_on(crate::bolt11_invoice(units::amount_from_sat(amount_sat)))?;
+        Ok(ffi::Bolt11Invoice {
+            bolt11_invoice: invoice.to_string(),
+            payment_secret: invoice.payment_secret().to_string(),
+            payment_hash: invoice.payment_hash().to_string(),
+        })
+    })
+}
+
+pub(crate) fn prune_expired_receives() -> anyhow::Result<Vec<String>> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::prune_expired_receives()))
+}
+
+pub(crate) fn recreate_invoice(payment_hash: &str) -> anyhow::Result<ffi::Bolt11Invoice> {
+    crate::catch_panic(|| {
+        let payment = PaymentHash::from_str(payment_hash)
+            .with_context(|| format!("Invalid payment hash format: '{}'", payment_hash))?;
+        let invoice = crate::TOKIO_RUNTIME.block_on(crate::recreate_invoice(payment))?;
+        Ok(ffi::Bolt11Invoice {
+            bolt11_invoice: invoice.to_string(),
+            payment_secret: invoice.payment_secret().to_string(),
+            payment_hash: invoice.payment_hash().to_string(),
+        })
+    })
+}
+
+pub(crate) fn lightning_receive_status(
+    payment_hash: String,
+) -> anyhow::Result<*const ffi::LightningReceive> {
+    crate::catch_panic(|| {
+        let payment = bark::ark::lightning::PaymentHash::from_str(&payment_hash)
+            .with_context(|| format!("Invalid payment hash format: '{}'", payment_hash))?;
+        let status = crate::TOKIO_RUNTIME.block_on(crate::lightning_receive_status(payment))?;
+
+        if status.is_none() {
+            return Ok(std::ptr::null());
+        }
+
+        let status = Box::new(lightning_receive_to_ffi(&status.unwrap()));
+        Ok(Box::into_raw(status))
+    })
+}
+
+fn lightning_receive_to_ffi(
+    status: &bark::persist::models::LightningReceive,
+) -> ffi::LightningReceive {
+    ffi::LightningReceive {
+        payment_hash: status.payment_hash.to_string(),
+        payment_preimage: status.payment_preimage.to_string(),
+        invoice: status.invoice.to_string(),
+        preimage_revealed_at: status.preimage_revealed_at.map_or(std::ptr::null(), |v| {
+            Box::into_raw(Box::new(v.timestamp() as u64))
+        }),
+        finished_at: status.finished_at.map_or(std::ptr::null(), |v| {
+            Box::into_raw(Box::new(v.timestamp() as u64))
+        }),
+    }
+}
+
+fn lightning_receive_to_cxx(
+    receive: &bark::persist::models::LightningReceive,
+) -> ffi::CxxLightningReceive {
+    use bark::lightning_invoice::Bolt11InvoiceDescriptionRef;
+
+    let invoice = &receive.invoice;
+    let state = utils::LightningReceiveState::new(
+        receive.preimage_revealed_at.is_some(),
+        receive.finished_at.is_some(),
+        invoice.is_expired(),
+    );
+    ffi::CxxLightningReceive {
+        payment_hash: receive.payment_hash.to_string(),
+        bolt11: invoice.to_string(),
+        amount_msat: invoice.amount_milli_satoshis().unwrap_or(0),
+        description: match invoice.description() {
+            Bolt11InvoiceDescriptionRef::Direct(description) => description.to_string(),
+            Bolt11InvoiceDescriptionRef::Hash(_) => String::new(),
+        },
+        status: state.as_str().to_string(),
+        created_at: utils::format_utc_timestamp(invoice.duration_since_epoch().as_secs() as i64),
+    }
+}
+
+pub(crate) fn get_lightning_receive(
+    payment_hash: &str,
+) -> anyhow::Result<ffi::CxxLightningReceive> {
+    crate::catch_panic(|| {
+        let payment = bark::ark::lightning::PaymentHash::from_str(payment_hash)
+            .with_context(|| format!("Invalid payment hash format: '{}'", payment_hash))?;
+        let receive = crate::TOKIO_RUNTIME.block_on(crate::get_lightning_receive(payment))?;
+        Ok(lightning_receive_to_cxx(&receive))
+    })
+}
+
+pub(crate) fn get_all_lightning_receives() -> anyhow::Result<Vec<ffi::CxxLightningReceive>> {
+    crate::catch_panic(|| {
+        let receives = crate::TOKIO_RUNTIME.block_on(crate::get_all_lightning_receives())?;
+        Ok(receives.iter().map(lightning_receive_to_cxx).collect())
+    })
+}
+
+pub(crate) fn sync_pending_boards() -> anyhow::Result<()> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::sync_pending_boards()))
+}
+
+pub(crate) fn maintenance() -> anyhow::Result<()> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::maintenance()))
+}
+
+pub(crate) fn maintenance_delegated() -> anyhow::Result<()> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::maintenance_delegated()))
+}
+
+pub(crate) fn maintenance_with_onchain() -> anyhow::Result<()> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::maintenance_with_onchain()))
 }
 
 pub(crate) fn maintenance_with_onchain_delegated() -> anyhow::Result<()> {
-    crate::TOKIO_RUNTIME.block_on(crate::maintenance_with_onchain_delegated())
+    crate::catch_panic(|| {
+        crate::TOKIO_RUNTIME.block_on(crate::maintenance_with_onchain_delegated())
+    })
+}
+
+pub(crate) fn maintenance_refresh() -> anyhow::Result<()> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::maintenance_refresh()))
+}
+
+pub(crate) fn set_auto_refresh_policy(
+    enabled: bool,
+    threshold_blocks: u32,
+    quiet_hours_start: u8,
+    quiet_hours_end: u8,
+    timezone_offset_minutes: i32,
+) -> anyhow::Result<()> {
+    crate::catch_panic(|| {
+        let policy = crate::AutoRefreshPolicy {
+            enabled,
+            threshold_blocks,
+            quiet_hours_start,
+            quiet_hours_end,
+            timezone_offset_minutes,
+        };
+        crate::TOKIO_RUNTIME.block_on(crate::set_auto_refresh_policy(policy))
+    })
+}
+
+pub(crate) fn get_auto_refresh_policy() -> anyhow::Result<ffi::BarkAutoRefreshPolicy> {
+    crate::catch_panic(|| {
+        let policy = crate::TOKIO_RUNTIME.block_on(crate::get_auto_refresh_policy())?;
+        Ok(ffi::BarkAutoRefreshPolicy {
+            enabled: policy.enabled,
+            threshold_blocks: policy.threshold_blocks,
+            quiet_hours_start: policy.quiet_hours_start,
+            quiet_hours_end: policy.quiet_hours_end,
+            timezone_offset_minutes: policy.timezone_offset_minutes,
+        })
+    })
+}
+
+pub(crate) fn set_lightning_fee_cap(cap_bps: u32, floor_sat: u64) -> anyhow::Result<()> {
+    crate::catch_panic(|| {
+        crate::TOKIO_RUNTIME.block_on(crate::set_lightning_fee_cap(crate::LightningFeeCap {
+            cap_bps,
+            floor_sat,
+        }))
+    })
+}
+
+pub(crate) fn get_lightning_fee_cap() -> anyhow::Result<ffi::BarkLightningFeeCap> {
+    crate::catch_panic(|| {
+        let cap = crate::TOKIO_RUNTIME.block_on(crate::get_lightning_fee_cap())?;
+        Ok(ffi::BarkLightningFeeCap {
+            cap_bps: cap.cap_bps,
+            floor_sat: cap.floor_sat,
+        })
+    })
 }
 
-pub(crate) fn maintenance_refresh() -> anyhow::Result<()> {
-    crate::TOKIO_RUNTIME.block_on(crate::maintenance_refresh())
+pub(crate) fn set_allow_spending_unrefreshed_arkoor(allow: bool) -> anyhow::Result<()> {
+    crate::catch_panic(|| {
+        crate::TOKIO_RUNTIME.block_on(crate::set_allow_spending_unrefreshed_arkoor(allow))
+    })
+}
+
+pub(crate) fn allow_spending_unrefreshed_arkoor() -> anyhow::Result<bool> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::allow_spending_unrefreshed_arkoor()))
+}
+
+pub(crate) fn poll_events() -> anyhow::Result<Vec<ffi::BarkEvent>> {
+    crate::catch_panic(|| {
+        Ok(crate::take_events()
+            .into_iter()
+            .map(|event| ffi::BarkEvent {
+                kind: event.kind.as_str().to_string(),
+                payload_json: event.payload.to_string(),
+                created_at: event.created_at,
+            })
+            .collect())
+    })
+}
+
+pub(crate) fn get_metrics_snapshot() -> anyhow::Result<ffi::BarkMetricsSnapshot> {
+    crate::catch_panic(|| {
+        let snapshot = crate::metrics::metrics_snapshot();
+        Ok(ffi::BarkMetricsSnapshot {
+            entries: snapshot
+                .entries
+                .into_iter()
+                .map(|entry| ffi::BarkMetricEntry {
+                    operation: entry.operation.to_string(),
+                    duration_ms: entry.duration_ms,
+                    success: entry.success,
+                    error_class: entry.error_class.unwrap_or_default().to_string(),
+                    recorded_at: entry.recorded_at,
+                })
+                .collect(),
+            operations: snapshot
+                .operations
+                .into_iter()
+                .map(|stats| ffi::BarkOperationStats {
+                    operation: stats.operation.to_string(),
+                    count: stats.count,
+                    failure_count: stats.failure_count,
+                    p50_ms: stats.p50_ms,
+                    p95_ms: stats.p95_ms,
+                    max_ms: stats.max_ms,
+                })
+                .collect(),
+        })
+    })
+}
+
+pub(crate) fn reset_metrics() -> anyhow::Result<()> {
+    crate::catch_panic(|| {
+        crate::metrics::reset_metrics();
+        Ok(())
+    })
+}
+
+pub(crate) fn get_lock_diagnostics() -> anyhow::Result<ffi::BarkLockDiagnostics> {
+    crate::catch_panic(|| {
+        let diagnostics = crate::metrics::lock_diagnostics();
+        let holder = diagnostics.current_holder.as_ref();
+        Ok(ffi::BarkLockDiagnostics {
+            records: diagnostics
+                .records
+                .iter()
+                .map(|record| ffi::BarkLockRecord {
+                    operation: record.operation.to_string(),
+                    waited_ms: record.waited_ms,
+                    held_ms: record.held_ms,
+                    acquired_at: record.acquired_at,
+                })
+                .collect(),
+            current_holder: holder.map_or(String::new(), |h| h.operation.to_string()),
+            current_holder_since: holder.map_or(0, |h| h.since),
+        })
+    })
+}
+
+pub(crate) fn export_event_journal(
+    dest_path: &str,
+    from_ts: i64,
+    to_ts: i64,
+) -> anyhow::Result<u64> {
+    crate::catch_panic(|| {
+        crate::TOKIO_RUNTIME.block_on(crate::export_event_journal(
+            Path::new(dest_path),
+            from_ts,
+            to_ts,
+        ))
+    })
+}
+
+pub(crate) fn cancel_expired_htlcs() -> anyhow::Result<u32> {
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::cancel_expired_htlcs()))
+}
+
+pub(crate) fn get_next_round_eta() -> anyhow::Result<ffi::CxxNextRoundEta> {
+    crate::catch_panic(|| {
+        let eta = crate::TOKIO_RUNTIME.block_on(crate::get_next_round_eta())?;
+        let last_round = eta.last_round.as_ref();
+        Ok(ffi::CxxNextRoundEta {
+            eta_secs: eta.eta_secs.unwrap_or(eta.round_interval_secs),
+            eta_known: eta.eta_secs.is_some(),
+            last_round_txid: last_round.map_or(String::new(), |r| r.funding_txid.clone()),
+            last_round_at: last_round.map_or(0, |r| r.at),
+            last_round_wait_secs: last_round.map_or(0, |r| r.wait_secs),
+            round_interval_secs: eta.round_interval_secs,
+        })
+    })
 }
 
 pub(crate) fn refresh_server() -> anyhow::Result<()> {
-    crate::TOKIO_RUNTIME.block_on(crate::refresh_server())
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::refresh_server()))
 }
 
 pub(crate) fn sync() -> anyhow::Result<()> {
-    crate::TOKIO_RUNTIME.block_on(crate::sync())
+    crate::catch_panic(|| crate::TOKIO_RUNTIME.block_on(crate::sync()))
+}
+
+/// 0 if the wallet was never synced
+pub(crate) fn get_last_successful_sync_timestamp() -> anyhow::Result<i64> {
+    crate::catch_panic(|| {
+        let timestamp =
+            crate::TOKIO_RUNTIME.block_on(crate::get_last_successful_sync_timestamp())?;
+        Ok(timestamp.unwrap_or(0))
+    })
 }
 
 pub(crate) fn create_wallet(datadir: &str, opts: ffi::CreateOpts) -> anyhow::Result<()> {
-    let create_opts = utils::ffi_config_to_config(opts)?;
+    crate::catch_panic(|| {
+        let create_opts = utils::ffi_config_to_config(opts)?;
 
-    log::info!("Creating wallet with options: {:?}", create_opts);
+        log::info!("Creating wallet with options: {:?}", create_opts);
 
-    crate::TOKIO_RUNTIME.block_on(crate::create_wallet(Path::new(datadir), create_opts))
+        crate::TOKIO_RUNTIME.block_on(crate::create_wallet(Path::new(datadir), create_opts))
+    })
 }
 
 pub(crate) fn load_wallet(datadir: &str, config: ffi::CreateOpts) -> anyhow::Result<()> {
-    let mnemonic = bip39::Mnemonic::from_str(&config.mnemonic)
-        .with_context(|| format!("Invalid mnemonic format: '{}'", config.mnemonic))?;
+    crate::catch_panic(|| load_wallet_with_lock(datadir, config, false))
+}
 
+/// Loads the wallet, taking over the datadir lock if the process holding it
+/// died without releasing it.
+pub(crate) fn load_wallet_steal_lock(datadir: &str, config: ffi::CreateOpts) -> anyhow::Result<()> {
+    crate::catch_panic(|| load_wallet_with_lock(datadir, config, true))
+}
+
+fn load_wallet_with_lock(
+    datadir: &str,
+    config: ffi::CreateOpts,
+    steal_lock: bool,
+) -> anyhow::Result<()> {
     log::info!("Loading wallet with datadir: {}", datadir);
 
     let create_opts = utils::ffi_config_to_config(config)?;
+    let mnemonic = create_opts.mnemonic.expose_secret().clone();
 
     let (config, _) = utils::merge_config_opts(create_opts)?;
 
-    crate::TOKIO_RUNTIME.block_on(crate::load_wallet(Path::new(datadir), mnemonic, config))
+    crate::TOKIO_RUNTIME.block_on(crate::load_wallet(
+        Path::new(datadir),
+        mnemonic,
+        config,
+        steal_lock,
+    ))
 }
 
-pub(crate) fn board_amount(amount_sat: u64) -> anyhow::Result<ffi::BoardResult> {
-    let amount = bark::ark::bitcoin::Amount::from_sat(amount_sat);
-    let board_result = crate::TOKIO_RUNTIME.block_on(crate::board_amount(amount))?;
+pub(crate) fn get_board_requirements() -> anyhow::Result<ffi::CxxBoardRequirements> {
+    crate::catch_panic(|| {
+        let requirements = crate::TOKIO_RUNTIME.block_on(crate::get_board_requirements())?;
+        Ok(ffi::CxxBoardRequirements {
+            min_board_sat: requirements.min_board_sat,
+            max_board_sat: requirements.max_board_sat.unwrap_or(0),
+            estimated_onchain_fee_sat: requirements.estimated_onchain_fee_sat,
+            estimated_round_fee_sat: requirements.estimated_round_fee_sat,
+        })
+    })
+}
 
-    Ok(ffi::BoardResult {
-        vtxos: board_result
-            .vtxos
-            .iter()
-            .map(|vtxo| vtxo.to_string())
-            .collect(),
-        funding_txid: board_result.funding_tx.compute_txid().to_string(),
+pub(crate) fn board_amount(amount_sat: u64) -> anyhow::Result<ffi::CxxBoardResult> {
+    crate::catch_panic(|| {
+        let amount = units::amount_from_sat(amount_sat);
+        let board_result = crate::TOKIO_RUNTIME.block_on(crate::board_amount(amount))?;
+        Ok(board_result.into())
     })
 }
 
-pub(crate) fn board_all() -> anyhow::Result<ffi::BoardResult> {
-    let board_result = crate::TOKIO_RUNTIME.block_on(crate::board_all())?;
+pub(crate) fn board_all() -> anyhow::Result<ffi::CxxBoardResult> {
+    crate::catch_panic(|| {
+        let board_result = crate::TOKIO_RUNTIME.block_on(crate::board_all())?;
+        Ok(board_result.into())
+    })
+}
 
-    Ok(ffi::BoardResult {
-        vtxos: board_result
-            .vtxos
-            .iter()
-            .map(|vtxo| vtxo.to_string())
-            .collect(),
-        funding_txid: board_result.funding_tx.compute_txid().to_string(),
+impl From<crate::NitroBoardResult> for ffi::CxxBoardResult {
+    fn from(result: crate::NitroBoardResult) -> Self {
+        ffi::CxxBoardResult {
+            txid: result.txid,
+            amount_sat: result.amount_sat,
+            fee_sat: result.fee_sat,
+            vtxo_ids: result.vtxo_ids,
+        }
+    }
+}
+
+pub(crate) fn list_pending_boards() -> anyhow::Result<Vec<ffi::BarkPendingBoard>> {
+    crate::catch_panic(|| {
+        let boards = crate::TOKIO_RUNTIME.block_on(crate::list_pending_boards())?;
+        Ok(boards
+            .into_iter()
+            .map(|board| ffi::BarkPendingBoard {
+                funding_txid: board.funding_txid,
+                vout: board.vout,
+                amount_sat: board.amount_sat,
+                vtxo_ids: board.vtxo_ids,
+                confirmations: board.confirmations,
+                required_confirmations: board.required_confirmations,
+                state: board.state.as_str().to_string(),
+            })
+            .collect())
+    })
+}
+
+fn board_intent_to_ffi(intent: crate::BoardIntent) -> ffi::BarkBoardIntent {
+    ffi::BarkBoardIntent {
+        id: intent.id,
+        amount_sat: intent.amount_sat.unwrap_or_default(),
+        funding_txid: intent.funding_txid.unwrap_or_default(),
+        state: intent.state.as_str().to_string(),
+        error: intent.error.unwrap_or_default(),
+        created_at: intent.created_at,
+        updated_at: intent.updated_at,
+    }
+}
+
+pub(crate) fn resume_pending_boards() -> anyhow::Result<ffi::BoardResumeReport> {
+    crate::catch_panic(|| {
+        let report = crate::TOKIO_RUNTIME.block_on(crate::resume_pending_boards())?;
+        Ok(ffi::BoardResumeReport {
+            resumed: report
+                .resumed
+                .into_iter()
+                .map(board_intent_to_ffi)
+                .collect(),
+            abandoned: report
+                .abandoned
+                .into_iter()
+                .map(board_intent_to_ffi)
+                .collect(),
+            pending: report
+                .pending
+                .into_iter()
+                .map(board_intent_to_ffi)
+                .collect(),
+        })
+    })
+}
+
+pub(crate) fn parse_bip21_uri(uri: &str) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let payment = utils::parse_bip21_uri(uri)?;
+        serde_json::to_string(&payment).map_err(Into::into)
+    })
+}
+
+/// Zero amounts and empty strings are treated as absent.
+pub(crate) fn get_receive_bundle(amount_sat: *const u64) -> anyhow::Result<ffi::CxxReceiveBundle> {
+    crate::catch_panic(|| {
+        let amount = unsafe { amount_sat.as_ref().map(|r| *r) }.map(units::amount_from_sat);
+        let bundle = crate::TOKIO_RUNTIME.block_on(crate::get_receive_bundle(amount))?;
+        Ok(ffi::CxxReceiveBundle {
+            ark_address: bundle.ark_address,
+            onchain_address: bundle.onchain_address,
+            bolt11: bundle.bolt11.unwrap_or_default(),
+            unified_uri: bundle.unified_uri,
+        })
+    })
+}
+
+pub(crate) fn generate_bip21_uri(
+    address: &str,
+    amount_sat: u64,
+    label: &str,
+    bolt11: &str,
+) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        utils::generate_bip21_uri(
+            address,
+            (amount_sat > 0).then_some(amount_sat),
+            (!label.is_empty()).then_some(label),
+            (!bolt11.is_empty()).then_some(bolt11),
+        )
     })
 }
 
 pub(crate) fn validate_arkoor_address(address: &str) -> anyhow::Result<()> {
-    let address = bark::ark::Address::from_str(address)
-        .with_context(|| format!("Invalid address format: '{}'", address))?;
-    crate::TOKIO_RUNTIME.block_on(crate::validate_arkoor_address(address))
+    crate::catch_panic(|| {
+        let address = bark::ark::Address::from_str(address)
+            .with_context(|| format!("Invalid address format: '{}'", address))?;
+        crate::TOKIO_RUNTIME.block_on(crate::validate_arkoor_address(address))
+    })
 }
 
 pub(crate) fn send_arkoor_payment(
     destination: &str,
     amount_sat: u64,
 ) -> anyhow::Result<ArkoorPaymentResult> {
-    let amount = bark::ark::bitcoin::Amount::from_sat(amount_sat);
-    let dest = bark::ark::Address::from_str(destination)
-        .with_context(|| format!("Invalid destination address format: '{}'", destination))?;
-    let oor_result = crate::TOKIO_RUNTIME.block_on(crate::send_arkoor_payment(dest, amount))?;
+    crate::catch_panic(|| {
+        let amount = units::amount_from_sat(amount_sat);
+        let dest = bark::ark::Address::from_str(destination)
+            .with_context(|| format!("Invalid destination address format: '{}'", destination))?;
+        let outputs = crate::TOKIO_RUNTIME.block_on(crate::send_arkoor_payment(dest, amount))?;
 
-    Ok(ArkoorPaymentResult {
-        vtxos: oor_result.iter().map(utils::vtxo_to_bark_vtxo).collect(),
+        Ok(arkoor_payment_result(destination, amount_sat, &outputs))
+    })
+}
+
+fn arkoor_payment_result(
+    destination: &str,
+    amount_sat: u64,
+    outputs: &crate::ArkoorOutputs,
+) -> ArkoorPaymentResult {
+    let sent_vtxos = outputs
+        .sent
+        .iter()
+        .map(utils::vtxo_to_bark_vtxo)
+        .collect::<Vec<_>>();
+    let change_vtxos = outputs
+        .change
+        .iter()
+        .map(utils::vtxo_to_bark_vtxo)
+        .collect::<Vec<_>>();
+    ArkoorPaymentResult {
+        vtxos: sent_vtxos.iter().chain(&change_vtxos).cloned().collect(),
+        sent_vtxos,
+        change_vtxos,
+        change_amount_sat: outputs.change_amount().to_sat(),
         destination_pubkey: destination.to_string(),
         amount_sat,
+    }
+}
+
+pub(crate) fn send_arkoor_payment_from(
+    destination: &str,
+    amount_sat: u64,
+    vtxo_ids: Vec<String>,
+) -> anyhow::Result<ffi::ArkoorPaymentFromResult> {
+    crate::catch_panic(|| {
+        let amount = units::amount_from_sat(amount_sat);
+        let dest = bark::ark::Address::from_str(destination)
+            .with_context(|| format!("Invalid destination address format: '{}'", destination))?;
+        let vtxo_ids = vtxo_ids
+            .iter()
+            .map(|id| {
+                bark::ark::VtxoId::from_str(id)
+                    .with_context(|| format!("Invalid vtxo id: '{}'", id))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let result = crate::TOKIO_RUNTIME
+            .block_on(crate::send_arkoor_payment_from(dest, amount, vtxo_ids))?;
+
+        Ok(ffi::ArkoorPaymentFromResult {
+            payment: arkoor_payment_result(destination, amount_sat, &result.outputs),
+            consumed_vtxo_ids: result.consumed_vtxo_ids,
+            change_vtxo_id: result.change_vtxo_id.unwrap_or_default(),
+        })
+    })
+}
+
+pub(crate) fn get_pending_payments() -> anyhow::Result<Vec<ffi::CxxPendingPayment>> {
+    crate::catch_panic(|| {
+        let payments = crate::TOKIO_RUNTIME.block_on(crate::get_pending_payments())?;
+        Ok(payments
+            .into_iter()
+            .map(|p| ffi::CxxPendingPayment {
+                payment_hash: p.payment_hash,
+                bolt11: p.bolt11,
+                amount_msat: p.amount_msat.unwrap_or(0),
+                destination: p.destination.unwrap_or_default(),
+                started_at: p.started_at,
+                status: p.status.as_str().to_string(),
+            })
+            .collect())
+    })
+}
+
+/// Returns the preimage of the retried payment.
+pub(crate) fn retry_failed_payment(
+    payment_hash: &str,
+    max_fee_increase_pct: u32,
+    timeout_secs: u32,
+) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let opts = crate::RetryPaymentOpts {
+            max_fee_increase_pct,
+            timeout_secs,
+        };
+        let preimage =
+            crate::TOKIO_RUNTIME.block_on(crate::retry_failed_payment(payment_hash, opts))?;
+        Ok(preimage.to_lower_hex_string())
     })
 }
 
 pub(crate) fn pay_lightning_invoice(
     destination: &str,
     amount_sat: *const u64,
+    max_fee_sat: *const u64,
 ) -> anyhow::Result<ffi::LightningSend> {
-    let amount_opt =
-        unsafe { amount_sat.as_ref().map(|r| *r) }.map(bark::ark::bitcoin::Amount::from_sat);
-
-    let invoice = lightning::Invoice::from_str(destination)?;
-
-    let send_result =
-        crate::TOKIO_RUNTIME.block_on(crate::pay_lightning_invoice(invoice, amount_opt))?;
-
-    Ok(ffi::LightningSend {
-        htlc_vtxos: send_result
-            .htlc_vtxos
-            .into_iter()
-            .map(utils::wallet_vtxo_to_bark_vtxo)
-            .collect(),
-        amount: send_result.amount.to_sat(),
-        invoice: send_result.invoice.to_string(),
-        payment_hash: send_result.invoice.payment_hash().to_string(),
-        movement_id: send_result.movement_id.0,
-        preimage: send_result
-            .preimage
-            .map_or(String::new(), |p| p.to_lower_hex_string()),
+    crate::catch_panic(|| {
+        let amount_opt = unsafe { amount_sat.as_ref().map(|r| *r) }.map(units::amount_from_sat);
+        let max_fee = unsafe { max_fee_sat.as_ref().map(|r| *r) }.map(units::amount_from_sat);
+
+        let invoice = lightning::Invoice::from_str(destination)?;
+
+        let send_result = crate::TOKIO_RUNTIME
+            .block_on(crate::pay_lightning_invoice(invoice, amount_opt, max_fee))?;
+
+        Ok(ffi::LightningSend {
+            htlc_vtxos: send_result
+                .htlc_vtxos
+                .into_iter()
+                .map(utils::wallet_vtxo_to_bark_vtxo)
+                .collect(),
+            amount: send_result.amount.to_sat(),
+            invoice: send_result.invoice.to_string(),
+            payment_hash: send_result.invoice.payment_hash().to_string(),
+            movement_id: send_result.movement_id.0,
+            preimage: send_result
+                .preimage
+                .map_or(String::new(), |p| p.to_lower_hex_string()),
+        })
     })
 }
 
@@ -628,28 +2404,55 @@ pub(crate) fn pay_lightning_offer(
     offer: &str,
     amount_sat: *const u64,
 ) -> anyhow::Result<ffi::LightningSend> {
-    let amount_opt =
-        unsafe { amount_sat.as_ref().map(|r| *r) }.map(bark::ark::bitcoin::Amount::from_sat);
-
-    let offer = lightning::Offer::from_str(offer)
-        .map_err(|err| anyhow::anyhow!("Failed to parse bolt12 offer: {:?}", err))?;
-
-    let send_result =
-        crate::TOKIO_RUNTIME.block_on(crate::pay_lightning_offer(offer.clone(), amount_opt))?;
+    crate::catch_panic(|| {
+        let amount_opt = unsafe { amount_sat.as_ref().map(|r| *r) }.map(units::amount_from_sat);
+
+        let offer = lightning::Offer::from_str(offer)
+            .map_err(|err| anyhow::anyhow!("Failed to parse bolt12 offer: {:?}", err))?;
+
+        let send_result =
+            crate::TOKIO_RUNTIME.block_on(crate::pay_lightning_offer(offer.clone(), amount_opt))?;
+
+        Ok(ffi::LightningSend {
+            htlc_vtxos: send_result
+                .htlc_vtxos
+                .into_iter()
+                .map(utils::wallet_vtxo_to_bark_vtxo)
+                .collect(),
+            amount: send_result.amount.to_sat(),
+            invoice: send_result.invoice.to_string(),
+            payment_hash: send_result.invoice.payment_hash().to_string(),
+            movement_id: send_result.movement_id.0,
+            preimage: send_result
+                .preimage
+                .map_or(String::new(), |p| p.to_lower_hex_string()),
+        })
+    })
+}
 
-    Ok(ffi::LightningSend {
-        htlc_vtxos: send_result
-            .htlc_vtxos
-            .into_iter()
-            .map(utils::wallet_vtxo_to_bark_vtxo)
-            .collect(),
-        amount: send_result.amount.to_sat(),
-        invoice: send_result.invoice.to_string(),
-        payment_hash: send_result.invoice.payment_hash().to_string(),
-        movement_id: send_result.movement_id.0,
-        preimage: send_result
-            .preimage
-            .map_or(String::new(), |p| p.to_lower_hex_string()),
+pub(crate) fn resolve_lightning_address(
+    addr: &str,
+    amount_sat: u64,
+    comment: &str,
+) -> anyhow::Result<ffi::CxxResolvedInvoice> {
+    crate::catch_panic(|| {
+        let amount = units::amount_from_sat(amount_sat);
+        let comment_opt = if comment.is_empty() {
+            None
+        } else {
+            Some(comment)
+        };
+        let resolved = crate::TOKIO_RUNTIME.block_on(crate::resolve_lightning_address(
+            addr,
+            amount,
+            comment_opt,
+        ))?;
+        Ok(ffi::CxxResolvedInvoice {
+            bolt11: resolved.invoice.to_string(),
+            description: resolved.description,
+            amount_sat: resolved.amount_sat,
+            expires_at: resolved.expires_at,
+        })
     })
 }
 
@@ -657,121 +2460,135 @@ pub(crate) fn pay_lightning_address(
     addr: &str,
     amount_sat: u64,
     comment: &str,
+    max_fee_sat: *const u64,
 ) -> anyhow::Result<ffi::LightningSend> {
-    let amount = bark::ark::bitcoin::Amount::from_sat(amount_sat);
-    let comment_opt = if comment.is_empty() {
-        None
-    } else {
-        Some(comment)
-    };
-    let send_result =
-        crate::TOKIO_RUNTIME.block_on(crate::pay_lightning_address(addr, amount, comment_opt))?;
-
-    Ok(ffi::LightningSend {
-        htlc_vtxos: send_result
-            .htlc_vtxos
-            .into_iter()
-            .map(utils::wallet_vtxo_to_bark_vtxo)
-            .collect(),
-        amount: send_result.amount.to_sat(),
-        invoice: send_result.invoice.to_string(),
-        payment_hash: send_result.invoice.payment_hash().to_string(),
-        movement_id: send_result.movement_id.0,
-        preimage: send_result
-            .preimage
-            .map_or(String::new(), |p| p.to_lower_hex_string()),
+    crate::catch_panic(|| {
+        let amount = units::amount_from_sat(amount_sat);
+        let comment_opt = if comment.is_empty() {
+            None
+        } else {
+            Some(comment)
+        };
+        let max_fee = unsafe { max_fee_sat.as_ref().map(|r| *r) }.map(units::amount_from_sat);
+        let send_result = crate::TOKIO_RUNTIME.block_on(crate::pay_lightning_address(
+            addr,
+            amount,
+            comment_opt,
+            max_fee,
+        ))?;
+
+        Ok(ffi::LightningSend {
+            htlc_vtxos: send_result
+                .htlc_vtxos
+                .into_iter()
+                .map(utils::wallet_vtxo_to_bark_vtxo)
+                .collect(),
+            amount: send_result.amount.to_sat(),
+            invoice: send_result.invoice.to_string(),
+            payment_hash: send_result.invoice.payment_hash().to_string(),
+            movement_id: send_result.movement_id.0,
+            preimage: send_result
+                .preimage
+                .map_or(String::new(), |p| p.to_lower_hex_string()),
+        })
+    })
+}
+
+/// Returns the payment result as JSON, see [`crate::PaymentResult`].
+pub(crate) fn send_payment(
+    destination: &str,
+    amount_sat: u64,
+    comment: &str,
+    no_sync: bool,
+) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let amount_sat = (amount_sat > 0).then_some(amount_sat);
+        let comment = (!comment.is_empty()).then(|| comment.to_string());
+        crate::TOKIO_RUNTIME.block_on(crate::send_payment(
+            destination,
+            amount_sat,
+            comment,
+            no_sync,
+        ))
     })
 }
 
 pub(crate) fn send_onchain(destination: &str, amount_sat: u64) -> anyhow::Result<String> {
-    let amount = bark::ark::bitcoin::Amount::from_sat(amount_sat);
-    let address_unchecked = bitcoin::Address::from_str(destination)
-        .with_context(|| format!("Invalid destination address format: '{}'", destination))?;
-
-    let ark_info = crate::TOKIO_RUNTIME.block_on(crate::get_ark_info())?;
-
-    // Now require the network to match the wallet's network
-    let destination_address = address_unchecked
-        .require_network(ark_info.network)
-        .with_context(|| {
-            format!(
-                "address '{}' is not valid for configured network {}",
-                destination, ark_info.network
-            )
+    crate::catch_panic(|| {
+        let amount = units::amount_from_sat(amount_sat);
+        let result = crate::TOKIO_RUNTIME.block_on(async {
+            let destination_address = crate::parse_wallet_address(destination).await?;
+            crate::send_onchain(destination_address, amount).await
         })?;
 
-    let result = crate::TOKIO_RUNTIME.block_on(crate::send_onchain(destination_address, amount))?;
+        Ok(result.to_string())
+    })
+}
+
+pub(crate) fn send_onchain_many(
+    outputs: Vec<ffi::SendManyOutput>,
+) -> anyhow::Result<Vec<ffi::OnchainOutputResult>> {
+    crate::catch_panic(|| {
+        let network = crate::TOKIO_RUNTIME.block_on(crate::wallet_network())?;
+        let outputs = outputs
+            .iter()
+            .map(|output| {
+                let address = utils::parse_onchain_address(&output.destination, network)?;
+                Ok((address, units::amount_from_sat(output.amount_sat)))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
 
-    Ok(result.to_string())
+        let results = crate::TOKIO_RUNTIME.block_on(crate::send_onchain_many(outputs))?;
+        Ok(results
+            .into_iter()
+            .map(|result| ffi::OnchainOutputResult {
+                destination: result.destination,
+                amount_sat: result.amount_sat,
+                txid: result.txid.unwrap_or_default(),
+                error: result.error.unwrap_or_default(),
+            })
+            .collect())
+    })
 }
 
 pub(crate) fn offboard_specific(
     vtxo_ids: Vec<String>,
     destination_address: &str,
 ) -> anyhow::Result<String> {
-    let ids = vtxo_ids
-        .into_iter()
-        .map(|s| bark::ark::VtxoId::from_str(&s))
-        .collect::<Result<Vec<_>, _>>()?;
-
-    let ark_info = crate::TOKIO_RUNTIME.block_on(crate::get_ark_info())?;
-
-    let destination_address_opt =
-        Address::<address::NetworkUnchecked>::from_str(destination_address).with_context(|| {
-            format!(
-                "Invalid destination address format: '{}'",
-                destination_address
-            )
-        })?;
-    let addr = destination_address_opt
-        .require_network(ark_info.network)
-        .with_context(|| {
-            format!(
-                "Address '{}' is not valid for configured network {:?}",
-                destination_address, ark_info.network
-            )
-        })?;
+    crate::catch_panic(|| {
+        let ids = utils::parse_vtxo_ids(&vtxo_ids)?;
 
-    if ids.is_empty() {
-        bail!("At least one VTXO ID must be provided for specific offboarding");
-    }
+        let addr =
+            crate::TOKIO_RUNTIME.block_on(crate::parse_wallet_address(destination_address))?;
 
-    info!(
-        "Attempting to offboard {} specific VTXOs to {:?}",
-        ids.len(),
-        addr
-    );
+        if ids.is_empty() {
+            bail!("At least one VTXO ID must be provided for specific offboarding");
+        }
+
+        info!(
+            "Attempting to offboard {} specific VTXOs to {:?}",
+            ids.len(),
+            addr
+        );
 
-    let offboard_specific_result =
-        crate::TOKIO_RUNTIME.block_on(crate::offboard_specific(ids, addr))?;
+        let offboard_specific_result =
+            crate::TOKIO_RUNTIME.block_on(crate::offboard_specific(ids, addr))?;
 
-    Ok(offboard_specific_result.encode_hex())
+        Ok(offboard_specific_result.encode_hex())
+    })
 }
 
 pub(crate) fn offboard_all(destination_address: &str) -> anyhow::Result<String> {
-    let ark_info = crate::TOKIO_RUNTIME.block_on(crate::get_ark_info())?;
-
-    let destination_address_opt =
-        Address::<address::NetworkUnchecked>::from_str(destination_address).with_context(|| {
-            format!(
-                "Invalid destination address format: '{}'",
-                destination_address
-            )
-        })?;
-    let addr = destination_address_opt
-        .require_network(ark_info.network)
-        .with_context(|| {
-            format!(
-                "Address '{}' is not valid for configured network {:?}",
-                destination_address, ark_info.network
-            )
-        })?;
+    crate::catch_panic(|| {
+        let addr =
+            crate::TOKIO_RUNTIME.block_on(crate::parse_wallet_address(destination_address))?;
 
-    info!("Attempting to offboard all VTXOs to {:?}", addr);
+        info!("Attempting to offboard all VTXOs to {:?}", addr);
 
-    let offboard_all_result = crate::TOKIO_RUNTIME.block_on(crate::offboard_all(addr))?;
+        let offboard_all_result = crate::TOKIO_RUNTIME.block_on(crate::offboard_all(addr))?;
 
-    Ok(offboard_all_result.encode_hex())
+        Ok(offboard_all_result.encode_hex())
+    })
 }
 
 pub(crate) fn try_claim_lightning_receive(
@@ -779,94 +2596,213 @@ pub(crate) fn try_claim_lightning_receive(
     wait: bool,
     token: *const String,
 ) -> anyhow::Result<ffi::LightningReceive> {
-    let payment_hash = PaymentHash::from_str(&payment_hash)?;
-    let token_opt = unsafe { token.as_ref().map(|s| s.clone()) };
+    crate::catch_panic(|| {
+        let payment_hash = PaymentHash::from_str(&payment_hash)?;
+        let token_opt = unsafe { token.as_ref().map(|s| s.clone()) };
 
-    let status = TOKIO_RUNTIME.block_on(crate::try_claim_lightning_receive(
-        payment_hash,
-        wait,
-        token_opt,
-    ))?;
+        let status = TOKIO_RUNTIME.block_on(crate::try_claim_lightning_receive(
+            payment_hash,
+            wait,
+            token_opt,
+        ))?;
 
-    Ok(ffi::LightningReceive {
-        payment_hash: status.payment_hash.to_string(),
-        payment_preimage: status.payment_preimage.to_string(),
-        invoice: status.invoice.to_string(),
-        preimage_revealed_at: status.preimage_revealed_at.map_or(std::ptr::null(), |v| {
-            Box::into_raw(Box::new(v.timestamp() as u64))
-        }),
-        finished_at: status.finished_at.map_or(std::ptr::null(), |v| {
-            Box::into_raw(Box::new(v.timestamp() as u64))
-        }),
+        Ok(lightning_receive_to_ffi(&status))
     })
 }
 
 pub(crate) fn try_claim_all_lightning_receives(wait: bool) -> anyhow::Result<()> {
-    crate::TOKIO_RUNTIME.block_on(crate::try_claim_all_lightning_receives(wait))?;
-    Ok(())
+    crate::catch_panic(|| {
+        crate::TOKIO_RUNTIME.block_on(crate::try_claim_all_lightning_receives(wait))?;
+        Ok(())
+    })
+}
+
+pub(crate) fn claim_all_lightning_receives_with_retry(max_wait_secs: u64) -> anyhow::Result<()> {
+    crate::catch_panic(|| {
+        crate::TOKIO_RUNTIME.block_on(crate::claim_all_lightning_receives_with_retry(
+            max_wait_secs,
+        ))
+    })
+}
+
+pub(crate) fn cancel_claim_retries() -> anyhow::Result<()> {
+    crate::catch_panic(|| {
+        crate::cancel_claim_retries();
+        Ok(())
+    })
+}
+
+pub(crate) fn get_at_risk_receives() -> anyhow::Result<Vec<ffi::CxxAtRiskReceive>> {
+    crate::catch_panic(|| {
+        Ok(crate::get_at_risk_receives()
+            .into_iter()
+            .map(|r| ffi::CxxAtRiskReceive {
+                payment_hash: r.payment_hash,
+                amount_msat: r.amount_msat.unwrap_or(0),
+                blocks_left: r.blocks_left,
+                failed_attempts: r.failed_attempts,
+                last_error: r.last_error,
+            })
+            .collect())
+    })
+}
+
+pub(crate) fn decode_lnurl(lnurl: &str) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let decoded = TOKIO_RUNTIME.block_on(crate::decode_lnurl(lnurl))?;
+        serde_json::to_string(&decoded).map_err(Into::into)
+    })
+}
+
+pub(crate) fn process_lnurl_withdraw(lnurl: &str) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let (_, preimage) = TOKIO_RUNTIME.block_on(crate::process_lnurl_withdraw(lnurl))?;
+        Ok(preimage.to_string())
+    })
 }
 
 pub(crate) fn check_lightning_payment(payment_hash: String, wait: bool) -> anyhow::Result<String> {
-    let payment_hash = PaymentHash::from_str(&payment_hash)?;
-    let result =
-        crate::TOKIO_RUNTIME.block_on(crate::check_lightning_payment(payment_hash, wait))?;
-    Ok(result.map_or(String::new(), |p| p.to_lower_hex_string()))
+    crate::catch_panic(|| {
+        let payment_hash = PaymentHash::from_str(&payment_hash)?;
+        let result =
+            crate::TOKIO_RUNTIME.block_on(crate::check_lightning_payment(payment_hash, wait))?;
+        Ok(result.map_or(String::new(), |p| p.to_lower_hex_string()))
+    })
 }
 
 pub(crate) fn sync_exits() -> anyhow::Result<()> {
-    TOKIO_RUNTIME.block_on(crate::sync_exits())
+    crate::catch_panic(|| TOKIO_RUNTIME.block_on(crate::sync_exits()))
+}
+
+pub(crate) fn sync_exits_with_progress() -> anyhow::Result<Vec<ffi::CxxExitStatus>> {
+    crate::catch_panic(|| {
+        let statuses = TOKIO_RUNTIME.block_on(crate::sync_exits_with_progress())?;
+        Ok(statuses
+            .into_iter()
+            .map(|status| ffi::CxxExitStatus {
+                vtxo_id: status.vtxo_id,
+                amount_sat: status.amount_sat,
+                state: status.state,
+                claimable: status.claimable,
+            })
+            .collect())
+    })
 }
 
 pub(crate) fn sync_pending_rounds() -> anyhow::Result<()> {
-    TOKIO_RUNTIME.block_on(crate::sync_pending_rounds())
+    crate::catch_panic(|| TOKIO_RUNTIME.block_on(crate::sync_pending_rounds()))
+}
+
+fn recipient_to_ffi(recipient: utils::Recipient) -> ffi::BarkRecipient {
+    ffi::BarkRecipient {
+        name: recipient.name.unwrap_or_default(),
+        destination: recipient.destination,
+        destination_type: recipient.destination_type,
+        last_used_at: recipient.last_used_at.map_or(0, |ts| ts as u64),
+        times_used: recipient.times_used,
+    }
+}
+
+pub(crate) fn get_recent_recipients(
+    limit: u32,
+    payment_type_filter: &str,
+) -> anyhow::Result<Vec<ffi::BarkRecipient>> {
+    crate::catch_panic(|| {
+        let filter = if payment_type_filter.is_empty() {
+            None
+        } else {
+            Some(payment_type_filter)
+        };
+        let recipients =
+            TOKIO_RUNTIME.block_on(crate::get_recent_recipients(limit as usize, filter))?;
+        Ok(recipients.into_iter().map(recipient_to_ffi).collect())
+    })
+}
+
+pub(crate) fn get_address_book() -> anyhow::Result<Vec<ffi::BarkRecipient>> {
+    crate::catch_panic(|| {
+        let entries = TOKIO_RUNTIME.block_on(crate::get_address_book())?;
+        Ok(entries.into_iter().map(recipient_to_ffi).collect())
+    })
+}
+
+pub(crate) fn add_address_book_entry(name: &str, destination: &str) -> anyhow::Result<()> {
+    crate::catch_panic(|| TOKIO_RUNTIME.block_on(crate::add_address_book_entry(name, destination)))
+}
+
+pub(crate) fn remove_address_book_entry(destination: &str) -> anyhow::Result<bool> {
+    crate::catch_panic(|| TOKIO_RUNTIME.block_on(crate::remove_address_book_entry(destination)))
 }
 
 // Onchain methods
 
 pub(crate) fn onchain_list_unspent() -> anyhow::Result<String> {
-    let unspent = TOKIO_RUNTIME.block_on(crate::onchain::list_unspent())?;
-    serde_json::to_string(&unspent).map_err(Into::into)
+    crate::catch_panic(|| {
+        let unspent = TOKIO_RUNTIME.block_on(crate::onchain::list_unspent())?;
+        serde_json::to_string(&unspent).map_err(Into::into)
+    })
 }
 
 pub(crate) fn onchain_sync() -> anyhow::Result<()> {
-    crate::TOKIO_RUNTIME.block_on(crate::onchain::sync())?;
-    Ok(())
+    crate::catch_panic(|| {
+        crate::TOKIO_RUNTIME.block_on(crate::onchain::sync())?;
+        Ok(())
+    })
 }
 
 pub(crate) fn onchain_address() -> anyhow::Result<String> {
-    let address = crate::TOKIO_RUNTIME.block_on(crate::onchain::address())?;
-    Ok(address.to_string())
+    crate::catch_panic(|| {
+        let address = crate::TOKIO_RUNTIME.block_on(crate::get_onchain_address())?;
+        Ok(address.to_string())
+    })
 }
 
 pub(crate) fn onchain_balance() -> anyhow::Result<ffi::OnChainBalance> {
-    let balance = crate::TOKIO_RUNTIME.block_on(crate::onchain::onchain_balance())?;
-    Ok(ffi::OnChainBalance {
-        immature: balance.immature.to_sat(),
-        trusted_pending: balance.trusted_pending.to_sat(),
-        untrusted_pending: balance.untrusted_pending.to_sat(),
-        confirmed: balance.confirmed.to_sat(),
+    crate::catch_panic(|| {
+        let balance = crate::TOKIO_RUNTIME.block_on(crate::onchain::onchain_balance())?;
+        Ok(ffi::OnChainBalance {
+            immature: balance.immature.to_sat(),
+            trusted_pending: balance.trusted_pending.to_sat(),
+            untrusted_pending: balance.untrusted_pending.to_sat(),
+            confirmed: balance.confirmed.to_sat(),
+        })
     })
 }
 
-pub(crate) fn onchain_utxos() -> anyhow::Result<String> {
-    let utxos = crate::TOKIO_RUNTIME.block_on(async { crate::onchain::utxos().await })?;
-
-    let res = utxos
-        .iter()
-        .map(|utxo| match utxo {
-            bark::onchain::Utxo::Local(local) => serde_json::json!({
-                "outpoint": format!("{}:{}", local.outpoint.txid, local.outpoint.vout),
-                "amount": local.amount.to_sat(),
-                "confirmation_height": local.confirmation_height.map_or(0, |_h| 0),
-            }),
-            bark::onchain::Utxo::Exit(exit) => serde_json::json!({
-                "vtxo": utils::vtxo_to_bark_vtxo(&exit.vtxo),
-                "height": exit.height
-            }),
+pub(crate) fn get_onchain_balance() -> anyhow::Result<ffi::CxxOnchainBalance> {
+    crate::catch_panic(|| {
+        let balance = crate::TOKIO_RUNTIME.block_on(crate::onchain::onchain_balance())?;
+        Ok(ffi::CxxOnchainBalance {
+            confirmed_sat: balance.confirmed.to_sat(),
+            trusted_pending_sat: balance.trusted_pending.to_sat(),
+            untrusted_pending_sat: balance.untrusted_pending.to_sat(),
+            immature_sat: balance.immature.to_sat(),
+            total_sat: balance.total().to_sat(),
         })
-        .collect::<Vec<_>>();
+    })
+}
+
+pub(crate) fn onchain_utxos() -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let utxos = crate::TOKIO_RUNTIME.block_on(crate::get_onchain_utxos())?;
+        crate::onchain::utxos_to_json(&utxos)
+    })
+}
 
-    serde_json::to_string(&res).map_err(Into::into)
+pub(crate) fn get_onchain_utxos_v2() -> anyhow::Result<Vec<ffi::CxxUtxo>> {
+    crate::catch_panic(|| {
+        let utxos = crate::TOKIO_RUNTIME.block_on(crate::onchain::unspent_outputs())?;
+        Ok(utxos
+            .into_iter()
+            .map(|utxo| ffi::CxxUtxo {
+                txid: utxo.txid,
+                vout: utxo.vout,
+                amount_sat: utxo.amount_sat,
+                script_pubkey: utxo.script_pubkey,
+                confirmations: utxo.confirmations,
+            })
+            .collect())
+    })
 }
 
 pub(crate) fn onchain_send(
@@ -874,96 +2810,154 @@ pub(crate) fn onchain_send(
     amount_sat: u64,
     fee_rate: *const u64,
 ) -> anyhow::Result<OnchainPaymentResult> {
-    let amount = bark::ark::bitcoin::Amount::from_sat(amount_sat);
-
-    let ark_info = crate::TOKIO_RUNTIME.block_on(crate::get_ark_info())?;
-
-    // Validate optional address string
-    let address_unchecked = Address::<address::NetworkUnchecked>::from_str(destination)
-        .with_context(|| format!("invalid destination address format: '{}'", destination))?;
-
-    // Now require the network to match the wallet's network
-    let destination_address = address_unchecked
-        .require_network(ark_info.network)
-        .with_context(|| {
-            format!(
-                "address '{}' is not valid for configured network {}",
-                destination, ark_info.network
-            )
-        })?;
+    crate::catch_panic(|| {
+        let amount = units::amount_from_sat(amount_sat);
+        let fee_rate = match unsafe { fee_rate.as_ref() } {
+            Some(rate) => Some(FeeRate::from_sat_per_vb(*rate).context("Invalid fee rate")?),
+            None => None,
+        };
 
-    let txid = crate::TOKIO_RUNTIME.block_on(async {
-        let fee_rate = if fee_rate.is_null() {
-            let mut manager = crate::GLOBAL_WALLET_MANAGER.lock().await;
-            manager
-                .with_context_async(|ctx| async { Ok(ctx.wallet.chain.fee_rates().await.regular) })
-                .await?
-        } else {
-            FeeRate::from_sat_per_vb(unsafe { *fee_rate }).context("Invalid fee rate")?
+        let (destination_address, txid) = crate::TOKIO_RUNTIME.block_on(crate::send_onchain_to(
+            destination,
+            amount,
+            fee_rate,
+            true,
+        ))?;
+
+        Ok(OnchainPaymentResult {
+            txid: txid.to_string(),
+            amount_sat,
+            destination_address: destination_address.to_string(),
+        })
+    })
+}
+
+pub(crate) fn onchain_drain(destination: &str, fee_rate: *const u64) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let fee_rate = match unsafe { fee_rate.as_ref() } {
+            Some(rate) => Some(FeeRate::from_sat_per_vb(*rate).context("Invalid fee rate")?),
+            None => None,
         };
+        let txid =
+            crate::TOKIO_RUNTIME.block_on(crate::drain_onchain_to(destination, fee_rate, true))?;
+        Ok(txid.to_string())
+    })
+}
 
-        crate::onchain::send(destination_address.clone(), amount, fee_rate).await
-    })?;
+pub(crate) fn drain_onchain(destination: &str, no_sync: bool) -> anyhow::Result<String> {
+    crate::catch_panic(|| {
+        let txid = crate::TOKIO_RUNTIME.block_on(crate::drain_onchain(destination, no_sync))?;
+        Ok(txid.to_string())
+    })
+}
 
-    Ok(OnchainPaymentResult {
-        txid: txid.to_string(),
-        amount_sat,
-        destination_address: destination_address.to_string(),
+pub(crate) fn onchain_bump_fee(
+    txid: &str,
+    new_fee_rate_sat_vb: u64,
+) -> anyhow::Result<ffi::CxxFeeBumpResult> {
+    crate::catch_panic(|| {
+        let txid =
+            bitcoin::Txid::from_str(txid).with_context(|| format!("Invalid txid: {}", txid))?;
+        let fee_rate = FeeRate::from_sat_per_vb(new_fee_rate_sat_vb).context("Invalid fee rate")?;
+        let result = crate::TOKIO_RUNTIME.block_on(crate::onchain::bump_fee(txid, fee_rate))?;
+        Ok(ffi::CxxFeeBumpResult {
+            txid: result.txid.to_string(),
+            additional_fee_sat: result.additional_fee.to_sat(),
+        })
     })
 }
 
-pub(crate) fn onchain_drain(destination: &str, fee_rate: *const u64) -> anyhow::Result<String> {
-    let txid = crate::TOKIO_RUNTIME.block_on(async {
-        let mut manager = crate::GLOBAL_WALLET_MANAGER.lock().await;
-        let (address, fee_rate) = manager
-            .with_context_async(|ctx| async {
-                let net = ctx.wallet.properties().await?.network;
-                let address = Address::from_str(destination)?
-                    .require_network(net)
-                    .context("Address on wrong network")?;
-                let fee_rate = if fee_rate.is_null() {
-                    ctx.wallet.chain.fee_rates().await.regular
-                } else {
-                    FeeRate::from_sat_per_vb(unsafe { *fee_rate }).context("Invalid fee rate")?
-                };
-                Ok((address, fee_rate))
-            })
-            .await?;
+fn self_transfer_to_ffi(transfer: crate::onchain::SelfTransfer) -> ffi::CxxSelfTransfer {
+    ffi::CxxSelfTransfer {
+        txid: transfer.txid.to_string(),
+        address: transfer.address.to_string(),
+        amount_sat: transfer.amount.to_sat(),
+        fee_sat: transfer.fee.to_sat(),
+    }
+}
+
+pub(crate) fn consolidate_onchain(fee_rate: *const u64) -> anyhow::Result<ffi::CxxSelfTransfer> {
+    crate::catch_panic(|| {
+        let fee_rate = match unsafe { fee_rate.as_ref() } {
+            Some(rate) => Some(FeeRate::from_sat_per_vb(*rate).context("Invalid fee rate")?),
+            None => None,
+        };
+        let transfer = TOKIO_RUNTIME.block_on(crate::onchain::consolidate(fee_rate))?;
+        Ok(self_transfer_to_ffi(transfer))
+    })
+}
 
-        crate::onchain::drain(address, fee_rate).await
-    })?;
-    Ok(txid.to_string())
+pub(crate) fn send_to_self_onchain(
+    amount_sat: u64,
+    fee_rate: *const u64,
+) -> anyhow::Result<ffi::CxxSelfTransfer> {
+    crate::catch_panic(|| {
+        let amount = units::amount_from_sat(amount_sat);
+        let fee_rate = match unsafe { fee_rate.as_ref() } {
+            Some(rate) => Some(FeeRate::from_sat_per_vb(*rate).context("Invalid fee rate")?),
+            None => None,
+        };
+        let transfer = TOKIO_RUNTIME.block_on(crate::onchain::send_to_self(amount, fee_rate))?;
+        Ok(self_transfer_to_ffi(transfer))
+    })
 }
 
 pub(crate) fn onchain_send_many(
     outputs: Vec<ffi::SendManyOutput>,
     fee_rate: *const u64,
 ) -> anyhow::Result<String> {
-    let txid = crate::TOKIO_RUNTIME.block_on(async {
-        let mut manager = crate::GLOBAL_WALLET_MANAGER.lock().await;
-        let (destinations, fee_rate) = manager
-            .with_context_async(|ctx| async {
-                let mut destinations = Vec::new();
-                let net = ctx.wallet.properties().await?.network;
-                for output in outputs {
-                    let address = Address::from_str(&output.destination)
-                        .context("Invalid address format")?
-                        .require_network(net)
-                        .context("Address on wrong network")?;
-                    let amount = bark::ark::bitcoin::Amount::from_sat(output.amount_sat);
-                    destinations.push((address, amount));
-                }
-
-                let fee_rate = if fee_rate.is_null() {
-                    ctx.wallet.chain.fee_rates().await.regular
-                } else {
-                    FeeRate::from_sat_per_vb(unsafe { *fee_rate }).context("Invalid fee rate")?
-                };
-                Ok((destinations, fee_rate))
-            })
-            .await?;
+    crate::catch_panic(|| {
+        let txid = crate::TOKIO_RUNTIME.block_on(async {
+            let network = crate::wallet_network().await?;
+            let destinations = outputs
+                .iter()
+                .map(|output| {
+                    let address = utils::parse_onchain_address(&output.destination, network)?;
+                    Ok((address, units::amount_from_sat(output.amount_sat)))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            if fee_rate.is_null() {
+                return crate::onchain::send_many_with_config_fee(&destinations).await;
+            }
+            let fee_rate =
+                FeeRate::from_sat_per_vb(unsafe { *fee_rate }).context("Invalid fee rate")?;
+            crate::onchain::send_many(&destinations, fee_rate).await
+        })?;
+        Ok(txid.to_string())
+    })
+}
+
+pub(crate) fn export_onchain_descriptors() -> anyhow::Result<ffi::CxxOnchainDescriptors> {
+    crate::catch_panic(|| {
+        let descriptors = TOKIO_RUNTIME.block_on(crate::onchain::export_onchain_descriptors())?;
+        Ok(ffi::CxxOnchainDescriptors {
+            external: descriptors.external,
+            internal: descriptors.internal,
+            birthday_height: descriptors.birthday_height,
+            import_descriptors_json: descriptors.import_descriptors_json,
+        })
+    })
+}
+
+pub(crate) fn onchain_rescan(from_height: u32) -> anyhow::Result<ffi::CxxRescanSummary> {
+    crate::catch_panic(|| {
+        let summary = TOKIO_RUNTIME.block_on(crate::onchain::rescan(from_height))?;
+        Ok(ffi::CxxRescanSummary {
+            from_height: summary.from_height,
+            to_height: summary.to_height,
+            blocks_scanned: summary.blocks_scanned,
+            transactions_found: summary.transactions_found,
+            balance_delta_sat: summary.balance_delta_sat,
+            birthday_height: summary.birthday_height.unwrap_or(0),
+            has_birthday: summary.birthday_height.is_some(),
+        })
+    })
+}
 
-        crate::onchain::send_many(&destinations, fee_rate).await
-    })?;
-    Ok(txid.to_string())
+pub(crate) fn cancel_onchain_rescan() -> anyhow::Result<()> {
+    crate::catch_panic(|| {
+        crate::onchain::cancel_rescan();
+        Ok(())
+    })
 }
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use anyhow::{self, Context, bail};
+use logger::log::{info, warn};
+use rusqlite::OptionalExtension;
+
+use crate::lock::{DatadirLock, LOCK_FILE};
+use crate::store::{
+    checkpoint_sqlite_db, get_db_migration_version, open_bark_db_read_only, read_schema_version,
+    table_row_counts,
+};
+use crate::utils::{DB_FILE, STORE_FILE};
+
+/// Left in a datadir whose wallet was moved elsewhere, holding the new
+/// path. The old files stay as they were in case the move has to be undone.
+pub const MOVED_TOMBSTONE_FILE: &str = ".moved";
+
+/// What [`move_datadir`] copied and checked.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct DatadirMoveReport {
+    pub files_copied: u32,
+    pub bytes_copied: u64,
+    /// Last migration bark applied, the same in both datadirs
+    pub wallet_migration_version: u32,
+    /// Last migration of the local store, 0 if there is none
+    pub store_schema_version: u32,
+    /// Tables whose row counts matched in both datadirs
+    pub tables_verified: u32,
+    pub rows_verified: u64,
+}
+
+/// Migration versions and row counts of the databases in a datadir, to
+/// compare a copy against its original.
+#[derive(Debug, PartialEq, Eq)]
+struct DatadirFingerprint {
+    wallet_migration_version: u32,
+    store_schema_version: u32,
+    wallet_rows: BTreeMap<String, u64>,
+    store_rows: BTreeMap<String, u64>,
+}
+
+impl DatadirFingerprint {
+    fn read(datadir: &Path) -> anyhow::Result<Self> {
+        let wallet = open_bark_db_read_only(&datadir.join(DB_FILE))?;
+        let store_path = datadir.join(STORE_FILE);
+        let (store_schema_version, store_rows) = if store_path.exists() {
+            let store = open_bark_db_read_only(&store_path)?;
+            (read_schema_version(&store)?, table_row_counts(&store)?)
+        } else {
+            (0, BTreeMap::new())
+        };
+        Ok(DatadirFingerprint {
+            wallet_migration_version: get_db_migration_version(&wallet)?,
+            store_schema_version,
+            wallet_rows: table_row_counts(&wallet)?,
+            store_rows,
+        })
+    }
+}
+
+/// Where the wallet in `datadir` was moved to, if it was.
+pub fn moved_to(datadir: &Path) -> Option<PathBuf> {
+    let path = fs::read_to_string(datadir.join(MOVED_TOMBSTONE_FILE)).ok()?;
+    Some(PathBuf::from(path.trim_end()))
+}
+
+/// Checks `datadir` holds a wallet by reading bark's properties table.
+fn check_wallet_datadir(datadir: &Path) -> anyhow::Result<()> {
+    let db_path = datadir.join(DB_FILE);
+    if !db_path.exists() {
+        bail!("{} doesn't contain a wallet", datadir.display());
+    }
+    let conn = open_bark_db_read_only(&db_path)?;
+    conn.query_row("SELECT 1 FROM bark_properties LIMIT 1", [], |_| Ok(()))
+        .optional()
+        .with_context(|| format!("Failed to read wallet properties in {}", datadir.display()))?;
+    Ok(())
+}
+
+/// Copies the files under `from` to `to`, leaving out the datadir lock.
+/// Returns the number of files and bytes copied.
+fn copy_dir(from: &Path, to: &Path) -> anyhow::Result<(u32, u64)> {
+    fs::create_dir_all(to).with_context(|| format!("Failed to create {}", to.display()))?;
+
+    let (mut files, mut bytes) = (0, 0);
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_name() == LOCK_FILE {
+            continue;
+        }
+        let source = entry.path();
+        let dest = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            let (dir_files, dir_bytes) = copy_dir(&source, &dest)?;
+            files += dir_files;
+            bytes += dir_bytes;
+            continue;
+        }
+        bytes += fs::copy(&source, &dest).with_context(|| {
+            format!("Failed to copy {} to {}", source.display(), dest.display())
+        })?;
+        File::open(&dest)?.sync_all()?;
+        files += 1;
+    }
+    Ok((files, bytes))
+}
+
+/// Copies the wallet in `old` to `new`, checks the copy and marks `old` with
+/// a [`MOVED_TOMBSTONE_FILE`]. The wallet must not be loaded.
+///
+/// `new` must not exist or be empty. The copy is made next to it and only
+/// renamed to `new` once verified, so an interrupted move never leaves a
+/// partial wallet behind at `new`.
+pub fn move_datadir(old: &Path, new: &Path) -> anyhow::Result<DatadirMoveReport> {
+    if let Some(moved_to) = moved_to(old) {
+        bail!(
+            "The wallet in {} was already moved to {}",
+            old.display(),
+            moved_to.display()
+        );
+    }
+    check_wallet_datadir(old)?;
+    if new.starts_with(old) {
+        bail!("Can't move the wallet in {} into itself", old.display());
+    }
+    if new.exists() && fs::read_dir(new)?.next().is_some() {
+        bail!("{} is not empty", new.display());
+    }
+    let staging = match new.file_name().and_then(|name| name.to_str()) {
+        Some(name) => new.with_file_name(format!("{}.moving", name)),
+        None => bail!("Invalid datadir path {}", new.display()),
+    };
+
+    // Keeps any other process from opening the wallet while it is copied
+    let _lock = DatadirLock::acquire(old, false)?;
+    for file in [DB_FILE, STORE_FILE] {
+        let path = old.join(file);
+        if path.exists() {
+            checkpoint_sqlite_db(&path)?;
+        }
+    }
+    let original = DatadirFingerprint::read(old)?;
+
+    if staging.exists() {
+        warn!("Removing {} left by an interrupted move", staging.display());
+        fs::remove_dir_all(&staging)?;
+    }
+    let (files_copied, bytes_copied) = copy_dir(old, &staging)?;
+
+    let copy = DatadirFingerprint::read(&staging)?;
+    if copy != original {
+        fs::remove_dir_all(&staging)?;
+        bail!(
+            "The copy of the wallet in {} doesn't match the original: {:?} != {:?}",
+            old.display(),
+            copy,
+            original
+        );
+    }
+
+    if new.exists() {
+        fs::remove_dir(new)?;
+    }
+    fs::rename(&staging, new)
+        .with_context(|| format!("Failed to move {} to {}", staging.display(), new.display()))?;
+
+    let tombstone = old.join(MOVED_TOMBSTONE_FILE);
+    let tombstone_tmp = old.join(format!("{}.tmp", MOVED_TOMBSTONE_FILE));
+    fs::write(&tombstone_tmp, new.display().to_string())?;
+    File::open(&tombstone_tmp)?.sync_all()?;
+    fs::rename(&tombstone_tmp, &tombstone)?;
+
+    info!("Moved wallet from {} to {}", old.display(), new.display());
+    let rows = original
+        .wallet_rows
+        .values()
+        .chain(original.store_rows.values());
+    Ok(DatadirMoveReport {
+        files_copied,
+        bytes_copied,
+        wallet_migration_version: original.wallet_migration_version,
+        store_schema_version: original.store_schema_version,
+        tables_verified: (original.wallet_rows.len() + original.store_rows.len()) as u32,
+        rows_verified: rows.sum(),
+    })
+}
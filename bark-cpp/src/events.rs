@@ -0,0 +1,62 @@
+use std::collections::VecDeque;
+use std::sync::{LazyLock, Mutex};
+
+use crate::store::now_timestamp;
+
+/// Maximum number of events kept around until the app polls them.
+const MAX_QUEUED_EVENTS: usize = 1000;
+
+static EVENT_QUEUE: LazyLock<Mutex<VecDeque<WalletEvent>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::new()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletEventKind {
+    RoundParticipated,
+    VtxoQuarantined,
+    LightningReceiveExpired,
+    RescanProgress,
+    LightningReceiveAtRisk,
+    BoardConfirmed,
+    ExpectedPaymentReceived,
+}
+
+impl WalletEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WalletEventKind::RoundParticipated => "RoundParticipated",
+            WalletEventKind::VtxoQuarantined => "VtxoQuarantined",
+            WalletEventKind::LightningReceiveExpired => "LightningReceiveExpired",
+            WalletEventKind::RescanProgress => "RescanProgress",
+            WalletEventKind::LightningReceiveAtRisk => "LightningReceiveAtRisk",
+            WalletEventKind::BoardConfirmed => "BoardConfirmed",
+            WalletEventKind::ExpectedPaymentReceived => "ExpectedPaymentReceived",
+        }
+    }
+}
+
+/// Something that happened in the wallet without the app asking for it.
+#[derive(Debug, Clone)]
+pub struct WalletEvent {
+    pub kind: WalletEventKind,
+    pub payload: serde_json::Value,
+    pub created_at: i64,
+}
+
+/// Queues an event for the app. The oldest events are dropped once the queue is full.
+pub fn emit_event(kind: WalletEventKind, payload: serde_json::Value) {
+    let mut queue = EVENT_QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    if queue.len() >= MAX_QUEUED_EVENTS {
+        queue.pop_front();
+    }
+    queue.push_back(WalletEvent {
+        kind,
+        payload,
+        created_at: now_timestamp(),
+    });
+}
+
+/// Takes all queued events, oldest first.
+pub fn take_events() -> Vec<WalletEvent> {
+    let mut queue = EVENT_QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    queue.drain(..).collect()
+}
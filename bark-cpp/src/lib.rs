@@ -1,3 +1,5 @@
+#![warn(clippy::needless_pass_by_ref_mut)]
+
 use anyhow::{self, bail};
 use bark::persist::models::LightningSend;
 use bark::{self, ark::bitcoin::Address};
@@ -17,75 +19,312 @@ use bark::ark::lightning::PaymentHash;
 use bark::ark::lightning::{self, Preimage};
 use bark::lightning_invoice::Bolt11Invoice;
 use bark::lnurllib::lightning_address::LightningAddress;
-use bark::movement::Movement;
-use bark::onchain::OnchainWallet;
+use bark::movement::{Movement, MovementId};
+use bark::onchain::{OnchainWallet, Utxo};
 use bark::persist::BarkPersister;
 use bark::persist::models::{LightningReceive, PendingBoard};
 use bark::persist::sqlite::SqliteClient;
 use bark::round::RoundStatus;
+use bark::vtxo::{VtxoState, VtxoStateKind};
 use bdk_wallet::bitcoin::key::Keypair;
-use bdk_wallet::bitcoin::{Txid, bip32};
-use bitcoin_ext::BlockHeight;
+use bdk_wallet::bitcoin::{FeeRate, OutPoint, Transaction, Txid, bip32};
+use bdk_wallet::chain::ChainPosition;
+use bitcoin_ext::{BlockHeight, TxStatus};
 use tokio::runtime::Runtime;
 use tokio::sync::Mutex;
 mod cxx;
+mod datadir_move;
+mod events;
+mod lock;
+mod metrics;
 mod onchain;
+mod panics;
+mod receive_claims;
+mod round_failures;
+mod store;
+mod units;
 mod utils;
+mod vtxo_transitions;
 
 use bip39::Mnemonic;
-use logger::log::{debug, info};
-use std::path::Path;
+use lock::DatadirLock;
+use logger::log::{debug, info, warn};
+use metrics::timed;
+use receive_claims::{
+    claim_failures, note_claim_failure, receive_blocks_left, reset_claim_retry_cancel,
+    sleep_unless_cancelled,
+};
+use round_failures::on_round_failure;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::LazyLock;
 use std::sync::Once;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU8, Ordering};
 use utils::DB_FILE;
+use utils::STORE_FILE;
 use utils::try_create_wallet;
+use vtxo_transitions::{
+    ALL_VTXO_STATES, force_vtxo_state_unchecked, transition_vtxo_state, vtxo_state_kind_name,
+};
+use zeroize::Zeroize;
 
+pub use datadir_move::{DatadirMoveReport, MOVED_TOMBSTONE_FILE};
+pub use events::*;
+pub use lock::LockError;
+pub use panics::{InternalPanic, catch_panic, get_last_panic_report, init_panic_handler};
+pub use receive_claims::{FailedClaim, cancel_claim_retries};
+pub use round_failures::{RoundFailure, RoundFailureReason};
+pub use store::*;
 pub use utils::*;
+pub use vtxo_transitions::IllegalVtxoTransition;
 
 use std::str::FromStr;
 
 use anyhow::Context;
 #[cfg(test)]
+mod test_vectors;
+#[cfg(test)]
 mod tests;
 
 // Use a static Once to ensure the logger is initialized only once.
 static LOGGER_INIT: Once = Once::new();
-const ARK_PURPOSE_INDEX: u32 = 350;
+/// The BIP32 purpose index bark derives the wallet's Ark keys under.
+pub const DEFAULT_ARK_PURPOSE_INDEX: u32 = 350;
+
+/// Fails unless `purpose_index` is [`DEFAULT_ARK_PURPOSE_INDEX`]. bark's key
+/// derivation can't be configured, so keys under any other purpose would not
+/// belong to the wallet.
+pub fn check_ark_purpose_index(purpose_index: u32) -> anyhow::Result<()> {
+    if purpose_index != DEFAULT_ARK_PURPOSE_INDEX {
+        bail!(
+            "Ark purpose index {} not supported: bark derives the wallet's keys under {}",
+            purpose_index,
+            DEFAULT_ARK_PURPOSE_INDEX
+        );
+    }
+    Ok(())
+}
 
 pub static TOKIO_RUNTIME: LazyLock<Runtime> =
     LazyLock::new(|| Runtime::new().expect("Failed to create Tokio runtime"));
 
 // Global wallet manager instance
 static GLOBAL_WALLET_MANAGER: LazyLock<Mutex<WalletManager>> =
-    LazyLock::new(|| Mutex::new(WalletManager::new()));
+    LazyLock::new(|| Mutex::new(WalletManager::with_state(GLOBAL_WALLET_STATE.clone())));
+
+/// State of the global wallet manager, readable without locking it
+static GLOBAL_WALLET_STATE: LazyLock<Arc<AtomicU8>> =
+    LazyLock::new(|| Arc::new(AtomicU8::new(WalletState::NotLoaded as u8)));
+
+#[cfg(test)]
+pub(crate) static MANAGER_LOCK_COUNTS: LazyLock<
+    std::sync::Mutex<std::collections::HashMap<&'static str, usize>>,
+> = LazyLock::new(Default::default);
+
+/// The wallet manager, held by a named operation. Records how long the
+/// operation waited for and held the lock in the lock diagnostics.
+pub(crate) struct ManagerGuard {
+    guard: tokio::sync::MutexGuard<'static, WalletManager>,
+    operation: &'static str,
+    waited_ms: u64,
+    acquired: std::time::Instant,
+}
+
+impl std::ops::Deref for ManagerGuard {
+    type Target = WalletManager;
+
+    fn deref(&self) -> &WalletManager {
+        &self.guard
+    }
+}
+
+impl std::ops::DerefMut for ManagerGuard {
+    fn deref_mut(&mut self) -> &mut WalletManager {
+        &mut self.guard
+    }
+}
+
+impl Drop for ManagerGuard {
+    fn drop(&mut self) {
+        metrics::record_lock_released(
+            self.operation,
+            self.waited_ms,
+            self.acquired.elapsed().as_millis() as u64,
+        );
+    }
+}
+
+/// Acquires the global wallet manager on behalf of `operation`.
+pub(crate) async fn lock_manager(operation: &'static str) -> ManagerGuard {
+    #[cfg(test)]
+    {
+        let mut counts = MANAGER_LOCK_COUNTS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        *counts.entry(operation).or_default() += 1;
+    }
+    let blocked_by = metrics::current_lock_holder();
+    let started = std::time::Instant::now();
+    let guard = GLOBAL_WALLET_MANAGER.lock().await;
+    let waited_ms = started.elapsed().as_millis() as u64;
+    metrics::record_lock_acquired(operation, waited_ms, blocked_by);
+    ManagerGuard {
+        guard,
+        operation,
+        waited_ms,
+        acquired: std::time::Instant::now(),
+    }
+}
 
 // Wallet context that holds all wallet-related components
 pub struct WalletContext {
     pub wallet: Wallet,
     pub onchain_wallet: OnchainWallet,
+    pub store: Store,
+    /// The config in effect. Starts out as the config the wallet was opened
+    /// with; fields in [`LIVE_CONFIG_FIELDS`] can be changed while loaded.
+    pub config: Config,
+    /// The bark database, for queries the wallet doesn't expose
+    pub db: Arc<SqliteClient>,
+    pub datadir: PathBuf,
+    /// Fingerprint of the wallet's master key, from its properties
+    pub fingerprint: bip32::Fingerprint,
+    /// The invoice last handed out by [`get_receive_bundle`]
+    pub receive_invoice: Option<Bolt11Invoice>,
+    /// The address last handed out by [`new_address`], until it receives
+    /// a payment
+    pub pending_address: Option<bark::ark::Address>,
+    /// The last round we took part in since loading
+    pub last_round: Option<ObservedRound>,
+    /// Held for as long as the wallet is loaded
+    _lock: DatadirLock,
+}
+
+/// Lifecycle state of the wallet, see [`get_wallet_state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum WalletState {
+    NotLoaded,
+    Loaded,
+    /// A wallet is being created or loaded
+    Loading,
+    /// The last attempt to create or load the wallet failed
+    Error,
+}
+
+impl WalletState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => WalletState::Loaded,
+            2 => WalletState::Loading,
+            3 => WalletState::Error,
+            _ => WalletState::NotLoaded,
+        }
+    }
 }
 
-// Wallet manager that manages the wallet context lifecycle
+/// Wallet manager that manages the wallet context lifecycle
+///
+/// Operations reach the loaded wallet through the `with_context*` helpers.
+/// `with_context_ref` and `with_context_ref_async` hand out a shared
+/// `&WalletContext` and are meant for everything that only reads, while
+/// `with_context` and `with_context_async` hand out `&mut WalletContext` for
+/// operations that change the context itself, e.g. its config or the cached
+/// receive invoice, or that need the onchain wallet mutably. Either way the
+/// manager lock is held for the whole call; the shared variants just make the
+/// intent visible at the call site.
 pub struct WalletManager {
     context: Option<WalletContext>,
+    /// When set, calls that need the Ark server fail right away instead of
+    /// waiting on an unreachable server.
+    offline_mode: bool,
+    /// Shared so it can be read while the manager is locked, e.g. loading
+    state: Arc<AtomicU8>,
 }
 
 impl WalletManager {
     pub fn new() -> Self {
-        Self { context: None }
+        Self::with_state(Arc::new(AtomicU8::new(WalletState::NotLoaded as u8)))
+    }
+
+    fn with_state(state: Arc<AtomicU8>) -> Self {
+        Self {
+            context: None,
+            offline_mode: false,
+            state,
+        }
     }
 
     pub fn is_loaded(&self) -> bool {
         self.context.is_some()
     }
 
+    pub fn state(&self) -> WalletState {
+        WalletState::from_u8(self.state.load(Ordering::Acquire))
+    }
+
+    fn set_state(&self, state: WalletState) {
+        self.state.store(state as u8, Ordering::Release);
+    }
+
+    pub fn set_offline_mode(&mut self, offline: bool) {
+        info!(
+            "Offline mode {}",
+            if offline { "enabled" } else { "disabled" }
+        );
+        self.offline_mode = offline;
+    }
+
+    pub fn is_offline_mode(&self) -> bool {
+        self.offline_mode
+    }
+
+    /// Fails if the wallet is in offline mode, to be checked before any call
+    /// that talks to the Ark server.
+    pub fn ensure_online(&self) -> anyhow::Result<()> {
+        if self.offline_mode {
+            bail!("wallet is in offline mode");
+        }
+        Ok(())
+    }
+
+    /// A stable identifier of the loaded wallet: the hex fingerprint of its
+    /// master key, so apps can key their own storage without the mnemonic.
+    pub fn get_wallet_id(&self) -> anyhow::Result<String> {
+        self.with_context_ref(|ctx| Ok(ctx.fingerprint.to_string()))
+    }
+
     async fn create_wallet(&mut self, datadir: &Path, opts: CreateOpts) -> anyhow::Result<()> {
         debug!("Creating wallet in {}", datadir.display());
 
+        let previous = self.state();
+        self.set_state(WalletState::Loading);
+        let created = self.create_wallet_files(datadir, opts).await;
+        self.set_state(match &created {
+            Ok(()) => previous,
+            Err(_) => WalletState::Error,
+        });
+        created
+    }
+
+    async fn create_wallet_files(&self, datadir: &Path, opts: CreateOpts) -> anyhow::Result<()> {
+        let purpose_index = opts
+            .config
+            .ark_purpose_index
+            .unwrap_or(DEFAULT_ARK_PURPOSE_INDEX);
+        check_ark_purpose_index(purpose_index)?;
         let (config, net) = merge_config_opts(opts.clone())?;
 
-        try_create_wallet(datadir, net, config.clone(), Some(opts.mnemonic.clone())).await?;
+        try_create_wallet(
+            datadir,
+            net,
+            config.clone(),
+            Some(opts.mnemonic.expose_secret().clone()),
+        )
+        .await?;
+
+        Store::open(&datadir.join(STORE_FILE))?.set_ark_purpose_index(purpose_index)?;
 
         Ok(())
     }
@@ -95,25 +334,76 @@ impl WalletManager {
         datadir: &Path,
         mnemonic: Mnemonic,
         config: Config,
+        steal_lock: bool,
     ) -> anyhow::Result<()> {
         if self.context.is_some() {
             return Ok(());
         }
 
+        self.set_state(WalletState::Loading);
+        let loaded = self
+            .open_context(datadir, mnemonic, config, steal_lock)
+            .await;
+        self.set_state(match &loaded {
+            Ok(()) => WalletState::Loaded,
+            Err(_) => WalletState::Error,
+        });
+        loaded
+    }
+
+    async fn open_context(
+        &mut self,
+        datadir: &Path,
+        mnemonic: Mnemonic,
+        config: Config,
+        steal_lock: bool,
+    ) -> anyhow::Result<()> {
         debug!("Loading wallet in {}", datadir.display());
 
         if !datadir.exists() {
             bail!("Datadir does not exist. Please create a new wallet first.");
         }
+        if let Some(moved_to) = datadir_move::moved_to(datadir) {
+            bail!(
+                "The wallet in {} was moved to {}, load it from there",
+                datadir.display(),
+                moved_to.display()
+            );
+        }
+
+        // Released again by dropping it if anything below fails
+        let lock = DatadirLock::acquire(datadir, steal_lock)?;
 
         info!("Attempting to open wallet...");
-        let (wallet, onchain_wallet) = self.open_wallet(datadir, mnemonic, config).await?;
+        // Opened first, so a store from a newer app refuses the load before
+        // bark migrates its database
+        let store = Store::open(&datadir.join(STORE_FILE))?;
+        let (wallet, onchain_wallet, db, fingerprint) =
+            self.open_wallet(datadir, mnemonic, config).await?;
 
         self.context = Some(WalletContext {
+            config: wallet.config().clone(),
             wallet,
             onchain_wallet,
+            store,
+            db,
+            datadir: datadir.to_path_buf(),
+            fingerprint,
+            receive_invoice: None,
+            pending_address: None,
+            last_round: None,
+            _lock: lock,
         });
 
+        panics::set_panic_report_dir(Some(datadir));
+
+        // Undo the locks of a coin-controlled payment interrupted by a crash
+        if let Some(ctx) = &self.context {
+            if let Err(e) = restore_coin_control_locks(ctx).await {
+                warn!("Failed to restore coin control locks: {:#}", e);
+            }
+        }
+
         Ok(())
     }
 
@@ -122,13 +412,56 @@ impl WalletManager {
             bail!("No wallet is currently loaded.");
         }
         self.context = None;
+        self.set_state(WalletState::NotLoaded);
+        panics::set_panic_report_dir(None);
         info!("Wallet closed successfully.");
+        logger::Logger::flush();
+        Ok(())
+    }
+
+    /// Closes the wallet if one is loaded and wipes the datadir, leaving it
+    /// empty for a new wallet.
+    ///
+    /// Only datadirs holding a wallet database are wiped, so a wrong path
+    /// can't delete unrelated files.
+    pub async fn reset_to_clean_state(&mut self, datadir: &Path) -> anyhow::Result<()> {
+        if !datadir.join(DB_FILE).exists() && !datadir.join(STORE_FILE).exists() {
+            bail!(
+                "{} doesn't look like a wallet datadir, refusing to wipe it",
+                datadir.display()
+            );
+        }
+
+        // Dropping the context closes the bark database and the local store,
+        // all other handles to them are owned by the context. A wallet
+        // loaded from another datadir stays loaded.
+        let loaded_here = self
+            .context
+            .as_ref()
+            .is_some_and(|ctx| ctx.datadir == datadir);
+        if loaded_here {
+            self.context = None;
+            info!("Closed the wallet before reset.");
+            panics::set_panic_report_dir(None);
+        }
+        if self.context.is_none() {
+            self.set_state(WalletState::NotLoaded);
+        }
+        let _lock = DatadirLock::acquire(datadir, false)?;
+
+        tokio::fs::remove_dir_all(datadir)
+            .await
+            .with_context(|| format!("Failed to delete {}", datadir.display()))?;
+        tokio::fs::create_dir_all(datadir)
+            .await
+            .with_context(|| format!("Failed to recreate {}", datadir.display()))?;
+        info!("Wallet datadir {} was reset.", datadir.display());
         Ok(())
     }
 
     pub async fn get_config(&self) -> anyhow::Result<Config> {
         match &self.context {
-            Some(ctx) => Ok(ctx.wallet.config().clone()),
+            Some(ctx) => Ok(ctx.config.clone()),
             None => bail!("Wallet not loaded"),
         }
     }
@@ -164,9 +497,32 @@ impl WalletManager {
         }
     }
 
-    pub async fn with_context_ref_async<T, F, Fut>(&self, f: F) -> anyhow::Result<T>
+    /// Like [`Self::with_context_async`] for mutating operations, appending
+    /// the outcome of `f` to the event journal of the wallet before the lock
+    /// is released.
+    pub async fn with_context_journaled_async<T, F>(
+        &mut self,
+        operation: &'static str,
+        params: String,
+        f: F,
+    ) -> anyhow::Result<T>
+    where
+        F: AsyncFnOnce(&mut WalletContext) -> anyhow::Result<T>,
+    {
+        match &mut self.context {
+            Some(ctx) => {
+                let result = f(ctx).await;
+                append_journal(&ctx.store, operation, &params, &result);
+                result
+            }
+            None => bail!("Wallet not loaded"),
+        }
+    }
+
+    #[must_use = "the result of the operation may be an error"]
+    pub async fn with_context_ref_async<'a, T, F, Fut>(&'a self, f: F) -> anyhow::Result<T>
     where
-        F: FnOnce(&WalletContext) -> Fut,
+        F: FnOnce(&'a WalletContext) -> Fut,
         Fut: std::future::Future<Output = anyhow::Result<T>>,
     {
         match &self.context {
@@ -180,22 +536,28 @@ impl WalletManager {
         datadir: &Path,
         mnemonic: Mnemonic,
         config: Config,
-    ) -> anyhow::Result<(Wallet, OnchainWallet)> {
+    ) -> anyhow::Result<(Wallet, OnchainWallet, Arc<SqliteClient>, bip32::Fingerprint)> {
         debug!("Opening bark wallet in {}", datadir.display());
 
+        check_wallet_db_schema(
+            &datadir.join(DB_FILE),
+            supported_wallet_db_version(datadir)?,
+        )?;
         let db = Arc::new(SqliteClient::open(datadir.join(DB_FILE))?);
         let properties = db
             .read_properties()
             .await?
             .context("Failed to read properties from db for opening wallet")?;
 
+        let mut seed = mnemonic.to_seed("");
         let onchain_wallet =
-            OnchainWallet::load_or_create(properties.network, mnemonic.to_seed(""), db.clone())
-                .await?;
+            OnchainWallet::load_or_create(properties.network, seed, db.clone()).await;
+        seed.zeroize();
+        let onchain_wallet = onchain_wallet?;
         let wallet =
             Wallet::open_with_onchain(&mnemonic, db.clone(), &onchain_wallet, config).await?;
 
-        Ok((wallet, onchain_wallet))
+        Ok((wallet, onchain_wallet, db, properties.fingerprint))
     }
 }
 
@@ -211,548 +573,3761 @@ pub fn init_logger() {
     LOGGER_INIT.call_once(|| {
         logger::Logger::new(logger::log::LevelFilter::Debug);
     });
+    init_panic_handler();
+}
+
+/// Like [`init_logger`], but also writes logs at `level` and above to a
+/// size-rotated file at `path`. Fails if the logger was already initialized.
+pub fn init_logger_with_file(path: &Path, level: &str) -> anyhow::Result<()> {
+    let level = logger::log::LevelFilter::from_str(level)
+        .with_context(|| format!("Invalid log level '{}'", level))?;
+    let mut result = Err(anyhow::anyhow!("Logger is already initialized"));
+    LOGGER_INIT.call_once(|| {
+        result = logger::Logger::with_config(logger::LogConfig {
+            level,
+            file_path: Some(path.to_path_buf()),
+            ..logger::LogConfig::default()
+        })
+        .map(|_| ())
+        .with_context(|| format!("Failed to open log file {}", path.display()));
+    });
+    init_panic_handler();
+    result
 }
 
 pub fn create_mnemonic() -> anyhow::Result<String> {
+    create_mnemonic_with_options(12, bip39::Language::English)
+}
+
+/// Generates a mnemonic of `word_count` words from the `language` wordlist.
+///
+/// The seed is derived from the words, so the same entropy gives a
+/// different wallet in another language.
+pub fn create_mnemonic_with_options(
+    word_count: usize,
+    language: bip39::Language,
+) -> anyhow::Result<String> {
     info!("Attempting to create a new mnemonic using cxx bridge...");
-    let mnemonic = Mnemonic::generate(12).context("failed to generate mnemonic")?;
+    let mnemonic =
+        Mnemonic::generate_in(language, word_count).context("failed to generate mnemonic")?;
     info!("Successfully created a new mnemonic using cxx bridge.");
     Ok(mnemonic.to_string())
 }
 
+/// Checks `phrase` is a valid mnemonic and returns the language its words
+/// are from.
+pub fn validate_mnemonic(phrase: &str) -> anyhow::Result<bip39::Language> {
+    Ok(parse_mnemonic(phrase)?.language())
+}
+
 pub async fn create_wallet(datadir: &Path, opts: CreateOpts) -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager.create_wallet(datadir, opts).await
+    timed("create_wallet", async move {
+        let mut manager = lock_manager("create_wallet").await;
+        manager.create_wallet(datadir, opts).await
+    })
+    .await
 }
 
-pub async fn load_wallet(datadir: &Path, mnemonic: Mnemonic, config: Config) -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager.load_wallet(datadir, mnemonic, config).await
+/// Loads the wallet in `datadir`, failing with [`LockError::WalletInUse`] if
+/// another process has it open. `steal_lock` takes over a lock whose holder
+/// died without releasing it long enough ago.
+pub async fn load_wallet(
+    datadir: &Path,
+    mnemonic: Mnemonic,
+    config: Config,
+    steal_lock: bool,
+) -> anyhow::Result<()> {
+    timed("load_wallet", async move {
+        let mut manager = lock_manager("load_wallet").await;
+        manager
+            .load_wallet(datadir, mnemonic, config, steal_lock)
+            .await
+    })
+    .await
+}
+
+/// Factory reset: deletes the wallet in `datadir`. `confirm` must be true,
+/// as a guard against resetting by accident.
+pub async fn reset_wallet(datadir: &Path, confirm: bool) -> anyhow::Result<()> {
+    timed("reset_wallet", async move {
+        if !confirm {
+            bail!("Resetting the wallet deletes all its data, confirm to proceed");
+        }
+        let mut manager = lock_manager("reset_wallet").await;
+        manager.reset_to_clean_state(datadir).await
+    })
+    .await
 }
 
 pub async fn close_wallet() -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager.close_wallet()
+    timed("close_wallet", async move {
+        let mut manager = lock_manager("close_wallet").await;
+        manager.close_wallet()
+    })
+    .await
 }
 
-pub async fn is_wallet_loaded() -> bool {
-    let manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager.is_loaded()
+/// Features of the bark dependency compiled into this library, keep in sync
+/// with Cargo.toml.
+pub const COMPILED_FEATURES: &[&str] = &["onchain_bdk", "sqlite", "tls-webpki-roots", "bolt12"];
+
+/// What the native library was built with, so the app can degrade gracefully.
+#[derive(Debug, Clone)]
+pub struct LibraryInfo {
+    pub version: &'static str,
+    /// `git describe` of the build, empty if it wasn't built from a checkout
+    pub git_describe: &'static str,
+    pub bark_version: &'static str,
+    pub persister_backend: &'static str,
+    pub features: Vec<String>,
+    /// Schema version of the local store, if a wallet is loaded
+    pub store_schema_version: Option<u32>,
 }
 
-pub async fn balance() -> anyhow::Result<bark::Balance> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async { ctx.wallet.balance().await })
-        .await
+pub async fn get_library_info() -> LibraryInfo {
+    let manager = lock_manager("get_library_info").await;
+    let store_schema_version = manager
+        .with_context_ref(|ctx| ctx.store.schema_version())
+        .ok();
+
+    LibraryInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_describe: env!("BARK_CPP_GIT_DESCRIBE"),
+        bark_version: env!("BARK_CPP_BARK_VERSION"),
+        persister_backend: "sqlite",
+        features: COMPILED_FEATURES.iter().map(|f| f.to_string()).collect(),
+        store_schema_version,
+    }
 }
 
-pub async fn get_ark_info() -> anyhow::Result<ArkInfo> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    let info = manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .ark_info()
-                .await
-                .context("Failed to get ark info")
-        })
-        .await;
-
-    match info {
-        Ok(info) => {
-            if let Some(info) = info {
-                Ok(info)
-            } else {
-                bail!("Failed to get ark info, returned as null")
-            }
-        }
-        Err(err) => Err(err),
+/// A message for the user if the wallet at `datadir` needs a newer app
+/// version, to be checked when loading the wallet fails.
+pub fn get_required_app_version_hint(datadir: &Path) -> anyhow::Result<Option<String>> {
+    if !datadir.exists() {
+        return Ok(None);
     }
+    required_app_version_hint(datadir, supported_wallet_db_version(datadir)?)
 }
 
-pub async fn derive_store_next_keypair() -> anyhow::Result<Keypair> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .derive_store_next_keypair()
-                .await
-                .map(|(keypair, _)| keypair)
-        })
-        .await
+static SUPPORTED_WALLET_DB_VERSION: OnceLock<u32> = OnceLock::new();
+
+/// The last migration bark applies to its database. bark doesn't expose its
+/// migrations, so this lets it create a database in `scratch_dir` once and
+/// reads the version from it.
+fn supported_wallet_db_version(scratch_dir: &Path) -> anyhow::Result<u32> {
+    if let Some(version) = SUPPORTED_WALLET_DB_VERSION.get() {
+        return Ok(*version);
+    }
+
+    let probe = scratch_dir.join("db-version-probe.sqlite");
+    let remove_probe = || {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", probe.display(), suffix));
+        }
+    };
+    remove_probe();
+    let version = create_wallet_db_version(&probe)
+        .context("Failed to determine the supported wallet database version");
+    remove_probe();
+
+    let version = version?;
+    Ok(*SUPPORTED_WALLET_DB_VERSION.get_or_init(|| version))
 }
 
-pub async fn peak_keypair(index: u32) -> anyhow::Result<Keypair> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .peak_keypair(index)
-                .await
-                .context("Failed to peak keypair")
-        })
-        .await
+/// Lets bark create its database at `path` and reads the version it
+/// migrated it to.
+fn create_wallet_db_version(path: &Path) -> anyhow::Result<u32> {
+    drop(SqliteClient::open(path.to_path_buf())?);
+    get_db_migration_version(&open_bark_db_read_only(path)?)
 }
 
-pub async fn new_address() -> anyhow::Result<bark::ark::Address> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .new_address()
-                .await
-                .context("Failed to create new address")
-        })
-        .await
+pub async fn set_offline_mode(offline: bool) {
+    let mut manager = lock_manager("set_offline_mode").await;
+    manager.set_offline_mode(offline);
 }
 
-pub async fn peak_address(index: u32) -> anyhow::Result<bark::ark::Address> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .peak_address(index)
-                .await
-                .context("Failed to peak address")
-        })
-        .await
+pub async fn is_offline_mode() -> bool {
+    let manager = lock_manager("is_offline_mode").await;
+    manager.is_offline_mode()
 }
 
-pub async fn refresh_server() -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .refresh_server()
-                .await
-                .context("Failed to refresh server connection")
-        })
-        .await
+pub async fn get_wallet_id() -> anyhow::Result<String> {
+    timed("get_wallet_id", async move {
+        let manager = lock_manager("get_wallet_id").await;
+        manager.get_wallet_id()
+    })
+    .await
 }
 
-pub async fn sign_message(
-    message: &str,
-    index: u32,
-) -> anyhow::Result<bark::ark::bitcoin::secp256k1::ecdsa::Signature> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            let wallet = &ctx.wallet;
-            let keypair = wallet
-                .peak_keypair(index)
-                .await
-                .context("Failed to peak keypair")?;
-            let hash = bark::ark::bitcoin::sign_message::signed_msg_hash(message);
-            let secp = bark::ark::bitcoin::secp256k1::Secp256k1::new();
-            let msg = bark::ark::bitcoin::secp256k1::Message::from_digest_slice(&hash[..])?;
-            let ecdsa_sig = secp.sign_ecdsa(&msg, &keypair.secret_key());
-
-            Ok(ecdsa_sig)
+/// When the wallet database was created, as recorded by its first migration.
+pub async fn get_wallet_created_at() -> anyhow::Result<String> {
+    timed("get_wallet_created_at", async move {
+        let manager = lock_manager("get_wallet_created_at").await;
+        manager.with_context_ref(|ctx| {
+            let conn = open_bark_db_read_only(&ctx.datadir.join(DB_FILE))?;
+            get_db_created_at(&conn)
         })
-        .await
+    })
+    .await
 }
 
-pub async fn sign_messsage_with_mnemonic(
-    message: &str,
-    mnemonic: Mnemonic,
-    network: Network,
-    index: u32,
-) -> anyhow::Result<bark::ark::bitcoin::secp256k1::ecdsa::Signature> {
-    let secp = bark::ark::bitcoin::secp256k1::Secp256k1::new();
-    let keypair = bip32::Xpriv::new_master(network, &mnemonic.to_seed(""))?
-        .derive_priv(&secp, &[ARK_PURPOSE_INDEX.into()])?
-        .derive_priv(&secp, &[index.into()])?
-        .to_keypair(&secp);
-
-    let hash = bark::ark::bitcoin::sign_message::signed_msg_hash(message);
-    let msg = bark::ark::bitcoin::secp256k1::Message::from_digest_slice(&hash[..]).unwrap();
-    let ecdsa_sig = secp.sign_ecdsa(&msg, &keypair.secret_key());
-
-    Ok(ecdsa_sig)
+/// Bytes used by the loaded wallet's databases, bark's and the local store.
+pub async fn get_database_size_bytes() -> anyhow::Result<u64> {
+    timed("get_database_size_bytes", async move {
+        let manager = lock_manager("get_database_size_bytes").await;
+        manager.with_context_ref(|ctx| {
+            let mut size = 0;
+            for file in [DB_FILE, STORE_FILE] {
+                size += database_size(&open_bark_db_read_only(&ctx.datadir.join(file))?)?;
+            }
+            Ok(size)
+        })
+    })
+    .await
 }
 
-pub async fn derive_keypair_from_mnemonic(
-    mnemonic: Mnemonic,
-    network: Network,
-    index: u32,
-) -> anyhow::Result<Keypair> {
-    let secp = bark::ark::bitcoin::secp256k1::Secp256k1::new();
-    let keypair = bip32::Xpriv::new_master(network, &mnemonic.to_seed(""))?
-        .derive_priv(&secp, &[ARK_PURPOSE_INDEX.into()])?
-        .derive_priv(&secp, &[index.into()])?
-        .to_keypair(&secp);
-    Ok(keypair)
+/// Row counts of the tables of bark's database and of the local store.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DatabaseTableStats {
+    pub wallet: std::collections::BTreeMap<String, u64>,
+    pub store: std::collections::BTreeMap<String, u64>,
 }
 
-pub async fn verify_message(
-    message: &str,
-    signature: bark::ark::bitcoin::secp256k1::ecdsa::Signature,
-    public_key: &bark::ark::bitcoin::secp256k1::PublicKey,
-) -> anyhow::Result<bool> {
-    let hash = bark::ark::bitcoin::sign_message::signed_msg_hash(message);
-    let secp = bark::ark::bitcoin::secp256k1::Secp256k1::new();
-    let msg = bark::ark::bitcoin::secp256k1::Message::from_digest_slice(&hash[..]).unwrap();
-    Ok(secp.verify_ecdsa(&msg, &signature, public_key).is_ok())
-}
-
-pub async fn bolt11_invoice(amount: u64) -> anyhow::Result<Bolt11Invoice> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            let invoice = ctx
-                .wallet
-                .bolt11_invoice(Amount::from_sat(amount))
-                .await
-                .context("Failed to create bolt11_invoice")?;
-            Ok(invoice)
+pub async fn get_database_table_stats() -> anyhow::Result<DatabaseTableStats> {
+    timed("get_database_table_stats", async move {
+        let manager = lock_manager("get_database_table_stats").await;
+        manager.with_context_ref(|ctx| {
+            Ok(DatabaseTableStats {
+                wallet: table_row_counts(&open_bark_db_read_only(&ctx.datadir.join(DB_FILE))?)?,
+                store: table_row_counts(&open_bark_db_read_only(&ctx.datadir.join(STORE_FILE))?)?,
+            })
         })
-        .await
+    })
+    .await
 }
 
-pub async fn lightning_receive_status(
-    payment: PaymentHash,
-) -> anyhow::Result<Option<LightningReceive>> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .lightning_receive_status(payment)
-                .await
-                .context("Failed to get lightning receive status")
-        })
-        .await
+/// Backs up the wallet database in `datadir` to `backup_path`, with the
+/// current time prefixed to its file name. Returns the path written.
+pub fn backup_database(datadir: &Path, backup_path: &Path) -> anyhow::Result<PathBuf> {
+    let backup_path = timestamped_backup_path(backup_path, now_timestamp())?;
+    backup_sqlite_db(&datadir.join(DB_FILE), &backup_path)?;
+    info!("Backed up wallet database to {}", backup_path.display());
+    Ok(backup_path)
 }
 
-pub async fn try_claim_lightning_receive(
-    payment_hash: PaymentHash,
-    wait: bool,
-    token: Option<String>,
-) -> anyhow::Result<LightningReceive> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .try_claim_lightning_receive(payment_hash, wait, token.as_deref())
-                .await
-                .context("Failed to claim bolt11 payment")
-        })
-        .await
+/// Backs up the database of the loaded wallet, see [`backup_database`].
+pub async fn backup_wallet_database(backup_path: &Path) -> anyhow::Result<PathBuf> {
+    timed("backup_wallet_database", async move {
+        // Holding the manager keeps other calls from writing during the backup
+        let manager = lock_manager("backup_wallet_database").await;
+        manager.with_context_ref(|ctx| backup_database(&ctx.datadir, backup_path))
+    })
+    .await
 }
 
-pub async fn try_claim_all_lightning_receives(wait: bool) -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .try_claim_all_lightning_receives(wait)
-                .await
-                .context("Failed to claim all open invoices")?;
-            Ok(())
-        })
-        .await
+/// Moves the wallet in `old_path` to `new_path`, e.g. when the app's
+/// container path changed. See [`datadir_move::move_datadir`].
+pub async fn move_wallet_datadir(
+    old_path: &Path,
+    new_path: &Path,
+) -> anyhow::Result<DatadirMoveReport> {
+    timed("move_wallet_datadir", async move {
+        // Holding the manager keeps a wallet from being loaded during the move
+        let manager = lock_manager("move_wallet_datadir").await;
+        if manager.is_loaded() {
+            bail!("Close the wallet before moving its datadir");
+        }
+        datadir_move::move_datadir(old_path, new_path)
+    })
+    .await
 }
 
-pub async fn sync_pending_boards() -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .sync_pending_boards()
-                .await
-                .context("Failed to sync pending boards")?;
-            Ok(())
-        })
-        .await
+pub async fn is_wallet_loaded() -> bool {
+    let manager = lock_manager("is_wallet_loaded").await;
+    manager.is_loaded()
 }
 
-pub async fn maintenance() -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .maintenance()
-                .await
-                .context("Failed to perform wallet maintenance")?;
-            Ok(())
-        })
-        .await
+/// The wallet's lifecycle state. Doesn't take the manager lock, so it
+/// returns right away while a wallet is being created or loaded.
+pub fn get_wallet_state() -> WalletState {
+    WalletState::from_u8(GLOBAL_WALLET_STATE.load(Ordering::Acquire))
 }
 
-pub async fn maintenance_delegated() -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .maintenance_delegated()
-                .await
-                .context("Failed to perform wallet maintenance delegated")?;
-            Ok(())
-        })
-        .await
+/// The effective config of the loaded wallet
+pub async fn get_config() -> anyhow::Result<Config> {
+    timed("get_config", async move {
+        let manager = lock_manager("get_config").await;
+        manager.get_config().await
+    })
+    .await
 }
 
-pub async fn maintenance_with_onchain() -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .maintenance_with_onchain(&mut ctx.onchain_wallet)
-                .await
-                .context("Failed to perform wallet maintenance with onchain")?;
-            Ok(())
+/// Applies a new config to the loaded wallet.
+///
+/// Fields in [`LIVE_CONFIG_FIELDS`] take effect right away. bark keeps its
+/// own copy of the other fields, so those are reported as requiring a reload
+/// and left unchanged until then.
+pub async fn apply_config(opts: ConfigOpts) -> anyhow::Result<ConfigApplyResult> {
+    timed("apply_config", async move {
+        let mut manager = lock_manager("apply_config").await;
+        manager.with_context(|ctx| {
+            let mut new = ctx.config.clone();
+            new.htlc_recv_claim_delta = opts.htlc_recv_claim_delta;
+            new.vtxo_exit_margin = opts.vtxo_exit_margin;
+            new.round_tx_required_confirmations = opts.round_tx_required_confirmations;
+            opts.merge_into(&mut new).context("invalid configuration")?;
+
+            let result = apply_live_config(&mut ctx.config, &new);
+            if !result.applied.is_empty() {
+                info!("Applied config changes: {}", result.applied.join(", "));
+            }
+            Ok(result)
         })
-        .await
+    })
+    .await
 }
 
-pub async fn maintenance_with_onchain_delegated() -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .maintenance_with_onchain_delegated(&mut ctx.onchain_wallet)
-                .await
-                .context("Failed to perform wallet maintenance with onchain delegated")?;
-            Ok(())
-        })
-        .await
+/// The wallet balance, synced with the server first unless `no_sync` is
+/// set, in which case only local state is read.
+pub async fn get_balance(no_sync: bool) -> anyhow::Result<OffchainBalance> {
+    timed("get_balance", async move {
+        if !no_sync {
+            sync().await?;
+        }
+        balance().await
+    })
+    .await
 }
 
-pub async fn maintenance_refresh() -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .maintenance_refresh()
-                .await
-                .context("Failed to perform vtxo maintenance refresh")?;
-            Ok(())
-        })
-        .await
+/// The offchain balance with VTXOs that need a refresh to be trusted split
+/// out of spendable.
+#[derive(Debug, Clone)]
+pub struct OffchainBalance {
+    /// bark's balance, without quarantined and unrefreshed arkoor VTXOs in
+    /// its spendable amount
+    pub balance: bark::Balance,
+    /// Spendable VTXOs received out of round and not refreshed since
+    pub pending_trustless: Amount,
 }
 
-pub async fn sync() -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet.sync().await;
-            Ok(())
-        })
-        .await
+pub async fn balance() -> anyhow::Result<OffchainBalance> {
+    timed("balance", async move {
+        let manager = lock_manager("balance").await;
+        manager
+            .with_context_ref_async(|ctx| async { offchain_balance_of(ctx).await })
+            .await
+    })
+    .await
 }
 
-pub async fn history() -> anyhow::Result<Vec<Movement>> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async { ctx.wallet.history().await })
-        .await
+async fn offchain_balance_of(ctx: &WalletContext) -> anyhow::Result<OffchainBalance> {
+    let mut balance = balance_without_quarantined(ctx).await?;
+    let (spendable, untrusted_ids) = spendable_by_trust(ctx).await?;
+    let (_, pending_trustless) = split_trusted_amount(&spendable, &untrusted_ids);
+    balance.spendable = balance
+        .spendable
+        .checked_sub(pending_trustless)
+        .unwrap_or(Amount::ZERO);
+    Ok(OffchainBalance {
+        balance,
+        pending_trustless,
+    })
 }
 
-pub async fn vtxos() -> anyhow::Result<Vec<WalletVtxo>> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async { ctx.wallet.vtxos().await })
-        .await
+/// The spendable offchain amount, see [`balance`] for the parts that are
+/// still pending.
+pub async fn offchain_balance() -> anyhow::Result<Amount> {
+    timed("offchain_balance", async move {
+        Ok(balance().await?.balance.spendable)
+    })
+    .await
 }
 
-pub async fn get_expiring_vtxos(threshold: BlockHeight) -> anyhow::Result<Vec<WalletVtxo>> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .get_expiring_vtxos(threshold)
-                .await
-                .context("Failed to get expiring vtxos")
-        })
-        .await
+/// Everything in the onchain wallet, confirmed or not.
+pub async fn onchain_balance() -> anyhow::Result<Amount> {
+    timed("onchain_balance", async move {
+        Ok(onchain::onchain_balance().await?.total())
+    })
+    .await
 }
 
-pub async fn refresh_vtxos(vtxos: Vec<Vtxo>) -> anyhow::Result<Option<RoundStatus>> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .refresh_vtxos(vtxos)
-                .await
-                .context("Failed to refresh vtxos")
-        })
-        .await
+/// The amount in VTXOs being exited onchain.
+pub async fn pending_exit_balance() -> anyhow::Result<Amount> {
+    timed("pending_exit_balance", async move {
+        Ok(balance()
+            .await?
+            .balance
+            .pending_exit
+            .unwrap_or(Amount::ZERO))
+    })
+    .await
 }
 
-/// Returns the block height at which the first VTXO will expire
-pub async fn get_first_expiring_vtxo_blockheight() -> anyhow::Result<Option<BlockHeight>> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .get_first_expiring_vtxo_blockheight()
-                .await
-                .context("Failed to get first expiring vtxo blockheight")
-        })
-        .await
+/// The spendable VTXOs that are not quarantined, and the ids of the ones
+/// among them that need a refresh to be trusted.
+async fn spendable_by_trust(
+    ctx: &WalletContext,
+) -> anyhow::Result<(Vec<(String, u64)>, std::collections::HashSet<String>)> {
+    let vtxos = ctx.wallet.vtxos().await?;
+    let quarantined = ctx
+        .store
+        .get_quarantined_vtxos()?
+        .into_iter()
+        .map(|q| q.vtxo_id)
+        .collect::<std::collections::HashSet<_>>();
+    let spendable = spendable_vtxo_ids(&vtxos)
+        .into_iter()
+        .filter(|(id, _)| !quarantined.contains(id))
+        .collect();
+    Ok((spendable, untrusted_vtxo_ids(&vtxos)))
 }
 
-/// Returns the next block height at which we have a VTXO that we
-/// want to refresh
-pub async fn get_next_required_refresh_blockheight() -> anyhow::Result<Option<BlockHeight>> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .get_next_required_refresh_blockheight()
-                .await
-                .context("Failed to get next required refresh blockheight")
-        })
-        .await
+/// Keeps quarantined VTXOs out of the next payment by locking them like
+/// coin control does.
+///
+/// The caller must pass the payment's result through
+/// [`unlock_after_payment`], also if this fails.
+async fn exclude_quarantined_vtxos(ctx: &WalletContext) -> anyhow::Result<()> {
+    let quarantined = ctx
+        .store
+        .get_quarantined_vtxos()?
+        .into_iter()
+        .map(|q| q.vtxo_id)
+        .collect::<std::collections::HashSet<_>>();
+    if quarantined.is_empty() {
+        return Ok(());
+    }
+    let to_lock = spendable_vtxo_ids(&ctx.wallet.vtxos().await?)
+        .into_iter()
+        .map(|(id, _)| id)
+        .filter(|id| quarantined.contains(id))
+        .collect::<Vec<_>>();
+    ctx.store.record_coin_control_locks(&to_lock)?;
+    lock_vtxos(ctx, &to_lock).await
 }
 
-pub async fn board_amount(amount: Amount) -> anyhow::Result<PendingBoard> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .board_amount(&mut ctx.onchain_wallet, amount)
-                .await
-        })
-        .await
+/// Keeps quarantined VTXOs out of the next payment, and unrefreshed arkoor
+/// VTXOs unless spending them is allowed, by locking them like coin control
+/// does. Fails with [`InsufficientTrustedFunds`] if only the unrefreshed
+/// ones could cover `amount`.
+///
+/// The caller must pass the payment's result through
+/// [`unlock_after_payment`], also if this fails.
+async fn exclude_untrusted_vtxos(
+    ctx: &WalletContext,
+    amount: Option<Amount>,
+) -> anyhow::Result<()> {
+    exclude_quarantined_vtxos(ctx).await?;
+    if ctx.store.get_allow_spending_unrefreshed_arkoor()? {
+        return Ok(());
+    }
+    let (spendable, untrusted_ids) = spendable_by_trust(ctx).await?;
+    let to_lock =
+        untrusted_vtxos_to_exclude(&spendable, &untrusted_ids, amount.map(|a| a.to_sat()))?;
+    ctx.store.record_coin_control_locks(&to_lock)?;
+    lock_vtxos(ctx, &to_lock).await
 }
 
-pub async fn board_all() -> anyhow::Result<PendingBoard> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async { ctx.wallet.board_all(&mut ctx.onchain_wallet).await })
-        .await
+/// Undoes [`exclude_untrusted_vtxos`] and [`exclude_quarantined_vtxos`]
+/// after a payment, returning the payment's result.
+async fn unlock_after_payment<T>(
+    ctx: &WalletContext,
+    result: anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let restored = restore_coin_control_locks(ctx).await;
+    let paid = result?;
+    restored?;
+    Ok(paid)
 }
 
-pub async fn validate_arkoor_address(address: bark::ark::Address) -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .validate_arkoor_address(&address)
-                .await
-                .context("Failed to validate address")
-        })
-        .await
+/// The amount payments may spend, leaving out unrefreshed arkoor VTXOs
+/// unless spending them is allowed.
+async fn payable_amount(ctx: &WalletContext) -> anyhow::Result<Amount> {
+    if ctx.store.get_allow_spending_unrefreshed_arkoor()? {
+        return Ok(balance_without_quarantined(ctx).await?.spendable);
+    }
+    Ok(offchain_balance_of(ctx).await?.balance.spendable)
 }
 
-pub async fn send_arkoor_payment(
-    destination: bark::ark::Address,
-    amount_sat: Amount,
-) -> anyhow::Result<Vec<Vtxo>> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            info!(
-                "Attempting to send OOR payment of {} to pubkey {:?}",
-                amount_sat, destination
-            );
-            let oor_result = ctx
-                .wallet
-                .send_arkoor_payment(&destination, amount_sat)
-                .await?;
-            Ok(oor_result)
-        })
-        .await
+pub async fn allow_spending_unrefreshed_arkoor() -> anyhow::Result<bool> {
+    timed("allow_spending_unrefreshed_arkoor", async move {
+        let manager = lock_manager("allow_spending_unrefreshed_arkoor").await;
+        manager.with_context_ref(|ctx| ctx.store.get_allow_spending_unrefreshed_arkoor())
+    })
+    .await
 }
 
-pub async fn check_lightning_payment(
-    payment_hash: PaymentHash,
-    wait: bool,
-) -> anyhow::Result<Option<Preimage>> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet.check_lightning_payment(payment_hash, wait).await
-        })
-        .await
+pub async fn set_allow_spending_unrefreshed_arkoor(allow: bool) -> anyhow::Result<()> {
+    timed("set_allow_spending_unrefreshed_arkoor", async move {
+        let manager = lock_manager("set_allow_spending_unrefreshed_arkoor").await;
+        manager.with_context_ref(|ctx| ctx.store.set_allow_spending_unrefreshed_arkoor(allow))
+    })
+    .await
+}
+
+/// The wallet balance, not counting quarantined VTXOs as spendable
+async fn balance_without_quarantined(ctx: &WalletContext) -> anyhow::Result<bark::Balance> {
+    let mut balance = ctx.wallet.balance().await?;
+
+    let quarantined = ctx.store.get_quarantined_vtxos()?;
+    if quarantined.is_empty() {
+        return Ok(balance);
+    }
+
+    let quarantined_ids = quarantined.into_iter().map(|q| q.vtxo_id).collect();
+    let spendable = spendable_vtxo_ids(&ctx.wallet.vtxos().await?);
+    let excluded = quarantined_spendable_amount(&spendable, &quarantined_ids);
+    balance.spendable = balance
+        .spendable
+        .checked_sub(excluded)
+        .unwrap_or(Amount::ZERO);
+
+    Ok(balance)
+}
+
+/// Whether the onchain wallet can pay `amount`, optionally together with the
+/// fee of sending it at the configured fee rate. Uses local state only.
+pub async fn has_sufficient_onchain_balance(
+    amount: Amount,
+    include_fees: bool,
+) -> anyhow::Result<bool> {
+    timed("has_sufficient_onchain_balance", async move {
+        let manager = lock_manager("has_sufficient_onchain_balance").await;
+        manager.with_context_ref(|ctx| {
+            let utxo_values = ctx
+                .onchain_wallet
+                .list_unspent()
+                .into_iter()
+                .map(|utxo| utxo.txout.value)
+                .collect::<Vec<_>>();
+            let fee_rate =
+                include_fees.then(|| onchain::fee_rate_or_min_relay(ctx.config.fallback_fee_rate));
+            Ok(utxos_cover_amount(&utxo_values, amount, fee_rate))
+        })
+    })
+    .await
+}
+
+/// Whether the spendable offchain balance covers `amount`. Uses local state
+/// only.
+pub async fn has_sufficient_offchain_balance(amount: Amount) -> anyhow::Result<bool> {
+    timed("has_sufficient_offchain_balance", async move {
+        let manager = lock_manager("has_sufficient_offchain_balance").await;
+        manager
+            .with_context_ref_async(|ctx| async move { Ok(payable_amount(ctx).await? >= amount) })
+            .await
+    })
+    .await
+}
+
+pub async fn get_ark_info() -> anyhow::Result<ArkInfo> {
+    timed("get_ark_info", async move {
+        let manager = lock_manager("get_ark_info").await;
+        let info = manager
+            .with_context_ref_async(|ctx| async {
+                ctx.wallet
+                    .ark_info()
+                    .await
+                    .context("Failed to get ark info")
+            })
+            .await;
+
+        match info {
+            Ok(info) => {
+                if let Some(info) = info {
+                    Ok(info)
+                } else {
+                    bail!("Failed to get ark info, returned as null")
+                }
+            }
+            Err(err) => Err(err),
+        }
+    })
+    .await
+}
+
+pub async fn derive_store_next_keypair() -> anyhow::Result<Keypair> {
+    timed("derive_store_next_keypair", async move {
+        let mut manager = lock_manager("derive_store_next_keypair").await;
+        manager
+            .with_context_async(|ctx| async {
+                ctx.wallet
+                    .derive_store_next_keypair()
+                    .await
+                    .map(|(keypair, _)| keypair)
+            })
+            .await
+    })
+    .await
+}
+
+pub async fn peak_keypair(index: u32) -> anyhow::Result<Keypair> {
+    timed("peak_keypair", async move {
+        let manager = lock_manager("peak_keypair").await;
+        manager
+            .with_context_ref_async(|ctx| async {
+                ctx.wallet
+                    .peak_keypair(index)
+                    .await
+                    .context("Failed to peak keypair")
+            })
+            .await
+    })
+    .await
+}
+
+/// An address to receive on. The same address is handed out again until a
+/// payment arrives on it, so showing an address that is never used doesn't
+/// use up key indices.
+pub async fn new_address() -> anyhow::Result<bark::ark::Address> {
+    timed("new_address", async move {
+        let mut manager = lock_manager("new_address").await;
+        manager
+            .with_context_async(|ctx| async { pending_address(ctx).await })
+            .await
+    })
+    .await
+}
+
+async fn pending_address(ctx: &mut WalletContext) -> anyhow::Result<bark::ark::Address> {
+    if let Some(address) = &ctx.pending_address {
+        let user_pubkey = address.policy().user_pubkey();
+        let received = ctx
+            .wallet
+            .vtxos()
+            .await?
+            .iter()
+            .any(|v| v.vtxo.user_pubkey() == user_pubkey);
+        if !received {
+            return Ok(address.clone());
+        }
+    }
+
+    let address = ctx
+        .wallet
+        .new_address()
+        .await
+        .context("Failed to create new address")?;
+    ctx.pending_address = Some(address.clone());
+    Ok(address)
+}
+
+/// Derives the next receive address ahead of time, if the pending one was
+/// used or there is none.
+pub async fn prefetch_address() -> anyhow::Result<()> {
+    timed("prefetch_address", async move {
+        let mut manager = lock_manager("prefetch_address").await;
+        manager
+            .with_context_async(|ctx| async {
+                pending_address(ctx).await?;
+                Ok(())
+            })
+            .await
+    })
+    .await
+}
+
+/// The address [`new_address`] would hand out, without deriving one.
+pub async fn get_pending_address() -> anyhow::Result<Option<bark::ark::Address>> {
+    timed("get_pending_address", async move {
+        let manager = lock_manager("get_pending_address").await;
+        manager.with_context_ref(|ctx| Ok(ctx.pending_address.clone()))
+    })
+    .await
+}
+
+/// Forgets the pending address, e.g. once a payment to it was received
+/// outside this wallet's view, so the next [`new_address`] derives a new one.
+pub async fn consume_pending_address() -> anyhow::Result<()> {
+    timed("consume_pending_address", async move {
+        let mut manager = lock_manager("consume_pending_address").await;
+        manager.with_context(|ctx| {
+            ctx.pending_address = None;
+            Ok(())
+        })
+    })
+    .await
+}
+
+pub async fn peak_address(index: u32) -> anyhow::Result<bark::ark::Address> {
+    timed("peak_address", async move {
+        let manager = lock_manager("peak_address").await;
+        manager
+            .with_context_ref_async(|ctx| async {
+                ctx.wallet
+                    .peak_address(index)
+                    .await
+                    .context("Failed to peak address")
+            })
+            .await
+    })
+    .await
+}
+
+/// The Ark address to receive arkoor payments on: the one at `index` if
+/// given, without storing it, or else a newly allocated one.
+pub async fn get_ark_receive_address(index: Option<u32>) -> anyhow::Result<String> {
+    timed("get_ark_receive_address", async move {
+        let address = match index {
+            Some(index) => peak_address(index).await?,
+            None => new_address().await?,
+        };
+        Ok(address.to_string())
+    })
+    .await
+}
+
+pub async fn refresh_server() -> anyhow::Result<()> {
+    timed("refresh_server", async move {
+        let mut manager = lock_manager("refresh_server").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_async(|ctx| async {
+                ctx.wallet
+                    .refresh_server()
+                    .await
+                    .context("Failed to refresh server connection")
+            })
+            .await
+    })
+    .await
+}
+
+pub async fn sign_message(
+    message: &str,
+    index: u32,
+) -> anyhow::Result<bark::ark::bitcoin::secp256k1::ecdsa::Signature> {
+    timed("sign_message", async move {
+        let manager = lock_manager("sign_message").await;
+        manager
+            .with_context_ref_async(|ctx| async {
+                let wallet = &ctx.wallet;
+                let keypair = wallet
+                    .peak_keypair(index)
+                    .await
+                    .context("Failed to peak keypair")?;
+                let hash = bark::ark::bitcoin::sign_message::signed_msg_hash(message);
+                let secp = bark::ark::bitcoin::secp256k1::Secp256k1::new();
+                let msg = bark::ark::bitcoin::secp256k1::Message::from_digest_slice(&hash[..])?;
+                let ecdsa_sig = secp.sign_ecdsa(&msg, &keypair.secret_key());
+
+                Ok(ecdsa_sig)
+            })
+            .await
+    })
+    .await
+}
+
+/// The Ark keypair at `index` under `purpose_index` of `mnemonic`.
+pub fn derive_ark_keypair(
+    mnemonic: &Mnemonic,
+    network: Network,
+    purpose_index: u32,
+    index: u32,
+) -> anyhow::Result<Keypair> {
+    let secp = bark::ark::bitcoin::secp256k1::Secp256k1::new();
+    let mut seed = mnemonic.to_seed("");
+    let master = bip32::Xpriv::new_master(network, &seed);
+    seed.zeroize();
+    let keypair = master?
+        .derive_priv(&secp, &[purpose_index.into()])?
+        .derive_priv(&secp, &[index.into()])?
+        .to_keypair(&secp);
+    Ok(keypair)
+}
+
+/// Known answer for [`verify_derivation_integrity`]: the key at index 0
+/// under the default purpose of the all-zero-entropy mnemonic.
+const DERIVATION_CHECK_MNEMONIC: &str =
+    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+const DERIVATION_CHECK_PUBKEY: &str =
+    "036be4943d5c6e0f9199e47e94adb6c02ed89b40cdb47a3e9404341b4ee2d365af";
+
+/// Re-derives a known key and checks it matches, for startup diagnostics.
+/// Returns false if this build derives different keys than earlier ones.
+pub fn verify_derivation_integrity() -> anyhow::Result<bool> {
+    let mnemonic = Mnemonic::from_str(DERIVATION_CHECK_MNEMONIC)?;
+    let keypair = derive_ark_keypair(&mnemonic, Network::Bitcoin, DEFAULT_ARK_PURPOSE_INDEX, 0)?;
+    let derived = keypair.public_key().to_string();
+    if derived != DERIVATION_CHECK_PUBKEY {
+        warn!(
+            "Key derivation mismatch: expected {}, derived {}",
+            DERIVATION_CHECK_PUBKEY, derived
+        );
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// The purpose index of the loaded wallet, or the default one if no wallet
+/// is loaded.
+pub async fn ark_purpose_index() -> anyhow::Result<u32> {
+    timed("ark_purpose_index", async move {
+        let manager = lock_manager("ark_purpose_index").await;
+        match manager.is_loaded() {
+            true => manager.with_context_ref(|ctx| ctx.store.get_ark_purpose_index()),
+            false => Ok(DEFAULT_ARK_PURPOSE_INDEX),
+        }
+    })
+    .await
+}
+
+pub async fn sign_messsage_with_mnemonic(
+    message: &str,
+    mnemonic: Mnemonic,
+    network: Network,
+    purpose_index: u32,
+    index: u32,
+) -> anyhow::Result<bark::ark::bitcoin::secp256k1::ecdsa::Signature> {
+    timed("sign_messsage_with_mnemonic", async move {
+        let secp = bark::ark::bitcoin::secp256k1::Secp256k1::new();
+        let keypair = derive_ark_keypair(&mnemonic, network, purpose_index, index)?;
+
+        let hash = bark::ark::bitcoin::sign_message::signed_msg_hash(message);
+        let msg = bark::ark::bitcoin::secp256k1::Message::from_digest_slice(&hash[..]).unwrap();
+        let ecdsa_sig = secp.sign_ecdsa(&msg, &keypair.secret_key());
+
+        Ok(ecdsa_sig)
+    })
+    .await
+}
+
+pub async fn derive_keypair_from_mnemonic(
+    mnemonic: Mnemonic,
+    network: Network,
+    purpose_index: u32,
+    index: u32,
+) -> anyhow::Result<Keypair> {
+    timed("derive_keypair_from_mnemonic", async move {
+        derive_ark_keypair(&mnemonic, network, purpose_index, index)
+    })
+    .await
+}
+
+pub async fn verify_message(
+    message: &str,
+    signature: bark::ark::bitcoin::secp256k1::ecdsa::Signature,
+    public_key: &bark::ark::bitcoin::secp256k1::PublicKey,
+) -> anyhow::Result<bool> {
+    timed("verify_message", async move {
+        let hash = bark::ark::bitcoin::sign_message::signed_msg_hash(message);
+        let secp = bark::ark::bitcoin::secp256k1::Secp256k1::new();
+        let msg = bark::ark::bitcoin::secp256k1::Message::from_digest_slice(&hash[..]).unwrap();
+        Ok(secp.verify_ecdsa(&msg, &signature, public_key).is_ok())
+    })
+    .await
+}
+
+/// Signs the SHA256 of the hex encoded `data_hex` with a BIP340 Schnorr
+/// signature using the keypair at `index`. Returns the signature as hex.
+pub async fn sign_data_schnorr(data_hex: &str, index: u32) -> anyhow::Result<String> {
+    timed("sign_data_schnorr", async move {
+        let keypair = peak_keypair(index).await?;
+        Ok(sign_data_schnorr_with_keypair(data_hex, &keypair)?.to_string())
+    })
+    .await
+}
+
+pub async fn bolt11_invoice(amount: Amount) -> anyhow::Result<Bolt11Invoice> {
+    timed("bolt11_invoice", async move {
+        let mut manager = lock_manager("bolt11_invoice").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async("bolt11_invoice", amount.to_string(), async |ctx| {
+                let invoice = ctx
+                    .wallet
+                    .bolt11_invoice(amount)
+                    .await
+                    .context("Failed to create bolt11_invoice")?;
+                Ok(invoice)
+            })
+            .await
+    })
+    .await
+}
+
+/// A fresh ark address, a fresh onchain address and, when an amount is
+/// given, a bolt11 invoice, together with a unified BIP21 URI of them.
+///
+/// Every call hands out new addresses. The invoice of the previous call is
+/// handed out again while it is for the same amount, unpaid, and valid for
+/// at least [`MIN_REUSED_INVOICE_VALIDITY_SECS`], so re-rendering the receive
+/// screen doesn't pile up invoices.
+pub async fn get_receive_bundle(amount: Option<Amount>) -> anyhow::Result<ReceiveBundle> {
+    timed("get_receive_bundle", async move {
+        let mut manager = lock_manager("get_receive_bundle").await;
+        if amount.is_some() {
+            manager.ensure_online()?;
+        }
+        manager
+            .with_context_async(|ctx| async move {
+                let ark_address = ctx
+                    .wallet
+                    .new_address()
+                    .await
+                    .context("Failed to create new address")?;
+                let onchain_address = ctx.onchain_wallet.address().await?;
+
+                let bolt11 = match amount {
+                    Some(amount) => Some(receive_bundle_invoice(ctx, amount).await?),
+                    None => None,
+                };
+
+                ReceiveBundle::new(
+                    ark_address.to_string(),
+                    onchain_address.to_string(),
+                    amount.map(|a| a.to_sat()),
+                    bolt11.map(|invoice| invoice.to_string()),
+                )
+            })
+            .await
+    })
+    .await
+}
+
+async fn receive_bundle_invoice(
+    ctx: &mut WalletContext,
+    amount: Amount,
+) -> anyhow::Result<Bolt11Invoice> {
+    if let Some(invoice) = &ctx.receive_invoice {
+        let payment_hash = PaymentHash::from_str(&invoice.payment_hash().to_string())?;
+        let payment_started = ctx
+            .wallet
+            .lightning_receive_status(payment_hash)
+            .await?
+            .is_some_and(|r| r.preimage_revealed_at.is_some() || r.finished_at.is_some());
+        if can_reuse_receive_invoice(
+            invoice,
+            amount.to_sat(),
+            payment_started,
+            now_timestamp() as u64,
+        ) {
+            return Ok(invoice.clone());
+        }
+    }
+
+    let invoice = ctx
+        .wallet
+        .bolt11_invoice(amount)
+        .await
+        .context("Failed to create bolt11_invoice")?;
+    ctx.receive_invoice = Some(invoice.clone());
+    Ok(invoice)
+}
+
+pub async fn lightning_receive_status(
+    payment: PaymentHash,
+) -> anyhow::Result<Option<LightningReceive>> {
+    timed("lightning_receive_status", async move {
+        let manager = lock_manager("lightning_receive_status").await;
+        manager
+            .with_context_ref_async(|ctx| async {
+                ctx.wallet
+                    .lightning_receive_status(payment)
+                    .await
+                    .context("Failed to get lightning receive status")
+            })
+            .await
+    })
+    .await
+}
+
+pub async fn get_lightning_receive(payment: PaymentHash) -> anyhow::Result<LightningReceive> {
+    timed("get_lightning_receive", async move {
+        lightning_receive_status(payment)
+            .await?
+            .with_context(|| format!("No lightning receive for payment hash {}", payment))
+    })
+    .await
+}
+
+/// The lightning receives bark still tracks, i.e. those not finished yet.
+/// Finished receives can be looked up by payment hash.
+pub async fn get_all_lightning_receives() -> anyhow::Result<Vec<LightningReceive>> {
+    timed("get_all_lightning_receives", async move {
+        let manager = lock_manager("get_all_lightning_receives").await;
+        manager
+            .with_context_ref_async(|ctx| async {
+                ctx.wallet
+                    .pending_lightning_receives()
+                    .await
+                    .context("Failed to get lightning receives")
+            })
+            .await
+    })
+    .await
+}
+
+pub async fn try_claim_lightning_receive(
+    payment_hash: PaymentHash,
+    wait: bool,
+    token: Option<String>,
+) -> anyhow::Result<LightningReceive> {
+    timed("try_claim_lightning_receive", async move {
+        let mut manager = lock_manager("try_claim_lightning_receive").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async(
+                "try_claim_lightning_receive",
+                payment_hash.to_string(),
+                async |ctx| {
+                    ctx.wallet
+                        .try_claim_lightning_receive(payment_hash, wait, token.as_deref())
+                        .await
+                        .context("Failed to claim bolt11 payment")
+                },
+            )
+            .await
+    })
+    .await
+}
+
+pub async fn try_claim_all_lightning_receives(wait: bool) -> anyhow::Result<()> {
+    timed("try_claim_all_lightning_receives", async move {
+        let mut manager = lock_manager("try_claim_all_lightning_receives").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async(
+                "try_claim_all_lightning_receives",
+                wait.to_string(),
+                async |ctx| claim_open_lightning_receives(ctx, wait).await,
+            )
+            .await
+    })
+    .await
+}
+
+async fn claim_open_lightning_receives(ctx: &WalletContext, wait: bool) -> anyhow::Result<()> {
+    // Claimed one by one to skip receives whose invoice expired
+    // unpaid, which bark would keep retrying
+    prune_expired_lightning_receives(ctx).await?;
+    let expired = ctx.store.get_expired_receives()?;
+    let mut pending = std::collections::HashSet::new();
+    let mut failed = 0;
+    for receive in ctx.wallet.pending_lightning_receives().await? {
+        let payment_hash = receive.payment_hash.to_string();
+        if expired.contains(&payment_hash) {
+            continue;
+        }
+        pending.insert(payment_hash.clone());
+        match ctx
+            .wallet
+            .try_claim_lightning_receive(receive.payment_hash, wait, None)
+            .await
+        {
+            Ok(_) => claim_failures().record_success(&payment_hash),
+            Err(err) => {
+                warn!("Failed to claim {}: {:#}", payment_hash, err);
+                let blocks_left = receive_blocks_left(&receive.invoice, now_timestamp() as u64);
+                if note_claim_failure(
+                    &mut claim_failures(),
+                    &payment_hash,
+                    receive.invoice.amount_milli_satoshis(),
+                    blocks_left,
+                    &format!("{:#}", err),
+                ) {
+                    warn!(
+                        "Receive {} is at risk, its HTLCs expire in about {} blocks",
+                        payment_hash, blocks_left
+                    );
+                }
+                failed += 1;
+            }
+        }
+    }
+    claim_failures().retain_pending(&pending);
+    if failed > 0 {
+        bail!("Failed to claim {} open invoices", failed);
+    }
+    Ok(())
+}
+
+/// Claims all open lightning receives like [`try_claim_all_lightning_receives`]
+/// and keeps retrying the failed ones until they are claimed, `max_wait_secs`
+/// passed or [`cancel_claim_retries`] is called.
+///
+/// Retries come faster as the HTLCs of a failing receive near expiry. The
+/// wallet lock is released while waiting between attempts.
+pub async fn claim_all_lightning_receives_with_retry(max_wait_secs: u64) -> anyhow::Result<()> {
+    timed("claim_all_lightning_receives_with_retry", async move {
+        reset_claim_retry_cancel();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(max_wait_secs);
+        loop {
+            let err = match try_claim_all_lightning_receives(false).await {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+            let Some(interval) = claim_failures().next_retry() else {
+                return Err(err);
+            };
+            if std::time::Instant::now() + interval > deadline {
+                return Err(err.context("Gave up retrying claims"));
+            }
+            debug!("Retrying failed claims in {}s", interval.as_secs());
+            if sleep_unless_cancelled(interval).await {
+                bail!("Claim retries cancelled");
+            }
+        }
+    })
+    .await
+}
+
+/// Receives whose claim failed while their HTLCs are close to expiry, most
+/// urgent first.
+pub fn get_at_risk_receives() -> Vec<FailedClaim> {
+    claim_failures().at_risk()
+}
+
+/// Has bark follow up on its pending boards, emitting a `BoardConfirmed`
+/// event for each board the server credited.
+pub async fn sync_pending_boards() -> anyhow::Result<()> {
+    timed("sync_pending_boards", async move {
+        let mut manager = lock_manager("sync_pending_boards").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async("sync_pending_boards", String::new(), async |ctx| {
+                let before = ctx.wallet.pending_boards().await?;
+                ctx.wallet
+                    .sync_pending_boards()
+                    .await
+                    .context("Failed to sync pending boards")?;
+                let still_pending = ctx
+                    .wallet
+                    .pending_boards()
+                    .await?
+                    .iter()
+                    .map(|board| board.funding_tx.compute_txid())
+                    .collect::<std::collections::HashSet<_>>();
+
+                for board in before {
+                    let txid = board.funding_tx.compute_txid();
+                    if still_pending.contains(&txid) || !board_credited(ctx, &board).await? {
+                        continue;
+                    }
+                    info!("Board {} was credited", txid);
+                    emit_event(
+                        WalletEventKind::BoardConfirmed,
+                        serde_json::json!({
+                            "funding_txid": txid.to_string(),
+                            "amount_sat": board.amount.to_sat(),
+                            "state": PendingBoardState::Credited.as_str(),
+                            "vtxo_ids": board
+                                .vtxos
+                                .iter()
+                                .map(|id| id.to_string())
+                                .collect::<Vec<_>>(),
+                        }),
+                    );
+                }
+                Ok(())
+            })
+            .await
+    })
+    .await
+}
+
+/// Whether the VTXOs of a board bark stopped following became spendable,
+/// rather than the board being given up on.
+async fn board_credited(ctx: &WalletContext, board: &PendingBoard) -> anyhow::Result<bool> {
+    let Some(id) = board.vtxos.first() else {
+        return Ok(false);
+    };
+    Ok(ctx
+        .db
+        .get_wallet_vtxo(*id)
+        .await?
+        .is_some_and(|vtxo| matches!(vtxo.state, VtxoState::Spendable)))
+}
+
+/// Boards bark is still following, with the confirmations of their funding
+/// transaction looked up from the chain source.
+pub async fn list_pending_boards() -> anyhow::Result<Vec<PendingBoardInfo>> {
+    timed("list_pending_boards", async move {
+        let manager = lock_manager("list_pending_boards").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_ref_async(|ctx| async {
+                let boards = ctx.wallet.pending_boards().await?;
+                if boards.is_empty() {
+                    return Ok(Vec::new());
+                }
+
+                let required = ctx
+                    .wallet
+                    .ark_info()
+                    .await?
+                    .context("Ark info not available")?
+                    .required_board_confirmations as u32;
+                let tip = ctx.wallet.chain.tip().await?;
+                let mut infos = Vec::with_capacity(boards.len());
+                for board in boards {
+                    let txid = board.funding_tx.compute_txid();
+                    let confirmed_at = match ctx.wallet.chain.tx_status(txid).await? {
+                        TxStatus::Confirmed(block) => Some(block.height),
+                        TxStatus::Mempool | TxStatus::NotFound => None,
+                    };
+                    let anchor = match board.vtxos.first() {
+                        Some(id) => ctx.db.get_wallet_vtxo(*id).await?,
+                        None => None,
+                    }
+                    .map(|vtxo| vtxo.vtxo.chain_anchor())
+                    .with_context(|| format!("Board {} has no known vtxo", txid))?;
+                    infos.push(PendingBoardInfo::new(
+                        anchor,
+                        board.amount,
+                        &board.vtxos,
+                        confirmed_at,
+                        tip,
+                        required,
+                    ));
+                }
+                Ok(infos)
+            })
+            .await
+    })
+    .await
+}
+
+pub async fn maintenance() -> anyhow::Result<()> {
+    timed("maintenance", async move {
+        let mut manager = lock_manager("maintenance").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async("maintenance", String::new(), async |ctx| {
+                ctx.wallet
+                    .maintenance()
+                    .await
+                    .context("Failed to perform wallet maintenance")?;
+                if let Err(err) = resume_board_intents(ctx).await {
+                    warn!("Failed to resume pending boards: {:#}", err);
+                }
+                if let Err(err) = auto_refresh(ctx).await {
+                    warn!("Failed to auto refresh vtxos: {:#}", err);
+                }
+                if let Err(err) = revoke_expired_htlcs(ctx).await {
+                    warn!("Failed to cancel expired htlcs: {:#}", err);
+                }
+                if let Err(err) = prune_expired_lightning_receives(ctx).await {
+                    warn!("Failed to prune expired lightning receives: {:#}", err);
+                }
+                if let Err(err) = match_received_payments(ctx).await {
+                    warn!("Failed to match expected payments: {:#}", err);
+                }
+                if let Err(err) = ctx
+                    .store
+                    .prune_journal(now_timestamp() - JOURNAL_RETENTION_SECS, JOURNAL_MAX_ROWS)
+                {
+                    warn!("Failed to prune event journal: {:#}", err);
+                }
+                Ok(())
+            })
+            .await
+    })
+    .await
+}
+
+pub async fn maintenance_delegated() -> anyhow::Result<()> {
+    timed("maintenance_delegated", async move {
+        let mut manager = lock_manager("maintenance_delegated").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async("maintenance_delegated", String::new(), async |ctx| {
+                ctx.wallet
+                    .maintenance_delegated()
+                    .await
+                    .context("Failed to perform wallet maintenance delegated")?;
+                Ok(())
+            })
+            .await
+    })
+    .await
+}
+
+pub async fn maintenance_with_onchain() -> anyhow::Result<()> {
+    timed("maintenance_with_onchain", async move {
+        let mut manager = lock_manager("maintenance_with_onchain").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async("maintenance_with_onchain", String::new(), async |ctx| {
+                ctx.wallet
+                    .maintenance_with_onchain(&mut ctx.onchain_wallet)
+                    .await
+                    .context("Failed to perform wallet maintenance with onchain")?;
+                if let Err(err) = resume_board_intents(ctx).await {
+                    warn!("Failed to resume pending boards: {:#}", err);
+                }
+                if let Err(err) = auto_refresh(ctx).await {
+                    warn!("Failed to auto refresh vtxos: {:#}", err);
+                }
+                if let Err(err) = revoke_expired_htlcs(ctx).await {
+                    warn!("Failed to cancel expired htlcs: {:#}", err);
+                }
+                if let Err(err) = prune_expired_lightning_receives(ctx).await {
+                    warn!("Failed to prune expired lightning receives: {:#}", err);
+                }
+                if let Err(err) = match_received_payments(ctx).await {
+                    warn!("Failed to match expected payments: {:#}", err);
+                }
+                if let Err(err) = ctx
+                    .store
+                    .prune_journal(now_timestamp() - JOURNAL_RETENTION_SECS, JOURNAL_MAX_ROWS)
+                {
+                    warn!("Failed to prune event journal: {:#}", err);
+                }
+                Ok(())
+            })
+            .await
+    })
+    .await
+}
+
+pub async fn maintenance_with_onchain_delegated() -> anyhow::Result<()> {
+    timed("maintenance_with_onchain_delegated", async move {
+        let mut manager = lock_manager("maintenance_with_onchain_delegated").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async(
+                "maintenance_with_onchain_delegated",
+                String::new(),
+                async |ctx| {
+                    ctx.wallet
+                        .maintenance_with_onchain_delegated(&mut ctx.onchain_wallet)
+                        .await
+                        .context("Failed to perform wallet maintenance with onchain delegated")?;
+                    Ok(())
+                },
+            )
+            .await
+    })
+    .await
+}
+
+pub async fn maintenance_refresh() -> anyhow::Result<()> {
+    timed("maintenance_refresh", async move {
+        let mut manager = lock_manager("maintenance_refresh").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async("maintenance_refresh", String::new(), async |ctx| {
+                ctx.wallet
+                    .maintenance_refresh()
+                    .await
+                    .context("Failed to perform vtxo maintenance refresh")?;
+                Ok(())
+            })
+            .await
+    })
+    .await
+}
+
+/// Waits for an arkoor payment to the pubkey at `pubkey_index`, of `amount`
+/// if given, for `expiry_secs`. Received VTXOs are matched against it on
+/// every [`sync`] and [`maintenance`].
+pub async fn register_expected_payment(
+    pubkey_index: u32,
+    amount: Option<Amount>,
+    memo: &str,
+    expiry_secs: u32,
+) -> anyhow::Result<ExpectedPayment> {
+    timed("register_expected_payment", async move {
+        let manager = lock_manager("register_expected_payment").await;
+        manager
+            .with_context_ref_async(|ctx| async {
+                let keypair = ctx
+                    .wallet
+                    .peak_keypair(pubkey_index)
+                    .await
+                    .context("Failed to peak keypair")?;
+                ctx.store.add_expected_payment(
+                    pubkey_index,
+                    &keypair.public_key().to_string(),
+                    amount.map(|a| a.to_sat()),
+                    memo,
+                    now_timestamp() + i64::from(expiry_secs),
+                )
+            })
+            .await
+    })
+    .await
+}
+
+/// Expected payments, all of them or those in `status`, oldest first.
+pub async fn list_expected_payments(
+    status: Option<ExpectedPaymentStatus>,
+) -> anyhow::Result<Vec<ExpectedPayment>> {
+    timed("list_expected_payments", async move {
+        let manager = lock_manager("list_expected_payments").await;
+        manager.with_context_ref(|ctx| ctx.store.get_expected_payments(status))
+    })
+    .await
+}
+
+/// Stops waiting for an expected payment. Returns whether it was open.
+pub async fn cancel_expected_payment(id: i64) -> anyhow::Result<bool> {
+    timed("cancel_expected_payment", async move {
+        let manager = lock_manager("cancel_expected_payment").await;
+        manager.with_context_ref(|ctx| ctx.store.cancel_expected_payment(id))
+    })
+    .await
+}
+
+/// Marks open expected payments paid by a received VTXO as fulfilled,
+/// emitting an `ExpectedPaymentReceived` event for each, and expires the
+/// ones that ran out of time.
+async fn match_received_payments(ctx: &WalletContext) -> anyhow::Result<()> {
+    let payments = ctx.store.get_expected_payments(None)?;
+    let claimed = payments
+        .iter()
+        .filter_map(|p| p.vtxo_id.clone())
+        .collect::<std::collections::HashSet<_>>();
+    let open = payments
+        .into_iter()
+        .filter(|p| p.status == ExpectedPaymentStatus::Open)
+        .collect::<Vec<_>>();
+    if open.is_empty() {
+        return Ok(());
+    }
+
+    let received = received_vtxos(&ctx.wallet.vtxos().await?);
+    for (id, vtxo_id) in match_expected_payments(&open, &received, &claimed) {
+        if !ctx.store.fulfill_expected_payment(id, &vtxo_id)? {
+            continue;
+        }
+        let Some(payment) = open.iter().find(|p| p.id == id) else {
+            continue;
+        };
+        let amount_sat = received
+            .iter()
+            .find(|v| v.vtxo_id == vtxo_id)
+            .map(|v| v.amount_sat);
+        info!("Expected payment {} was paid by {}", id, vtxo_id);
+        emit_event(
+            WalletEventKind::ExpectedPaymentReceived,
+            serde_json::json!({
+                "id": id,
+                "vtxo_id": vtxo_id,
+                "amount_sat": amount_sat,
+                "memo": payment.memo,
+            }),
+        );
+    }
+
+    ctx.store.expire_expected_payments(now_timestamp())?;
+    Ok(())
+}
+
+const SYNC_TYPE_WALLET: &str = "wallet";
+
+pub async fn sync() -> anyhow::Result<()> {
+    timed("sync", async move {
+        let mut manager = lock_manager("sync").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async("sync", String::new(), async |ctx| {
+                ctx.wallet.sync().await;
+                // bark's sync logs its errors instead of returning them, so
+                // the sync only counts as successful if the chain is reachable
+                match ctx.wallet.chain.tip().await {
+                    Ok(tip) => ctx.store.record_sync(SYNC_TYPE_WALLET, Some(tip), true)?,
+                    Err(err) => {
+                        ctx.store.record_sync(SYNC_TYPE_WALLET, None, false)?;
+                        return Err(err).context("Failed to get the chain tip after syncing");
+                    }
+                }
+                if let Err(err) = match_received_payments(ctx).await {
+                    warn!("Failed to match expected payments: {:#}", err);
+                }
+                Ok(())
+            })
+            .await
+    })
+    .await
+}
+
+/// Appends the outcome of `operation` to the event journal in `store`.
+fn append_journal<T>(
+    store: &Store,
+    operation: &'static str,
+    params: &str,
+    result: &anyhow::Result<T>,
+) {
+    let entry = JournalEntry {
+        created_at: now_timestamp(),
+        operation: operation.to_string(),
+        params_hash: params_hash(params),
+        result: match result {
+            Ok(_) => "ok".to_string(),
+            Err(_) => "error".to_string(),
+        },
+        error: result
+            .as_ref()
+            .err()
+            .map(|err| redact_sensitive(&format!("{:#}", err))),
+    };
+    if let Err(err) = store.append_journal_entry(&entry) {
+        debug!("Not journaling {}: {:#}", operation, err);
+    }
+}
+
+/// Writes the journal entries created in `[from_ts, to_ts]` to `dest_path`
+/// as JSON lines. Returns the number of entries written.
+pub async fn export_event_journal(
+    dest_path: &Path,
+    from_ts: i64,
+    to_ts: i64,
+) -> anyhow::Result<u64> {
+    timed("export_event_journal", async move {
+        let entries = {
+            let manager = lock_manager("export_event_journal").await;
+            manager.with_context_ref(|ctx| ctx.store.get_journal_entries(from_ts, to_ts))?
+        };
+
+        let mut lines = String::new();
+        for entry in &entries {
+            lines.push_str(&entry.to_json_line());
+            lines.push('\n');
+        }
+        tokio::fs::write(dest_path, lines)
+            .await
+            .with_context(|| format!("Failed to write journal to {}", dest_path.display()))?;
+        Ok(entries.len() as u64)
+    })
+    .await
+}
+
+/// Unix timestamp of the last successful `sync`, `None` if never synced
+pub async fn get_last_successful_sync_timestamp() -> anyhow::Result<Option<i64>> {
+    timed("get_last_successful_sync_timestamp", async move {
+        let manager = lock_manager("get_last_successful_sync_timestamp").await;
+        manager.with_context_ref(|ctx| ctx.store.last_successful_sync(SYNC_TYPE_WALLET))
+    })
+    .await
+}
+
+pub async fn history() -> anyhow::Result<Vec<Movement>> {
+    timed("history", async move {
+        let manager = lock_manager("history").await;
+        manager
+            .with_context_ref_async(|ctx| async { ctx.wallet.history().await })
+            .await
+    })
+    .await
+}
+
+/// Movements read per lock when exporting, to keep memory bounded
+const EXPORT_PAGE_SIZE: usize = 500;
+
+/// Writes the movements created in `[from_ts, to_ts]` to `dest_path` as
+/// CSV for accounting, with the running offchain balance. Returns the
+/// number of rows written.
+///
+/// The history is read a page at a time, oldest first. The fiat column
+/// stays empty as there is no exchange rate module.
+pub async fn export_movements_csv(
+    dest_path: &Path,
+    from_ts: i64,
+    to_ts: i64,
+) -> anyhow::Result<u64> {
+    timed("export_movements_csv", async move {
+        if from_ts > to_ts {
+            bail!("Export range starts after it ends");
+        }
+        let file = std::fs::File::create(dest_path)
+            .with_context(|| format!("Failed to create {}", dest_path.display()))?;
+        let write_failed = || format!("Failed to write {}", dest_path.display());
+        let mut csv = AccountingCsvWriter::new(std::io::BufWriter::new(file), from_ts, to_ts)
+            .with_context(write_failed)?;
+
+        let mut cursor = None;
+        loop {
+            let manager = lock_manager("export_movements_csv").await;
+            let (rows, last) = manager
+                .with_context_ref_async(|ctx| async move {
+                    let keys = {
+                        let conn = open_bark_db_read_only(&ctx.datadir.join(DB_FILE))?;
+                        get_movement_page(&conn, cursor, EXPORT_PAGE_SIZE, false)?
+                    };
+                    let last = keys.last().copied();
+                    let mut rows = Vec::with_capacity(keys.len());
+                    for key in keys {
+                        rows.push(AccountingRow::from_movement(
+                            &movement_by_id(ctx, key).await?,
+                        ));
+                    }
+                    Ok((rows, last))
+                })
+                .await?;
+            drop(manager);
+
+            let page_len = rows.len();
+            for row in &rows {
+                csv.push(row).with_context(write_failed)?;
+            }
+            let past_range = rows.last().is_some_and(|row| row.created_at > to_ts);
+            if page_len < EXPORT_PAGE_SIZE || past_range {
+                break;
+            }
+            cursor = last;
+        }
+
+        let written = csv.finish().with_context(write_failed)?;
+        info!("Exported {} movements to {}", written, dest_path.display());
+        Ok(written)
+    })
+    .await
+}
+
+/// Total number of movements, for paginating the history
+pub async fn get_movement_count() -> anyhow::Result<u64> {
+    timed("get_movement_count", async move {
+        let manager = lock_manager("get_movement_count").await;
+        manager.with_context_ref(|ctx| {
+            let conn = open_bark_db_read_only(&ctx.datadir.join(DB_FILE))?;
+            count_movements(&conn)
+        })
+    })
+    .await
+}
+
+/// A page of the movement history, newest first, starting after `cursor`
+/// or at the newest movement without one. Returns the movements and the
+/// cursor of the next page, `None` once the history is exhausted.
+pub async fn get_movements_after(
+    cursor: Option<MovementCursor>,
+    limit: usize,
+) -> anyhow::Result<(Vec<Movement>, Option<MovementCursor>)> {
+    timed("get_movements_after", async move {
+        if limit == 0 {
+            bail!("Page size must be at least 1");
+        }
+        let manager = lock_manager("get_movements_after").await;
+        manager
+            .with_context_ref_async(|ctx| async move {
+                // One more than asked for tells whether there is a next page
+                let keys = {
+                    let conn = open_bark_db_read_only(&ctx.datadir.join(DB_FILE))?;
+                    get_movement_page(&conn, cursor, limit + 1, true)?
+                };
+                let mut page = Vec::with_capacity(keys.len());
+                for key in keys {
+                    page.push((key, movement_by_id(ctx, key).await?));
+                }
+                Ok(movements_page(page, cursor, limit))
+            })
+            .await
+    })
+    .await
+}
+
+async fn movement_by_id(ctx: &WalletContext, key: MovementCursor) -> anyhow::Result<Movement> {
+    ctx.db
+        .get_movement_by_id(MovementId(key.id))
+        .await
+        .with_context(|| format!("Failed to read movement {}", key.id))
+}
+
+/// Totals since wallet creation, summed up by the wallet database on every
+/// call so they can't drift from the movement history.
+pub async fn get_wallet_stats() -> anyhow::Result<WalletStats> {
+    timed("get_wallet_stats", async move {
+        wallet_stats_in_range(i64::MIN, i64::MAX).await
+    })
+    .await
+}
+
+/// Stats over the movements created in `[from_ts, to_ts]`.
+async fn wallet_stats_in_range(from_ts: i64, to_ts: i64) -> anyhow::Result<WalletStats> {
+    let manager = lock_manager("wallet_stats_in_range").await;
+    manager.with_context_ref(|ctx| {
+        let conn = open_bark_db_read_only(&ctx.datadir.join(DB_FILE))?;
+        Ok(lifetime_stats(&get_subsystem_totals(
+            &conn, from_ts, to_ts,
+        )?))
+    })
+}
+
+/// The balance at the end of each of the last `days` days, oldest first,
+/// replayed from the daily movement totals and the onchain transactions.
+pub async fn get_balance_history(days: u32) -> anyhow::Result<Vec<BalanceSnapshot>> {
+    timed("get_balance_history", async move {
+        let manager = lock_manager("get_balance_history").await;
+        manager.with_context_ref(|ctx| {
+            let mut changes = {
+                let conn = open_bark_db_read_only(&ctx.datadir.join(DB_FILE))?;
+                get_daily_movement_changes(&conn)?
+            };
+
+            for wtx in ctx.onchain_wallet.transactions() {
+                let at = match wtx.chain_position {
+                    ChainPosition::Confirmed { anchor, .. } => anchor.confirmation_time as i64,
+                    ChainPosition::Unconfirmed { last_seen, .. } => {
+                        last_seen.map_or(now_timestamp(), |t| t as i64)
+                    }
+                };
+                let (sent, received) = ctx.onchain_wallet.sent_and_received(&wtx.tx_node.tx);
+                changes.push(BalanceChange {
+                    at,
+                    offchain_sat: 0,
+                    onchain_sat: received.to_sat() as i64 - sent.to_sat() as i64,
+                });
+            }
+
+            Ok(daily_balances(&changes, days, now_timestamp()))
+        })
+    })
+    .await
+}
+
+pub async fn get_total_received_sat() -> anyhow::Result<u64> {
+    timed("get_total_received_sat", async move {
+        Ok(get_wallet_stats().await?.total_received_sat)
+    })
+    .await
+}
+
+pub async fn get_total_sent_sat() -> anyhow::Result<u64> {
+    timed("get_total_sent_sat", async move {
+        Ok(get_wallet_stats().await?.total_sent_sat)
+    })
+    .await
+}
+
+/// Received by movements created in `[from_ts, to_ts]`
+pub async fn get_total_received_in_range(from_ts: i64, to_ts: i64) -> anyhow::Result<u64> {
+    timed("get_total_received_in_range", async move {
+        Ok(wallet_stats_in_range(from_ts, to_ts)
+            .await?
+            .total_received_sat)
+    })
+    .await
+}
+
+/// Sent by movements created in `[from_ts, to_ts]`
+pub async fn get_total_sent_in_range(from_ts: i64, to_ts: i64) -> anyhow::Result<u64> {
+    timed("get_total_sent_in_range", async move {
+        Ok(wallet_stats_in_range(from_ts, to_ts).await?.total_sent_sat)
+    })
+    .await
+}
+
+pub async fn vtxos() -> anyhow::Result<Vec<WalletVtxo>> {
+    timed("vtxos", async move {
+        let manager = lock_manager("vtxos").await;
+        manager
+            .with_context_ref_async(|ctx| async { ctx.wallet.vtxos().await })
+            .await
+    })
+    .await
+}
+
+/// The VTXOs matching `query`, and how many match in total.
+pub async fn get_vtxos_filtered(query: VtxoQuery) -> anyhow::Result<(Vec<WalletVtxo>, u64)> {
+    timed("get_vtxos_filtered", async move {
+        let manager = lock_manager("get_vtxos_filtered").await;
+        manager
+            .with_context_ref_async(|ctx| async move {
+                let states = match query.states.is_empty() {
+                    true => ALL_VTXO_STATES.to_vec(),
+                    false => query.states.clone(),
+                };
+                let vtxos = ctx.db.get_vtxos_by_state(&states).await?;
+                let (page, total) = query_vtxos(vtxos, wallet_vtxo_query_fields, &query);
+                Ok((page, total as u64))
+            })
+            .await
+    })
+    .await
+}
+
+/// Every state the VTXO went through, oldest first. Meant for debugging
+/// VTXOs that are stuck in some state.
+pub async fn get_vtxo_history(vtxo_id: VtxoId) -> anyhow::Result<Vec<VtxoStateTransition>> {
+    timed("get_vtxo_history", async move {
+        let manager = lock_manager("get_vtxo_history").await;
+        manager.with_context_ref(|ctx| {
+            let conn = open_bark_db_read_only(&ctx.datadir.join(DB_FILE))?;
+            let history = get_vtxo_state_history(&conn, &vtxo_id.to_string())?;
+            if history.is_empty() {
+                bail!("No history for VTXO {}", vtxo_id);
+            }
+            Ok(history)
+        })
+    })
+    .await
+}
+
+/// Puts a VTXO into `state` regardless of the state it is in. Meant for
+/// repair tooling only; everything else must go through the transition
+/// checks.
+pub async fn force_vtxo_state(vtxo_id: VtxoId, state: VtxoState) -> anyhow::Result<()> {
+    timed("force_vtxo_state", async move {
+        let mut manager = lock_manager("force_vtxo_state").await;
+        manager
+            .with_context_journaled_async(
+                "force_vtxo_state",
+                format!("{} {:?}", vtxo_id, state),
+                async move |ctx| force_vtxo_state_unchecked(&ctx.db, vtxo_id, state).await,
+            )
+            .await
+    })
+    .await
+}
+
+/// Number of VTXOs in any of the given states
+pub async fn get_vtxo_count(states: &[VtxoStateKind]) -> anyhow::Result<u64> {
+    timed("get_vtxo_count", async move {
+        let manager = lock_manager("get_vtxo_count").await;
+        manager.with_context_ref(|ctx| {
+            let conn = open_bark_db_read_only(&ctx.datadir.join(DB_FILE))?;
+            let state_kinds = states
+                .iter()
+                .map(|kind| vtxo_state_kind_name(*kind))
+                .collect::<Vec<_>>();
+            count_vtxos_in_states(&conn, &state_kinds)
+        })
+    })
+    .await
+}
+
+pub const SNAPSHOT_BALANCE: u32 = 1 << 0;
+pub const SNAPSHOT_ARK_INFO: u32 = 1 << 1;
+pub const SNAPSHOT_VTXOS: u32 = 1 << 2;
+pub const SNAPSHOT_MOVEMENTS: u32 = 1 << 3;
+pub const SNAPSHOT_ONCHAIN_UTXOS: u32 = 1 << 4;
+pub const SNAPSHOT_EXPIRING_VTXOS: u32 = 1 << 5;
+pub const SNAPSHOT_LIGHTNING_RECEIVES: u32 = 1 << 6;
+pub const SNAPSHOT_ALL: u32 = (1 << 7) - 1;
+
+/// Number of movements included in a snapshot, i.e. the first history page
+pub const SNAPSHOT_MOVEMENTS_PAGE_SIZE: usize = 20;
+
+/// Everything the app shows on start, gathered under a single lock.
+///
+/// Fields that were not requested are `None`. Requested fields carry their
+/// own result, so one failing component doesn't fail the whole snapshot.
+pub struct WalletSnapshot {
+    pub balance: Option<anyhow::Result<OffchainBalance>>,
+    pub ark_info: Option<anyhow::Result<ArkInfo>>,
+    pub vtxos: Option<anyhow::Result<Vec<WalletVtxo>>>,
+    pub movements: Option<anyhow::Result<Vec<Movement>>>,
+    pub onchain_utxos: Option<anyhow::Result<Vec<Utxo>>>,
+    pub expiring_vtxos: Option<anyhow::Result<Vec<WalletVtxo>>>,
+    pub lightning_receives: Option<anyhow::Result<Vec<LightningReceive>>>,
+}
+
+pub async fn get_wallet_snapshot(fields_mask: u32) -> anyhow::Result<WalletSnapshot> {
+    timed("get_wallet_snapshot", async move {
+        let manager = lock_manager("get_wallet_snapshot").await;
+        manager
+            .with_context_ref_async(|ctx| async move {
+                let wanted = |field: u32| fields_mask & field != 0;
+
+                let balance = match wanted(SNAPSHOT_BALANCE) {
+                    true => Some(offchain_balance_of(ctx).await),
+                    false => None,
+                };
+                let ark_info = match wanted(SNAPSHOT_ARK_INFO) {
+                    true => Some(
+                        ctx.wallet
+                            .ark_info()
+                            .await
+                            .context("Failed to get ark info")
+                            .and_then(|info| {
+                                info.context("Failed to get ark info, returned as null")
+                            }),
+                    ),
+                    false => None,
+                };
+                let vtxos = match wanted(SNAPSHOT_VTXOS) {
+                    true => Some(ctx.wallet.vtxos().await),
+                    false => None,
+                };
+                let movements = match wanted(SNAPSHOT_MOVEMENTS) {
+                    true => Some(ctx.wallet.history().await.map(|mut history| {
+                        history.truncate(SNAPSHOT_MOVEMENTS_PAGE_SIZE);
+                        history
+                    })),
+                    false => None,
+                };
+                let onchain_utxos = match wanted(SNAPSHOT_ONCHAIN_UTXOS) {
+                    true => Some(Ok(ctx.onchain_wallet.utxos())),
+                    false => None,
+                };
+                let expiring_vtxos = match wanted(SNAPSHOT_EXPIRING_VTXOS) {
+                    true => {
+                        let threshold = ctx.config.vtxo_refresh_expiry_threshold;
+                        Some(
+                            ctx.wallet
+                                .get_expiring_vtxos(threshold)
+                                .await
+                                .context("Failed to get expiring vtxos"),
+                        )
+                    }
+                    false => None,
+                };
+                let lightning_receives = match wanted(SNAPSHOT_LIGHTNING_RECEIVES) {
+                    true => Some(open_lightning_receives(ctx).await),
+                    false => None,
+                };
+
+                Ok(WalletSnapshot {
+                    balance,
+                    ark_info,
+                    vtxos,
+                    movements,
+                    onchain_utxos,
+                    expiring_vtxos,
+                    lightning_receives,
+                })
+            })
+            .await
+    })
+    .await
+}
+
+pub async fn get_expiring_vtxos(threshold: BlockHeight) -> anyhow::Result<Vec<WalletVtxo>> {
+    timed("get_expiring_vtxos", async move {
+        let manager = lock_manager("get_expiring_vtxos").await;
+
+        manager
+            .with_context_ref_async(|ctx| async {
+                ctx.wallet
+                    .get_expiring_vtxos(threshold)
+                    .await
+                    .context("Failed to get expiring vtxos")
+            })
+            .await
+    })
+    .await
+}
+
+pub async fn refresh_vtxos(vtxos: Vec<Vtxo>) -> anyhow::Result<Option<RoundStatus>> {
+    timed("refresh_vtxos", async move {
+        let mut manager = lock_manager("refresh_vtxos").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async(
+                "refresh_vtxos",
+                vtxos
+                    .iter()
+                    .map(|v| v.id().to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                async move |ctx| {
+                    let started = std::time::Instant::now();
+                    let status = match ctx.wallet.refresh_vtxos(vtxos).await {
+                        Ok(status) => status,
+                        Err(e) => {
+                            let e = e.context("Failed to refresh vtxos");
+                            return Err(on_round_failure(e, || ctx.wallet.sync()).await);
+                        }
+                    };
+                    if let Some(
+                        RoundStatus::Confirmed { funding_txid }
+                        | RoundStatus::Unconfirmed { funding_txid },
+                    ) = &status
+                    {
+                        record_round(ctx, *funding_txid, started);
+                    }
+                    Ok(status)
+                },
+            )
+            .await
+    })
+    .await
+}
+
+/// Returns the block height at which the first VTXO will expire
+pub async fn get_first_expiring_vtxo_blockheight() -> anyhow::Result<Option<BlockHeight>> {
+    timed("get_first_expiring_vtxo_blockheight", async move {
+        let manager = lock_manager("get_first_expiring_vtxo_blockheight").await;
+        manager
+            .with_context_ref_async(|ctx| async {
+                ctx.wallet
+                    .get_first_expiring_vtxo_blockheight()
+                    .await
+                    .context("Failed to get first expiring vtxo blockheight")
+            })
+            .await
+    })
+    .await
+}
+
+/// Returns the next block height at which we have a VTXO that we
+/// want to refresh
+pub async fn get_next_required_refresh_blockheight() -> anyhow::Result<Option<BlockHeight>> {
+    timed("get_next_required_refresh_blockheight", async move {
+        let manager = lock_manager("get_next_required_refresh_blockheight").await;
+        manager
+            .with_context_ref_async(|ctx| async {
+                let first_expiring = ctx
+                    .wallet
+                    .get_first_expiring_vtxo_blockheight()
+                    .await
+                    .context("Failed to get next required refresh blockheight")?;
+                let threshold = ctx.config.vtxo_refresh_expiry_threshold;
+                Ok(first_expiring.map(|height| height.saturating_sub(threshold)))
+            })
+            .await
+    })
+    .await
+}
+
+/// Records the outcome of a board on its intent so it can be resumed later.
+///
+/// A failure to record is only logged: the board already happened, or
+/// failed, and that is what the caller has to hear about. An intent left
+/// unrecorded is picked up by [`resume_pending_boards`].
+fn record_board_result(store: &Store, intent_id: i64, result: &anyhow::Result<PendingBoard>) {
+    let recorded = match result {
+        Ok(board) => store.update_board_intent(
+            intent_id,
+            BoardIntentState::Funded,
+            Some(&board.funding_tx.compute_txid().to_string()),
+            None,
+        ),
+        Err(err) => store.update_board_intent(
+            intent_id,
+            BoardIntentState::Failed,
+            None,
+            Some(&err.to_string()),
+        ),
+    };
+    if let Err(err) = recorded {
+        warn!(
+            "Failed to record the outcome of board {}: {:#}",
+            intent_id, err
+        );
+    }
+}
+
+/// The outcome of a board, with the fee paid for the funding transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NitroBoardResult {
+    pub txid: String,
+    pub amount_sat: u64,
+    pub fee_sat: u64,
+    pub vtxo_ids: Vec<String>,
+}
+
+impl NitroBoardResult {
+    /// `wallet_utxos` are the onchain wallet's outputs from before the board,
+    /// among them the ones the funding transaction spends.
+    pub fn new(
+        funding_tx: &Transaction,
+        amount: Amount,
+        vtxo_ids: &[VtxoId],
+        wallet_utxos: &[(OutPoint, Amount)],
+    ) -> Self {
+        NitroBoardResult {
+            txid: funding_tx.compute_txid().to_string(),
+            amount_sat: amount.to_sat(),
+            fee_sat: funding_tx_fee(funding_tx, wallet_utxos).to_sat(),
+            vtxo_ids: vtxo_ids.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    fn from_board(board: &PendingBoard, wallet_utxos: &[(OutPoint, Amount)]) -> Self {
+        NitroBoardResult::new(&board.funding_tx, board.amount, &board.vtxos, wallet_utxos)
+    }
+}
+
+fn wallet_utxos(onchain_wallet: &OnchainWallet) -> Vec<(OutPoint, Amount)> {
+    onchain_wallet
+        .list_unspent()
+        .into_iter()
+        .map(|utxo| (utxo.outpoint, utxo.txout.value))
+        .collect()
+}
+
+/// What boarding needs: the smallest and largest amount, and the fees to
+/// expect at the configured fee rate.
+///
+/// The server doesn't advertise a board minimum or fees in its ark info, so
+/// the minimum is the dust limit of the board output.
+pub async fn get_board_requirements() -> anyhow::Result<BoardRequirements> {
+    timed("get_board_requirements", async move {
+        let manager = lock_manager("get_board_requirements").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_ref_async(|ctx| async { board_requirements(ctx).await })
+            .await
+    })
+    .await
+}
+
+async fn board_requirements(ctx: &WalletContext) -> anyhow::Result<BoardRequirements> {
+    let ark_info = ctx
+        .wallet
+        .ark_info()
+        .await?
+        .context("Failed to get ark info, returned as null")?;
+    Ok(BoardRequirements::new(
+        None,
+        ark_info.max_vtxo_amount,
+        onchain::fee_rate_or_min_relay(ctx.config.fallback_fee_rate),
+    ))
+}
+
+/// Boards `amount`, failing with [`BoardAmountError`] before building the
+/// funding transaction if it is outside [`get_board_requirements`].
+pub async fn board_amount(amount: Amount) -> anyhow::Result<NitroBoardResult> {
+    timed("board_amount", async move {
+        let mut manager = lock_manager("board_amount").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async("board_amount", amount.to_string(), async |ctx| {
+                board_requirements(ctx).await?.check(amount)?;
+                let intent_id = ctx.store.create_board_intent(Some(amount.to_sat()))?;
+                let wallet_utxos = wallet_utxos(&ctx.onchain_wallet);
+                let result = ctx
+                    .wallet
+                    .board_amount(&mut ctx.onchain_wallet, amount)
+                    .await;
+                record_board_result(&ctx.store, intent_id, &result);
+                Ok(NitroBoardResult::from_board(&result?, &wallet_utxos))
+            })
+            .await
+    })
+    .await
+}
+
+pub async fn board_all() -> anyhow::Result<NitroBoardResult> {
+    timed("board_all", async move {
+        let mut manager = lock_manager("board_all").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async("board_all", String::new(), async |ctx| {
+                let intent_id = ctx.store.create_board_intent(None)?;
+                let wallet_utxos = wallet_utxos(&ctx.onchain_wallet);
+                let result = ctx.wallet.board_all(&mut ctx.onchain_wallet).await;
+                record_board_result(&ctx.store, intent_id, &result);
+                Ok(NitroBoardResult::from_board(&result?, &wallet_utxos))
+            })
+            .await
+    })
+    .await
+}
+
+async fn resume_board_intents(ctx: &WalletContext) -> anyhow::Result<BoardResumeReport> {
+    let mut report = BoardResumeReport::default();
+    let intents = ctx.store.get_open_board_intents()?;
+    if intents.is_empty() {
+        return Ok(report);
+    }
+
+    // Let bark register whatever it persisted itself before we look at our
+    // intents
+    let pending_before = ctx.wallet.pending_boards().await?;
+    ctx.wallet
+        .sync_pending_boards()
+        .await
+        .context("Failed to sync pending boards")?;
+    let still_pending = ctx
+        .wallet
+        .pending_boards()
+        .await?
+        .iter()
+        .map(|board| board.funding_tx.compute_txid())
+        .collect::<std::collections::HashSet<_>>();
+
+    let mut recorded_txids = intents
+        .iter()
+        .filter_map(|intent| intent.funding_txid.clone())
+        .collect::<std::collections::HashSet<_>>();
+    let unconfirmed_spends = ctx.onchain_wallet.transactions().any(|wtx| {
+        !wtx.chain_position.is_confirmed()
+            && ctx.onchain_wallet.sent_and_received(&wtx.tx_node.tx).0 > Amount::ZERO
+    });
+
+    for mut intent in intents {
+        // A board interrupted before its txid was recorded may still be
+        // followed by bark, take over its funding txid if so
+        if intent.funding_txid.is_none() {
+            let adopted = pending_before.iter().find(|board| {
+                let txid = board.funding_tx.compute_txid().to_string();
+                !recorded_txids.contains(&txid)
+                    && intent
+                        .amount_sat
+                        .is_none_or(|amount| amount == board.amount.to_sat())
+            });
+            if let Some(board) = adopted {
+                let txid = board.funding_tx.compute_txid().to_string();
+                info!("Board intent {} was funded by {}", intent.id, txid);
+                ctx.store.update_board_intent(
+                    intent.id,
+                    BoardIntentState::Funded,
+                    Some(&txid),
+                    None,
+                )?;
+                recorded_txids.insert(txid.clone());
+                intent.state = BoardIntentState::Funded;
+                intent.funding_txid = Some(txid);
+            }
+        }
+
+        let funding_txid = match &intent.funding_txid {
+            Some(txid) => Some(Txid::from_str(txid).context("Invalid board funding txid")?),
+            None => None,
+        };
+        let funding = match funding_txid {
+            Some(txid) => match ctx.wallet.chain.tx_status(txid).await {
+                Ok(TxStatus::Confirmed(_)) => Some(FundingTxStatus::Confirmed),
+                Ok(TxStatus::Mempool) => Some(FundingTxStatus::Unconfirmed),
+                Ok(TxStatus::NotFound) => Some(FundingTxStatus::NotFound),
+                Err(err) => {
+                    warn!(
+                        "Failed to look up funding tx {} of board intent {}: {:#}",
+                        txid, intent.id, err
+                    );
+                    report.pending.push(intent);
+                    continue;
+                }
+            },
+            None => None,
+        };
+
+        let abandon_reason = match board_resume_action(intent.state, funding, unconfirmed_spends) {
+            Some(BoardResumeAction::Register) => {
+                let txid = funding_txid.context("Confirmed board funding without a txid")?;
+                if still_pending.contains(&txid) {
+                    report.pending.push(intent);
+                    continue;
+                }
+                if board_registered(ctx, txid, &pending_before).await? {
+                    info!("Resumed board intent {}", intent.id);
+                    ctx.store.update_board_intent(
+                        intent.id,
+                        BoardIntentState::Registered,
+                        None,
+                        None,
+                    )?;
+                    intent.state = BoardIntentState::Registered;
+                    report.resumed.push(intent);
+                    continue;
+                }
+                "bark has no registered board for the confirmed funding transaction"
+            }
+            Some(BoardResumeAction::Wait) => {
+                report.pending.push(intent);
+                continue;
+            }
+            Some(BoardResumeAction::Abandon) => match funding {
+                None => "interrupted before funding, no unconfirmed spends are left",
+                Some(_) => "funding transaction not found onchain",
+            },
+            None => continue,
+        };
+
+        warn!("Abandoning board intent {}: {}", intent.id, abandon_reason);
+        ctx.store.update_board_intent(
+            intent.id,
+            BoardIntentState::Abandoned,
+            None,
+            Some(abandon_reason),
+        )?;
+        intent.state = BoardIntentState::Abandoned;
+        intent.error = Some(abandon_reason.to_string());
+        report.abandoned.push(intent);
+    }
+
+    Ok(report)
+}
+
+/// Whether bark registered the board funded by `txid`: its VTXO became
+/// spendable, either during the last sync of `pending` boards or earlier.
+async fn board_registered(
+    ctx: &WalletContext,
+    txid: Txid,
+    pending: &[PendingBoard],
+) -> anyhow::Result<bool> {
+    if let Some(board) = pending
+        .iter()
+        .find(|board| board.funding_tx.compute_txid() == txid)
+    {
+        return board_credited(ctx, board).await;
+    }
+    Ok(ctx
+        .wallet
+        .vtxos()
+        .await?
+        .iter()
+        .any(|vtxo| vtxo.vtxo.chain_anchor().txid == txid))
+}
+
+/// Has bark check every pending lightning send without a preimage. Sends
+/// whose HTLCs expired or failed get revoked, which unlocks their VTXOs.
+/// Returns the number of VTXOs unlocked.
+async fn revoke_expired_htlcs(ctx: &WalletContext) -> anyhow::Result<u32> {
+    let mut checked = Vec::new();
+    for send in ctx.db.get_all_pending_lightning_send().await? {
+        if send.preimage.is_some() {
+            continue;
+        }
+        let payment_hash = send.invoice.payment_hash();
+        let paid = match ctx
+            .wallet
+            .check_lightning_payment(payment_hash, false)
+            .await
+        {
+            Ok(preimage) => preimage.is_some(),
+            Err(err) => {
+                warn!(
+                    "Failed to check lightning payment {}: {:#}",
+                    payment_hash, err
+                );
+                continue;
+            }
+        };
+        checked.push(CheckedHtlcSend {
+            payment_hash: payment_hash.to_string(),
+            htlc_vtxo_count: send.htlc_vtxos.len(),
+            paid,
+        });
+    }
+
+    let still_pending = ctx
+        .db
+        .get_all_pending_lightning_send()
+        .await?
+        .iter()
+        .map(|send| send.invoice.payment_hash().to_string())
+        .collect();
+    let unlocked = count_unlocked_htlc_vtxos(&checked, &still_pending);
+    if unlocked > 0 {
+        info!("Unlocked {} vtxos from expired htlcs", unlocked);
+    }
+    Ok(unlocked)
+}
+
+/// Marks pending lightning receives whose invoice expired unpaid, emitting
+/// an event for each. Returns the payment hashes newly marked.
+async fn prune_expired_lightning_receives(ctx: &WalletContext) -> anyhow::Result<Vec<String>> {
+    let now = now_timestamp() as u64;
+    let mut expired = Vec::new();
+    for receive in ctx.wallet.pending_lightning_receives().await? {
+        let invoice = &receive.invoice;
+        if !lightning_receive_expired(
+            invoice,
+            receive.preimage_revealed_at.is_some(),
+            receive.finished_at.is_some(),
+            now,
+        ) {
+            continue;
+        }
+
+        let payment_hash = receive.payment_hash.to_string();
+        let expired_at = invoice
+            .expires_at()
+            .map_or(now as i64, |at| at.as_secs() as i64);
+        if ctx.store.mark_receive_expired(
+            &payment_hash,
+            invoice.amount_milli_satoshis(),
+            expired_at,
+        )? {
+            emit_event(
+                WalletEventKind::LightningReceiveExpired,
+                serde_json::json!({
+                    "payment_hash": payment_hash,
+                    "amount_msat": invoice.amount_milli_satoshis(),
+                    "expired_at": expired_at,
+                }),
+            );
+            expired.push(payment_hash);
+        }
+    }
+    if !expired.is_empty() {
+        info!("{} lightning receives expired unpaid", expired.len());
+    }
+    Ok(expired)
+}
+
+/// Pending lightning receives that can still be paid.
+async fn open_lightning_receives(ctx: &WalletContext) -> anyhow::Result<Vec<LightningReceive>> {
+    let expired = ctx.store.get_expired_receives()?;
+    let now = now_timestamp() as u64;
+    let receives = ctx
+        .wallet
+        .pending_lightning_receives()
+        .await
+        .context("Failed to get pending lightning receives")?;
+    Ok(receives
+        .into_iter()
+        .filter(|r| {
+            !expired.contains(&r.payment_hash.to_string())
+                && !lightning_receive_expired(
+                    &r.invoice,
+                    r.preimage_revealed_at.is_some(),
+                    r.finished_at.is_some(),
+                    now,
+                )
+        })
+        .collect())
+}
+
+/// Marks pending lightning receives whose invoice expired unpaid, so they
+/// are no longer listed or claimed. Returns the payment hashes newly marked.
+pub async fn prune_expired_receives() -> anyhow::Result<Vec<String>> {
+    timed("prune_expired_receives", async move {
+        let manager = lock_manager("prune_expired_receives").await;
+        manager
+            .with_context_ref_async(|ctx| async { prune_expired_lightning_receives(ctx).await })
+            .await
+    })
+    .await
+}
+
+/// Issues a fresh invoice for the amount of the unpaid receive
+/// `payment_hash`, typically after it expired.
+pub async fn recreate_invoice(payment_hash: PaymentHash) -> anyhow::Result<Bolt11Invoice> {
+    timed("recreate_invoice", async move {
+        let receive = get_lightning_receive(payment_hash).await?;
+        if receive.preimage_revealed_at.is_some() {
+            bail!("Lightning receive {} was already paid", payment_hash);
+        }
+        let amount_msat = receive
+            .invoice
+            .amount_milli_satoshis()
+            .context("Can't recreate an invoice without an amount")?;
+        bolt11_invoice(units::msat_to_sat_ceil(amount_msat)).await
+    })
+    .await
+}
+
+/// Cancels lightning sends whose HTLCs expired, making their VTXOs
+/// spendable again. Returns the number of VTXOs unlocked.
+pub async fn cancel_expired_htlcs() -> anyhow::Result<u32> {
+    timed("cancel_expired_htlcs", async move {
+        let mut manager = lock_manager("cancel_expired_htlcs").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async("cancel_expired_htlcs", String::new(), async |ctx| {
+                revoke_expired_htlcs(ctx).await
+            })
+            .await
+    })
+    .await
+}
+
+/// Refreshes expiring VTXOs when the auto refresh policy allows it right now.
+///
+/// Returns whether a refresh was triggered.
+async fn auto_refresh(ctx: &WalletContext) -> anyhow::Result<bool> {
+    let policy = ctx.store.get_auto_refresh_policy()?;
+    if !policy.enabled {
+        return Ok(false);
+    }
+
+    let now = now_timestamp();
+    let tip = ctx.wallet.chain.tip().await?;
+    let next_refresh = ctx
+        .wallet
+        .get_next_required_refresh_blockheight()
+        .await
+        .context("Failed to get next required refresh blockheight")?;
+
+    if !policy.should_refresh(now, tip, next_refresh) {
+        return Ok(false);
+    }
+
+    info!(
+        "Auto refreshing vtxos, next required refresh at {:?} with tip {}",
+        next_refresh, tip
+    );
+    ctx.wallet
+        .maintenance_refresh()
+        .await
+        .context("Failed to perform vtxo maintenance refresh")?;
+    emit_event(
+        WalletEventKind::RoundParticipated,
+        serde_json::json!({
+            "trigger": "auto_refresh",
+            "tip_height": tip,
+            "next_refresh_height": next_refresh,
+        }),
+    );
+
+    Ok(true)
+}
+
+pub async fn get_auto_refresh_policy() -> anyhow::Result<AutoRefreshPolicy> {
+    timed("get_auto_refresh_policy", async move {
+        let manager = lock_manager("get_auto_refresh_policy").await;
+        manager.with_context_ref(|ctx| ctx.store.get_auto_refresh_policy())
+    })
+    .await
+}
+
+pub async fn set_auto_refresh_policy(policy: AutoRefreshPolicy) -> anyhow::Result<()> {
+    timed("set_auto_refresh_policy", async move {
+        let manager = lock_manager("set_auto_refresh_policy").await;
+        manager.with_context_ref(|ctx| ctx.store.set_auto_refresh_policy(&policy))
+    })
+    .await
+}
+
+pub async fn get_lightning_fee_cap() -> anyhow::Result<LightningFeeCap> {
+    timed("get_lightning_fee_cap", async move {
+        let manager = lock_manager("get_lightning_fee_cap").await;
+        manager.with_context_ref(|ctx| ctx.store.get_lightning_fee_cap())
+    })
+    .await
+}
+
+pub async fn set_lightning_fee_cap(cap: LightningFeeCap) -> anyhow::Result<()> {
+    timed("set_lightning_fee_cap", async move {
+        let manager = lock_manager("set_lightning_fee_cap").await;
+        manager.with_context_ref(|ctx| ctx.store.set_lightning_fee_cap(&cap))
+    })
+    .await
+}
+
+/// Re-drives boards that were interrupted between funding and registration
+pub async fn resume_pending_boards() -> anyhow::Result<BoardResumeReport> {
+    timed("resume_pending_boards", async move {
+        let mut manager = lock_manager("resume_pending_boards").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async("resume_pending_boards", String::new(), async |ctx| {
+                resume_board_intents(ctx).await
+            })
+            .await
+    })
+    .await
+}
+
+/// Quarantines the spendable VTXOs missing from the server's listing of the
+/// VTXOs it knows about, and returns the ones that were newly quarantined.
+///
+/// Quarantined VTXOs are kept in the bark database so they can still be
+/// exited, but no longer count towards the spendable balance and are locked
+/// out of every payment and offboard.
+///
+/// Not implemented as part of [`sync`] or [`maintenance`]: bark beta.8 has
+/// no call that lists the VTXOs the server knows about, so the wallet can't
+/// fetch the listing itself and nothing runs this automatically. It only
+/// quarantines VTXOs when a caller passes a listing it obtained elsewhere.
+pub async fn reconcile_vtxos_with_server(
+    server_vtxo_ids: Vec<VtxoId>,
+) -> anyhow::Result<Vec<QuarantinedVtxo>> {
+    timed("reconcile_vtxos_with_server", async move {
+        let mut manager = lock_manager("reconcile_vtxos_with_server").await;
+        let params = format!("{:?}", server_vtxo_ids);
+        manager
+            .with_context_journaled_async("reconcile_vtxos_with_server", params, async |ctx| {
+                let server_vtxo_ids = server_vtxo_ids.iter().map(|id| id.to_string()).collect();
+                let spendable = spendable_vtxo_ids(&ctx.wallet.vtxos().await?);
+
+                let mut quarantined = Vec::new();
+                for (vtxo_id, amount_sat) in find_unknown_vtxos(&spendable, &server_vtxo_ids) {
+                    if !ctx
+                        .store
+                        .quarantine_vtxo(&vtxo_id, amount_sat, "unknown to server")?
+                    {
+                        continue;
+                    }
+
+                    warn!("Quarantined vtxo {} unknown to the server", vtxo_id);
+                    emit_event(
+                        WalletEventKind::VtxoQuarantined,
+                        serde_json::json!({
+                            "vtxo_id": vtxo_id,
+                            "amount_sat": amount_sat,
+                            "recommended_action": QUARANTINE_RECOMMENDED_ACTION,
+                        }),
+                    );
+                    quarantined.push(vtxo_id);
+                }
+
+                Ok(ctx
+                    .store
+                    .get_quarantined_vtxos()?
+                    .into_iter()
+                    .filter(|q| quarantined.contains(&q.vtxo_id))
+                    .collect())
+            })
+            .await
+    })
+    .await
+}
+
+pub async fn get_quarantined_vtxos() -> anyhow::Result<Vec<QuarantinedVtxo>> {
+    timed("get_quarantined_vtxos", async move {
+        let manager = lock_manager("get_quarantined_vtxos").await;
+        manager.with_context_ref(|ctx| ctx.store.get_quarantined_vtxos())
+    })
+    .await
+}
+
+/// Lifts the quarantine, e.g. once the server knows about the VTXO again
+pub async fn release_quarantined_vtxo(vtxo_id: VtxoId) -> anyhow::Result<bool> {
+    timed("release_quarantined_vtxo", async move {
+        let manager = lock_manager("release_quarantined_vtxo").await;
+        manager.with_context_ref(|ctx| ctx.store.release_quarantined_vtxo(&vtxo_id.to_string()))
+    })
+    .await
+}
+
+/// Estimates the onchain fees of unilaterally exiting the given VTXOs, or
+/// all spendable VTXOs when `vtxo_ids` is empty.
+pub async fn estimate_exit_cost(vtxo_ids: Vec<VtxoId>) -> anyhow::Result<ExitCostEstimate> {
+    timed("estimate_exit_cost", async move {
+        let manager = lock_manager("estimate_exit_cost").await;
+        manager
+            .with_context_ref_async(|ctx| async {
+                let vtxos = ctx.wallet.vtxos().await?;
+                let packages = if vtxo_ids.is_empty() {
+                    vtxos
+                        .iter()
+                        .filter(|v| matches!(v.state, VtxoState::Spendable))
+                        .map(|v| ExitPackage::from_vtxo(&v.vtxo))
+                        .collect::<Vec<_>>()
+                } else {
+                    vtxo_ids
+                        .iter()
+                        .map(|id| {
+                            vtxos
+                                .iter()
+                                .find(|v| v.vtxo.id() == *id)
+                                .map(|v| ExitPackage::from_vtxo(&v.vtxo))
+                                .with_context(|| format!("Unknown vtxo {}", id))
+                        })
+                        .collect::<anyhow::Result<Vec<_>>>()?
+                };
+
+                let fee_rate = ctx.wallet.chain.fee_rates().await.regular;
+                Ok(estimate_exit_fees(&packages, fee_rate))
+            })
+            .await
+    })
+    .await
+}
+
+pub async fn validate_arkoor_address(address: bark::ark::Address) -> anyhow::Result<()> {
+    timed("validate_arkoor_address", async move {
+        let manager = lock_manager("validate_arkoor_address").await;
+        manager
+            .with_context_ref_async(|ctx| async {
+                ctx.wallet
+                    .validate_arkoor_address(&address)
+                    .await
+                    .context("Failed to validate address")
+            })
+            .await
+    })
+    .await
+}
+
+/// The outputs of an arkoor payment.
+#[derive(Debug, Clone)]
+pub struct ArkoorOutputs {
+    /// Owned by the recipient
+    pub sent: Vec<Vtxo>,
+    /// Owned by this wallet
+    pub change: Vec<Vtxo>,
+}
+
+impl ArkoorOutputs {
+    pub fn change_amount(&self) -> Amount {
+        self.change.iter().map(|vtxo| vtxo.amount()).sum()
+    }
+}
+
+/// Splits `outputs` by whether their user key was derived by this wallet.
+async fn classify_arkoor_outputs(
+    ctx: &WalletContext,
+    outputs: Vec<Vtxo>,
+) -> anyhow::Result<ArkoorOutputs> {
+    let mut ours = std::collections::HashSet::new();
+    for vtxo in &outputs {
+        let pubkey = vtxo.user_pubkey();
+        if ctx.db.get_public_key_idx(&pubkey).await?.is_some() {
+            ours.insert(pubkey);
+        }
+    }
+    let (sent, change) = split_arkoor_outputs(outputs, |vtxo| ours.contains(&vtxo.user_pubkey()));
+    Ok(ArkoorOutputs { sent, change })
+}
+
+pub async fn send_arkoor_payment(
+    destination: bark::ark::Address,
+    amount_sat: Amount,
+) -> anyhow::Result<ArkoorOutputs> {
+    timed("send_arkoor_payment", async move {
+        let mut manager = lock_manager("send_arkoor_payment").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async(
+                "send_arkoor_payment",
+                format!("{} {}", destination, amount_sat),
+                async |ctx| {
+                    info!(
+                        "Attempting to send OOR payment of {} to pubkey {:?}",
+                        amount_sat, destination
+                    );
+                    let result = match exclude_untrusted_vtxos(ctx, Some(amount_sat)).await {
+                        Ok(()) => {
+                            ctx.wallet
+                                .send_arkoor_payment(&destination, amount_sat)
+                                .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    let oor_result = unlock_after_payment(ctx, result).await?;
+                    classify_arkoor_outputs(ctx, oor_result).await
+                },
+            )
+            .await
+    })
+    .await
+}
+
+/// An arkoor payment funded from chosen VTXOs only.
+#[derive(Debug, Clone)]
+pub struct ArkoorFromResult {
+    pub outputs: ArkoorOutputs,
+    /// Selected VTXOs the payment spent
+    pub consumed_vtxo_ids: Vec<String>,
+    pub change_vtxo_id: Option<String>,
+}
+
+/// Sends an arkoor payment funded only by `vtxo_ids`.
+///
+/// bark picks the inputs of an arkoor payment itself, so all other
+/// spendable VTXOs are locked for the duration of the payment and unlocked
+/// right after, or on the next load if the app dies in between.
+pub async fn send_arkoor_payment_from(
+    destination: bark::ark::Address,
+    amount: Amount,
+    vtxo_ids: Vec<VtxoId>,
+) -> anyhow::Result<ArkoorFromResult> {
+    timed("send_arkoor_payment_from", async move {
+        let mut manager = lock_manager("send_arkoor_payment_from").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async(
+                "send_arkoor_payment_from",
+                format!("{} {} {:?}", destination, amount, vtxo_ids),
+                async move |ctx| {
+                    let selected = vtxo_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+                    let vtxos = ctx.wallet.vtxos().await?;
+                    let known = vtxos
+                        .iter()
+                        .map(|v| v.vtxo.id().to_string())
+                        .collect::<Vec<_>>();
+                    let quarantined = ctx
+                        .store
+                        .get_quarantined_vtxos()?
+                        .into_iter()
+                        .map(|q| q.vtxo_id)
+                        .collect::<std::collections::HashSet<_>>();
+                    let spendable_before = spendable_vtxo_ids(&vtxos)
+                        .into_iter()
+                        .filter(|(id, _)| !quarantined.contains(id))
+                        .collect::<Vec<_>>();
+                    let to_lock =
+                        check_coin_control(&known, &spendable_before, &selected, amount.to_sat())?;
+
+                    ctx.store.record_coin_control_locks(&to_lock)?;
+                    let locked = match exclude_quarantined_vtxos(ctx).await {
+                        Ok(()) => lock_vtxos(ctx, &to_lock).await,
+                        Err(e) => Err(e),
+                    };
+                    let result = match locked {
+                        Ok(()) => ctx.wallet.send_arkoor_payment(&destination, amount).await,
+                        Err(e) => Err(e),
+                    };
+                    let restored = restore_coin_control_locks(ctx).await;
+                    let sent = result?;
+                    restored?;
+
+                    let spendable_after = spendable_vtxo_ids(&ctx.wallet.vtxos().await?)
+                        .into_iter()
+                        .filter(|(id, _)| !quarantined.contains(id))
+                        .collect::<Vec<_>>();
+                    let is_spendable_after =
+                        |id: &String| spendable_after.iter().any(|(s, _)| s == id);
+                    Ok(ArkoorFromResult {
+                        outputs: classify_arkoor_outputs(ctx, sent).await?,
+                        consumed_vtxo_ids: selected
+                            .iter()
+                            .filter(|id| !is_spendable_after(id))
+                            .cloned()
+                            .collect(),
+                        change_vtxo_id: spendable_after
+                            .iter()
+                            .map(|(id, _)| id)
+                            .find(|id| !spendable_before.iter().any(|(s, _)| s == *id))
+                            .cloned(),
+                    })
+                },
+            )
+            .await
+    })
+    .await
+}
+
+async fn lock_vtxos(ctx: &WalletContext, vtxo_ids: &[String]) -> anyhow::Result<()> {
+    for vtxo_id in vtxo_ids {
+        transition_vtxo_state(
+            &ctx.db,
+            VtxoId::from_str(vtxo_id)?,
+            VtxoState::Locked { movement_id: None },
+        )
+        .await
+        .with_context(|| format!("Failed to lock vtxo {}", vtxo_id))?;
+    }
+    Ok(())
+}
+
+/// Unlocks the VTXOs locked for a coin-controlled payment. VTXOs that are
+/// no longer locked, e.g. because a later payment spent them, are skipped.
+async fn restore_coin_control_locks(ctx: &WalletContext) -> anyhow::Result<()> {
+    for vtxo_id in ctx.store.get_coin_control_locks()? {
+        let restored =
+            transition_vtxo_state(&ctx.db, VtxoId::from_str(&vtxo_id)?, VtxoState::Spendable).await;
+        if let Err(e) = restored {
+            warn!("Not unlocking vtxo {}: {:#}", vtxo_id, e);
+        }
+        ctx.store.clear_coin_control_lock(&vtxo_id)?;
+    }
+    Ok(())
+}
+
+pub async fn check_lightning_payment(
+    payment_hash: PaymentHash,
+    wait: bool,
+) -> anyhow::Result<Option<Preimage>> {
+    timed("check_lightning_payment", async move {
+        let mut manager = lock_manager("check_lightning_payment").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_async(|ctx| async {
+                let preimage = ctx
+                    .wallet
+                    .check_lightning_payment(payment_hash, wait)
+                    .await?;
+                if preimage.is_some() {
+                    let removed = ctx.store.remove_pending_payment(&payment_hash.to_string());
+                    if let Err(err) = removed {
+                        warn!(
+                            "Failed to remove settled payment {}: {:#}",
+                            payment_hash, err
+                        );
+                    }
+                }
+                Ok(preimage)
+            })
+            .await
+    })
+    .await
+}
+
+/// Refuses a lightning payment of `amount` whose expected fee is above its
+/// budget, before anything is paid.
+///
+/// bark doesn't quote lightning fees, so the fee is expected at the rate the
+/// wallet's last lightning send paid, see [`record_lightning_fee`]. Without
+/// an earlier send nothing is refused.
+fn check_lightning_fee_budget(
+    store: &Store,
+    amount: Amount,
+    max_fee: Option<Amount>,
+) -> anyhow::Result<()> {
+    let budget = match max_fee {
+        Some(max_fee) => max_fee,
+        None => Amount::from_sat(store.get_lightning_fee_cap()?.budget_sat(amount.to_sat())),
+    };
+    let fee_ppm = match store.get_observed_lightning_fee_ppm()? {
+        Some(fee_ppm) => fee_ppm,
+        None => return Ok(()),
+    };
+    let expected_fee = expected_lightning_fee(amount, fee_ppm);
+    if expected_fee > budget {
+        return Err(FeeBudgetExceeded {
+            expected_fee_sat: expected_fee.to_sat(),
+            budget_sat: budget.to_sat(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Remembers the fee rate a lightning send paid, what was locked in its
+/// HTLCs beyond the amount, for [`check_lightning_fee_budget`].
+fn record_lightning_fee(store: &Store, send: &LightningSend) -> anyhow::Result<()> {
+    let htlc_amount = send.htlc_vtxos.iter().map(|v| v.vtxo.amount()).sum();
+    let fee = lightning_send_fee(htlc_amount, send.amount);
+    match lightning_fee_ppm(fee, send.amount) {
+        Some(fee_ppm) => store.set_observed_lightning_fee_ppm(fee_ppm),
+        None => Ok(()),
+    }
+}
+
+/// Outgoing lightning payments that have not settled yet, oldest first.
+/// Failed payments are left out, see [`retry_failed_payment`].
+///
+/// Payments bark doesn't track as pending settled or failed without us
+/// hearing about it, or were interrupted before sending any HTLC, and are
+/// dropped.
+pub async fn get_pending_payments() -> anyhow::Result<Vec<PendingPayment>> {
+    timed("get_pending_payments", async move {
+        let manager = lock_manager("get_pending_payments").await;
+        manager
+            .with_context_ref_async(|ctx| async {
+                let in_bark = ctx
+                    .db
+                    .get_all_pending_lightning_send()
+                    .await?
+                    .into_iter()
+                    .filter(|send| send.preimage.is_none())
+                    .map(|send| send.invoice.payment_hash().to_string())
+                    .collect::<std::collections::HashSet<_>>();
+
+                let mut pending = Vec::new();
+                for payment in ctx.store.get_pending_payments()? {
+                    if payment.status == PendingPaymentStatus::Failed {
+                        continue;
+                    }
+                    if in_bark.contains(&payment.payment_hash) {
+                        pending.push(payment);
+                    } else if let Err(err) = ctx.store.remove_pending_payment(&payment.payment_hash)
+                    {
+                        warn!(
+                            "Failed to remove payment {} bark no longer tracks: {:#}",
+                            payment.payment_hash, err
+                        );
+                    }
+                }
+                Ok(pending)
+            })
+            .await
+    })
+    .await
+}
+
+/// How a failed payment is retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPaymentOpts {
+    /// How much more fee than the default budget the retry may pay
+    pub max_fee_increase_pct: u32,
+    /// How long to wait for the preimage once the HTLCs are sent
+    pub timeout_secs: u32,
+}
+
+impl Default for RetryPaymentOpts {
+    fn default() -> Self {
+        RetryPaymentOpts {
+            max_fee_increase_pct: 50,
+            timeout_secs: 60,
+        }
+    }
+}
+
+/// Pays a lightning invoice again whose earlier attempt failed, with a fee
+/// budget raised by `opts.max_fee_increase_pct`.
+pub async fn retry_failed_payment(
+    payment_hash: &str,
+    opts: RetryPaymentOpts,
+) -> anyhow::Result<Preimage> {
+    timed("retry_failed_payment", async move {
+        let (payment, fee_cap) = {
+            let manager = lock_manager("retry_failed_payment").await;
+            manager.with_context_ref(|ctx| {
+                let payment = ctx.store.get_pending_payment(payment_hash)?;
+                Ok((payment, ctx.store.get_lightning_fee_cap()?))
+            })?
+        };
+        let payment = match payment {
+            Some(payment) if payment.status == PendingPaymentStatus::Failed => payment,
+            Some(_) => bail!("Payment {} has not failed", payment_hash),
+            None => bail!("No failed payment with hash {}", payment_hash),
+        };
+
+        let invoice = lightning::Invoice::from_str(&payment.bolt11)?;
+        let user_amount = payment
+            .amount_msat
+            .map(|msat| units::msat_to_sat_floor(msat).0);
+        let amount_sat = match user_amount {
+            Some(amount) => amount.to_sat(),
+            None => Bolt11Invoice::from_str(&payment.bolt11)
+                .ok()
+                .and_then(|invoice| invoice.amount_milli_satoshis())
+                .map(|msat| units::msat_to_sat_floor(msat).0.to_sat())
+                .unwrap_or(0),
+        };
+        let max_fee = Amount::from_sat(retry_fee_budget(
+            fee_cap.budget_sat(amount_sat),
+            opts.max_fee_increase_pct,
+        ));
+
+        info!(
+            "Retrying payment {} with a fee budget of {}",
+            payment_hash, max_fee
+        );
+        let send = pay_lightning_invoice(invoice, user_amount, Some(max_fee)).await?;
+        if let Some(preimage) = send.preimage {
+            return Ok(preimage);
+        }
+
+        let hash = send.invoice.payment_hash();
+        let timeout = std::time::Duration::from_secs(opts.timeout_secs as u64);
+        match tokio::time::timeout(timeout, check_lightning_payment(hash, true)).await {
+            Ok(Ok(Some(preimage))) => Ok(preimage),
+            Ok(Ok(None)) => bail!("Payment {} is still pending", payment_hash),
+            Ok(Err(err)) => Err(err),
+            Err(_) => bail!(
+                "Payment {} is still pending after {}s",
+                payment_hash,
+                opts.timeout_secs
+            ),
+        }
+    })
+    .await
 }
 
 pub async fn pay_lightning_invoice(
     destination: lightning::Invoice,
     amount_sat: Option<Amount>,
+    max_fee: Option<Amount>,
 ) -> anyhow::Result<LightningSend> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .pay_lightning_invoice(destination, amount_sat)
-                .await
-        })
-        .await
+    timed("pay_lightning_invoice", async move {
+        let mut manager = lock_manager("pay_lightning_invoice").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async(
+                "pay_lightning_invoice",
+                format!("{} {:?} {:?}", destination, amount_sat, max_fee),
+                async |ctx| {
+                    let amount = amount_sat.or_else(|| match &destination {
+                        lightning::Invoice::Bolt11(invoice) => {
+                            invoice.amount_milli_satoshis().map(units::msat_to_sat_ceil)
+                        }
+                        _ => None,
+                    });
+                    if let Some(amount) = amount {
+                        check_lightning_fee_budget(&ctx.store, amount, max_fee)?;
+                    }
+
+                    let payment_hash = destination.payment_hash().to_string();
+                    ctx.store.store_pending_payment(&PendingPayment {
+                        payment_hash: payment_hash.clone(),
+                        bolt11: destination.to_string(),
+                        amount_msat: amount_sat.map(units::amount_to_msat),
+                        destination: match &destination {
+                            lightning::Invoice::Bolt11(invoice) => {
+                                Some(invoice.get_payee_pub_key().to_string())
+                            }
+                            _ => None,
+                        },
+                        started_at: now_timestamp(),
+                        status: PendingPaymentStatus::Sending,
+                    })?;
+
+                    let result = match exclude_untrusted_vtxos(ctx, amount).await {
+                        Ok(()) => {
+                            ctx.wallet
+                                .pay_lightning_invoice(destination, amount_sat)
+                                .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    // Bookkeeping errors are only logged, they must never
+                    // change the outcome of the payment
+                    let send = match unlock_after_payment(ctx, result).await {
+                        Ok(send) => send,
+                        Err(err) => {
+                            if let Err(store_err) = ctx.store.update_pending_payment_status(
+                                &payment_hash,
+                                PendingPaymentStatus::Failed,
+                                None,
+                            ) {
+                                warn!(
+                                    "Failed to mark payment {} as failed: {:#}",
+                                    payment_hash, store_err
+                                );
+                            }
+                            return Err(err);
+                        }
+                    };
+                    let recorded = match send.preimage {
+                        Some(_) => ctx.store.remove_pending_payment(&payment_hash).map(|_| ()),
+                        None => ctx.store.update_pending_payment_status(
+                            &payment_hash,
+                            PendingPaymentStatus::InFlight,
+                            Some(units::amount_to_msat(send.amount)),
+                        ),
+                    };
+                    if let Err(err) = recorded {
+                        warn!(
+                            "Failed to update pending payment {}: {:#}",
+                            payment_hash, err
+                        );
+                    }
+                    // The payment went out, so failing to remember its fee
+                    // must not report it as failed
+                    if let Err(err) = record_lightning_fee(&ctx.store, &send) {
+                        warn!("Failed to record the lightning fee paid: {:#}", err);
+                    }
+                    Ok(send)
+                },
+            )
+            .await
+    })
+    .await
 }
 
 pub async fn pay_lightning_offer(
     offer: Offer,
     amount: Option<Amount>,
 ) -> anyhow::Result<LightningSend> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async { ctx.wallet.pay_lightning_offer(offer, amount).await })
-        .await
+    timed("pay_lightning_offer", async move {
+        let mut manager = lock_manager("pay_lightning_offer").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async(
+                "pay_lightning_offer",
+                format!("{} {:?}", offer, amount),
+                async |ctx| {
+                    let result = match exclude_untrusted_vtxos(ctx, amount).await {
+                        Ok(()) => ctx.wallet.pay_lightning_offer(offer, amount).await,
+                        Err(e) => Err(e),
+                    };
+                    unlock_after_payment(ctx, result).await
+                },
+            )
+            .await
+    })
+    .await
+}
+
+/// The network of the loaded wallet, read from its properties.
+pub async fn wallet_network() -> anyhow::Result<Network> {
+    timed("wallet_network", async move {
+        let manager = lock_manager("wallet_network").await;
+        manager
+            .with_context_ref_async(|ctx| async { Ok(ctx.wallet.properties().await?.network) })
+            .await
+    })
+    .await
+}
+
+/// A new receive address of the onchain wallet.
+pub async fn get_onchain_address() -> anyhow::Result<Address> {
+    timed(
+        "get_onchain_address",
+        async move { onchain::address().await },
+    )
+    .await
+}
+
+/// The onchain wallet's UTXOs, including those of unilateral exits.
+pub async fn get_onchain_utxos() -> anyhow::Result<Vec<Utxo>> {
+    timed("get_onchain_utxos", async move { onchain::utxos().await }).await
+}
+
+/// Parses an onchain address, which must be for the loaded wallet's network.
+pub async fn parse_wallet_address(destination: &str) -> anyhow::Result<Address> {
+    timed("parse_wallet_address", async move {
+        parse_onchain_address(destination, wallet_network().await?)
+    })
+    .await
+}
+
+/// Sends `amount` from the onchain wallet to `dest_str`, syncing the
+/// onchain wallet first unless `no_sync` is set. Without a `fee_rate` the
+/// configured fallback fee rate is used.
+pub async fn send_onchain_to(
+    dest_str: &str,
+    amount: Amount,
+    fee_rate: Option<FeeRate>,
+    no_sync: bool,
+) -> anyhow::Result<(Address, Txid)> {
+    timed("send_onchain_to", async move {
+        let destination = parse_wallet_address(dest_str).await?;
+        if !no_sync {
+            onchain::sync().await?;
+        }
+        let txid = match fee_rate {
+            Some(fee_rate) => onchain::send(destination.clone(), amount, fee_rate).await?,
+            None => onchain::send_with_config_fee(destination.clone(), amount).await?,
+        };
+        Ok((destination, txid))
+    })
+    .await
+}
+
+/// Sends all onchain funds to `dest_str`, syncing the onchain wallet first
+/// unless `no_sync` is set. Without a `fee_rate` the configured fallback fee
+/// rate is used.
+pub async fn drain_onchain_to(
+    dest_str: &str,
+    fee_rate: Option<FeeRate>,
+    no_sync: bool,
+) -> anyhow::Result<Txid> {
+    timed("drain_onchain_to", async move {
+        let destination = parse_wallet_address(dest_str).await?;
+        if !no_sync {
+            onchain::sync().await?;
+        }
+        match fee_rate {
+            Some(fee_rate) => onchain::drain(destination, fee_rate).await,
+            None => onchain::drain_with_config_fee(destination).await,
+        }
+    })
+    .await
+}
+
+/// Sends all onchain funds to `dest_str` at the configured fee rate, see
+/// [`drain_onchain_to`].
+pub async fn drain_onchain(dest_str: &str, no_sync: bool) -> anyhow::Result<Txid> {
+    timed("drain_onchain", async move {
+        drain_onchain_to(dest_str, None, no_sync).await
+    })
+    .await
 }
 
 pub async fn send_onchain(addr: Address, amount: Amount) -> anyhow::Result<Txid> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async { ctx.wallet.send_onchain(addr, amount).await })
+    timed("send_onchain", async move {
+        let mut manager = lock_manager("send_onchain").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async(
+                "send_onchain",
+                format!("{} {}", addr, amount),
+                async |ctx| {
+                    let result = match exclude_untrusted_vtxos(ctx, Some(amount)).await {
+                        Ok(()) => ctx.wallet.send_onchain(addr, amount).await,
+                        Err(e) => Err(e),
+                    };
+                    unlock_after_payment(ctx, result).await
+                },
+            )
+            .await
+    })
+    .await
+}
+
+/// Sends several onchain outputs through rounds under a single lock.
+///
+/// bark joins one round per onchain send, so the outputs are sent one after
+/// the other rather than in a single round. All outputs are validated
+/// up front; if a send fails, the remaining outputs are not attempted.
+pub async fn send_onchain_many(
+    outputs: Vec<(Address, Amount)>,
+) -> anyhow::Result<Vec<OnchainOutputResult>> {
+    timed("send_onchain_many", async move {
+        let mut manager = lock_manager("send_onchain_many").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async(
+                "send_onchain_many",
+                format!("{:?}", outputs),
+                async |ctx| {
+                    let total = outputs.iter().try_fold(Amount::ZERO, |total, (_, amount)| {
+                        total.checked_add(*amount)
+                    });
+                    let result = match exclude_untrusted_vtxos(ctx, total).await {
+                        Ok(()) => send_onchain_outputs(ctx, outputs).await,
+                        Err(e) => Err(e),
+                    };
+                    unlock_after_payment(ctx, result).await
+                },
+            )
+            .await
+    })
+    .await
+}
+
+async fn send_onchain_outputs(
+    ctx: &WalletContext,
+    outputs: Vec<(Address, Amount)>,
+) -> anyhow::Result<Vec<OnchainOutputResult>> {
+    let ark_info = ctx
+        .wallet
+        .ark_info()
+        .await?
+        .context("Failed to get ark info, returned as null")?;
+    let spendable = payable_amount(ctx).await?;
+    validate_onchain_outputs(&outputs, ark_info.max_vtxo_amount, spendable)?;
+
+    let mut results = Vec::with_capacity(outputs.len());
+    let mut failed = false;
+    for (addr, amount) in outputs {
+        let mut result = OnchainOutputResult {
+            destination: addr.to_string(),
+            amount_sat: amount.to_sat(),
+            txid: None,
+            error: None,
+        };
+        if failed {
+            result.error = Some("not attempted after an earlier failure".to_string());
+        } else {
+            match ctx.wallet.send_onchain(addr, amount).await {
+                Ok(txid) => result.txid = Some(txid.to_string()),
+                Err(e) => {
+                    warn!(
+                        "Failed to send {} onchain to {}: {:#}",
+                        amount, result.destination, e
+                    );
+                    result.error = Some(format!("{:#}", e));
+                    failed = true;
+                }
+            }
+        }
+        results.push(result);
+    }
+    Ok(results)
+}
+
+/// Fetches an invoice for `amount` from a lightning address without paying
+/// it, so it can be shown to the user before calling
+/// [`pay_lightning_invoice`].
+pub async fn resolve_lightning_address(
+    addr: &str,
+    amount: Amount,
+    comment: Option<&str>,
+) -> anyhow::Result<ResolvedInvoice> {
+    lock_manager("resolve_lightning_address")
         .await
+        .ensure_online()?;
+
+    timed("resolve_lightning_address", async move {
+        let lightning_address = LightningAddress::from_str(addr)
+            .with_context(|| format!("Invalid Lightning Address format: '{}'", addr))?;
+        let client = bark::lnurllib::Builder::default()
+            .build_async()
+            .map_err(|err| anyhow::anyhow!("Failed to build lnurl client: {:?}", err))?;
+
+        let response = client
+            .make_request(&lightning_address.lnurlp_url())
+            .await
+            .map_err(|err| anyhow::anyhow!("Failed to fetch lnurl pay request: {:?}", err))?;
+        let pay = match response {
+            bark::lnurllib::LnUrlResponse::LnUrlPayResponse(pay) => pay,
+            _ => bail!("Lightning address '{}' did not return a pay request", addr),
+        };
+
+        let amount_msat = units::amount_to_msat(amount);
+        check_lnurl_pay_request(&pay, amount_msat, comment)?;
+
+        let response = client
+            .get_invoice(&pay, amount_msat, None, comment)
+            .await
+            .map_err(|err| anyhow::anyhow!("Failed to fetch invoice: {:?}", err))?;
+        let invoice = Bolt11Invoice::from_str(&response.pr)
+            .with_context(|| format!("Lightning address '{}' returned an invalid invoice", addr))?;
+        validate_lnurl_pay_invoice(&invoice, amount_msat, &pay.metadata)?;
+
+        Ok(ResolvedInvoice {
+            description: lnurl_metadata_description(&pay.metadata),
+            amount_sat: amount.to_sat(),
+            expires_at: invoice
+                .expires_at()
+                .map(|at| at.as_secs())
+                .unwrap_or(u64::MAX),
+            invoice,
+        })
+    })
+    .await
 }
 
 pub async fn pay_lightning_address(
     addr: &str,
     amount: Amount,
     comment: Option<&str>,
+    max_fee: Option<Amount>,
 ) -> anyhow::Result<LightningSend> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            let lightning_address = LightningAddress::from_str(addr)
-                .with_context(|| format!("Invalid Lightning Address format: '{}'", addr))?;
-
-            ctx.wallet
-                .pay_lightning_address(&lightning_address, amount, comment)
-                .await
+    timed("pay_lightning_address", async move {
+        let mut manager = lock_manager("pay_lightning_address").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async(
+                "pay_lightning_address",
+                format!("{} {} {:?}", addr, amount, max_fee),
+                async |ctx| {
+                    let lightning_address = LightningAddress::from_str(addr)
+                        .with_context(|| format!("Invalid Lightning Address format: '{}'", addr))?;
+                    check_lightning_fee_budget(&ctx.store, amount, max_fee)?;
+
+                    let result = match exclude_untrusted_vtxos(ctx, Some(amount)).await {
+                        Ok(()) => {
+                            ctx.wallet
+                                .pay_lightning_address(&lightning_address, amount, comment)
+                                .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    let send = unlock_after_payment(ctx, result).await?;
+                    // The payment went out, so failing to remember its fee
+                    // must not report it as failed
+                    if let Err(err) = record_lightning_fee(&ctx.store, &send) {
+                        warn!("Failed to record the lightning fee paid: {:#}", err);
+                    }
+                    Ok(send)
+                },
+            )
+            .await
+    })
+    .await
+}
+
+/// What [`send_payment`] did, serialized with a `payment_type` tag.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "payment_type", rename_all = "snake_case")]
+pub enum PaymentResult {
+    Arkoor {
+        destination: String,
+        amount_sat: u64,
+        sent_vtxo_ids: Vec<String>,
+        change_amount_sat: u64,
+    },
+    Bolt11(LightningPaymentResult),
+    Bolt12(LightningPaymentResult),
+    LightningAddress(LightningPaymentResult),
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LightningPaymentResult {
+    pub destination: String,
+    pub amount_sat: u64,
+    pub invoice: String,
+    pub payment_hash: String,
+    /// Set once the payment succeeded, absent while it is in flight
+    pub preimage: Option<String>,
+}
+
+impl LightningPaymentResult {
+    fn new(destination: &str, send: LightningSend) -> Self {
+        use bark::ark::bitcoin::hex::DisplayHex;
+
+        LightningPaymentResult {
+            destination: destination.to_string(),
+            amount_sat: send.amount.to_sat(),
+            invoice: send.invoice.to_string(),
+            payment_hash: send.invoice.payment_hash().to_string(),
+            preimage: send.preimage.map(|p| p.to_lower_hex_string()),
+        }
+    }
+}
+
+/// Pays any supported destination: an ark address, a bolt11 invoice, a
+/// bolt12 offer or a lightning address. Syncs the wallet first unless
+/// `no_sync` is set. Returns a [`PaymentResult`] as JSON.
+pub async fn send_payment(
+    destination: &str,
+    amount_sat: Option<u64>,
+    comment: Option<String>,
+    no_sync: bool,
+) -> anyhow::Result<String> {
+    timed("send_payment", async move {
+        let route = payment_route(destination, amount_sat, comment)?;
+        if !no_sync {
+            sync().await?;
+        }
+
+        let result = match route {
+            PaymentRoute::Arkoor(address, amount) => {
+                let outputs = send_arkoor_payment(address, amount).await?;
+                PaymentResult::Arkoor {
+                    destination: destination.to_string(),
+                    amount_sat: amount.to_sat(),
+                    sent_vtxo_ids: outputs.sent.iter().map(|v| v.id().to_string()).collect(),
+                    change_amount_sat: outputs.change_amount().to_sat(),
+                }
+            }
+            PaymentRoute::Bolt11(invoice, amount) => {
+                let send = pay_lightning_invoice(lightning::Invoice::Bolt11(invoice), amount, None)
+                    .await?;
+                PaymentResult::Bolt11(LightningPaymentResult::new(destination, send))
+            }
+            PaymentRoute::Offer(offer, amount) => {
+                let send = pay_lightning_offer(offer, amount).await?;
+                PaymentResult::Bolt12(LightningPaymentResult::new(destination, send))
+            }
+            PaymentRoute::LnAddress(address, amount, comment) => {
+                let send =
+                    pay_lightning_address(&address.to_string(), amount, comment.as_deref(), None)
+                        .await?;
+                PaymentResult::LightningAddress(LightningPaymentResult::new(destination, send))
+            }
+        };
+        Ok(serde_json::to_string(&result)?)
+    })
+    .await
+}
+
+/// Decodes an LNURL and fetches its service document to find out what it
+/// offers.
+pub async fn decode_lnurl(lnurl: &str) -> anyhow::Result<LnurlDecoded> {
+    timed("decode_lnurl", async move {
+        let (_, url) = lnurl_service_url(lnurl)?;
+        if let Some(login) = LnurlDecoded::login(&url) {
+            return Ok(login);
+        }
+
+        let client = bark::lnurllib::Builder::default()
+            .build_async()
+            .map_err(|err| anyhow::anyhow!("Failed to build lnurl client: {:?}", err))?;
+        let response = client
+            .make_request(&url)
+            .await
+            .map_err(|err| anyhow::anyhow!("Failed to fetch lnurl service document: {:?}", err))?;
+        Ok(LnurlDecoded::from_response(url, response))
+    })
+    .await
+}
+
+/// Processes an LNURL-withdraw by creating an invoice for the maximum
+/// withdrawable amount, handing it to the service and waiting for the payment.
+pub async fn process_lnurl_withdraw(lnurl: &str) -> anyhow::Result<(Bolt11Invoice, Preimage)> {
+    timed("process_lnurl_withdraw", async move {
+        let url = decode_lnurl_url(lnurl)?;
+        let client = bark::lnurllib::Builder::default()
+            .build_async()
+            .map_err(|err| anyhow::anyhow!("Failed to build lnurl client: {:?}", err))?;
+
+        let response = client
+            .make_request(&url)
+            .await
+            .map_err(|err| anyhow::anyhow!("Failed to fetch lnurl service document: {:?}", err))?;
+        let withdraw = match response {
+            bark::lnurllib::LnUrlResponse::LnUrlWithdrawResponse(withdraw) => withdraw,
+            _ => bail!("Lnurl '{}' is not a withdraw request", lnurl),
+        };
+
+        let (amount, remainder_msat) = units::msat_to_sat_floor(withdraw.max_withdrawable);
+        if remainder_msat > 0 {
+            debug!(
+                "Withdrawing whole sats only, leaving {} msat behind",
+                remainder_msat
+            );
+        }
+        if amount == Amount::ZERO {
+            bail!("Lnurl withdraw service does not allow withdrawing any amount");
+        }
+
+        info!("Processing lnurl withdraw of {} from {}", amount, url);
+        let invoice = bolt11_invoice(amount).await?;
+
+        let result = client
+            .do_withdrawal(&withdraw, &invoice.to_string())
+            .await
+            .map_err(|err| anyhow::anyhow!("Failed to request lnurl withdrawal: {:?}", err))?;
+        if let bark::lnurllib::Response::Error { reason } = result {
+            bail!("Lnurl withdraw service returned an error: {}", reason);
+        }
+
+        let payment_hash = PaymentHash::from_str(&invoice.payment_hash().to_string())?;
+        let receive = try_claim_lightning_receive(payment_hash, true, None).await?;
+
+        Ok((invoice, receive.payment_preimage))
+    })
+    .await
+}
+
+fn record_round(ctx: &mut WalletContext, funding_txid: Txid, started: std::time::Instant) {
+    ctx.last_round = Some(ObservedRound {
+        funding_txid: funding_txid.to_string(),
+        at: now_timestamp(),
+        wait_secs: started.elapsed().as_secs(),
+    });
+}
+
+/// Estimates when the next round starts from the last round we took part
+/// in and the server's round interval.
+pub async fn get_next_round_eta() -> anyhow::Result<NextRoundEta> {
+    timed("get_next_round_eta", async move {
+        let round_interval_secs = get_ark_info().await?.round_interval.as_secs();
+        let manager = lock_manager("get_next_round_eta").await;
+        manager.with_context_ref(|ctx| {
+            let last_round = ctx.last_round.clone();
+            Ok(NextRoundEta {
+                eta_secs: next_round_eta(
+                    last_round.as_ref().map(|r| r.at),
+                    round_interval_secs,
+                    now_timestamp(),
+                ),
+                last_round,
+                round_interval_secs,
+            })
         })
-        .await
+    })
+    .await
 }
 
 pub async fn offboard_specific(vtxo_ids: Vec<VtxoId>, address: Address) -> anyhow::Result<Txid> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async { ctx.wallet.offboard_vtxos(vtxo_ids, address).await })
-        .await
+    timed("offboard_specific", async move {
+        let mut manager = lock_manager("offboard_specific").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async(
+                "offboard_specific",
+                format!("{:?} {}", vtxo_ids, address),
+                async move |ctx| {
+                    let started = std::time::Instant::now();
+                    let result = match exclude_quarantined_vtxos(ctx).await {
+                        Ok(()) => ctx.wallet.offboard_vtxos(vtxo_ids, address).await,
+                        Err(e) => Err(e),
+                    };
+                    let txid = match unlock_after_payment(ctx, result).await {
+                        Ok(txid) => txid,
+                        Err(e) => return Err(on_round_failure(e, || ctx.wallet.sync()).await),
+                    };
+                    record_round(ctx, txid, started);
+                    Ok(txid)
+                },
+            )
+            .await
+    })
+    .await
 }
 
 pub async fn offboard_all(address: Address) -> anyhow::Result<Txid> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async { ctx.wallet.offboard_all(address).await })
-        .await
+    timed("offboard_all", async move {
+        let mut manager = lock_manager("offboard_all").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async("offboard_all", address.to_string(), async move |ctx| {
+                let started = std::time::Instant::now();
+                let result = match exclude_quarantined_vtxos(ctx).await {
+                    Ok(()) => ctx.wallet.offboard_all(address).await,
+                    Err(e) => Err(e),
+                };
+                let txid = match unlock_after_payment(ctx, result).await {
+                    Ok(txid) => txid,
+                    Err(e) => return Err(on_round_failure(e, || ctx.wallet.sync()).await),
+                };
+                record_round(ctx, txid, started);
+                Ok(txid)
+            })
+            .await
+    })
+    .await
 }
 
 pub async fn sync_exits() -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .sync_exits(&mut ctx.onchain_wallet)
-                .await
-                .context("Failed to sync exits")?;
-            Ok(())
-        })
-        .await
+    timed("sync_exits", async move {
+        let mut manager = lock_manager("sync_exits").await;
+        manager
+            .with_context_journaled_async("sync_exits", String::new(), async |ctx| {
+                ctx.wallet
+                    .sync_exits(&mut ctx.onchain_wallet)
+                    .await
+                    .context("Failed to sync exits")?;
+                Ok(())
+            })
+            .await
+    })
+    .await
+}
+
+/// Syncs the exits and returns their status, read under the same lock so
+/// no other call can change the exits in between.
+pub async fn sync_exits_with_progress() -> anyhow::Result<Vec<ExitStatusEntry>> {
+    timed("sync_exits_with_progress", async move {
+        let mut manager = lock_manager("sync_exits_with_progress").await;
+        manager
+            .with_context_journaled_async("sync_exits_with_progress", String::new(), async |ctx| {
+                ctx.wallet
+                    .sync_exits(&mut ctx.onchain_wallet)
+                    .await
+                    .context("Failed to sync exits")?;
+
+                let exit = ctx.wallet.exit.read().await;
+                Ok(exit
+                    .get_exit_vtxos()
+                    .iter()
+                    .map(|exit_vtxo| ExitStatusEntry {
+                        vtxo_id: exit_vtxo.id().to_string(),
+                        amount_sat: exit_vtxo.vtxo().amount().to_sat(),
+                        state: exit_state_name(exit_vtxo.state()),
+                        claimable: exit_vtxo.is_claimable(),
+                    })
+                    .collect())
+            })
+            .await
+    })
+    .await
 }
 
 pub async fn sync_pending_rounds() -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.wallet
-                .sync_pending_rounds()
-                .await
-                .context("Failed to sync pending rounds")?;
-            Ok(())
+    timed("sync_pending_rounds", async move {
+        let mut manager = lock_manager("sync_pending_rounds").await;
+        manager.ensure_online()?;
+        manager
+            .with_context_journaled_async("sync_pending_rounds", String::new(), async |ctx| {
+                ctx.wallet
+                    .sync_pending_rounds()
+                    .await
+                    .context("Failed to sync pending rounds")?;
+                Ok(())
+            })
+            .await
+    })
+    .await
+}
+
+pub async fn get_recent_recipients(
+    limit: usize,
+    payment_method_filter: Option<&str>,
+) -> anyhow::Result<Vec<Recipient>> {
+    timed("get_recent_recipients", async move {
+        let manager = lock_manager("get_recent_recipients").await;
+        manager.with_context_ref(|ctx| {
+            let conn = open_bark_db_read_only(&ctx.datadir.join(DB_FILE))?;
+            let mut recipients = get_recipient_usage(&conn, limit, payment_method_filter)?;
+            for recipient in recipients.iter_mut() {
+                recipient.name = ctx
+                    .store
+                    .get_address_book_entry(&recipient.destination)?
+                    .map(|entry| entry.name);
+            }
+            Ok(recipients)
         })
-        .await
+    })
+    .await
+}
+
+pub async fn get_address_book() -> anyhow::Result<Vec<Recipient>> {
+    timed("get_address_book", async move {
+        let manager = lock_manager("get_address_book").await;
+        manager.with_context_ref(|ctx| {
+            let entries = ctx.store.get_address_book()?;
+            let used = {
+                let conn = open_bark_db_read_only(&ctx.datadir.join(DB_FILE))?;
+                get_recipient_usage(&conn, usize::MAX, None)?
+            };
+
+            Ok(entries
+                .into_iter()
+                .map(|entry| {
+                    let usage = used.iter().find(|r| r.destination == entry.destination);
+                    Recipient {
+                        destination_type: guess_destination_type(&entry.destination).to_string(),
+                        last_used_at: usage.and_then(|r| r.last_used_at),
+                        times_used: usage.map_or(0, |r| r.times_used),
+                        name: Some(entry.name),
+                        destination: entry.destination,
+                    }
+                })
+                .collect())
+        })
+    })
+    .await
+}
+
+pub async fn add_address_book_entry(name: &str, destination: &str) -> anyhow::Result<()> {
+    timed("add_address_book_entry", async move {
+        let manager = lock_manager("add_address_book_entry").await;
+        manager.with_context_ref(|ctx| ctx.store.add_address_book_entry(name, destination))
+    })
+    .await
+}
+
+pub async fn remove_address_book_entry(destination: &str) -> anyhow::Result<bool> {
+    timed("remove_address_book_entry", async move {
+        let manager = lock_manager("remove_address_book_entry").await;
+        manager.with_context_ref(|ctx| ctx.store.remove_address_book_entry(destination))
+    })
+    .await
 }
@@ -0,0 +1,143 @@
+use std::fmt;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{self, Context};
+use logger::log::{debug, warn};
+
+use crate::store::now_timestamp;
+
+pub(crate) const LOCK_FILE: &str = ".lock";
+
+/// How old a lock left behind by a process that didn't release it must be
+/// before it may be stolen.
+pub const STALE_LOCK_SECS: i64 = 30;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockError {
+    /// Another process, or an earlier load in this one, has the datadir open.
+    WalletInUse {
+        pid: Option<u32>,
+        age_secs: Option<i64>,
+    },
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockError::WalletInUse {
+                pid: Some(pid),
+                age_secs: Some(age_secs),
+            } => write!(
+                f,
+                "Wallet is in use by process {} since {}s ago",
+                pid, age_secs
+            ),
+            LockError::WalletInUse { .. } => write!(f, "Wallet is in use by another process"),
+        }
+    }
+}
+
+impl std::error::Error for LockError {}
+
+/// The process recorded in a lock file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockHolder {
+    pub pid: u32,
+    pub locked_at: i64,
+}
+
+impl LockHolder {
+    pub fn parse(contents: &str) -> Option<Self> {
+        let mut parts = contents.split_whitespace();
+        Some(LockHolder {
+            pid: parts.next()?.parse().ok()?,
+            locked_at: parts.next()?.parse().ok()?,
+        })
+    }
+
+    pub fn age_secs(&self) -> i64 {
+        (now_timestamp() - self.locked_at).max(0)
+    }
+}
+
+fn wallet_in_use(holder: Option<LockHolder>) -> LockError {
+    LockError::WalletInUse {
+        pid: holder.map(|h| h.pid),
+        age_secs: holder.map(|h| h.age_secs()),
+    }
+}
+
+fn read_holder(file: &mut File) -> Option<LockHolder> {
+    let mut contents = String::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_string(&mut contents).ok()?;
+    LockHolder::parse(&contents)
+}
+
+/// An exclusive advisory lock on a wallet datadir, so two processes never
+/// open the same database. Released when dropped.
+///
+/// The lock file records the holder's PID and when it took the lock, and is
+/// cleared on release. A record found under a lock that could be taken means
+/// its holder died without releasing; such a lock is only taken over when
+/// stealing is allowed and the record is older than [`STALE_LOCK_SECS`].
+#[derive(Debug)]
+pub struct DatadirLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl DatadirLock {
+    pub fn acquire(datadir: &Path, steal_lock: bool) -> anyhow::Result<Self> {
+        let path = datadir.join(LOCK_FILE);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                return Err(wallet_in_use(read_holder(&mut file)).into());
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock {}", path.display()));
+            }
+        }
+
+        if let Some(holder) = read_holder(&mut file) {
+            if !steal_lock || holder.age_secs() < STALE_LOCK_SECS {
+                return Err(wallet_in_use(Some(holder)).into());
+            }
+            warn!(
+                "Taking over the lock of process {} left {}s ago",
+                holder.pid,
+                holder.age_secs()
+            );
+        }
+
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write!(file, "{} {}", std::process::id(), now_timestamp())?;
+        file.sync_all()?;
+        debug!("Locked datadir with {}", path.display());
+
+        Ok(DatadirLock { file, path })
+    }
+}
+
+impl Drop for DatadirLock {
+    fn drop(&mut self) {
+        if let Err(e) = self.file.set_len(0) {
+            warn!("Failed to clear lock file {}: {}", self.path.display(), e);
+        }
+        if let Err(e) = self.file.unlock() {
+            warn!("Failed to unlock {}: {}", self.path.display(), e);
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::{LazyLock, Mutex};
+use std::time::Instant;
+
+use logger::log::warn;
+
+use crate::store::now_timestamp;
+
+/// Maximum number of timings kept; aggregates are computed over these.
+const MAX_METRIC_ENTRIES: usize = 500;
+
+static METRICS: LazyLock<Mutex<VecDeque<MetricEntry>>> =
+    LazyLock::new(|| Mutex::new(VecDeque::new()));
+
+/// The timing of a single operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricEntry {
+    pub operation: &'static str,
+    pub duration_ms: u64,
+    pub success: bool,
+    /// Coarse reason of a failure, `None` on success
+    pub error_class: Option<&'static str>,
+    pub recorded_at: i64,
+}
+
+/// Aggregates over the recorded timings of one operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperationStats {
+    pub operation: &'static str,
+    pub count: u64,
+    pub failure_count: u64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// Oldest first
+    pub entries: Vec<MetricEntry>,
+    /// In order of first occurrence
+    pub operations: Vec<OperationStats>,
+}
+
+/// Runs `operation`, recording how long it took and whether it failed.
+///
+/// Every public operation in `lib.rs` and `onchain.rs` that returns a
+/// `Result` runs through this, named after its function. The few that
+/// can't fail, such as `is_wallet_loaded`, only read in-memory state and
+/// aren't timed.
+pub async fn timed<T, F>(operation: &'static str, fut: F) -> anyhow::Result<T>
+where
+    F: Future<Output = anyhow::Result<T>>,
+{
+    let start = Instant::now();
+    let result = fut.await;
+    record(
+        operation,
+        start.elapsed().as_millis() as u64,
+        result.as_ref().err().map(classify_error),
+    );
+    result
+}
+
+pub fn record(operation: &'static str, duration_ms: u64, error_class: Option<&'static str>) {
+    let mut metrics = METRICS.lock().unwrap_or_else(|e| e.into_inner());
+    push_bounded(
+        &mut metrics,
+        MetricEntry {
+            operation,
+            duration_ms,
+            success: error_class.is_none(),
+            error_class,
+            recorded_at: now_timestamp(),
+        },
+        MAX_METRIC_ENTRIES,
+    );
+}
+
+pub fn push_bounded(entries: &mut VecDeque<MetricEntry>, entry: MetricEntry, max: usize) {
+    while entries.len() >= max {
+        entries.pop_front();
+    }
+    entries.push_back(entry);
+}
+
+pub fn metrics_snapshot() -> MetricsSnapshot {
+    let metrics = METRICS.lock().unwrap_or_else(|e| e.into_inner());
+    let entries = metrics.iter().cloned().collect::<Vec<_>>();
+    MetricsSnapshot {
+        operations: aggregate(&entries),
+        entries,
+    }
+}
+
+pub fn reset_metrics() {
+    METRICS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+pub fn aggregate(entries: &[MetricEntry]) -> Vec<OperationStats> {
+    let mut operations: Vec<&'static str> = Vec::new();
+    for entry in entries {
+        if !operations.contains(&entry.operation) {
+            operations.push(entry.operation);
+        }
+    }
+
+    operations
+        .into_iter()
+        .map(|operation| {
+            let matching = entries.iter().filter(|e| e.operation == operation);
+            let mut durations = matching.clone().map(|e| e.duration_ms).collect::<Vec<_>>();
+            durations.sort_unstable();
+            OperationStats {
+                operation,
+                count: durations.len() as u64,
+                failure_count: matching.filter(|e| !e.success).count() as u64,
+                p50_ms: percentile(&durations, 50),
+                p95_ms: percentile(&durations, 95),
+                max_ms: durations.last().copied().unwrap_or(0),
+            }
+        })
+        .collect()
+}
+
+/// Nearest-rank percentile of sorted values, 0 when there are none.
+pub fn percentile(sorted: &[u64], pct: u32) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (sorted.len() * pct as usize).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// Buckets an error into a class the app can group on without parsing
+/// error messages.
+pub fn classify_error(err: &anyhow::Error) -> &'static str {
+    let msg = format!("{:#}", err).to_lowercase();
+    if msg.contains("wallet not loaded") {
+        "not_loaded"
+    } else if msg.contains("offline mode") {
+        "offline"
+    } else if msg.contains("timeout") || msg.contains("timed out") {
+        "timeout"
+    } else if msg.contains("insufficient") || msg.contains("not enough") {
+        "insufficient_funds"
+    } else if msg.contains("connect") || msg.contains("transport") || msg.contains("dns") {
+        "network"
+    } else if msg.contains("invalid") {
+        "invalid_input"
+    } else {
+        "other"
+    }
+}
+
+/// Number of wallet manager lock records kept.
+const MAX_LOCK_RECORDS: usize = 100;
+
+/// Waits on the wallet manager longer than this are logged.
+pub const LOCK_WAIT_WARN_MS: u64 = 2_000;
+
+static LOCK_DIAGNOSTICS: LazyLock<Mutex<LockDiagnostics>> =
+    LazyLock::new(|| Mutex::new(LockDiagnostics::default()));
+
+/// One acquisition of the wallet manager lock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockRecord {
+    pub operation: &'static str,
+    /// How long the operation waited for the lock
+    pub waited_ms: u64,
+    /// How long the operation held the lock
+    pub held_ms: u64,
+    pub acquired_at: i64,
+}
+
+/// The operation holding the wallet manager lock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockHolding {
+    pub operation: &'static str,
+    pub since: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LockDiagnostics {
+    /// Oldest first, released acquisitions only
+    pub records: VecDeque<LockRecord>,
+    pub current_holder: Option<LockHolding>,
+}
+
+/// The operation currently holding the wallet manager lock, if any.
+pub fn current_lock_holder() -> Option<&'static str> {
+    let diagnostics = LOCK_DIAGNOSTICS.lock().unwrap_or_else(|e| e.into_inner());
+    diagnostics.current_holder.as_ref().map(|h| h.operation)
+}
+
+/// Records that `operation` got the wallet manager lock after waiting
+/// `waited_ms` on `blocked_by`, the holder when it started waiting.
+pub fn record_lock_acquired(
+    operation: &'static str,
+    waited_ms: u64,
+    blocked_by: Option<&'static str>,
+) {
+    if waited_ms > LOCK_WAIT_WARN_MS {
+        warn!(
+            "{} waited {}ms for the wallet held by {}",
+            operation,
+            waited_ms,
+            blocked_by.unwrap_or("unknown")
+        );
+    }
+    let mut diagnostics = LOCK_DIAGNOSTICS.lock().unwrap_or_else(|e| e.into_inner());
+    diagnostics.current_holder = Some(LockHolding {
+        operation,
+        since: now_timestamp(),
+    });
+}
+
+/// Records that `operation` released the wallet manager lock.
+pub fn record_lock_released(operation: &'static str, waited_ms: u64, held_ms: u64) {
+    let mut diagnostics = LOCK_DIAGNOSTICS.lock().unwrap_or_else(|e| e.into_inner());
+    let acquired_at = diagnostics
+        .current_holder
+        .take()
+        .map_or_else(now_timestamp, |holder| holder.since);
+    while diagnostics.records.len() >= MAX_LOCK_RECORDS {
+        diagnostics.records.pop_front();
+    }
+    diagnostics.records.push_back(LockRecord {
+        operation,
+        waited_ms,
+        held_ms,
+        acquired_at,
+    });
+}
+
+pub fn lock_diagnostics() -> LockDiagnostics {
+    LOCK_DIAGNOSTICS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
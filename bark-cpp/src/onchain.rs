@@ -1,44 +1,141 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{Context, bail};
 use bark::onchain::{ChainSync, Utxo};
-use bdk_wallet::bitcoin::{Address, Amount, FeeRate, Txid};
+use bdk_bitcoind_rpc::Emitter;
+use bdk_bitcoind_rpc::bitcoincore_rpc::{Auth, Client};
+use bdk_esplora::{EsploraAsyncExt, esplora_client};
+use bdk_wallet::bitcoin::{
+    Address, Amount, Block, FeeRate, OutPoint, ScriptBuf, Transaction, Txid,
+};
+use bdk_wallet::chain::spk_client::{FullScanRequest, FullScanResponse};
+use bdk_wallet::chain::{BlockId, ChainPosition, CheckPoint};
+use bdk_wallet::{KeychainKind, SignOptions};
+use logger::log::warn;
 
-use crate::GLOBAL_WALLET_MANAGER;
+use crate::events::{WalletEventKind, emit_event};
+use crate::lock_manager;
+use crate::metrics::timed;
+use crate::store::{Store, now_timestamp};
 
 /// Get onchain balance
 pub async fn onchain_balance() -> anyhow::Result<bdk_wallet::Balance> {
-    let manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager.with_context_ref(|ctx| Ok(ctx.onchain_wallet.balance()))
+    timed("onchain_wallet_balance", async move {
+        let manager = lock_manager("onchain_balance").await;
+        manager.with_context_ref(|ctx| Ok(ctx.onchain_wallet.balance()))
+    })
+    .await
 }
 
 /// Get a new address
 pub async fn address() -> anyhow::Result<Address> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async { ctx.onchain_wallet.address().await })
-        .await
+    timed("onchain_address", async move {
+        let mut manager = lock_manager("onchain_address").await;
+        manager
+            .with_context_async(|ctx| async { ctx.onchain_wallet.address().await })
+            .await
+    })
+    .await
 }
 
 /// Get unspent outputs
 pub async fn list_unspent() -> anyhow::Result<Vec<bdk_wallet::LocalOutput>> {
-    let manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager.with_context_ref(|ctx| Ok(ctx.onchain_wallet.list_unspent()))
+    timed("onchain_list_unspent", async move {
+        let manager = lock_manager("onchain_list_unspent").await;
+        manager.with_context_ref(|ctx| Ok(ctx.onchain_wallet.list_unspent()))
+    })
+    .await
 }
 
 /// Get utxos
 pub async fn utxos() -> anyhow::Result<Vec<Utxo>> {
-    let manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager.with_context_ref(|ctx| Ok(ctx.onchain_wallet.utxos()))
+    timed("onchain_utxos", async move {
+        let manager = lock_manager("onchain_utxos").await;
+        manager.with_context_ref(|ctx| Ok(ctx.onchain_wallet.utxos()))
+    })
+    .await
+}
+
+pub fn utxos_to_json(utxos: &[Utxo]) -> anyhow::Result<String> {
+    let res = utxos
+        .iter()
+        .map(|utxo| match utxo {
+            Utxo::Local(local) => serde_json::json!({
+                "outpoint": format!("{}:{}", local.outpoint.txid, local.outpoint.vout),
+                "amount": local.amount.to_sat(),
+                "confirmation_height": local.confirmation_height.map_or(0, |_h| 0),
+            }),
+            Utxo::Exit(exit) => serde_json::json!({
+                "vtxo": crate::utils::vtxo_to_bark_vtxo(&exit.vtxo),
+                "height": exit.height
+            }),
+        })
+        .collect::<Vec<_>>();
+
+    serde_json::to_string(&res).map_err(Into::into)
+}
+
+/// An unspent output of the onchain wallet
+#[derive(Debug, Clone)]
+pub struct UnspentOutput {
+    pub txid: String,
+    pub vout: u32,
+    pub amount_sat: u64,
+    pub script_pubkey: String,
+    pub confirmations: u32,
+}
+
+/// Get unspent outputs with their confirmation count at the current tip
+pub async fn unspent_outputs() -> anyhow::Result<Vec<UnspentOutput>> {
+    timed("onchain_unspent_outputs", async move {
+        let manager = lock_manager("onchain_unspent_outputs").await;
+        manager
+            .with_context_ref_async(|ctx| async {
+                let tip = ctx.wallet.chain.tip().await?;
+                let outputs = ctx
+                    .onchain_wallet
+                    .list_unspent()
+                    .into_iter()
+                    .map(|output| {
+                        let confirmations = match output.chain_position {
+                            ChainPosition::Confirmed { anchor, .. } => {
+                                tip.saturating_sub(anchor.block_id.height) + 1
+                            }
+                            ChainPosition::Unconfirmed { .. } => 0,
+                        };
+                        UnspentOutput {
+                            txid: output.outpoint.txid.to_string(),
+                            vout: output.outpoint.vout,
+                            amount_sat: output.txout.value.to_sat(),
+                            script_pubkey: output.txout.script_pubkey.to_hex_string(),
+                            confirmations,
+                        }
+                    })
+                    .collect();
+                Ok(outputs)
+            })
+            .await
+    })
+    .await
 }
 
 /// Send onchain transaction
 pub async fn send(dest: Address, amount: Amount, fee_rate: FeeRate) -> anyhow::Result<Txid> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.onchain_wallet
-                .send(&ctx.wallet.chain, dest, amount, fee_rate)
-                .await
-        })
-        .await
+    timed("onchain_send", async move {
+        let mut manager = lock_manager("onchain_send").await;
+        let params = format!("{} {} {}", dest, amount, fee_rate.to_sat_per_vb_ceil());
+        manager
+            .with_context_journaled_async("onchain_send", params, async |ctx| {
+                ctx.onchain_wallet
+                    .send(&ctx.wallet.chain, dest, amount, fee_rate)
+                    .await
+            })
+            .await
+    })
+    .await
 }
 
 /// Send many onchain transactions
@@ -46,32 +143,862 @@ pub async fn send_many(
     destinations: &[(Address, Amount)],
     fee_rate: FeeRate,
 ) -> anyhow::Result<Txid> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.onchain_wallet
-                .send_many(&ctx.wallet.chain, destinations, fee_rate)
-                .await
-        })
-        .await
+    timed("onchain_send_many", async move {
+        let mut manager = lock_manager("onchain_send_many").await;
+        let params = format!("{:?} {}", destinations, fee_rate.to_sat_per_vb_ceil());
+        manager
+            .with_context_journaled_async("onchain_send_many", params, async |ctx| {
+                ctx.onchain_wallet
+                    .send_many(&ctx.wallet.chain, destinations, fee_rate)
+                    .await
+            })
+            .await
+    })
+    .await
 }
 
 /// Drain the wallet to a destination address with a specified fee rate
 pub async fn drain(destination: Address, fee_rate: FeeRate) -> anyhow::Result<Txid> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async {
-            ctx.onchain_wallet
-                .drain(&ctx.wallet.chain, destination, fee_rate)
-                .await
-        })
+    timed("onchain_drain", async move {
+        let mut manager = lock_manager("onchain_drain").await;
+        let params = format!("{} {}", destination, fee_rate.to_sat_per_vb_ceil());
+        manager
+            .with_context_journaled_async("onchain_drain", params, async |ctx| {
+                ctx.onchain_wallet
+                    .drain(&ctx.wallet.chain, destination, fee_rate)
+                    .await
+            })
+            .await
+    })
+    .await
+}
+
+/// Funds moved to a fresh address of this wallet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTransfer {
+    pub txid: Txid,
+    pub address: Address,
+    pub amount: Amount,
+    pub fee: Amount,
+}
+
+/// Reveals the next change address and marks it used, so bdk won't also
+/// pick it for the change of the transaction paying to it.
+pub fn fresh_internal_address(wallet: &mut bdk_wallet::Wallet) -> Address {
+    let info = wallet.reveal_next_address(KeychainKind::Internal);
+    wallet.mark_used(KeychainKind::Internal, info.index);
+    info.address
+}
+
+/// Fails if consolidating the wallet's confirmed UTXOs would not merge
+/// anything.
+pub fn check_consolidation(confirmed_utxos: usize) -> anyhow::Result<()> {
+    match confirmed_utxos {
+        0 => bail!("No confirmed UTXOs to consolidate"),
+        1 => bail!("Nothing to consolidate, a single UTXO already holds the confirmed funds"),
+        _ => Ok(()),
+    }
+}
+
+pub fn check_self_transfer_amount(amount: Amount, spendable: Amount) -> anyhow::Result<()> {
+    if amount == Amount::ZERO {
+        bail!("Amount must be positive");
+    }
+    if amount > spendable {
+        bail!(
+            "Amount of {} exceeds the spendable onchain balance of {}",
+            amount,
+            spendable
+        );
+    }
+    Ok(())
+}
+
+async fn fee_rate_or_config(fee_rate: Option<FeeRate>) -> anyhow::Result<FeeRate> {
+    match fee_rate {
+        Some(fee_rate) => Ok(fee_rate),
+        None => config_fee_rate().await,
+    }
+}
+
+/// Signs, broadcasts and applies a self transfer paying to `address`.
+async fn finish_self_transfer(
+    ctx: &mut crate::WalletContext,
+    psbt: bdk_wallet::bitcoin::Psbt,
+    address: Address,
+) -> anyhow::Result<SelfTransfer> {
+    let mut psbt = psbt;
+    if !ctx.onchain_wallet.sign(&mut psbt, SignOptions::default())? {
+        bail!("Failed to sign the transfer to {}", address);
+    }
+    let tx = psbt.extract_tx()?;
+    let fee = ctx.onchain_wallet.calculate_fee(&tx)?;
+    let script = address.script_pubkey();
+    let amount = tx
+        .output
+        .iter()
+        .filter(|out| out.script_pubkey == script)
+        .map(|out| out.value)
+        .sum();
+
+    ctx.wallet
+        .chain
+        .broadcast_tx(&tx)
         .await
+        .context("Failed to broadcast the transfer")?;
+    ctx.onchain_wallet
+        .apply_unconfirmed_txs([(tx.clone(), now_timestamp() as u64)]);
+
+    Ok(SelfTransfer {
+        txid: tx.compute_txid(),
+        address,
+        amount,
+        fee,
+    })
+}
+
+/// Spends all confirmed UTXOs into a single output to a fresh change
+/// address of this wallet.
+pub async fn consolidate(fee_rate: Option<FeeRate>) -> anyhow::Result<SelfTransfer> {
+    timed("onchain_consolidate", async move {
+        let fee_rate = fee_rate_or_config(fee_rate).await?;
+        let mut manager = lock_manager("onchain_consolidate").await;
+        manager.ensure_online()?;
+        let params = fee_rate.to_sat_per_vb_ceil().to_string();
+        manager
+            .with_context_journaled_async("onchain_consolidate", params, async move |ctx| {
+                let confirmed = ctx
+                    .onchain_wallet
+                    .list_unspent()
+                    .into_iter()
+                    .filter(|utxo| utxo.chain_position.is_confirmed())
+                    .map(|utxo| utxo.outpoint)
+                    .collect::<Vec<_>>();
+                check_consolidation(confirmed.len())?;
+
+                let address = fresh_internal_address(&mut ctx.onchain_wallet);
+                let mut builder = ctx.onchain_wallet.build_tx();
+                builder
+                    .add_utxos(&confirmed)?
+                    .manually_selected_only()
+                    .drain_to(address.script_pubkey())
+                    .fee_rate(fee_rate);
+                let psbt = builder.finish()?;
+                finish_self_transfer(ctx, psbt, address).await
+            })
+            .await
+    })
+    .await
+}
+
+/// Sends `amount` to a fresh change address of this wallet.
+pub async fn send_to_self(
+    amount: Amount,
+    fee_rate: Option<FeeRate>,
+) -> anyhow::Result<SelfTransfer> {
+    timed("onchain_send_to_self", async move {
+        let fee_rate = fee_rate_or_config(fee_rate).await?;
+        let mut manager = lock_manager("onchain_send_to_self").await;
+        manager.ensure_online()?;
+        let params = format!("{} {}", amount, fee_rate.to_sat_per_vb_ceil());
+        manager
+            .with_context_journaled_async("onchain_send_to_self", params, async move |ctx| {
+                let balance = ctx.onchain_wallet.balance();
+                check_self_transfer_amount(amount, balance.trusted_spendable())?;
+
+                let address = fresh_internal_address(&mut ctx.onchain_wallet);
+                let mut builder = ctx.onchain_wallet.build_tx();
+                builder
+                    .add_recipient(address.script_pubkey(), amount)
+                    .fee_rate(fee_rate);
+                let psbt = builder.finish()?;
+                finish_self_transfer(ctx, psbt, address).await
+            })
+            .await
+    })
+    .await
+}
+
+/// The fee rate to use when the caller doesn't pass one: the configured
+/// fallback fee rate, or the minimum relay fee if none is configured.
+pub fn fee_rate_or_min_relay(fallback_fee_rate: Option<FeeRate>) -> FeeRate {
+    fallback_fee_rate.unwrap_or(FeeRate::BROADCAST_MIN)
+}
+
+async fn config_fee_rate() -> anyhow::Result<FeeRate> {
+    let manager = lock_manager("onchain_config_fee_rate").await;
+    let fallback_fee_rate = manager.with_context_ref(|ctx| Ok(ctx.config.fallback_fee_rate))?;
+    Ok(fee_rate_or_min_relay(fallback_fee_rate))
+}
+
+/// Send onchain transaction using the fee rate from the wallet config
+pub async fn send_with_config_fee(dest: Address, amount: Amount) -> anyhow::Result<Txid> {
+    timed("onchain_send_with_config_fee", async move {
+        let fee_rate = config_fee_rate().await?;
+        send(dest, amount, fee_rate).await
+    })
+    .await
+}
+
+/// Send many onchain transactions using the fee rate from the wallet config
+pub async fn send_many_with_config_fee(destinations: &[(Address, Amount)]) -> anyhow::Result<Txid> {
+    timed("onchain_send_many_with_config_fee", async move {
+        let fee_rate = config_fee_rate().await?;
+        send_many(destinations, fee_rate).await
+    })
+    .await
+}
+
+/// Drain the wallet to a destination address using the fee rate from the wallet config
+pub async fn drain_with_config_fee(destination: Address) -> anyhow::Result<Txid> {
+    timed("onchain_drain_with_config_fee", async move {
+        let fee_rate = config_fee_rate().await?;
+        drain(destination, fee_rate).await
+    })
+    .await
 }
 
 /// Synchronize the onchain wallet with the blockchain
 pub async fn sync() -> anyhow::Result<()> {
-    let mut manager = GLOBAL_WALLET_MANAGER.lock().await;
-    manager
-        .with_context_async(|ctx| async { ctx.onchain_wallet.sync(&ctx.wallet.chain).await })
-        .await
+    timed("onchain_sync", async move {
+        let mut manager = lock_manager("onchain_sync").await;
+        manager
+            .with_context_async(|ctx| async { ctx.onchain_wallet.sync(&ctx.wallet.chain).await })
+            .await
+    })
+    .await
+}
+
+/// Why a transaction can't be fee bumped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeeBumpError {
+    /// Not a transaction of this wallet
+    UnknownTransaction(Txid),
+    AlreadyConfirmed(Txid),
+    /// The transaction doesn't signal BIP125 replaceability
+    NotReplaceable(Txid),
+    /// A board's VTXO is anchored on the funding output of the original
+    /// transaction, so replacing it would invalidate the board
+    BoardFunding(Txid),
+    FeeRateTooLow {
+        current: FeeRate,
+        requested: FeeRate,
+    },
+}
+
+impl fmt::Display for FeeBumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeeBumpError::UnknownTransaction(txid) => {
+                write!(f, "Transaction {} does not belong to this wallet", txid)
+            }
+            FeeBumpError::AlreadyConfirmed(txid) => {
+                write!(f, "Transaction {} is already confirmed", txid)
+            }
+            FeeBumpError::NotReplaceable(txid) => {
+                write!(f, "Transaction {} does not signal replace-by-fee", txid)
+            }
+            FeeBumpError::BoardFunding(txid) => write!(
+                f,
+                "Transaction {} funds a board and can't be replaced without invalidating it",
+                txid
+            ),
+            FeeBumpError::FeeRateTooLow { current, requested } => write!(
+                f,
+                "New fee rate of {} sat/vB must be higher than the current {} sat/vB",
+                requested.to_sat_per_vb_ceil(),
+                current.to_sat_per_vb_ceil()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FeeBumpError {}
+
+/// What the wallet knows about a transaction that is to be fee bumped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BumpCandidate {
+    pub confirmed: bool,
+    pub signals_rbf: bool,
+    pub fee_rate: FeeRate,
+    /// Whether the transaction funds one of our pending boards
+    pub board_funding: bool,
+}
+
+/// Checks that the transaction `txid`, described by `candidate` if the
+/// wallet knows it, can be replaced at `fee_rate`.
+pub fn check_fee_bump(
+    txid: Txid,
+    candidate: Option<&BumpCandidate>,
+    fee_rate: FeeRate,
+) -> Result<(), FeeBumpError> {
+    let Some(candidate) = candidate else {
+        return Err(FeeBumpError::UnknownTransaction(txid));
+    };
+    if candidate.confirmed {
+        return Err(FeeBumpError::AlreadyConfirmed(txid));
+    }
+    if candidate.board_funding {
+        return Err(FeeBumpError::BoardFunding(txid));
+    }
+    if !candidate.signals_rbf {
+        return Err(FeeBumpError::NotReplaceable(txid));
+    }
+    if fee_rate <= candidate.fee_rate {
+        return Err(FeeBumpError::FeeRateTooLow {
+            current: candidate.fee_rate,
+            requested: fee_rate,
+        });
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeBumpResult {
+    pub txid: Txid,
+    /// Fee paid by the replacement on top of the fee of the original
+    pub additional_fee: Amount,
+}
+
+/// Replaces an unconfirmed send of this wallet with one paying `fee_rate`,
+/// then broadcasts it.
+pub async fn bump_fee(txid: Txid, fee_rate: FeeRate) -> anyhow::Result<FeeBumpResult> {
+    timed("onchain_bump_fee", async move {
+        let mut manager = lock_manager("onchain_bump_fee").await;
+        manager.ensure_online()?;
+        let params = format!("{} {}", txid, fee_rate.to_sat_per_vb_ceil());
+        manager
+            .with_context_journaled_async("onchain_bump_fee", params, async |ctx| {
+                let board_funding = ctx
+                    .store
+                    .get_open_board_intents()?
+                    .iter()
+                    .any(|intent| intent.funding_txid.as_deref() == Some(&txid.to_string()));
+                let original = ctx.onchain_wallet.get_tx(txid).map(|wtx| {
+                    let confirmed = wtx.chain_position.is_confirmed();
+                    (wtx.tx_node.tx.clone(), confirmed)
+                });
+                let original_fee = match &original {
+                    Some((tx, _)) => Some(ctx.onchain_wallet.calculate_fee(tx)?),
+                    None => None,
+                };
+                let candidate = match &original {
+                    Some((tx, confirmed)) => Some(BumpCandidate {
+                        confirmed: *confirmed,
+                        signals_rbf: tx.is_explicitly_rbf(),
+                        fee_rate: ctx.onchain_wallet.calculate_fee_rate(tx)?,
+                        board_funding,
+                    }),
+                    None => None,
+                };
+                check_fee_bump(txid, candidate.as_ref(), fee_rate)?;
+
+                let mut builder = ctx.onchain_wallet.build_fee_bump(txid)?;
+                builder.fee_rate(fee_rate);
+                let mut psbt = builder.finish()?;
+                if !ctx.onchain_wallet.sign(&mut psbt, SignOptions::default())? {
+                    bail!("Failed to sign the replacement of {}", txid);
+                }
+                let tx = psbt.extract_tx()?;
+                let new_fee = ctx.onchain_wallet.calculate_fee(&tx)?;
+
+                ctx.wallet
+                    .chain
+                    .broadcast_tx(&tx)
+                    .await
+                    .with_context(|| format!("Failed to broadcast the replacement of {}", txid))?;
+                ctx.onchain_wallet
+                    .apply_unconfirmed_txs([(tx.clone(), now_timestamp() as u64)]);
+
+                Ok(FeeBumpResult {
+                    txid: tx.compute_txid(),
+                    additional_fee: new_fee
+                        .checked_sub(original_fee.unwrap_or(Amount::ZERO))
+                        .unwrap_or(Amount::ZERO),
+                })
+            })
+            .await
+    })
+    .await
+}
+
+/// The public descriptors of the onchain wallet, for importing it as
+/// watch-only into an external node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnchainDescriptors {
+    /// Receive descriptor, with checksum
+    pub external: String,
+    /// Change descriptor, with checksum
+    pub internal: String,
+    /// Height of the wallet's first confirmed transaction, or the synced tip
+    /// when it has none yet
+    pub birthday_height: u32,
+    /// Ready to pass to bitcoind's `importdescriptors`
+    pub import_descriptors_json: String,
+}
+
+/// Fails if `descriptor` carries private key material.
+pub fn ensure_public_descriptor(descriptor: &str) -> anyhow::Result<()> {
+    if descriptor.contains("prv") {
+        bail!("Refusing to export a descriptor containing private keys");
+    }
+    Ok(())
+}
+
+/// The `importdescriptors` request for the wallet's descriptors. Without a
+/// `birthday_time` bitcoind only watches from now on.
+pub fn import_descriptors_payload(
+    external: &str,
+    internal: &str,
+    birthday_time: Option<u64>,
+) -> String {
+    let timestamp = birthday_time.map_or(serde_json::json!("now"), |t| serde_json::json!(t));
+    serde_json::json!([
+        {
+            "desc": external,
+            "timestamp": timestamp,
+            "active": true,
+            "internal": false,
+        },
+        {
+            "desc": internal,
+            "timestamp": timestamp,
+            "active": true,
+            "internal": true,
+        },
+    ])
+    .to_string()
+}
+
+pub async fn export_onchain_descriptors() -> anyhow::Result<OnchainDescriptors> {
+    timed("export_onchain_descriptors", async move {
+        let manager = lock_manager("export_onchain_descriptors").await;
+        manager.with_context_ref(|ctx| {
+            let wallet = &ctx.onchain_wallet;
+            let external = wallet.public_descriptor(KeychainKind::External).to_string();
+            let internal = wallet.public_descriptor(KeychainKind::Internal).to_string();
+            ensure_public_descriptor(&external)?;
+            ensure_public_descriptor(&internal)?;
+
+            let first_confirmed = wallet
+                .transactions()
+                .filter_map(|wtx| match wtx.chain_position {
+                    ChainPosition::Confirmed { anchor, .. } => {
+                        Some((anchor.block_id.height, anchor.confirmation_time))
+                    }
+                    ChainPosition::Unconfirmed { .. } => None,
+                })
+                .min();
+            let birthday_height =
+                first_confirmed.map_or(wallet.latest_checkpoint().height(), |(height, _)| height);
+            // A rescan may have found activity before what the wallet holds now
+            let birthday_height = match ctx.store.get_onchain_birthday_height()? {
+                Some(recorded) => birthday_height.min(recorded),
+                None => birthday_height,
+            };
+
+            Ok(OnchainDescriptors {
+                import_descriptors_json: import_descriptors_payload(
+                    &external,
+                    &internal,
+                    first_confirmed.map(|(_, time)| time),
+                ),
+                external,
+                internal,
+                birthday_height,
+            })
+        })
+    })
+    .await
+}
+
+/// Set to stop a running rescan after the block it is scanning.
+static RESCAN_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// A progress event is emitted every this many scanned blocks.
+const RESCAN_PROGRESS_INTERVAL: u32 = 1000;
+
+/// Outcome of a rescan of the onchain wallet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RescanSummary {
+    pub from_height: u32,
+    pub to_height: u32,
+    pub blocks_scanned: u32,
+    /// Transactions the wallet didn't know before the rescan
+    pub transactions_found: u64,
+    /// Change of the total onchain balance, in sats
+    pub balance_delta_sat: i64,
+    /// Height of the first confirmed transaction found by this or an earlier
+    /// rescan, stored as the birthday the exported descriptors use
+    pub birthday_height: Option<u32>,
+}
+
+/// Stores `first_confirmed` as the wallet's birthday if it is earlier than
+/// the stored one, returning the birthday after that. The rescan already
+/// happened, so failing to store it is only logged.
+pub fn record_birthday(store: &Store, first_confirmed: Option<u32>) -> Option<u32> {
+    let recorded = match first_confirmed {
+        Some(height) => store.lower_onchain_birthday_height(height).map(Some),
+        None => store.get_onchain_birthday_height(),
+    };
+    match recorded {
+        Ok(birthday) => birthday,
+        Err(e) => {
+            warn!("Failed to record the onchain birthday: {:#}", e);
+            first_confirmed
+        }
+    }
+}
+
+/// Fails unless a rescan can start at `from_height` with the chain at `tip`.
+pub fn check_rescan_height(from_height: u32, tip: u32) -> anyhow::Result<()> {
+    if from_height > tip {
+        bail!(
+            "Invalid rescan height {}: the chain tip is at {}",
+            from_height,
+            tip
+        );
+    }
+    Ok(())
+}
+
+/// The wallet before or after a rescan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RescanState {
+    pub tx_count: usize,
+    pub balance: Amount,
+}
+
+pub fn rescan_summary(
+    from_height: u32,
+    to_height: u32,
+    blocks_scanned: u32,
+    before: RescanState,
+    after: RescanState,
+    birthday_height: Option<u32>,
+) -> RescanSummary {
+    RescanSummary {
+        from_height,
+        to_height,
+        blocks_scanned,
+        transactions_found: after.tx_count.saturating_sub(before.tx_count) as u64,
+        balance_delta_sat: after.balance.to_sat() as i64 - before.balance.to_sat() as i64,
+        birthday_height,
+    }
+}
+
+/// A source of blocks to rescan, in chain order.
+pub trait RescanSource {
+    type Block;
+
+    /// The next block and its height, `None` once the tip is reached.
+    fn next_block(&mut self) -> anyhow::Result<Option<(u32, Self::Block)>>;
+}
+
+impl RescanSource for Emitter<&Client> {
+    type Block = (Block, bdk_wallet::chain::BlockId);
+
+    fn next_block(&mut self) -> anyhow::Result<Option<(u32, Self::Block)>> {
+        let event = Emitter::next_block(self).context("Failed to fetch block from bitcoind")?;
+        Ok(event.map(|event| {
+            let height = event.block_height();
+            let connected_to = event.connected_to();
+            (height, (event.block, connected_to))
+        }))
+    }
+}
+
+/// Feeds every block of `source` to `apply`, calling `progress` every
+/// [`RESCAN_PROGRESS_INTERVAL`] blocks. Stops with an error when `cancelled`
+/// gets set; blocks passed to `apply` until then are kept.
+pub fn scan_blocks<S: RescanSource>(
+    source: &mut S,
+    cancelled: &AtomicBool,
+    mut apply: impl FnMut(u32, S::Block) -> anyhow::Result<()>,
+    mut progress: impl FnMut(u32, u32),
+) -> anyhow::Result<u32> {
+    let mut blocks_scanned = 0;
+    while let Some((height, block)) = source.next_block()? {
+        if cancelled.load(Ordering::SeqCst) {
+            bail!("Rescan cancelled at height {}", height);
+        }
+        apply(height, block)?;
+        blocks_scanned += 1;
+        if blocks_scanned % RESCAN_PROGRESS_INTERVAL == 0 {
+            progress(height, blocks_scanned);
+        }
+    }
+    Ok(blocks_scanned)
+}
+
+fn bitcoind_client(config: &bark::Config) -> anyhow::Result<Client> {
+    let Some(url) = config.bitcoind_address.as_ref() else {
+        bail!("Rescanning requires a bitcoind chain source");
+    };
+    let auth = match (
+        &config.bitcoind_cookiefile,
+        &config.bitcoind_user,
+        &config.bitcoind_pass,
+    ) {
+        (Some(cookie), _, _) => Auth::CookieFile(cookie.clone()),
+        (None, Some(user), Some(pass)) => Auth::UserPass(user.clone(), pass.clone()),
+        _ => Auth::None,
+    };
+    Client::new(url, auth).context("Failed to connect to bitcoind")
+}
+
+fn rescan_state(wallet: &bdk_wallet::Wallet) -> RescanState {
+    RescanState {
+        tx_count: wallet.transactions().count(),
+        balance: wallet.balance().total(),
+    }
+}
+
+/// Scripts and outpoints of the onchain wallet, for keeping only what the
+/// wallet cares about of the blocks scanned while the wallet isn't locked.
+#[derive(Debug, Default)]
+pub struct WalletFilter {
+    spks: HashSet<ScriptBuf>,
+    outpoints: HashSet<OutPoint>,
+}
+
+impl WalletFilter {
+    pub fn new(
+        spks: impl IntoIterator<Item = ScriptBuf>,
+        outpoints: impl IntoIterator<Item = OutPoint>,
+    ) -> Self {
+        WalletFilter {
+            spks: spks.into_iter().collect(),
+            outpoints: outpoints.into_iter().collect(),
+        }
+    }
+
+    /// Every script the wallet derived, lookahead included, and every
+    /// output it knows.
+    fn of(wallet: &bdk_wallet::Wallet) -> Self {
+        WalletFilter::new(
+            wallet.spk_index().inner().all_spks().values().cloned(),
+            wallet.list_output().map(|output| output.outpoint),
+        )
+    }
+
+    /// Drops the transactions of `block` that neither pay to nor spend from
+    /// the wallet. Outputs paying to the wallet are watched for spends in
+    /// later blocks.
+    pub fn keep_relevant(&mut self, mut block: Block) -> Block {
+        block.txdata.retain(|tx| {
+            let spends = tx
+                .input
+                .iter()
+                .any(|input| self.outpoints.contains(&input.previous_output));
+            let mut receives = false;
+            for (vout, output) in tx.output.iter().enumerate() {
+                if self.spks.contains(&output.script_pubkey) {
+                    self.outpoints
+                        .insert(OutPoint::new(tx.compute_txid(), vout as u32));
+                    receives = true;
+                }
+            }
+            spends || receives
+        });
+        block
+    }
+}
+
+/// Unused scripts esplora scans past the last used one of a keychain.
+const RESCAN_STOP_GAP: usize = 50;
+
+/// Requests sent to esplora at once during a rescan.
+const RESCAN_PARALLEL_REQUESTS: usize = 5;
+
+/// How a rescan reads the chain, set up while the wallet is locked.
+enum RescanScan {
+    Bitcoind {
+        client: Client,
+        start: CheckPoint,
+        filter: WalletFilter,
+    },
+    Esplora {
+        client: esplora_client::AsyncClient,
+        request: FullScanRequest<KeychainKind>,
+    },
+}
+
+/// What a rescan found, applied once the wallet is locked again.
+enum RescanUpdate {
+    Blocks(Vec<(u32, Block, BlockId)>),
+    FullScan(FullScanResponse<KeychainKind>),
+}
+
+/// Walks the blocks from `from_height` to the tip over bitcoind, keeping the
+/// wallet's transactions. If the scan fails or gets cancelled, the blocks
+/// scanned until then are returned with the error so they can still be
+/// applied.
+fn scan_bitcoind(
+    client: &Client,
+    start: CheckPoint,
+    mut filter: WalletFilter,
+    from_height: u32,
+    tip: u32,
+) -> (Vec<(u32, Block, BlockId)>, anyhow::Result<u32>) {
+    let mut blocks = Vec::new();
+    let mut emitter = Emitter::new(
+        client,
+        start,
+        from_height,
+        std::iter::empty::<Arc<Transaction>>(),
+    );
+    let scanned = scan_blocks(
+        &mut emitter,
+        &RESCAN_CANCELLED,
+        |height, (block, connected_to)| {
+            blocks.push((height, filter.keep_relevant(block), connected_to));
+            Ok(())
+        },
+        |height, blocks_scanned| {
+            emit_event(
+                WalletEventKind::RescanProgress,
+                serde_json::json!({
+                    "height": height,
+                    "tip": tip,
+                    "blocks_scanned": blocks_scanned,
+                }),
+            )
+        },
+    );
+    (blocks, scanned)
+}
+
+/// Stops a running [`rescan`] after the block it is scanning.
+pub fn cancel_rescan() {
+    RESCAN_CANCELLED.store(true, Ordering::SeqCst);
+}
+
+/// Scans the chain for transactions of the onchain wallet, for recovering
+/// funds received before the wallet's checkpoint.
+///
+/// With bitcoind every block from `from_height` to the tip is scanned and
+/// `RescanProgress` events are emitted along the way. With esplora the
+/// history of every wallet script is fetched instead, up to
+/// [`RESCAN_STOP_GAP`] unused scripts past the last used one, and
+/// `from_height` is only checked against the tip. An esplora scan can only
+/// be cancelled before its result is applied.
+///
+/// The chain is read without holding the wallet lock; only setting up the
+/// scan and applying what it found lock the wallet.
+pub async fn rescan(from_height: u32) -> anyhow::Result<RescanSummary> {
+    timed("onchain_rescan", async move {
+        let (tip, datadir, before, scan) = {
+            let manager = lock_manager("onchain_rescan").await;
+            manager.ensure_online()?;
+            manager
+                .with_context_ref_async(|ctx| async move {
+                    let tip = ctx.wallet.chain.tip().await?;
+                    check_rescan_height(from_height, tip)?;
+                    let scan = match (&ctx.config.bitcoind_address, &ctx.config.esplora_address) {
+                        (Some(_), _) => RescanScan::Bitcoind {
+                            client: bitcoind_client(&ctx.config)?,
+                            start: ctx
+                                .onchain_wallet
+                                .latest_checkpoint()
+                                .floor_at(from_height.saturating_sub(1))
+                                .context("Wallet has no checkpoint below the rescan height")?,
+                            filter: WalletFilter::of(&ctx.onchain_wallet),
+                        },
+                        (None, Some(url)) => RescanScan::Esplora {
+                            client: esplora_client::Builder::new(url)
+                                .build_async()
+                                .context("Failed to create esplora client")?,
+                            request: ctx.onchain_wallet.start_full_scan().build(),
+                        },
+                        (None, None) => {
+                            bail!("Rescanning requires a bitcoind or esplora chain source")
+                        }
+                    };
+                    Ok((
+                        tip,
+                        ctx.datadir.clone(),
+                        rescan_state(&ctx.onchain_wallet),
+                        scan,
+                    ))
+                })
+                .await?
+        };
+
+        RESCAN_CANCELLED.store(false, Ordering::SeqCst);
+        let (update, scanned) = match scan {
+            RescanScan::Bitcoind {
+                client,
+                start,
+                filter,
+            } => {
+                let (blocks, scanned) = tokio::task::block_in_place(|| {
+                    scan_bitcoind(&client, start, filter, from_height, tip)
+                });
+                (RescanUpdate::Blocks(blocks), scanned)
+            }
+            RescanScan::Esplora { client, request } => {
+                let response = client
+                    .full_scan(request, RESCAN_STOP_GAP, RESCAN_PARALLEL_REQUESTS)
+                    .await
+                    .context("Failed to scan esplora");
+                match response {
+                    Ok(_) if RESCAN_CANCELLED.load(Ordering::SeqCst) => (
+                        RescanUpdate::Blocks(Vec::new()),
+                        Err(anyhow::anyhow!("Rescan cancelled")),
+                    ),
+                    Ok(response) => (RescanUpdate::FullScan(response), Ok(0)),
+                    Err(err) => (RescanUpdate::Blocks(Vec::new()), Err(err)),
+                }
+            }
+        };
+
+        let mut manager = lock_manager("onchain_rescan_apply").await;
+        manager
+            .with_context_journaled_async(
+                "onchain_rescan",
+                from_height.to_string(),
+                async move |ctx| {
+                    if ctx.datadir != datadir {
+                        bail!("The wallet was changed while rescanning");
+                    }
+                    match update {
+                        RescanUpdate::Blocks(blocks) => {
+                            for (height, block, connected_to) in blocks {
+                                ctx.onchain_wallet
+                                    .apply_block_connected_to(&block, height, connected_to)
+                                    .with_context(|| format!("Failed to apply block {}", height))?;
+                            }
+                        }
+                        RescanUpdate::FullScan(response) => ctx
+                            .onchain_wallet
+                            .apply_update(response)
+                            .context("Failed to apply esplora scan")?,
+                    }
+                    let blocks_scanned = scanned?;
+                    ctx.onchain_wallet.sync(&ctx.wallet.chain).await?;
+
+                    let after = rescan_state(&ctx.onchain_wallet);
+                    let first_confirmed = ctx
+                        .onchain_wallet
+                        .transactions()
+                        .filter_map(|wtx| match wtx.chain_position {
+                            ChainPosition::Confirmed { anchor, .. } => Some(anchor.block_id.height),
+                            ChainPosition::Unconfirmed { .. } => None,
+                        })
+                        .min();
+                    let birthday_height = record_birthday(&ctx.store, first_confirmed);
+                    Ok(rescan_summary(
+                        from_height,
+                        ctx.onchain_wallet.latest_checkpoint().height(),
+                        blocks_scanned,
+                        before,
+                        after,
+                        birthday_height,
+                    ))
+                },
+            )
+            .await
+    })
+    .await
 }
@@ -0,0 +1,130 @@
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, Once};
+
+use logger::log::error;
+
+/// Written to the datadir of the loaded wallet, so the report of a panic
+/// that took the app down can be read after a restart.
+pub const PANIC_REPORT_FILE: &str = "last_panic.txt";
+
+static PANIC_HANDLER_INIT: Once = Once::new();
+
+static PANIC_REPORTS: LazyLock<Mutex<PanicReports>> =
+    LazyLock::new(|| Mutex::new(PanicReports::default()));
+
+#[derive(Default)]
+struct PanicReports {
+    /// Where to write reports, the datadir of the loaded wallet
+    dir: Option<PathBuf>,
+    last: Option<String>,
+}
+
+fn panic_reports() -> std::sync::MutexGuard<'static, PanicReports> {
+    // A panic while holding the lock must not disable reporting
+    PANIC_REPORTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A panic caught at the bridge, returned instead of unwinding into C++.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternalPanic {
+    pub message: String,
+}
+
+impl fmt::Display for InternalPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Internal panic: {}", self.message)
+    }
+}
+
+impl std::error::Error for InternalPanic {}
+
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+pub fn format_panic_report(
+    timestamp: i64,
+    thread: &str,
+    message: &str,
+    location: Option<String>,
+    backtrace: &str,
+) -> String {
+    format!(
+        "time: {}\nthread: {}\nmessage: {}\nlocation: {}\n\n{}\n",
+        timestamp,
+        thread,
+        message,
+        location.as_deref().unwrap_or("unknown"),
+        backtrace
+    )
+}
+
+fn record_panic(info: &PanicHookInfo<'_>) {
+    let report = format_panic_report(
+        crate::store::now_timestamp(),
+        std::thread::current().name().unwrap_or("unnamed"),
+        &panic_message(info.payload()),
+        info.location().map(|l| l.to_string()),
+        &Backtrace::force_capture().to_string(),
+    );
+    error!("{}", report);
+    logger::Logger::flush();
+
+    let mut reports = panic_reports();
+    if let Some(dir) = &reports.dir {
+        let path = dir.join(PANIC_REPORT_FILE);
+        if let Err(e) = std::fs::write(&path, &report) {
+            error!("Failed to write {}: {}", path.display(), e);
+        }
+    }
+    reports.last = Some(report);
+}
+
+/// Records every panic in the log and, once a wallet is loaded, in
+/// [`PANIC_REPORT_FILE`] in its datadir. The previous hook still runs.
+pub fn init_panic_handler() {
+    PANIC_HANDLER_INIT.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            record_panic(info);
+            previous(info);
+        }));
+    });
+}
+
+pub(crate) fn set_panic_report_dir(dir: Option<&Path>) {
+    panic_reports().dir = dir.map(Path::to_path_buf);
+}
+
+/// The report of the last panic in this process, or else the one left in
+/// the datadir of the loaded wallet by an earlier run.
+pub fn get_last_panic_report() -> Option<String> {
+    let reports = panic_reports();
+    if let Some(report) = &reports.last {
+        return Some(report.clone());
+    }
+    let path = reports.dir.as_ref()?.join(PANIC_REPORT_FILE);
+    std::fs::read_to_string(path).ok()
+}
+
+/// Runs a bridge call, turning a panic into an [`InternalPanic`] error so
+/// it doesn't unwind across the FFI boundary and abort the app.
+pub fn catch_panic<T>(f: impl FnOnce() -> anyhow::Result<T>) -> anyhow::Result<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => Err(InternalPanic {
+            message: panic_message(payload.as_ref()),
+        }
+        .into()),
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use std::time::Duration;
+
+use bark::lightning_invoice::Bolt11Invoice;
+
+use crate::events::{WalletEventKind, emit_event};
+
+/// Average time between blocks.
+const SECS_PER_BLOCK: u64 = 600;
+
+/// A receive whose claim keeps failing is reported as at risk once its HTLCs
+/// expire within this many blocks.
+pub const RECEIVE_AT_RISK_BLOCKS: i64 = 36;
+
+static CLAIM_FAILURES: LazyLock<Mutex<ClaimFailures>> =
+    LazyLock::new(|| Mutex::new(ClaimFailures::default()));
+
+/// Set to stop a running claim retry loop before its next attempt.
+static CLAIM_RETRY_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Blocks left until the HTLCs of a receive expire at `now`.
+///
+/// bark doesn't expose the HTLC expiry, so this assumes the HTLCs were set
+/// up when the invoice was created with just the invoice's
+/// min_final_cltv_expiry_delta, which errs on the early side.
+pub fn htlc_blocks_left(
+    invoice_created_at: u64,
+    min_final_cltv_expiry_delta: u64,
+    now: u64,
+) -> i64 {
+    let elapsed_blocks = now
+        .saturating_sub(invoice_created_at)
+        .div_ceil(SECS_PER_BLOCK);
+    min_final_cltv_expiry_delta as i64 - elapsed_blocks as i64
+}
+
+pub fn receive_blocks_left(invoice: &Bolt11Invoice, now: u64) -> i64 {
+    htlc_blocks_left(
+        invoice.duration_since_epoch().as_secs(),
+        invoice.min_final_cltv_expiry_delta(),
+        now,
+    )
+}
+
+/// How long to wait before retrying a failed claim with `blocks_left` until
+/// HTLC expiry. `None` once the HTLCs expired and retrying is pointless.
+pub fn claim_retry_interval(blocks_left: i64) -> Option<Duration> {
+    match blocks_left {
+        ..=0 => None,
+        1..10 => Some(Duration::from_secs(5)),
+        10..=100 => Some(Duration::from_secs(15)),
+        _ => Some(Duration::from_secs(30)),
+    }
+}
+
+/// A lightning receive whose last claim failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedClaim {
+    pub payment_hash: String,
+    pub amount_msat: Option<u64>,
+    pub blocks_left: i64,
+    pub failed_attempts: u32,
+    pub last_error: String,
+}
+
+impl FailedClaim {
+    pub fn is_at_risk(&self) -> bool {
+        self.blocks_left <= RECEIVE_AT_RISK_BLOCKS
+    }
+}
+
+/// Lightning receives whose claim failed, by payment hash.
+#[derive(Debug, Default)]
+pub struct ClaimFailures {
+    receives: HashMap<String, FailedClaim>,
+}
+
+impl ClaimFailures {
+    pub fn record_failure(
+        &mut self,
+        payment_hash: &str,
+        amount_msat: Option<u64>,
+        blocks_left: i64,
+        error: &str,
+    ) -> &FailedClaim {
+        let failure = self
+            .receives
+            .entry(payment_hash.to_string())
+            .or_insert_with(|| FailedClaim {
+                payment_hash: payment_hash.to_string(),
+                amount_msat,
+                blocks_left,
+                failed_attempts: 0,
+                last_error: String::new(),
+            });
+        failure.blocks_left = blocks_left;
+        failure.failed_attempts += 1;
+        failure.last_error = error.to_string();
+        failure
+    }
+
+    pub fn record_success(&mut self, payment_hash: &str) {
+        self.receives.remove(payment_hash);
+    }
+
+    /// Forgets receives that are no longer pending.
+    pub fn retain_pending(&mut self, pending: &HashSet<String>) {
+        self.receives.retain(|hash, _| pending.contains(hash));
+    }
+
+    /// Failing receives close to HTLC expiry, most urgent first.
+    pub fn at_risk(&self) -> Vec<FailedClaim> {
+        let mut at_risk = self
+            .receives
+            .values()
+            .filter(|f| f.is_at_risk())
+            .cloned()
+            .collect::<Vec<_>>();
+        at_risk.sort_by_key(|f| f.blocks_left);
+        at_risk
+    }
+
+    /// The wait before the most urgent failing receive should be retried.
+    pub fn next_retry(&self) -> Option<Duration> {
+        self.receives
+            .values()
+            .filter_map(|f| claim_retry_interval(f.blocks_left))
+            .min()
+    }
+}
+
+pub fn claim_failures() -> MutexGuard<'static, ClaimFailures> {
+    CLAIM_FAILURES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Records a failed claim and emits a `LightningReceiveAtRisk` event if the
+/// receive's HTLCs are close to expiry. Returns whether it is at risk.
+pub fn note_claim_failure(
+    failures: &mut ClaimFailures,
+    payment_hash: &str,
+    amount_msat: Option<u64>,
+    blocks_left: i64,
+    error: &str,
+) -> bool {
+    let failure = failures.record_failure(payment_hash, amount_msat, blocks_left, error);
+    if !failure.is_at_risk() {
+        return false;
+    }
+    emit_event(
+        WalletEventKind::LightningReceiveAtRisk,
+        serde_json::json!({
+            "payment_hash": failure.payment_hash,
+            "amount_msat": failure.amount_msat,
+            "blocks_left": failure.blocks_left,
+            "failed_attempts": failure.failed_attempts,
+            "last_error": failure.last_error,
+        }),
+    );
+    true
+}
+
+/// Stops a running claim retry loop before its next attempt.
+pub fn cancel_claim_retries() {
+    CLAIM_RETRY_CANCELLED.store(true, Ordering::SeqCst);
+}
+
+pub(crate) fn reset_claim_retry_cancel() {
+    CLAIM_RETRY_CANCELLED.store(false, Ordering::SeqCst);
+}
+
+/// Sleeps for `duration`, waking early if the retries are cancelled.
+/// Returns whether they were.
+pub(crate) async fn sleep_unless_cancelled(duration: Duration) -> bool {
+    let step = Duration::from_millis(500);
+    let mut slept = Duration::ZERO;
+    while slept < duration {
+        if CLAIM_RETRY_CANCELLED.load(Ordering::SeqCst) {
+            return true;
+        }
+        let next = step.min(duration - slept);
+        tokio::time::sleep(next).await;
+        slept += next;
+    }
+    CLAIM_RETRY_CANCELLED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,110 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bark::ark::VtxoId;
+use logger::log::warn;
+
+/// Why the server turned down our participation in a round, as far as it can
+/// be told from the error.
+///
+/// bark reports these as plain error chains, so they are recognized by the
+/// server's messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoundFailureReason {
+    /// One of our inputs was already spent, e.g. in an earlier round that
+    /// this wallet didn't learn about. `None` if the error doesn't name it.
+    InputAlreadySpent(Option<VtxoId>),
+    RoundFull,
+    ServerUnreachable,
+    SignatureRejected,
+    Other(String),
+}
+
+impl RoundFailureReason {
+    pub fn classify(err: &anyhow::Error) -> Self {
+        let message = format!("{:#}", err);
+        let lower = message.to_lowercase();
+        if lower.contains("already spent")
+            || lower.contains("already forfeited")
+            || lower.contains("double spend")
+        {
+            RoundFailureReason::InputAlreadySpent(find_vtxo_id(&message))
+        } else if lower.contains("round is full") || lower.contains("too many participants") {
+            RoundFailureReason::RoundFull
+        } else if lower.contains("signature")
+            && (lower.contains("invalid") || lower.contains("rejected"))
+        {
+            RoundFailureReason::SignatureRejected
+        } else if lower.contains("connect")
+            || lower.contains("transport")
+            || lower.contains("unavailable")
+            || lower.contains("timed out")
+        {
+            RoundFailureReason::ServerUnreachable
+        } else {
+            RoundFailureReason::Other(message)
+        }
+    }
+
+    /// Stable name of the reason, for the app to match on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            RoundFailureReason::InputAlreadySpent(_) => "input_already_spent",
+            RoundFailureReason::RoundFull => "round_full",
+            RoundFailureReason::ServerUnreachable => "server_unreachable",
+            RoundFailureReason::SignatureRejected => "signature_rejected",
+            RoundFailureReason::Other(_) => "other",
+        }
+    }
+}
+
+fn find_vtxo_id(message: &str) -> Option<VtxoId> {
+    message.split_whitespace().find_map(|token| {
+        let token = token.trim_matches(|c: char| !c.is_ascii_hexdigit() && c != ':');
+        VtxoId::from_str(token).ok()
+    })
+}
+
+/// A refresh or offboard that failed in the round. Its message starts with
+/// `Round failed [<code>]` so the reason survives the bridge.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundFailure {
+    pub reason: RoundFailureReason,
+    pub message: String,
+}
+
+impl fmt::Display for RoundFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Round failed [{}]", self.reason.code())?;
+        if let RoundFailureReason::InputAlreadySpent(Some(vtxo_id)) = &self.reason {
+            write!(f, " {}", vtxo_id)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for RoundFailure {}
+
+/// Turns the error of a failed round participation into a [`RoundFailure`].
+///
+/// If one of our inputs was already spent our view of the VTXOs is behind
+/// the server's, so `resync` is run to catch up before the error is returned.
+pub async fn on_round_failure<F, Fut>(err: anyhow::Error, resync: F) -> anyhow::Error
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let reason = RoundFailureReason::classify(&err);
+    if let RoundFailureReason::InputAlreadySpent(vtxo_id) = &reason {
+        warn!(
+            "Round input {} was already spent, syncing with the server",
+            vtxo_id.map_or("unknown".to_string(), |id| id.to_string())
+        );
+        resync().await;
+    }
+    RoundFailure {
+        reason,
+        message: format!("{:#}", err),
+    }
+    .into()
+}
@@ -0,0 +1,1879 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{self, Context, bail};
+use logger::log::{debug, info};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params};
+
+use crate::utils::{BalanceChange, DB_FILE, MovementCursor, Recipient, STORE_FILE};
+
+/// A schema migration of the local store.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// The migrations of the local store, registered by version.
+///
+/// Migrations must be registered in order and form a contiguous sequence
+/// starting at version 1, each named `mNNNN_<description>` after its version.
+#[derive(Debug, Default)]
+pub struct MigrationRegistry {
+    migrations: Vec<Migration>,
+}
+
+impl MigrationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(mut self, version: u32, name: &'static str, sql: &'static str) -> Self {
+        self.migrations.push(Migration { version, name, sql });
+        self
+    }
+
+    /// Checks that there are no gaps, duplicates or out of order migrations.
+    pub fn verify(&self) -> anyhow::Result<()> {
+        for (idx, migration) in self.migrations.iter().enumerate() {
+            let expected = idx as u32 + 1;
+            if migration.version != expected {
+                bail!(
+                    "Migration {} has version {}, expected version {}",
+                    migration.name,
+                    migration.version,
+                    expected
+                );
+            }
+            if !migration
+                .name
+                .starts_with(&format!("m{:04}_", migration.version))
+            {
+                bail!(
+                    "Migration {} is not named after its version {}",
+                    migration.name,
+                    migration.version
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn migrations(&self) -> &[Migration] {
+        &self.migrations
+    }
+
+    /// The version the schema is at once all migrations are applied
+    pub fn latest_version(&self) -> u32 {
+        self.migrations.last().map_or(0, |m| m.version)
+    }
+}
+
+/// All migrations of the local store. New migrations are appended at the end.
+///
+/// Panics if the sequence is broken, which is a bug that must never reach a
+/// user's database.
+pub fn migration_registry() -> MigrationRegistry {
+    let registry = MigrationRegistry::new()
+        .register(
+            1,
+            "m0001_address_book",
+            "CREATE TABLE IF NOT EXISTS bark_address_book (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                destination TEXT NOT NULL UNIQUE,
+                created_at INTEGER NOT NULL
+            );",
+        )
+        .register(
+            2,
+            "m0002_pending_board_intents",
+            "CREATE TABLE IF NOT EXISTS bark_pending_board_intents (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                amount_sat INTEGER,
+                funding_txid TEXT,
+                state TEXT NOT NULL,
+                error TEXT,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );",
+        )
+        .register(
+            3,
+            "m0003_nitro_config",
+            "CREATE TABLE IF NOT EXISTS bark_nitro_config (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                auto_refresh_enabled INTEGER NOT NULL DEFAULT 0,
+                auto_refresh_threshold_blocks INTEGER NOT NULL DEFAULT 144,
+                auto_refresh_quiet_hours_start INTEGER NOT NULL DEFAULT 0,
+                auto_refresh_quiet_hours_end INTEGER NOT NULL DEFAULT 0,
+                auto_refresh_timezone_offset_minutes INTEGER NOT NULL DEFAULT 0
+            );
+            INSERT OR IGNORE INTO bark_nitro_config (id) VALUES (1);",
+        )
+        .register(
+            4,
+            "m0004_quarantined_vtxos",
+            "CREATE TABLE IF NOT EXISTS bark_quarantined_vtxos (
+                vtxo_id TEXT PRIMARY KEY,
+                amount_sat INTEGER NOT NULL,
+                reason TEXT NOT NULL,
+                quarantined_at INTEGER NOT NULL
+            );",
+        )
+        .register(
+            5,
+            "m0005_sync_log",
+            "CREATE TABLE IF NOT EXISTS bark_sync_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                sync_type TEXT NOT NULL,
+                completed_at INTEGER NOT NULL,
+                block_height INTEGER,
+                success INTEGER NOT NULL
+            );",
+        )
+        .register(
+            6,
+            "m0006_lightning_fee_cap",
+            "ALTER TABLE bark_nitro_config ADD COLUMN lightning_fee_cap_bps INTEGER NOT NULL DEFAULT 100;
+            ALTER TABLE bark_nitro_config ADD COLUMN lightning_fee_floor_sat INTEGER NOT NULL DEFAULT 5;",
+        )
+        .register(
+            7,
+            "m0007_event_journal",
+            "CREATE TABLE IF NOT EXISTS bark_event_journal (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                created_at INTEGER NOT NULL,
+                operation TEXT NOT NULL,
+                params_hash TEXT NOT NULL,
+                result TEXT NOT NULL,
+                error TEXT
+            );
+            CREATE INDEX IF NOT EXISTS bark_event_journal_created_at
+                ON bark_event_journal (created_at);",
+        )
+        .register(
+            8,
+            "m0008_pending_payments",
+            "CREATE TABLE IF NOT EXISTS bark_pending_payment (
+                id INTEGER PRIMARY KEY,
+                payment_hash TEXT NOT NULL UNIQUE,
+                bolt11 TEXT NOT NULL,
+                amount_msat INTEGER,
+                destination TEXT,
+                started_at INTEGER NOT NULL,
+                status TEXT NOT NULL
+            );",
+        )
+        .register(
+            9,
+            "m0009_coin_control_locks",
+            "CREATE TABLE IF NOT EXISTS bark_coin_control_locks (
+                vtxo_id TEXT PRIMARY KEY,
+                locked_at INTEGER NOT NULL
+            );",
+        )
+        .register(
+            10,
+            "m0010_expired_receives",
+            "CREATE TABLE IF NOT EXISTS bark_expired_receives (
+                payment_hash TEXT PRIMARY KEY,
+                amount_msat INTEGER,
+                expired_at INTEGER NOT NULL
+            );",
+        )
+        .register(
+            11,
+            "m0011_ark_purpose_index",
+            "ALTER TABLE bark_nitro_config ADD COLUMN ark_purpose_index INTEGER NOT NULL DEFAULT 350;",
+        )
+        .register(
+            12,
+            "m0012_allow_spending_unrefreshed_arkoor",
+            "ALTER TABLE bark_nitro_config ADD COLUMN allow_spending_unrefreshed_arkoor INTEGER NOT NULL DEFAULT 1;",
+        )
+        .register(
+            13,
+            "m0013_expected_payments",
+            "CREATE TABLE IF NOT EXISTS bark_expected_payments (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                pubkey_index INTEGER NOT NULL,
+                user_pubkey TEXT NOT NULL,
+                amount_sat INTEGER,
+                memo TEXT NOT NULL,
+                status TEXT NOT NULL,
+                vtxo_id TEXT,
+                created_at INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );",
+        )
+        .register(
+            14,
+            "m0014_observed_lightning_fee",
+            "ALTER TABLE bark_nitro_config ADD COLUMN observed_lightning_fee_ppm INTEGER;",
+        )
+        .register(
+            15,
+            "m0015_onchain_birthday",
+            "ALTER TABLE bark_nitro_config ADD COLUMN onchain_birthday_height INTEGER;",
+        );
+
+    if let Err(err) = registry.verify() {
+        panic!("Invalid local store migrations: {:#}", err);
+    }
+    registry
+}
+
+/// Errors opening the local store or bark's database that the app can act
+/// upon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreError {
+    /// The store was migrated by a newer version of the app, which happens
+    /// when the app is downgraded.
+    SchemaTooNew {
+        db_version: u32,
+        supported_version: u32,
+    },
+    /// Like [`StoreError::SchemaTooNew`], for bark's wallet database.
+    WalletSchemaTooNew {
+        db_version: u32,
+        supported_version: u32,
+    },
+    /// The file has tables but no migrations table, so it is not a database
+    /// we created and must not be migrated.
+    Corrupt,
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::SchemaTooNew {
+                db_version,
+                supported_version,
+            } => write!(
+                f,
+                "Local store schema version {} is newer than the supported version {}",
+                db_version, supported_version
+            ),
+            StoreError::WalletSchemaTooNew {
+                db_version,
+                supported_version,
+            } => write!(
+                f,
+                "Wallet database schema version {} is newer than the supported version {}",
+                db_version, supported_version
+            ),
+            StoreError::Corrupt => write!(f, "Database is corrupt: missing migrations table"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+/// Whether the database open on `conn` has its migrations table. An empty
+/// database is fine without one, any other database is corrupt.
+fn has_migrations_table(conn: &Connection, table: &str) -> anyhow::Result<bool> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        [table],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Ok(true);
+    }
+
+    let tables: u32 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'",
+        [],
+        |row| row.get(0),
+    )?;
+    match tables {
+        0 => Ok(false),
+        _ => Err(StoreError::Corrupt.into()),
+    }
+}
+
+/// Checks that the store at `conn` can be migrated by this version of the app.
+fn check_schema(conn: &Connection) -> anyhow::Result<()> {
+    if !has_migrations_table(conn, "nitro_migrations")? {
+        return Ok(());
+    }
+
+    let db_version: u32 = conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM nitro_migrations",
+        [],
+        |row| row.get(0),
+    )?;
+    let supported_version = migration_registry().latest_version();
+    if db_version > supported_version {
+        return Err(StoreError::SchemaTooNew {
+            db_version,
+            supported_version,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// The version of the last migration applied to the local store open on
+/// `conn`, without migrating it.
+pub fn read_schema_version(conn: &Connection) -> anyhow::Result<u32> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM nitro_migrations",
+        [],
+        |row| row.get(0),
+    )
+    .context("Failed to read local store schema version")
+}
+
+/// Checks, without migrating it, that bark's database at `path` was not
+/// migrated past `supported_version` by a newer version of the app. Must
+/// run before bark opens the database, as opening it runs its migrations.
+pub fn check_wallet_db_schema(path: &Path, supported_version: u32) -> anyhow::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let conn = open_bark_db_read_only(path)?;
+    if !has_migrations_table(&conn, "migrations")? {
+        return Ok(());
+    }
+    let db_version = get_db_migration_version(&conn)?;
+    if db_version > supported_version {
+        return Err(StoreError::WalletSchemaTooNew {
+            db_version,
+            supported_version,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+fn check_store_file_schema(path: &Path) -> anyhow::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open local store at {}", path.display()))?;
+    check_schema(&conn)
+}
+
+/// Explains what the user should do if the local store or bark's database
+/// in `datadir` can't be opened by this version of the app, or `None` if
+/// both can. `supported_wallet_version` is the last bark migration this
+/// version knows.
+pub fn required_app_version_hint(
+    datadir: &Path,
+    supported_wallet_version: u32,
+) -> anyhow::Result<Option<String>> {
+    let checked = check_store_file_schema(&datadir.join(STORE_FILE))
+        .and_then(|()| check_wallet_db_schema(&datadir.join(DB_FILE), supported_wallet_version));
+    let err = match checked {
+        Ok(()) => return Ok(None),
+        Err(err) => err,
+    };
+
+    match err.downcast_ref::<StoreError>() {
+        Some(
+            StoreError::SchemaTooNew {
+                db_version,
+                supported_version,
+            }
+            | StoreError::WalletSchemaTooNew {
+                db_version,
+                supported_version,
+            },
+        ) => Ok(Some(format!(
+            "This wallet was last opened by a newer version of the app (data version {}, \
+            this version supports up to {}). Please update the app.",
+            db_version, supported_version
+        ))),
+        Some(StoreError::Corrupt) => Ok(Some(
+            "The wallet data is corrupt. Please restore the wallet from its mnemonic.".to_string(),
+        )),
+        None => Err(err),
+    }
+}
+
+/// A named entry in the address book.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressBookEntry {
+    pub name: String,
+    pub destination: String,
+    pub created_at: i64,
+}
+
+/// The phases a board goes through from our point of view.
+///
+/// An intent is written before the funding transaction is built so a board
+/// interrupted by an app restart can be picked up again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardIntentState {
+    /// The board was started but we never learned its funding txid
+    Started,
+    /// The funding transaction was broadcast, awaiting registration with the server
+    Funded,
+    /// The board was registered with the server
+    Registered,
+    /// Boarding failed before the funding transaction was broadcast
+    Failed,
+    /// The board was given up on during resume
+    Abandoned,
+}
+
+impl BoardIntentState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BoardIntentState::Started => "started",
+            BoardIntentState::Funded => "funded",
+            BoardIntentState::Registered => "registered",
+            BoardIntentState::Failed => "failed",
+            BoardIntentState::Abandoned => "abandoned",
+        }
+    }
+}
+
+impl std::str::FromStr for BoardIntentState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "started" => Ok(BoardIntentState::Started),
+            "funded" => Ok(BoardIntentState::Funded),
+            "registered" => Ok(BoardIntentState::Registered),
+            "failed" => Ok(BoardIntentState::Failed),
+            "abandoned" => Ok(BoardIntentState::Abandoned),
+            _ => bail!("Unknown board intent state: {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardIntent {
+    pub id: i64,
+    /// `None` when boarding all onchain funds
+    pub amount_sat: Option<u64>,
+    pub funding_txid: Option<String>,
+    pub state: BoardIntentState,
+    pub error: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// A VTXO we hold locally but which the server doesn't know about.
+///
+/// Only the id is tracked here, the VTXO itself stays in the bark database so
+/// it can still be exited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuarantinedVtxo {
+    pub vtxo_id: String,
+    pub amount_sat: u64,
+    pub reason: String,
+    pub quarantined_at: i64,
+}
+
+/// When the wallet may refresh expiring VTXOs on its own.
+///
+/// Quiet hours are given in local time as `[start, end)` and may wrap around
+/// midnight. Equal start and end hours mean there are no quiet hours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoRefreshPolicy {
+    pub enabled: bool,
+    /// Refresh once the next required refresh is at most this many blocks away
+    pub threshold_blocks: u32,
+    pub quiet_hours_start: u8,
+    pub quiet_hours_end: u8,
+    pub timezone_offset_minutes: i32,
+}
+
+impl AutoRefreshPolicy {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.quiet_hours_start > 23 || self.quiet_hours_end > 23 {
+            bail!("Quiet hours must be between 0 and 23");
+        }
+        if self.timezone_offset_minutes.abs() > 14 * 60 {
+            bail!(
+                "Invalid timezone offset: {} minutes",
+                self.timezone_offset_minutes
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether the given unix timestamp falls within the quiet hours.
+    pub fn is_quiet_time(&self, unix_ts: i64) -> bool {
+        let (start, end) = (self.quiet_hours_start, self.quiet_hours_end);
+        if start == end {
+            return false;
+        }
+
+        let local_secs = (unix_ts + self.timezone_offset_minutes as i64 * 60).rem_euclid(86400);
+        let hour = (local_secs / 3600) as u8;
+        if start < end {
+            hour >= start && hour < end
+        } else {
+            hour >= start || hour < end
+        }
+    }
+
+    /// Whether a refresh should be triggered right now.
+    pub fn should_refresh(
+        &self,
+        unix_ts: i64,
+        tip_height: u32,
+        next_refresh_height: Option<u32>,
+    ) -> bool {
+        if !self.enabled || self.is_quiet_time(unix_ts) {
+            return false;
+        }
+        next_refresh_height
+            .is_some_and(|height| height <= tip_height.saturating_add(self.threshold_blocks))
+    }
+}
+
+/// Local sqlite store for data the bark persister has no place for.
+///
+/// It lives next to the bark database in the wallet datadir and carries its
+/// own migrations, so it never touches bark's schema.
+pub struct Store {
+    conn: Mutex<Connection>,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        debug!("Opening local store at {}", path.display());
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open local store at {}", path.display()))?;
+        check_schema(&conn)?;
+        let store = Store {
+            conn: Mutex::new(conn),
+        };
+        store.migrate()?;
+        Ok(store)
+    }
+
+    /// A panic while holding the connection poisons the mutex. SQLite rolls
+    /// back whatever statement was interrupted, so the connection is still
+    /// usable and is recovered instead of failing every later call.
+    fn conn(&self) -> anyhow::Result<std::sync::MutexGuard<'_, Connection>> {
+        Ok(self
+            .conn
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+
+    /// Panics while holding the connection, to test recovering from it.
+    #[cfg(test)]
+    pub(crate) fn poison_for_test(&self) {
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _conn = self.conn.lock();
+            panic!("deliberate test panic holding the local store");
+        }));
+    }
+
+    /// The version of the last applied migration
+    pub fn schema_version(&self) -> anyhow::Result<u32> {
+        read_schema_version(&self.conn()?)
+    }
+
+    fn migrate(&self) -> anyhow::Result<()> {
+        let mut conn = self.conn()?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS nitro_migrations (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        let current: u32 = conn.query_row(
+            "SELECT COALESCE(MAX(version), 0) FROM nitro_migrations",
+            [],
+            |row| row.get(0),
+        )?;
+
+        for migration in migration_registry().migrations() {
+            if migration.version <= current {
+                continue;
+            }
+
+            info!("Applying local store migration {}", migration.name);
+            let tx = conn.transaction()?;
+            tx.execute_batch(migration.sql)
+                .with_context(|| format!("Failed to apply migration {}", migration.name))?;
+            tx.execute(
+                "INSERT INTO nitro_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+                params![migration.version, migration.name, now_timestamp()],
+            )?;
+            tx.commit()?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds a named address book entry, renaming it if the destination is already known.
+    pub fn add_address_book_entry(&self, name: &str, destination: &str) -> anyhow::Result<()> {
+        if destination.is_empty() {
+            bail!("Address book destination cannot be empty");
+        }
+
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO bark_address_book (name, destination, created_at) VALUES (?1, ?2, ?3)
+            ON CONFLICT(destination) DO UPDATE SET name = excluded.name",
+            params![name, destination, now_timestamp()],
+        )
+        .context("Failed to store address book entry")?;
+        Ok(())
+    }
+
+    /// Removes the entry for `destination`, returning whether one existed.
+    pub fn remove_address_book_entry(&self, destination: &str) -> anyhow::Result<bool> {
+        let conn = self.conn()?;
+        let removed = conn
+            .execute(
+                "DELETE FROM bark_address_book WHERE destination = ?1",
+                params![destination],
+            )
+            .context("Failed to remove address book entry")?;
+        Ok(removed > 0)
+    }
+
+    pub fn get_address_book_entry(
+        &self,
+        destination: &str,
+    ) -> anyhow::Result<Option<AddressBookEntry>> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT name, destination, created_at FROM bark_address_book WHERE destination = ?1",
+            params![destination],
+            |row| {
+                Ok(AddressBookEntry {
+                    name: row.get(0)?,
+                    destination: row.get(1)?,
+                    created_at: row.get(2)?,
+                })
+            },
+        )
+        .optional()
+        .context("Failed to read address book entry")
+    }
+
+    pub fn get_address_book(&self) -> anyhow::Result<Vec<AddressBookEntry>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT name, destination, created_at FROM bark_address_book ORDER BY name ASC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(AddressBookEntry {
+                name: row.get(0)?,
+                destination: row.get(1)?,
+                created_at: row.get(2)?,
+            })
+        })?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+            .context("Failed to read address book")
+    }
+}
+
+impl Store {
+    pub fn create_board_intent(&self, amount_sat: Option<u64>) -> anyhow::Result<i64> {
+        let conn = self.conn()?;
+        let now = now_timestamp();
+        conn.execute(
+            "INSERT INTO bark_pending_board_intents (amount_sat, state, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?3)",
+            params![amount_sat, BoardIntentState::Started.as_str(), now],
+        )
+        .context("Failed to store board intent")?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Moves an intent to `state`. The funding txid and error are only
+    /// overwritten when given.
+    pub fn update_board_intent(
+        &self,
+        id: i64,
+        state: BoardIntentState,
+        funding_txid: Option<&str>,
+        error: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let conn = self.conn()?;
+        let updated = conn
+            .execute(
+                "UPDATE bark_pending_board_intents
+                SET state = ?2,
+                    funding_txid = COALESCE(?3, funding_txid),
+                    error = COALESCE(?4, error),
+                    updated_at = ?5
+                WHERE id = ?1",
+                params![id, state.as_str(), funding_txid, error, now_timestamp()],
+            )
+            .context("Failed to update board intent")?;
+        if updated == 0 {
+            bail!("Board intent {} does not exist", id);
+        }
+        Ok(())
+    }
+
+    /// Returns all intents that have not reached a final state, oldest first.
+    pub fn get_open_board_intents(&self) -> anyhow::Result<Vec<BoardIntent>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, amount_sat, funding_txid, state, error, created_at, updated_at
+            FROM bark_pending_board_intents
+            WHERE state IN ('started', 'funded')
+            ORDER BY id ASC",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<u64>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, i64>(5)?,
+                row.get::<_, i64>(6)?,
+            ))
+        })?;
+
+        let mut intents = Vec::new();
+        for row in rows {
+            let (id, amount_sat, funding_txid, state, error, created_at, updated_at) = row?;
+            intents.push(BoardIntent {
+                id,
+                amount_sat,
+                funding_txid,
+                state: state.parse()?,
+                error,
+                created_at,
+                updated_at,
+            });
+        }
+        Ok(intents)
+    }
+}
+
+impl Store {
+    pub fn get_auto_refresh_policy(&self) -> anyhow::Result<AutoRefreshPolicy> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT auto_refresh_enabled, auto_refresh_threshold_blocks,
+                auto_refresh_quiet_hours_start, auto_refresh_quiet_hours_end,
+                auto_refresh_timezone_offset_minutes
+            FROM bark_nitro_config WHERE id = 1",
+            [],
+            |row| {
+                Ok(AutoRefreshPolicy {
+                    enabled: row.get(0)?,
+                    threshold_blocks: row.get(1)?,
+                    quiet_hours_start: row.get(2)?,
+                    quiet_hours_end: row.get(3)?,
+                    timezone_offset_minutes: row.get(4)?,
+                })
+            },
+        )
+        .context("Failed to read auto refresh policy")
+    }
+
+    pub fn set_auto_refresh_policy(&self, policy: &AutoRefreshPolicy) -> anyhow::Result<()> {
+        policy.validate()?;
+
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE bark_nitro_config SET
+                auto_refresh_enabled = ?1,
+                auto_refresh_threshold_blocks = ?2,
+                auto_refresh_quiet_hours_start = ?3,
+                auto_refresh_quiet_hours_end = ?4,
+                auto_refresh_timezone_offset_minutes = ?5
+            WHERE id = 1",
+            params![
+                policy.enabled,
+                policy.threshold_blocks,
+                policy.quiet_hours_start,
+                policy.quiet_hours_end,
+                policy.timezone_offset_minutes,
+            ],
+        )
+        .context("Failed to store auto refresh policy")?;
+        Ok(())
+    }
+}
+
+/// Default fee budget for lightning payments made without an explicit one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LightningFeeCap {
+    /// Share of the payment amount in basis points, 100 is 1%
+    pub cap_bps: u32,
+    /// Minimum budget, so small payments can still be routed
+    pub floor_sat: u64,
+}
+
+impl Default for LightningFeeCap {
+    fn default() -> Self {
+        LightningFeeCap {
+            cap_bps: 100,
+            floor_sat: 5,
+        }
+    }
+}
+
+impl LightningFeeCap {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.cap_bps > 10_000 {
+            bail!("Fee cap can't exceed 100% of the payment amount");
+        }
+        Ok(())
+    }
+
+    /// The fee budget for paying `amount_sat`, rounded up
+    pub fn budget_sat(&self, amount_sat: u64) -> u64 {
+        let share = (amount_sat as u128 * self.cap_bps as u128).div_ceil(10_000) as u64;
+        share.max(self.floor_sat)
+    }
+}
+
+impl Store {
+    pub fn get_lightning_fee_cap(&self) -> anyhow::Result<LightningFeeCap> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT lightning_fee_cap_bps, lightning_fee_floor_sat FROM bark_nitro_config WHERE id = 1",
+            [],
+            |row| {
+                Ok(LightningFeeCap {
+                    cap_bps: row.get(0)?,
+                    floor_sat: row.get(1)?,
+                })
+            },
+        )
+        .context("Failed to read lightning fee cap")
+    }
+
+    pub fn set_lightning_fee_cap(&self, cap: &LightningFeeCap) -> anyhow::Result<()> {
+        cap.validate()?;
+
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE bark_nitro_config SET lightning_fee_cap_bps = ?1, lightning_fee_floor_sat = ?2
+            WHERE id = 1",
+            params![cap.cap_bps, cap.floor_sat],
+        )
+        .context("Failed to store lightning fee cap")?;
+        Ok(())
+    }
+
+    /// Fee of the last settled lightning send in parts per million of its
+    /// amount, `None` before the first one.
+    pub fn get_observed_lightning_fee_ppm(&self) -> anyhow::Result<Option<u64>> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT observed_lightning_fee_ppm FROM bark_nitro_config WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .context("Failed to read observed lightning fee")
+    }
+
+    pub fn set_observed_lightning_fee_ppm(&self, fee_ppm: u64) -> anyhow::Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE bark_nitro_config SET observed_lightning_fee_ppm = ?1 WHERE id = 1",
+            params![fee_ppm],
+        )
+        .context("Failed to store observed lightning fee")?;
+        Ok(())
+    }
+
+    /// Height of the first onchain activity of the wallet, as found by a
+    /// rescan. `None` until a rescan found any.
+    pub fn get_onchain_birthday_height(&self) -> anyhow::Result<Option<u32>> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT onchain_birthday_height FROM bark_nitro_config WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .context("Failed to read onchain birthday height")
+    }
+
+    /// Moves the onchain birthday back to `height` if that is earlier than
+    /// the stored one, and returns the birthday stored afterwards.
+    pub fn lower_onchain_birthday_height(&self, height: u32) -> anyhow::Result<u32> {
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE bark_nitro_config
+            SET onchain_birthday_height = MIN(COALESCE(onchain_birthday_height, ?1), ?1)
+            WHERE id = 1",
+            params![height],
+        )
+        .context("Failed to store onchain birthday height")?;
+        conn.query_row(
+            "SELECT onchain_birthday_height FROM bark_nitro_config WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .context("Failed to read onchain birthday height")
+    }
+}
+
+impl Store {
+    /// The BIP32 purpose index the wallet derives its standalone Ark keys
+    /// under, set when the wallet is created.
+    pub fn get_ark_purpose_index(&self) -> anyhow::Result<u32> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT ark_purpose_index FROM bark_nitro_config WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .context("Failed to read ark purpose index")
+    }
+
+    pub fn set_ark_purpose_index(&self, purpose_index: u32) -> anyhow::Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE bark_nitro_config SET ark_purpose_index = ?1 WHERE id = 1",
+            params![purpose_index],
+        )
+        .context("Failed to store ark purpose index")?;
+        Ok(())
+    }
+}
+
+impl Store {
+    /// Whether payments may spend VTXOs received out of round that were not
+    /// refreshed yet.
+    pub fn get_allow_spending_unrefreshed_arkoor(&self) -> anyhow::Result<bool> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT allow_spending_unrefreshed_arkoor FROM bark_nitro_config WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .context("Failed to read unrefreshed arkoor spending setting")
+    }
+
+    pub fn set_allow_spending_unrefreshed_arkoor(&self, allow: bool) -> anyhow::Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE bark_nitro_config SET allow_spending_unrefreshed_arkoor = ?1 WHERE id = 1",
+            params![allow],
+        )
+        .context("Failed to store unrefreshed arkoor spending setting")?;
+        Ok(())
+    }
+}
+
+impl Store {
+    /// Returns false if the VTXO was already quarantined.
+    pub fn quarantine_vtxo(
+        &self,
+        vtxo_id: &str,
+        amount_sat: u64,
+        reason: &str,
+    ) -> anyhow::Result<bool> {
+        let conn = self.conn()?;
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO bark_quarantined_vtxos
+                (vtxo_id, amount_sat, reason, quarantined_at) VALUES (?1, ?2, ?3, ?4)",
+                params![vtxo_id, amount_sat, reason, now_timestamp()],
+            )
+            .context("Failed to quarantine vtxo")?;
+        Ok(inserted > 0)
+    }
+
+    pub fn release_quarantined_vtxo(&self, vtxo_id: &str) -> anyhow::Result<bool> {
+        let conn = self.conn()?;
+        let removed = conn
+            .execute(
+                "DELETE FROM bark_quarantined_vtxos WHERE vtxo_id = ?1",
+                params![vtxo_id],
+            )
+            .context("Failed to release quarantined vtxo")?;
+        Ok(removed > 0)
+    }
+
+    pub fn get_quarantined_vtxos(&self) -> anyhow::Result<Vec<QuarantinedVtxo>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT vtxo_id, amount_sat, reason, quarantined_at
+            FROM bark_quarantined_vtxos ORDER BY quarantined_at, vtxo_id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(QuarantinedVtxo {
+                vtxo_id: row.get(0)?,
+                amount_sat: row.get(1)?,
+                reason: row.get(2)?,
+                quarantined_at: row.get(3)?,
+            })
+        })?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+            .context("Failed to read quarantined vtxos")
+    }
+}
+
+/// VTXOs locked in bark to keep them out of a coin-controlled payment. They
+/// are recorded before locking, so a crash mid-payment can be undone on the
+/// next load.
+impl Store {
+    pub fn record_coin_control_locks(&self, vtxo_ids: &[String]) -> anyhow::Result<()> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        for vtxo_id in vtxo_ids {
+            tx.execute(
+                "INSERT OR IGNORE INTO bark_coin_control_locks (vtxo_id, locked_at)
+                VALUES (?1, ?2)",
+                params![vtxo_id, now_timestamp()],
+            )
+            .context("Failed to record coin control lock")?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_coin_control_locks(&self) -> anyhow::Result<Vec<String>> {
+        let conn = self.conn()?;
+        let mut stmt = conn
+            .prepare("SELECT vtxo_id FROM bark_coin_control_locks ORDER BY locked_at, vtxo_id")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+            .context("Failed to read coin control locks")
+    }
+
+    pub fn clear_coin_control_lock(&self, vtxo_id: &str) -> anyhow::Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "DELETE FROM bark_coin_control_locks WHERE vtxo_id = ?1",
+            params![vtxo_id],
+        )
+        .context("Failed to clear coin control lock")?;
+        Ok(())
+    }
+}
+
+/// Lightning receives whose invoice expired unpaid, kept out of claiming.
+impl Store {
+    /// Returns false if the receive was already marked expired.
+    pub fn mark_receive_expired(
+        &self,
+        payment_hash: &str,
+        amount_msat: Option<u64>,
+        expired_at: i64,
+    ) -> anyhow::Result<bool> {
+        let conn = self.conn()?;
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO bark_expired_receives (payment_hash, amount_msat, expired_at)
+                VALUES (?1, ?2, ?3)",
+                params![payment_hash, amount_msat, expired_at],
+            )
+            .context("Failed to mark lightning receive expired")?;
+        Ok(inserted > 0)
+    }
+
+    pub fn get_expired_receives(&self) -> anyhow::Result<HashSet<String>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT payment_hash FROM bark_expired_receives")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+
+        rows.collect::<Result<HashSet<_>, _>>()
+            .context("Failed to read expired lightning receives")
+    }
+}
+
+/// Number of sync log rows kept per sync type.
+const SYNC_LOG_RETENTION: i64 = 100;
+
+impl Store {
+    pub fn record_sync(
+        &self,
+        sync_type: &str,
+        block_height: Option<u32>,
+        success: bool,
+    ) -> anyhow::Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO bark_sync_log (sync_type, completed_at, block_height, success)
+            VALUES (?1, ?2, ?3, ?4)",
+            params![sync_type, now_timestamp(), block_height, success],
+        )
+        .context("Failed to record sync")?;
+        conn.execute(
+            "DELETE FROM bark_sync_log WHERE sync_type = ?1 AND id NOT IN (
+                SELECT id FROM bark_sync_log WHERE sync_type = ?1 ORDER BY id DESC LIMIT ?2
+            )",
+            params![sync_type, SYNC_LOG_RETENTION],
+        )
+        .context("Failed to prune sync log")?;
+        Ok(())
+    }
+
+    /// Unix timestamp of the last successful sync of the given type
+    pub fn last_successful_sync(&self, sync_type: &str) -> anyhow::Result<Option<i64>> {
+        let conn = self.conn()?;
+        conn.query_row(
+            "SELECT MAX(completed_at) FROM bark_sync_log WHERE sync_type = ?1 AND success = 1",
+            params![sync_type],
+            |row| row.get(0),
+        )
+        .context("Failed to read sync log")
+    }
+}
+
+/// Journal entries older than this are pruned during maintenance.
+pub const JOURNAL_RETENTION_SECS: i64 = 30 * 24 * 60 * 60;
+/// At most this many journal entries are kept.
+pub const JOURNAL_MAX_ROWS: i64 = 10_000;
+
+/// A record of something the wallet did, kept for support diagnostics.
+///
+/// Parameters are only stored as a hash and errors are redacted, so the
+/// journal can be shared without leaking invoices or secrets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub created_at: i64,
+    pub operation: String,
+    pub params_hash: String,
+    /// `ok` or `error`
+    pub result: String,
+    pub error: Option<String>,
+}
+
+impl JournalEntry {
+    pub fn to_json_line(&self) -> String {
+        serde_json::json!({
+            "timestamp": self.created_at,
+            "operation": self.operation,
+            "params_hash": self.params_hash,
+            "result": self.result,
+            "error": self.error,
+        })
+        .to_string()
+    }
+}
+
+impl Store {
+    pub fn append_journal_entry(&self, entry: &JournalEntry) -> anyhow::Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO bark_event_journal (created_at, operation, params_hash, result, error)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                entry.created_at,
+                entry.operation,
+                entry.params_hash,
+                entry.result,
+                entry.error
+            ],
+        )
+        .context("Failed to append journal entry")?;
+        Ok(())
+    }
+
+    /// Deletes entries created before `older_than` and all but the newest
+    /// `max_rows`. Returns the number of entries deleted.
+    pub fn prune_journal(&self, older_than: i64, max_rows: i64) -> anyhow::Result<usize> {
+        let conn = self.conn()?;
+        let expired = conn
+            .execute(
+                "DELETE FROM bark_event_journal WHERE created_at < ?1",
+                params![older_than],
+            )
+            .context("Failed to prune journal")?;
+        let excess = conn
+            .execute(
+                "DELETE FROM bark_event_journal WHERE id NOT IN (
+                    SELECT id FROM bark_event_journal ORDER BY id DESC LIMIT ?1
+                )",
+                params![max_rows],
+            )
+            .context("Failed to prune journal")?;
+        Ok(expired + excess)
+    }
+
+    /// Entries created in `[from_ts, to_ts]`, oldest first
+    pub fn get_journal_entries(
+        &self,
+        from_ts: i64,
+        to_ts: i64,
+    ) -> anyhow::Result<Vec<JournalEntry>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT created_at, operation, params_hash, result, error FROM bark_event_journal
+            WHERE created_at >= ?1 AND created_at <= ?2 ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![from_ts, to_ts], |row| {
+            Ok(JournalEntry {
+                created_at: row.get(0)?,
+                operation: row.get(1)?,
+                params_hash: row.get(2)?,
+                result: row.get(3)?,
+                error: row.get(4)?,
+            })
+        })?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+            .context("Failed to read journal")
+    }
+}
+
+/// How far an outgoing lightning payment got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PendingPaymentStatus {
+    /// The payment was started but the HTLCs may not have been sent yet
+    Sending,
+    /// The HTLCs were sent and we are waiting for the preimage
+    InFlight,
+    /// Sending failed, kept so the payment can be retried
+    Failed,
+}
+
+impl PendingPaymentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PendingPaymentStatus::Sending => "sending",
+            PendingPaymentStatus::InFlight => "in_flight",
+            PendingPaymentStatus::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for PendingPaymentStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sending" => Ok(PendingPaymentStatus::Sending),
+            "in_flight" => Ok(PendingPaymentStatus::InFlight),
+            "failed" => Ok(PendingPaymentStatus::Failed),
+            _ => bail!("Unknown pending payment status: {}", s),
+        }
+    }
+}
+
+/// An outgoing lightning payment that has not settled yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingPayment {
+    pub payment_hash: String,
+    pub bolt11: String,
+    /// `None` until known, for invoices without an amount
+    pub amount_msat: Option<u64>,
+    /// The payee's node id, if the invoice names one
+    pub destination: Option<String>,
+    pub started_at: i64,
+    pub status: PendingPaymentStatus,
+}
+
+impl Store {
+    /// Records a payment as started. Starting a payment again replaces the
+    /// record of the earlier attempt.
+    pub fn store_pending_payment(&self, payment: &PendingPayment) -> anyhow::Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR REPLACE INTO bark_pending_payment
+            (payment_hash, bolt11, amount_msat, destination, started_at, status)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                payment.payment_hash,
+                payment.bolt11,
+                payment.amount_msat,
+                payment.destination,
+                payment.started_at,
+                payment.status.as_str(),
+            ],
+        )
+        .context("Failed to store pending payment")?;
+        Ok(())
+    }
+
+    /// Moves a payment to `status`. The amount is only overwritten when given.
+    pub fn update_pending_payment_status(
+        &self,
+        payment_hash: &str,
+        status: PendingPaymentStatus,
+        amount_msat: Option<u64>,
+    ) -> anyhow::Result<()> {
+        let conn = self.conn()?;
+        let updated = conn
+            .execute(
+                "UPDATE bark_pending_payment
+                SET status = ?2, amount_msat = COALESCE(?3, amount_msat)
+                WHERE payment_hash = ?1",
+                params![payment_hash, status.as_str(), amount_msat],
+            )
+            .context("Failed to update pending payment")?;
+        if updated == 0 {
+            bail!("No pending payment with hash {}", payment_hash);
+        }
+        Ok(())
+    }
+
+    pub fn get_pending_payment(
+        &self,
+        payment_hash: &str,
+    ) -> anyhow::Result<Option<PendingPayment>> {
+        Ok(self
+            .get_pending_payments()?
+            .into_iter()
+            .find(|p| p.payment_hash == payment_hash))
+    }
+
+    /// Returns all pending and failed payments, oldest first.
+    pub fn get_pending_payments(&self) -> anyhow::Result<Vec<PendingPayment>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT payment_hash, bolt11, amount_msat, destination, started_at, status
+            FROM bark_pending_payment ORDER BY started_at, id",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<u64>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, String>(5)?,
+            ))
+        })?;
+
+        let mut payments = Vec::new();
+        for row in rows {
+            let (payment_hash, bolt11, amount_msat, destination, started_at, status) = row?;
+            payments.push(PendingPayment {
+                payment_hash,
+                bolt11,
+                amount_msat,
+                destination,
+                started_at,
+                status: status.parse()?,
+            });
+        }
+        Ok(payments)
+    }
+
+    /// Forgets a payment once it settled or failed. Returns whether it was
+    /// pending.
+    pub fn remove_pending_payment(&self, payment_hash: &str) -> anyhow::Result<bool> {
+        let conn = self.conn()?;
+        let removed = conn
+            .execute(
+                "DELETE FROM bark_pending_payment WHERE payment_hash = ?1",
+                [payment_hash],
+            )
+            .context("Failed to remove pending payment")?;
+        Ok(removed > 0)
+    }
+}
+
+/// The phases of a payment registered with
+/// [`Store::add_expected_payment`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExpectedPaymentStatus {
+    Open,
+    /// A matching VTXO was received
+    Fulfilled,
+    /// Nothing matching arrived before it expired
+    Expired,
+    Cancelled,
+}
+
+impl ExpectedPaymentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExpectedPaymentStatus::Open => "open",
+            ExpectedPaymentStatus::Fulfilled => "fulfilled",
+            ExpectedPaymentStatus::Expired => "expired",
+            ExpectedPaymentStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl std::str::FromStr for ExpectedPaymentStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(ExpectedPaymentStatus::Open),
+            "fulfilled" => Ok(ExpectedPaymentStatus::Fulfilled),
+            "expired" => Ok(ExpectedPaymentStatus::Expired),
+            "cancelled" => Ok(ExpectedPaymentStatus::Cancelled),
+            _ => bail!("Unknown expected payment status: {}", s),
+        }
+    }
+}
+
+/// An arkoor payment a merchant waits for, recognized by the pubkey it is
+/// paid to and, if set, its amount.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ExpectedPayment {
+    pub id: i64,
+    pub pubkey_index: u32,
+    pub user_pubkey: String,
+    /// `None` to accept any amount
+    pub amount_sat: Option<u64>,
+    pub memo: String,
+    pub status: ExpectedPaymentStatus,
+    /// The VTXO that fulfilled it
+    pub vtxo_id: Option<String>,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+impl Store {
+    pub fn add_expected_payment(
+        &self,
+        pubkey_index: u32,
+        user_pubkey: &str,
+        amount_sat: Option<u64>,
+        memo: &str,
+        expires_at: i64,
+    ) -> anyhow::Result<ExpectedPayment> {
+        let conn = self.conn()?;
+        let now = now_timestamp();
+        conn.execute(
+            "INSERT INTO bark_expected_payments
+            (pubkey_index, user_pubkey, amount_sat, memo, status, created_at, expires_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?6)",
+            params![
+                pubkey_index,
+                user_pubkey,
+                amount_sat,
+                memo,
+                ExpectedPaymentStatus::Open.as_str(),
+                now,
+                expires_at,
+            ],
+        )
+        .context("Failed to store expected payment")?;
+        Ok(ExpectedPayment {
+            id: conn.last_insert_rowid(),
+            pubkey_index,
+            user_pubkey: user_pubkey.to_string(),
+            amount_sat,
+            memo: memo.to_string(),
+            status: ExpectedPaymentStatus::Open,
+            vtxo_id: None,
+            created_at: now,
+            expires_at,
+        })
+    }
+
+    /// Expected payments, all of them or those in `status`, oldest first.
+    pub fn get_expected_payments(
+        &self,
+        status: Option<ExpectedPaymentStatus>,
+    ) -> anyhow::Result<Vec<ExpectedPayment>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, pubkey_index, user_pubkey, amount_sat, memo, status, vtxo_id,
+                created_at, expires_at
+            FROM bark_expected_payments
+            WHERE ?1 IS NULL OR status = ?1
+            ORDER BY created_at, id",
+        )?;
+        let rows = stmt.query_map([status.map(|s| s.as_str())], |row| {
+            Ok((
+                ExpectedPayment {
+                    id: row.get(0)?,
+                    pubkey_index: row.get(1)?,
+                    user_pubkey: row.get(2)?,
+                    amount_sat: row.get(3)?,
+                    memo: row.get(4)?,
+                    status: ExpectedPaymentStatus::Open,
+                    vtxo_id: row.get(6)?,
+                    created_at: row.get(7)?,
+                    expires_at: row.get(8)?,
+                },
+                row.get::<_, String>(5)?,
+            ))
+        })?;
+
+        let mut payments = Vec::new();
+        for row in rows {
+            let (mut payment, status) = row?;
+            payment.status = status.parse()?;
+            payments.push(payment);
+        }
+        Ok(payments)
+    }
+
+    /// Marks an open expected payment as paid by `vtxo_id`. Returns whether
+    /// it was still open.
+    pub fn fulfill_expected_payment(&self, id: i64, vtxo_id: &str) -> anyhow::Result<bool> {
+        let conn = self.conn()?;
+        let updated = conn
+            .execute(
+                "UPDATE bark_expected_payments SET status = ?2, vtxo_id = ?3, updated_at = ?4
+                WHERE id = ?1 AND status = ?5",
+                params![
+                    id,
+                    ExpectedPaymentStatus::Fulfilled.as_str(),
+                    vtxo_id,
+                    now_timestamp(),
+                    ExpectedPaymentStatus::Open.as_str(),
+                ],
+            )
+            .context("Failed to fulfill expected payment")?;
+        Ok(updated > 0)
+    }
+
+    /// Marks open expected payments that expired by `now` as expired.
+    /// Returns how many did.
+    pub fn expire_expected_payments(&self, now: i64) -> anyhow::Result<usize> {
+        let conn = self.conn()?;
+        conn.execute(
+            "UPDATE bark_expected_payments SET status = ?1, updated_at = ?2
+            WHERE status = ?3 AND expires_at <= ?2",
+            params![
+                ExpectedPaymentStatus::Expired.as_str(),
+                now,
+                ExpectedPaymentStatus::Open.as_str(),
+            ],
+        )
+        .context("Failed to expire expected payments")
+    }
+
+    /// Stops waiting for an expected payment. Returns whether it was open.
+    pub fn cancel_expected_payment(&self, id: i64) -> anyhow::Result<bool> {
+        let conn = self.conn()?;
+        let updated = conn
+            .execute(
+                "UPDATE bark_expected_payments SET status = ?2, updated_at = ?3
+                WHERE id = ?1 AND status = ?4",
+                params![
+                    id,
+                    ExpectedPaymentStatus::Cancelled.as_str(),
+                    now_timestamp(),
+                    ExpectedPaymentStatus::Open.as_str(),
+                ],
+            )
+            .context("Failed to cancel expected payment")?;
+        Ok(updated > 0)
+    }
+}
+
+/// A state a VTXO entered, as recorded by bark.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct VtxoStateTransition {
+    pub state: String,
+    pub transitioned_at: String,
+}
+
+/// Opens bark's own database for reads the persister doesn't offer.
+pub fn open_bark_db_read_only(path: &Path) -> anyhow::Result<Connection> {
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open wallet database at {}", path.display()))
+}
+
+/// The states a VTXO went through, oldest first, from bark's
+/// `bark_vtxo_state` table.
+pub fn get_vtxo_state_history(
+    conn: &Connection,
+    vtxo_id: &str,
+) -> anyhow::Result<Vec<VtxoStateTransition>> {
+    let mut stmt = conn.prepare(
+        "SELECT state_kind, created_at FROM bark_vtxo_state
+        WHERE vtxo_id = ?1 ORDER BY created_at ASC, id ASC",
+    )?;
+    let rows = stmt.query_map([vtxo_id], |row| {
+        Ok(VtxoStateTransition {
+            state: row.get(0)?,
+            transitioned_at: row.get(1)?,
+        })
+    })?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .context("Failed to read vtxo state history")
+}
+
+/// Number of VTXOs whose latest state in bark's `bark_vtxo_state` table is
+/// one of `state_kinds`.
+pub fn count_vtxos_in_states(conn: &Connection, state_kinds: &[&str]) -> anyhow::Result<u64> {
+    if state_kinds.is_empty() {
+        return Ok(0);
+    }
+    let placeholders = vec!["?"; state_kinds.len()].join(", ");
+    conn.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM bark_vtxo_state AS s
+            WHERE s.id = (
+                SELECT id FROM bark_vtxo_state WHERE vtxo_id = s.vtxo_id
+                ORDER BY created_at DESC, id DESC LIMIT 1
+            )
+            AND s.state_kind IN ({})",
+            placeholders
+        ),
+        rusqlite::params_from_iter(state_kinds),
+        |row| row.get(0),
+    )
+    .context("Failed to count vtxos")
+}
+
+/// Number of movements in bark's `bark_movement` table.
+pub fn count_movements(conn: &Connection) -> anyhow::Result<u64> {
+    conn.query_row("SELECT COUNT(*) FROM bark_movement", [], |row| row.get(0))
+        .context("Failed to count movements")
+}
+
+/// The creation time of a movement in unix seconds, as movement pages are
+/// ordered and compared on it.
+const MOVEMENT_CREATED_TS: &str = "CAST(strftime('%s', created_at) AS INTEGER)";
+
+/// The cursors of up to `limit` movements after `after`, or from the start
+/// without one. Newest first if `newest_first`, otherwise oldest first.
+///
+/// Rows are compared on `(created_at, id)`, so movements added between two
+/// page fetches don't shift later pages. `bark_movement` belongs to bark and
+/// has no index on this key, so each page sorts the table, which is cheap
+/// at the number of movements a wallet has.
+pub fn get_movement_page(
+    conn: &Connection,
+    after: Option<MovementCursor>,
+    limit: usize,
+    newest_first: bool,
+) -> anyhow::Result<Vec<MovementCursor>> {
+    let (cmp, order) = match newest_first {
+        true => ("<", "DESC"),
+        false => (">", "ASC"),
+    };
+    let filter = match after {
+        Some(_) => format!("WHERE ({}, id) {} (?2, ?3)", MOVEMENT_CREATED_TS, cmp),
+        None => String::new(),
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ts}, id FROM bark_movement {filter}
+        ORDER BY {ts} {order}, id {order} LIMIT ?1",
+        ts = MOVEMENT_CREATED_TS,
+    ))?;
+    let to_cursor = |row: &rusqlite::Row| -> rusqlite::Result<MovementCursor> {
+        Ok(MovementCursor {
+            created_at: row.get(0)?,
+            id: row.get(1)?,
+        })
+    };
+    let rows = match after {
+        Some(after) => {
+            stmt.query_map(params![limit as i64, after.created_at, after.id], to_cursor)?
+        }
+        None => stmt.query_map(params![limit as i64], to_cursor)?,
+    };
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .context("Failed to read movement page")
+}
+
+/// Distinct destinations sent to, from bark's `bark_recipient` table, most
+/// recently used first. When `payment_method_filter` is set only recipients
+/// of that payment method are returned.
+pub fn get_recipient_usage(
+    conn: &Connection,
+    limit: usize,
+    payment_method_filter: Option<&str>,
+) -> anyhow::Result<Vec<Recipient>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT r.payment_method, r.destination, MAX({ts}), COUNT(*)
+        FROM bark_recipient AS r JOIN bark_movement AS m ON m.id = r.movement_id
+        WHERE ?1 IS NULL OR r.payment_method = ?1
+        GROUP BY r.payment_method, r.destination
+        ORDER BY MAX({ts}) DESC, MAX(m.id) DESC LIMIT ?2",
+        ts = MOVEMENT_CREATED_TS,
+    ))?;
+    let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+    let rows = stmt.query_map(params![payment_method_filter, limit], |row| {
+        Ok(Recipient {
+            name: None,
+            destination_type: row.get(0)?,
+            destination: row.get(1)?,
+            last_used_at: row.get(2)?,
+            times_used: row.get(3)?,
+        })
+    })?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .context("Failed to read recent recipients")
+}
+
+/// The movements of one bark subsystem, summed up by the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubsystemTotals {
+    pub subsystem_name: String,
+    pub subsystem_kind: String,
+    pub count: u64,
+    pub received_sat: u64,
+    pub sent_sat: u64,
+    pub first_created_at: i64,
+}
+
+/// Totals per subsystem of the movements created in `[from_ts, to_ts]`,
+/// ordered by their first movement. Amounts follow the effective balance
+/// change, so failed movements don't add up.
+pub fn get_subsystem_totals(
+    conn: &Connection,
+    from_ts: i64,
+    to_ts: i64,
+) -> anyhow::Result<Vec<SubsystemTotals>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT subsystem_name, subsystem_kind, COUNT(*),
+            COALESCE(SUM(MAX(effective_balance, 0)), 0),
+            COALESCE(SUM(MAX(-effective_balance, 0)), 0),
+            MIN({ts})
+        FROM bark_movement WHERE {ts} BETWEEN ?1 AND ?2
+        GROUP BY subsystem_name, subsystem_kind
+        ORDER BY MIN({ts}) ASC, MIN(id) ASC",
+        ts = MOVEMENT_CREATED_TS,
+    ))?;
+    let rows = stmt.query_map(params![from_ts, to_ts], |row| {
+        Ok(SubsystemTotals {
+            subsystem_name: row.get(0)?,
+            subsystem_kind: row.get(1)?,
+            count: row.get(2)?,
+            received_sat: row.get(3)?,
+            sent_sat: row.get(4)?,
+            first_created_at: row.get(5)?,
+        })
+    })?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .context("Failed to read movement totals")
+}
+
+/// The offchain balance change of each UTC day that has movements, oldest
+/// first, timed at the day's first movement.
+pub fn get_daily_movement_changes(conn: &Connection) -> anyhow::Result<Vec<BalanceChange>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT MIN({ts}), SUM(effective_balance) FROM bark_movement
+        GROUP BY {ts} / 86400 ORDER BY MIN({ts}) ASC",
+        ts = MOVEMENT_CREATED_TS,
+    ))?;
+    let rows = stmt.query_map([], |row| {
+        Ok(BalanceChange {
+            at: row.get(0)?,
+            offchain_sat: row.get(1)?,
+            onchain_sat: 0,
+        })
+    })?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .context("Failed to read daily movement changes")
+}
+
+/// When bark applied its first migration to the database, which is when
+/// the wallet was created.
+pub fn get_db_created_at(conn: &Connection) -> anyhow::Result<String> {
+    conn.query_row(
+        "SELECT created_at FROM migrations ORDER BY value ASC LIMIT 1",
+        [],
+        |row| row.get(0),
+    )
+    .optional()
+    .context("Failed to read wallet database migrations")?
+    .context("Wallet database has no migrations")
+}
+
+/// The last migration bark applied to its database.
+pub fn get_db_migration_version(conn: &Connection) -> anyhow::Result<u32> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(value), 0) FROM migrations",
+        [],
+        |row| row.get(0),
+    )
+    .context("Failed to read wallet database migrations")
+}
+
+/// Moves everything in the write-ahead log of the database at `path` into
+/// the database file, so copying the file alone copies all of it.
+pub fn checkpoint_sqlite_db(path: &Path) -> anyhow::Result<()> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)
+        .with_context(|| format!("Failed to open database at {}", path.display()))?;
+    let busy: i64 = conn
+        .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))
+        .with_context(|| format!("Failed to checkpoint {}", path.display()))?;
+    if busy != 0 {
+        bail!("Database {} is in use, can't checkpoint it", path.display());
+    }
+    Ok(())
+}
+
+/// Size of the SQLite database in bytes, from its page count and page
+/// size. Doesn't include a write-ahead log that wasn't checkpointed yet.
+pub fn database_size(conn: &Connection) -> anyhow::Result<u64> {
+    let page_count: u64 = conn
+        .query_row("PRAGMA page_count", [], |row| row.get(0))
+        .context("Failed to read database page count")?;
+    let page_size: u64 = conn
+        .query_row("PRAGMA page_size", [], |row| row.get(0))
+        .context("Failed to read database page size")?;
+    Ok(page_count * page_size)
+}
+
+/// The number of rows in each table of the database, SQLite's internal
+/// tables left out.
+pub fn table_row_counts(conn: &Connection) -> anyhow::Result<BTreeMap<String, u64>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+    )?;
+    let tables = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to list database tables")?;
+
+    let mut counts = BTreeMap::new();
+    for table in tables {
+        let count = conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM \"{}\"", table.replace('"', "\"\"")),
+                [],
+                |row| row.get(0),
+            )
+            .with_context(|| format!("Failed to count rows of {}", table))?;
+        counts.insert(table, count);
+    }
+    Ok(counts)
+}
+
+/// `backup_path` with its file name prefixed by `timestamp`, so repeated
+/// backups don't overwrite each other.
+pub fn timestamped_backup_path(backup_path: &Path, timestamp: i64) -> anyhow::Result<PathBuf> {
+    let file_name = backup_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("Invalid backup path {}", backup_path.display()))?;
+    Ok(backup_path.with_file_name(format!("{}-{}", timestamp, file_name)))
+}
+
+/// Writes a consistent copy of the SQLite database at `db_path` to
+/// `backup_path`, which must not exist yet. Safe while the wallet has the
+/// database open.
+pub fn backup_sqlite_db(db_path: &Path, backup_path: &Path) -> anyhow::Result<()> {
+    if backup_path.exists() {
+        bail!("Backup file {} already exists", backup_path.display());
+    }
+    let backup = backup_path
+        .to_str()
+        .with_context(|| format!("Invalid backup path {}", backup_path.display()))?;
+    let conn = open_bark_db_read_only(db_path)?;
+    conn.execute("VACUUM INTO ?1", [backup])
+        .with_context(|| format!("Failed to back up database to {}", backup))?;
+    Ok(())
+}
+
+/// Current unix time in seconds.
+pub fn now_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,67 @@
+//! Known-answer vectors for the Ark key derivation.
+//!
+//! These pin the keys existing wallets derive. A change to any of them means
+//! users would derive different keys from the same mnemonic, so never update
+//! them to make a test pass.
+
+/// Message signed with the key at index 0 under the default purpose.
+pub const SIGNED_MESSAGE: &str = "nitro-ark test vector";
+
+pub struct DerivationVectors {
+    pub mnemonic: &'static str,
+    /// Compressed pubkeys under the default purpose, by index
+    pub keys: [(u32, &'static str); 4],
+    /// Compressed pubkey at index 0 under purpose 351
+    pub purpose_351_key: &'static str,
+    /// DER signature of [`SIGNED_MESSAGE`]
+    pub signature: &'static str,
+}
+
+pub const DERIVATION_VECTORS: [DerivationVectors; 2] = [
+    DerivationVectors {
+        mnemonic: "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+        keys: [
+            (
+                0,
+                "036be4943d5c6e0f9199e47e94adb6c02ed89b40cdb47a3e9404341b4ee2d365af",
+            ),
+            (
+                1,
+                "02225f019eb057bc77f16ccdeb7d4596101591a200956e5d7fed7c74f7d53e990e",
+            ),
+            (
+                350,
+                "03304eacbe11c4da9372040dfa3db59c2e59ac1cf629e66bbaa4d6e0f274844993",
+            ),
+            (
+                0x7fff_ffff,
+                "036ffb65bb37e8387874b2b1a10eedfc74f965736386db8d910def4a9870779c83",
+            ),
+        ],
+        purpose_351_key: "03151f9db32238a4bd0d57228949c4ebbd91889c5d26526e6598d17ba223e80d64",
+        signature: "3045022100d19b440535cc71b1a7283529c29fe6fd56fef8a5ccb66a48144b558ff97df9d002207fbd439cbff3e3ee82e0640b1f0467cafdc334eb249ea8b10a7ca799a3e45933",
+    },
+    DerivationVectors {
+        mnemonic: "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        keys: [
+            (
+                0,
+                "029ec1da6680b98b04cb95ac66d5e8e9547205e1d7258851bebd8c6dd9106015ed",
+            ),
+            (
+                1,
+                "022696e665c824ce10f433d1376cd32ca735c86d217c58db32910844cf9f5db46c",
+            ),
+            (
+                350,
+                "02eb27cb2fe711a4b3cfa5a16cd2c95fd6320d25eadc2657d2e49ceed222b1fab3",
+            ),
+            (
+                0x7fff_ffff,
+                "025ceec5658c591f05beb2d474b2c1c203d1f083a7f1e36d2f58bd4a87e0a3bdcf",
+            ),
+        ],
+        purpose_351_key: "03828aa76c98818f24e35d7630e8ad86202a91126d1684ea9e324039441a8bc56b",
+        signature: "3045022100eb612c077f48a948a0c218bc35bf3a1a0c24214df10567d8b956f613687c112702200a36aa9556822862c5b86d2658da161b53de54c51b86457a0bdf3cb026807293",
+    },
+];
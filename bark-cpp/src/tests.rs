@@ -12,6 +12,13 @@ use tempfile::tempdir;
 
 // --- Test Setup ---
 
+/// Opens a fresh local store in a temporary directory.
+fn temp_store() -> (tempfile::TempDir, crate::store::Store) {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let store = crate::store::Store::open(&temp_dir.path().join("nitro.sqlite")).unwrap();
+    (temp_dir, store)
+}
+
 /// Creates a temporary directory and basic wallet creation options for tests.
 fn setup_test_wallet_opts() -> (tempfile::TempDir, ffi::CreateOpts) {
     let temp_dir = tempdir().expect("Failed to create temp dir");
@@ -31,6 +38,7 @@ fn setup_test_wallet_opts() -> (tempfile::TempDir, ffi::CreateOpts) {
         htlc_recv_claim_delta: 18,
         vtxo_exit_margin: 12,
         round_tx_required_confirmations: 0,
+        ark_purpose_index: 0,
     };
 
     let create_opts = ffi::CreateOpts {
@@ -52,11 +60,11 @@ struct WalletTestFixture {
 
 impl WalletTestFixture {
     fn new() -> Self {
-        cxx::init_logger();
+        cxx::init_logger().unwrap();
         let (temp_dir, opts) = setup_test_wallet_opts();
         let datadir_str = temp_dir.path().to_str().unwrap();
 
-        if cxx::is_wallet_loaded() {
+        if cxx::is_wallet_loaded().unwrap() {
             cxx::close_wallet().unwrap();
         }
 
@@ -72,7 +80,7 @@ impl WalletTestFixture {
 
 impl Drop for WalletTestFixture {
     fn drop(&mut self) {
-        if cxx::is_wallet_loaded() {
+        if cxx::is_wallet_loaded().unwrap() {
             cxx::close_wallet().expect("Failed to close wallet in test teardown");
         }
     }
@@ -84,12 +92,12 @@ impl Drop for WalletTestFixture {
 fn test_init_logger_ffi() {
     // This just ensures the function can be called without panicking.
     // The logger is initialized globally, so this will be a no-op on subsequent calls.
-    cxx::init_logger();
+    cxx::init_logger().unwrap();
 }
 
 #[test]
 fn test_create_mnemonic_ffi() {
-    cxx::init_logger();
+    cxx::init_logger().unwrap();
     let result = cxx::create_mnemonic();
     assert!(result.is_ok());
     let mnemonic_str = result.unwrap();
@@ -99,12 +107,12 @@ fn test_create_mnemonic_ffi() {
 #[test]
 #[ignore = "requires live regtest backend"]
 fn test_wallet_management_ffi() {
-    cxx::init_logger();
+    cxx::init_logger().unwrap();
     let (temp_dir, opts) = setup_test_wallet_opts();
     let datadir_str = temp_dir.path().to_str().unwrap();
 
     // 1. Wallet should not be loaded initially
-    assert!(!cxx::is_wallet_loaded());
+    assert!(!cxx::is_wallet_loaded().unwrap());
 
     // 2. Load wallet
     let load_result = cxx::create_wallet(datadir_str, opts);
@@ -113,7 +121,7 @@ fn test_wallet_management_ffi() {
         "Failed to load wallet: {:?}",
         load_result.err()
     );
-    assert!(cxx::is_wallet_loaded());
+    assert!(cxx::is_wallet_loaded().unwrap());
 
     // 3. Try loading again (should fail)
     let (_temp_dir2, opts2) = setup_test_wallet_opts();
@@ -127,7 +135,7 @@ fn test_wallet_management_ffi() {
     // 4. Close wallet
     let close_result = cxx::close_wallet();
     assert!(close_result.is_ok());
-    assert!(!cxx::is_wallet_loaded());
+    assert!(!cxx::is_wallet_loaded().unwrap());
 
     // 5. Try closing again (should fail)
     let close_again_result = cxx::close_wallet();
@@ -150,6 +158,57 @@ fn test_get_onchain_address_ffi() {
     );
 }
 
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_new_ark_address_ffi() {
+    let _fixture = WalletTestFixture::new();
+    let result = cxx::new_address().unwrap();
+    // Ark addresses on test networks use the "tark" prefix, mainnet uses "ark"
+    assert!(
+        result.address.starts_with("tark1"),
+        "Address should be a test network ark address"
+    );
+    assert!(cxx::validate_arkoor_address(&result.address).is_ok());
+    assert!(cxx::validate_arkoor_address("tark1invalid").is_err());
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_ark_receive_address_ffi() {
+    let _fixture = WalletTestFixture::new();
+    let allocated = cxx::get_new_ark_receive_address().unwrap();
+    assert!(cxx::validate_arkoor_address(&allocated).is_ok());
+
+    // Peeking at an index is stable and doesn't allocate
+    let at_index = cxx::get_ark_receive_address_at_index(0).unwrap();
+    assert_eq!(at_index, cxx::get_ark_receive_address_at_index(0).unwrap());
+    assert_eq!(at_index, cxx::peak_address(0).unwrap().address);
+    assert_ne!(at_index, cxx::get_ark_receive_address_at_index(1).unwrap());
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_pending_ark_address_ffi() {
+    let _fixture = WalletTestFixture::new();
+    assert_eq!(cxx::get_pending_ark_address().unwrap(), "");
+
+    cxx::prefetch_ark_address().unwrap();
+    let pending = cxx::get_pending_ark_address().unwrap();
+    assert!(cxx::validate_arkoor_address(&pending).is_ok());
+
+    // Unused addresses are handed out again instead of deriving new ones
+    cxx::prefetch_ark_address().unwrap();
+    assert_eq!(cxx::get_pending_ark_address().unwrap(), pending);
+    assert_eq!(cxx::new_address().unwrap().address, pending);
+    assert_eq!(cxx::get_new_ark_receive_address().unwrap(), pending);
+
+    cxx::consume_pending_ark_address().unwrap();
+    assert_eq!(cxx::get_pending_ark_address().unwrap(), "");
+    let next = cxx::new_address().unwrap().address;
+    assert_ne!(next, pending);
+    assert_eq!(cxx::get_pending_ark_address().unwrap(), next);
+}
+
 #[test]
 #[ignore = "requires live regtest backend"]
 fn test_get_onchain_balance_ffi() {
@@ -161,6 +220,54 @@ fn test_get_onchain_balance_ffi() {
     assert_eq!(balance, 0);
 }
 
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_get_onchain_balance_breakdown_ffi() {
+    let _fixture = WalletTestFixture::new();
+    let balance = cxx::get_onchain_balance().unwrap();
+    assert_eq!(
+        balance.total_sat,
+        balance.confirmed_sat
+            + balance.trusted_pending_sat
+            + balance.untrusted_pending_sat
+            + balance.immature_sat
+    );
+    assert_eq!(
+        balance.confirmed_sat,
+        cxx::onchain_balance().unwrap().confirmed
+    );
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_balance_components_ffi() {
+    let _fixture = WalletTestFixture::new();
+    let offchain = cxx::offchain_balance().unwrap();
+    assert_eq!(cxx::offchain_balance_sat().unwrap(), offchain.spendable);
+    assert_eq!(
+        cxx::pending_exit_balance_sat().unwrap(),
+        offchain.pending_exit
+    );
+    assert_eq!(
+        cxx::onchain_balance_sat().unwrap(),
+        cxx::get_onchain_balance().unwrap().total_sat
+    );
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_vtxo_counts_ffi() {
+    let _fixture = WalletTestFixture::new();
+    let spendable = cxx::get_spendable_vtxo_count().unwrap();
+    let spent = cxx::get_spent_vtxo_count().unwrap();
+    let locked = cxx::get_locked_vtxo_count().unwrap();
+    assert_eq!(
+        cxx::get_total_vtxo_count().unwrap(),
+        spendable + spent + locked
+    );
+    assert_eq!(spendable, cxx::vtxos().unwrap().len() as u64);
+}
+
 #[test]
 #[ignore = "requires live regtest backend"]
 fn test_get_vtxo_pubkey_ffi() {
@@ -182,8 +289,8 @@ fn test_bolt11_invoice_ffi() {
     let _fixture = WalletTestFixture::new();
     // This test requires a running LDK node, which is part of the wallet.
     // It should succeed even without onchain funds.
-    let amount_msat = 100_000; // 100 sat
-    let invoice_res = cxx::bolt11_invoice(amount_msat);
+    let amount_sat = 10_000;
+    let invoice_res = cxx::bolt11_invoice(amount_sat);
     assert!(
         invoice_res.is_ok(),
         "Failed to create bolt11 invoice: {:?}",
@@ -265,6 +372,21 @@ fn test_drain_onchain_ffi() {
     assert_eq!(txid.len(), 64);
 }
 
+#[test]
+#[ignore = "requires live regtest backend and a funded wallet"]
+fn test_drain_onchain_synced_ffi() {
+    let _fixture = WalletTestFixture::new();
+
+    // A mainnet address is rejected by the regtest wallet before syncing
+    let mainnet = "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq";
+    assert!(cxx::drain_onchain(mainnet, false).is_err());
+
+    // This test requires the address to be funded manually.
+    let address = cxx::onchain_address().unwrap();
+    let txid = cxx::drain_onchain(&address, false).unwrap();
+    assert_eq!(txid.len(), 64);
+}
+
 #[test]
 #[ignore = "requires live regtest backend and a funded wallet"]
 fn test_send_many_onchain_ffi() {
@@ -329,7 +451,11 @@ fn test_send_bolt11_payment_ffi() {
     // Here we test sending to a bolt11 invoice.
     let invoice = cxx::bolt11_invoice(10000).unwrap();
     let amount: u64 = 5000;
-    let send_res = cxx::pay_lightning_invoice(&invoice.bolt11_invoice, &amount as *const u64);
+    let send_res = cxx::pay_lightning_invoice(
+        &invoice.bolt11_invoice,
+        &amount as *const u64,
+        std::ptr::null(),
+    );
     assert!(
         send_res.is_ok(),
         "send_payment (bolt11) failed: {:?}",
@@ -363,3 +489,4086 @@ fn test_claim_bolt11_payment_ffi() {
     // The key is that it shouldn't panic.
     assert!(claim_res.is_err(), "Claiming an unpaid invoice should fail");
 }
+
+#[test]
+fn test_address_book_entries() {
+    let (temp_dir, store) = temp_store();
+
+    store
+        .add_address_book_entry("Alice", "alice@example.com")
+        .unwrap();
+    store.add_address_book_entry("Bob", "bcrt1qbob").unwrap();
+    // Adding an existing destination renames it instead of duplicating it
+    store
+        .add_address_book_entry("Alice W.", "alice@example.com")
+        .unwrap();
+    assert!(store.add_address_book_entry("Empty", "").is_err());
+
+    let entries = store.get_address_book().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].name, "Alice W.");
+    assert_eq!(entries[1].destination, "bcrt1qbob");
+
+    assert!(store.remove_address_book_entry("bcrt1qbob").unwrap());
+    assert!(!store.remove_address_book_entry("bcrt1qbob").unwrap());
+    assert!(store.get_address_book_entry("bcrt1qbob").unwrap().is_none());
+
+    // Reopening keeps the data and does not re-run migrations
+    drop(store);
+    let store = crate::store::Store::open(&temp_dir.path().join("nitro.sqlite")).unwrap();
+    assert_eq!(store.get_address_book().unwrap().len(), 1);
+}
+
+#[test]
+fn test_get_recipient_usage() {
+    use crate::store::{get_recipient_usage, open_bark_db_read_only};
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("db.sqlite");
+    // Fixture of the tables bark keeps movements and their recipients in
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE bark_movement (
+            id INTEGER PRIMARY KEY,
+            created_at DATETIME NOT NULL
+        );
+        CREATE TABLE bark_recipient (
+            id INTEGER PRIMARY KEY,
+            movement_id INTEGER NOT NULL REFERENCES bark_movement(id),
+            payment_method TEXT NOT NULL,
+            destination TEXT NOT NULL
+        );",
+    )
+    .unwrap();
+    let read = || open_bark_db_read_only(&path).unwrap();
+    assert!(get_recipient_usage(&read(), 10, None).unwrap().is_empty());
+
+    conn.execute_batch(
+        "INSERT INTO bark_movement (id, created_at) VALUES
+            (1, '1970-01-01 00:01:40'),
+            (2, '1970-01-01 00:03:20'),
+            (3, '1970-01-01 00:05:00'),
+            (4, '1970-01-01 00:06:40'),
+            (5, '1970-01-01 00:00:50');
+        INSERT INTO bark_recipient (movement_id, payment_method, destination) VALUES
+            (1, 'ark', 'ark1alice'),
+            (2, 'lightning-address', 'bob@example.com'),
+            (3, 'bitcoin', 'bcrt1qcarol'),
+            (4, 'ark', 'ark1alice'),
+            (5, 'invoice', 'lnbcrt1invoice');",
+    )
+    .unwrap();
+
+    let recipients = get_recipient_usage(&read(), 10, None).unwrap();
+    assert_eq!(recipients.len(), 4);
+    assert_eq!(recipients[0].destination, "ark1alice");
+    assert_eq!(recipients[0].times_used, 2);
+    assert_eq!(recipients[0].last_used_at, Some(400));
+    assert_eq!(recipients[1].destination, "bcrt1qcarol");
+    assert_eq!(recipients[3].destination, "lnbcrt1invoice");
+    assert!(recipients.iter().all(|r| r.name.is_none()));
+
+    assert_eq!(get_recipient_usage(&read(), 2, None).unwrap().len(), 2);
+    assert_eq!(
+        get_recipient_usage(&read(), usize::MAX, None)
+            .unwrap()
+            .len(),
+        4
+    );
+
+    let ark_only = get_recipient_usage(&read(), 10, Some("ark")).unwrap();
+    assert_eq!(ark_only.len(), 1);
+    assert_eq!(ark_only[0].destination_type, "ark");
+}
+
+#[test]
+fn test_parse_lnurl_withdraw_destination() {
+    // Example from LUD-01
+    let lnurl = "LNURL1DP68GURN8GHJ7UM9WFMXJCM99E3K7MF0V9CXJ0M385EKVCENXC6R2C35XVUKXEFCV5MKVV34X5EKZD3EV56NYD3HXQURZEPEXEJXXEPNXSCRVWFNV9NXZCN9XQ6XYEFHVGCXXCMYXYMNSERXFQ5FNS";
+
+    match crate::parse_send_destination(lnurl).unwrap() {
+        crate::SendDestination::LnurlWithdraw(s) => assert_eq!(s, lnurl),
+        _ => panic!("Expected an lnurl withdraw destination"),
+    }
+    let url = crate::decode_lnurl_url(&lnurl.to_lowercase()).unwrap();
+    assert!(url.starts_with("https://service.com/api?q="));
+
+    assert!(crate::parse_send_destination("lnurl1invalid").is_err());
+}
+
+#[test]
+fn test_board_intent_transitions() {
+    use crate::{BoardIntentState, BoardResumeAction, FundingTxStatus};
+
+    let (_temp_dir, store) = temp_store();
+
+    // A board that got funded, one that failed before funding and one that
+    // was interrupted before we learned anything about it.
+    let funded = store.create_board_intent(Some(50_000)).unwrap();
+    store
+        .update_board_intent(
+            funded,
+            BoardIntentState::Funded,
+            Some("aa".repeat(32).as_str()),
+            None,
+        )
+        .unwrap();
+    let failed = store.create_board_intent(None).unwrap();
+    store
+        .update_board_intent(
+            failed,
+            BoardIntentState::Failed,
+            None,
+            Some("insufficient funds"),
+        )
+        .unwrap();
+    let interrupted = store.create_board_intent(Some(10_000)).unwrap();
+
+    let open = store.get_open_board_intents().unwrap();
+    assert_eq!(open.len(), 2);
+    assert_eq!(open[0].id, funded);
+    assert_eq!(open[0].state, BoardIntentState::Funded);
+    assert_eq!(open[0].amount_sat, Some(50_000));
+    assert_eq!(open[1].id, interrupted);
+    assert_eq!(open[1].state, BoardIntentState::Started);
+    assert!(open[1].funding_txid.is_none());
+
+    // Updating without a txid keeps the one already recorded
+    store
+        .update_board_intent(funded, BoardIntentState::Registered, None, None)
+        .unwrap();
+    assert_eq!(store.get_open_board_intents().unwrap().len(), 1);
+    assert!(
+        store
+            .update_board_intent(9999, BoardIntentState::Failed, None, None)
+            .is_err()
+    );
+
+    assert_eq!(
+        crate::board_resume_action(BoardIntentState::Started, None, false),
+        Some(BoardResumeAction::Abandon)
+    );
+    // An unconfirmed spend may be the funding tx we never learned about
+    assert_eq!(
+        crate::board_resume_action(BoardIntentState::Started, None, true),
+        Some(BoardResumeAction::Wait)
+    );
+    assert_eq!(
+        crate::board_resume_action(
+            BoardIntentState::Funded,
+            Some(FundingTxStatus::Confirmed),
+            false
+        ),
+        Some(BoardResumeAction::Register)
+    );
+    assert_eq!(
+        crate::board_resume_action(
+            BoardIntentState::Funded,
+            Some(FundingTxStatus::Unconfirmed),
+            false
+        ),
+        Some(BoardResumeAction::Wait)
+    );
+    assert_eq!(
+        crate::board_resume_action(
+            BoardIntentState::Funded,
+            Some(FundingTxStatus::NotFound),
+            true
+        ),
+        Some(BoardResumeAction::Abandon)
+    );
+    for state in [
+        BoardIntentState::Registered,
+        BoardIntentState::Failed,
+        BoardIntentState::Abandoned,
+    ] {
+        assert_eq!(
+            crate::board_resume_action(state, Some(FundingTxStatus::Confirmed), true),
+            None
+        );
+    }
+}
+
+#[test]
+fn test_count_movements() {
+    use crate::store::{count_movements, open_bark_db_read_only};
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("db.sqlite");
+    {
+        // Fixture of the table bark keeps movements in
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE bark_movement (
+                id INTEGER PRIMARY KEY,
+                created_at DATETIME NOT NULL
+            );",
+        )
+        .unwrap();
+    }
+    assert_eq!(
+        count_movements(&open_bark_db_read_only(&path).unwrap()).unwrap(),
+        0
+    );
+
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "INSERT INTO bark_movement (created_at) VALUES
+                ('2025-01-01 10:00:00'),
+                ('2025-01-01 11:00:00'),
+                ('2025-01-02 09:30:00');",
+        )
+        .unwrap();
+    }
+    assert_eq!(
+        count_movements(&open_bark_db_read_only(&path).unwrap()).unwrap(),
+        3
+    );
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_get_movement_count_ffi() {
+    let _fixture = WalletTestFixture::new();
+    let count = cxx::get_movement_count().unwrap();
+    let history = cxx::history().unwrap();
+    assert_eq!(count, history.len() as u64);
+}
+
+#[test]
+fn test_auto_refresh_policy() {
+    use crate::AutoRefreshPolicy;
+
+    let (_temp_dir, store) = temp_store();
+
+    let default_policy = store.get_auto_refresh_policy().unwrap();
+    assert!(!default_policy.enabled);
+
+    // Quiet from 22:00 until 07:00 local time in UTC+2
+    let policy = AutoRefreshPolicy {
+        enabled: true,
+        threshold_blocks: 100,
+        quiet_hours_start: 22,
+        quiet_hours_end: 7,
+        timezone_offset_minutes: 120,
+    };
+    store.set_auto_refresh_policy(&policy).unwrap();
+    assert_eq!(store.get_auto_refresh_policy().unwrap(), policy);
+
+    let day = 86_400 * 20_000;
+    // 21:00 UTC is 23:00 local, 04:59 UTC is 06:59 local, 05:00 UTC is 07:00 local
+    assert!(policy.is_quiet_time(day + 21 * 3600));
+    assert!(policy.is_quiet_time(day + 4 * 3600 + 59 * 60));
+    assert!(!policy.is_quiet_time(day + 5 * 3600));
+    assert!(!policy.is_quiet_time(day + 12 * 3600));
+
+    let noon = day + 10 * 3600;
+    assert!(policy.should_refresh(noon, 1000, Some(1100)));
+    assert!(!policy.should_refresh(noon, 1000, Some(1101)));
+    assert!(!policy.should_refresh(noon, 1000, None));
+    assert!(!policy.should_refresh(day + 21 * 3600, 1000, Some(1000)));
+
+    let no_quiet_hours = AutoRefreshPolicy {
+        quiet_hours_start: 3,
+        quiet_hours_end: 3,
+        ..policy
+    };
+    assert!(!no_quiet_hours.is_quiet_time(day + 3 * 3600));
+
+    let invalid = AutoRefreshPolicy {
+        quiet_hours_start: 24,
+        ..policy
+    };
+    assert!(store.set_auto_refresh_policy(&invalid).is_err());
+}
+
+#[test]
+fn test_create_opts_debug_redacts_secrets() {
+    let (_temp_dir, mut opts) = setup_test_wallet_opts();
+    // Fixed mnemonic so no word collides with a field name or value like "true"
+    opts.mnemonic =
+        "legal winner thank year wave sausage worth useful legal winner thank yellow".to_string();
+    opts.config.bitcoind_pass = "hunter2".to_string();
+    let words: Vec<String> = opts.mnemonic.split_whitespace().map(String::from).collect();
+
+    let create_opts = crate::ffi_config_to_config(opts).unwrap();
+    let debug = format!("{:?}", create_opts);
+    for word in &words {
+        assert!(
+            !debug
+                .split(|c: char| !c.is_alphanumeric())
+                .any(|w| w == word),
+            "Debug output leaks mnemonic word '{}'",
+            word
+        );
+    }
+    assert!(!debug.contains("hunter2"));
+    assert!(debug.contains("[REDACTED]"));
+
+    // The secret is still available where it is actually needed
+    assert_eq!(
+        create_opts.mnemonic.expose_secret().to_string(),
+        words.join(" ")
+    );
+    let secret = crate::Secret::new("hunter2".to_string());
+    assert_eq!(format!("{:?}", secret), "[REDACTED]");
+    assert_eq!(secret.expose_secret(), "hunter2");
+}
+
+#[test]
+fn test_parse_bip21_uri() {
+    let address = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+
+    let payment = crate::parse_bip21_uri(&format!("bitcoin:{}", address)).unwrap();
+    assert_eq!(payment.address, address);
+    assert_eq!(payment.amount_sat, None);
+    assert_eq!(payment.label, None);
+    assert_eq!(payment.message, None);
+    assert_eq!(payment.lightning, None);
+
+    let payment = crate::parse_bip21_uri(&format!(
+        "BITCOIN:{}?amount=0.001&label=Coffee%20Shop&message=Thanks&foo=bar",
+        address
+    ))
+    .unwrap();
+    assert_eq!(payment.amount_sat, Some(100_000));
+    assert_eq!(payment.label.as_deref(), Some("Coffee Shop"));
+    assert_eq!(payment.message.as_deref(), Some("Thanks"));
+
+    assert!(crate::parse_bip21_uri(&format!("bitcoin:{}?amount=abc", address)).is_err());
+    assert!(crate::parse_bip21_uri(&format!("bitcoin:{}?req-foo=1", address)).is_err());
+    assert!(crate::parse_bip21_uri("bitcoin:notanaddress").is_err());
+    assert!(crate::parse_bip21_uri(&format!("lightning:{}", address)).is_err());
+}
+
+#[test]
+fn test_generate_bip21_uri() {
+    let address = "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080";
+
+    assert_eq!(
+        crate::generate_bip21_uri(address, None, None, None).unwrap(),
+        format!("bitcoin:{}", address)
+    );
+
+    let uri =
+        crate::generate_bip21_uri(address, Some(150_000), Some("Coffee & cake"), None).unwrap();
+    assert_eq!(
+        uri,
+        format!(
+            "bitcoin:{}?amount=0.0015&label=Coffee%20%26%20cake",
+            address
+        )
+    );
+    let payment = crate::parse_bip21_uri(&uri).unwrap();
+    assert_eq!(payment.amount_sat, Some(150_000));
+    assert_eq!(payment.label.as_deref(), Some("Coffee & cake"));
+
+    assert!(crate::generate_bip21_uri("notanaddress", None, None, None).is_err());
+    assert!(crate::generate_bip21_uri(address, None, None, Some("lnbcinvalid")).is_err());
+
+    // The bridge treats zero and empty values as absent
+    assert_eq!(
+        cxx::generate_bip21_uri(address, 0, "", "").unwrap(),
+        format!("bitcoin:{}", address)
+    );
+    let json = cxx::parse_bip21_uri(&format!("bitcoin:{}?amount=1", address)).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["amount_sat"], 100_000_000);
+}
+
+#[test]
+fn test_wallet_snapshot_takes_the_lock_once() {
+    let count = || {
+        let counts = crate::MANAGER_LOCK_COUNTS.lock().unwrap();
+        counts.get("get_wallet_snapshot").copied().unwrap_or(0)
+    };
+
+    let before = count();
+    // Without a loaded wallet the snapshot fails as a whole, but the lock
+    // must still only be taken once for all requested fields.
+    let result = crate::TOKIO_RUNTIME.block_on(crate::get_wallet_snapshot(crate::SNAPSHOT_ALL));
+    let after = count();
+
+    if !cxx::is_wallet_loaded().unwrap() {
+        assert!(result.is_err());
+    }
+    assert_eq!(after - before, 1);
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_wallet_snapshot_ffi() {
+    let _fixture = WalletTestFixture::new();
+
+    let snapshot =
+        cxx::get_wallet_snapshot(crate::SNAPSHOT_BALANCE | crate::SNAPSHOT_VTXOS).unwrap();
+    assert_eq!(
+        snapshot.fields_mask,
+        crate::SNAPSHOT_BALANCE | crate::SNAPSHOT_VTXOS
+    );
+    assert!(snapshot.balance_error.is_empty());
+    assert!(snapshot.vtxos_error.is_empty());
+    // Fields that weren't requested are left empty
+    assert!(snapshot.movements.is_empty());
+    assert!(snapshot.onchain_utxos.is_empty());
+
+    let full = cxx::get_wallet_snapshot(crate::SNAPSHOT_ALL).unwrap();
+    assert!(full.ark_info_error.is_empty());
+    assert!(full.movements.len() <= crate::SNAPSHOT_MOVEMENTS_PAGE_SIZE);
+}
+
+#[test]
+fn test_config_deltas_round_trip_through_merge() {
+    let (_temp_dir, mut opts) = setup_test_wallet_opts();
+    opts.config.bitcoind = "http://127.0.0.1:18443".to_string();
+    opts.config.htlc_recv_claim_delta = 24;
+    opts.config.vtxo_exit_margin = 14;
+    opts.config.round_tx_required_confirmations = 3;
+
+    let create_opts = crate::ffi_config_to_config(opts).unwrap();
+    let (config, network) = crate::merge_config_opts(create_opts).unwrap();
+
+    assert_eq!(network, bark::ark::bitcoin::Network::Regtest);
+    assert_eq!(config.htlc_recv_claim_delta, 24);
+    assert_eq!(config.vtxo_exit_margin, 14);
+    assert_eq!(config.round_tx_required_confirmations, 3);
+    assert_eq!(config.vtxo_refresh_expiry_threshold, 3600);
+}
+
+#[test]
+fn test_get_default_config() {
+    let signet = cxx::get_default_config("signet").unwrap();
+    assert_eq!(signet.server_address, "https://ark.signet.2nd.dev");
+    assert_eq!(signet.esplora_address, "https://esplora.signet.2nd.dev");
+    assert!(signet.bitcoind_address.is_empty());
+    assert!(!signet.has_password);
+    assert_eq!(signet.fallback_fee_rate, 100_000);
+    assert_eq!(signet.htlc_recv_claim_delta, 18);
+    assert_eq!(signet.vtxo_exit_margin, 12);
+
+    let regtest = cxx::get_default_config("regtest").unwrap();
+    assert!(!regtest.bitcoind_address.is_empty());
+    assert!(regtest.esplora_address.is_empty());
+
+    let mainnet = cxx::get_default_config("mainnet").unwrap();
+    assert!(mainnet.server_address.is_empty());
+    assert!(mainnet.round_tx_required_confirmations > signet.round_tx_required_confirmations);
+
+    assert!(cxx::get_default_config("testnet4").is_err());
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_get_config_round_trip_ffi() {
+    cxx::init_logger().unwrap();
+    let (temp_dir, mut opts) = setup_test_wallet_opts();
+    opts.config.bitcoind = "http://127.0.0.1:18443".to_string();
+    opts.config.bitcoind_user = "second".to_string();
+    opts.config.bitcoind_pass = "ark".to_string();
+    opts.config.fallback_fee_rate = 10_000;
+    let mnemonic = opts.mnemonic.clone();
+    let datadir = temp_dir.path().to_str().unwrap();
+
+    if cxx::is_wallet_loaded().unwrap() {
+        cxx::close_wallet().unwrap();
+    }
+    cxx::create_wallet(datadir, opts).unwrap();
+
+    let (_, mut opts) = setup_test_wallet_opts();
+    opts.mnemonic = mnemonic;
+    opts.config.bitcoind = "http://127.0.0.1:18443".to_string();
+    opts.config.bitcoind_user = "second".to_string();
+    opts.config.bitcoind_pass = "ark".to_string();
+    opts.config.fallback_fee_rate = 10_000;
+    cxx::load_wallet(datadir, opts).unwrap();
+
+    let config = cxx::get_config().unwrap();
+    assert_eq!(config.server_address, "http://127.0.0.1:50051");
+    assert_eq!(config.esplora_address, "");
+    assert_eq!(config.bitcoind_address, "http://127.0.0.1:18443");
+    assert_eq!(config.bitcoind_cookiefile, "");
+    assert_eq!(config.bitcoind_user, "second");
+    assert!(config.has_password);
+    assert_eq!(config.vtxo_refresh_expiry_threshold, 3600);
+    assert_eq!(config.fallback_fee_rate, 10_000);
+    assert_eq!(config.htlc_recv_claim_delta, 18);
+    assert_eq!(config.vtxo_exit_margin, 12);
+    assert_eq!(config.round_tx_required_confirmations, 0);
+
+    cxx::close_wallet().unwrap();
+}
+
+#[test]
+fn test_quarantine_vtxos_unknown_to_server() {
+    use std::collections::HashSet;
+
+    let (_temp_dir, store) = temp_store();
+
+    let known = format!("{}:0", "aa".repeat(32));
+    let forgotten = format!("{}:1", "bb".repeat(32));
+    let spendable = vec![(known.clone(), 10_000), (forgotten.clone(), 25_000)];
+
+    // Stubbed server listing that lost one of our vtxos
+    let server_listing: HashSet<String> = [known.clone()].into_iter().collect();
+    let unknown = crate::find_unknown_vtxos(&spendable, &server_listing);
+    assert_eq!(unknown, vec![(forgotten.clone(), 25_000)]);
+
+    for (vtxo_id, amount_sat) in &unknown {
+        assert!(
+            store
+                .quarantine_vtxo(vtxo_id, *amount_sat, "unknown to server")
+                .unwrap()
+        );
+        // Quarantining twice is a no-op
+        assert!(
+            !store
+                .quarantine_vtxo(vtxo_id, *amount_sat, "unknown to server")
+                .unwrap()
+        );
+    }
+
+    let quarantined = store.get_quarantined_vtxos().unwrap();
+    assert_eq!(quarantined.len(), 1);
+    assert_eq!(quarantined[0].vtxo_id, forgotten);
+    assert_eq!(quarantined[0].amount_sat, 25_000);
+
+    let quarantined_ids = quarantined.into_iter().map(|q| q.vtxo_id).collect();
+    assert_eq!(
+        crate::quarantined_spendable_amount(&spendable, &quarantined_ids),
+        Amount::from_sat(25_000)
+    );
+
+    assert!(store.release_quarantined_vtxo(&forgotten).unwrap());
+    assert!(!store.release_quarantined_vtxo(&forgotten).unwrap());
+    assert!(store.get_quarantined_vtxos().unwrap().is_empty());
+}
+
+#[test]
+fn test_offline_mode() {
+    let mut manager = crate::WalletManager::new();
+    assert!(!manager.is_offline_mode());
+    assert!(manager.ensure_online().is_ok());
+
+    manager.set_offline_mode(true);
+    assert!(manager.is_offline_mode());
+    let err = manager.ensure_online().unwrap_err();
+    assert_eq!(err.to_string(), "wallet is in offline mode");
+
+    manager.set_offline_mode(false);
+    assert!(manager.ensure_online().is_ok());
+}
+
+#[test]
+fn test_get_library_info() {
+    let info = cxx::get_library_info().unwrap();
+
+    let manifest =
+        fs::read_to_string(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml")).unwrap();
+    let version_line = format!("version = \"{}\"", info.version);
+    assert!(manifest.contains(&version_line));
+
+    assert!(!info.bark_version.is_empty());
+    assert!(manifest.contains(&format!("tag = \"bark-{}\"", info.bark_version)));
+    assert_eq!(info.persister_backend, "sqlite");
+    assert!(info.features.iter().any(|f| f == "sqlite"));
+}
+
+#[test]
+#[ignore = "requires live regtest backend and a funded wallet"]
+fn test_get_onchain_utxos_v2_ffi() {
+    let _fixture = WalletTestFixture::new();
+    cxx::onchain_sync().unwrap();
+
+    let typed = cxx::get_onchain_utxos_v2().unwrap();
+    let json: Vec<serde_json::Value> =
+        serde_json::from_str(&cxx::onchain_utxos().unwrap()).unwrap();
+    assert_eq!(
+        typed.len(),
+        json.iter()
+            .filter(|utxo| utxo.get("outpoint").is_some())
+            .count()
+    );
+    for utxo in &typed {
+        assert_eq!(utxo.txid.len(), 64);
+        assert!(utxo.amount_sat > 0);
+        assert!(!utxo.script_pubkey.is_empty());
+    }
+}
+
+#[test]
+fn test_migration_sequence_is_contiguous() {
+    use crate::store::MigrationRegistry;
+
+    let registry = crate::store::migration_registry();
+    assert!(registry.verify().is_ok());
+    for (idx, migration) in registry.migrations().iter().enumerate() {
+        assert_eq!(migration.version, idx as u32 + 1);
+    }
+
+    // A fresh store ends up at the latest version
+    let (_temp_dir, store) = temp_store();
+    assert_eq!(store.schema_version().unwrap(), registry.latest_version());
+
+    let gap = MigrationRegistry::new()
+        .register(1, "m0001_first", "")
+        .register(3, "m0003_third", "");
+    assert!(gap.verify().is_err());
+
+    let out_of_order = MigrationRegistry::new()
+        .register(2, "m0002_second", "")
+        .register(1, "m0001_first", "");
+    assert!(out_of_order.verify().is_err());
+
+    let misnamed = MigrationRegistry::new().register(1, "m0002_first", "");
+    assert!(misnamed.verify().is_err());
+}
+
+#[test]
+fn test_store_schema_too_new() {
+    use crate::store::{Store, StoreError};
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("nitro.sqlite");
+    let supported_version = crate::store::migration_registry().latest_version();
+
+    // Fixture of a store migrated by a future version of the app
+    Store::open(&path).unwrap();
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute(
+            "INSERT INTO nitro_migrations (version, name, applied_at) VALUES (?1, 'm9999_future', 0)",
+            [supported_version + 1],
+        )
+        .unwrap();
+    }
+
+    let err = Store::open(&path)
+        .err()
+        .expect("Opening a newer store must fail");
+    assert_eq!(
+        err.downcast_ref::<StoreError>(),
+        Some(&StoreError::SchemaTooNew {
+            db_version: supported_version + 1,
+            supported_version,
+        })
+    );
+
+    let hint = cxx::get_required_app_version_hint(temp_dir.path().to_str().unwrap()).unwrap();
+    assert!(hint.contains("update"));
+}
+
+#[test]
+fn test_wallet_db_schema_too_new() {
+    use crate::store::{StoreError, check_wallet_db_schema};
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("db.sqlite");
+    check_wallet_db_schema(&path, 5).unwrap();
+
+    // Fixture of a wallet database migrated by a future version of bark
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE migrations (value INTEGER NOT NULL);
+            INSERT INTO migrations (value) VALUES (1), (9999);",
+        )
+        .unwrap();
+    }
+    check_wallet_db_schema(&path, 9999).unwrap();
+    let err = check_wallet_db_schema(&path, 5)
+        .err()
+        .expect("A newer wallet database must be refused");
+    assert_eq!(
+        err.downcast_ref::<StoreError>(),
+        Some(&StoreError::WalletSchemaTooNew {
+            db_version: 9999,
+            supported_version: 5,
+        })
+    );
+
+    // Refused before bark gets to migrate it, and explained to the user
+    let hint = cxx::get_required_app_version_hint(temp_dir.path().to_str().unwrap()).unwrap();
+    assert!(hint.contains("update"));
+    let create_opts = crate::ffi_config_to_config(setup_test_wallet_opts().1).unwrap();
+    let mnemonic = create_opts.mnemonic.expose_secret().clone();
+    let (config, _) = crate::merge_config_opts(create_opts).unwrap();
+    let mut manager = crate::WalletManager::new();
+    let err = crate::TOKIO_RUNTIME
+        .block_on(manager.load_wallet(temp_dir.path(), mnemonic, config, false))
+        .unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<StoreError>(),
+        Some(&StoreError::WalletSchemaTooNew {
+            db_version: 9999,
+            supported_version: crate::supported_wallet_db_version(temp_dir.path()).unwrap(),
+        })
+    );
+    assert!(!temp_dir.path().join("db-version-probe.sqlite").exists());
+
+    let foreign = temp_dir.path().join("foreign.sqlite");
+    rusqlite::Connection::open(&foreign)
+        .unwrap()
+        .execute("CREATE TABLE unrelated (id INTEGER)", [])
+        .unwrap();
+    let err = check_wallet_db_schema(&foreign, 5).unwrap_err();
+    assert_eq!(err.downcast_ref::<StoreError>(), Some(&StoreError::Corrupt));
+}
+
+#[test]
+fn test_store_without_migrations_table_is_corrupt() {
+    use crate::store::{Store, StoreError};
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("nitro.sqlite");
+    {
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute("CREATE TABLE unrelated (id INTEGER)", [])
+            .unwrap();
+    }
+
+    let err = Store::open(&path)
+        .err()
+        .expect("Opening a foreign db must fail");
+    assert_eq!(err.downcast_ref::<StoreError>(), Some(&StoreError::Corrupt));
+
+    // A missing or compatible store needs no hint
+    let other_dir = tempdir().expect("Failed to create temp dir");
+    assert_eq!(
+        cxx::get_required_app_version_hint(other_dir.path().to_str().unwrap()).unwrap(),
+        ""
+    );
+    Store::open(&other_dir.path().join("nitro.sqlite")).unwrap();
+    assert_eq!(
+        cxx::get_required_app_version_hint(other_dir.path().to_str().unwrap()).unwrap(),
+        ""
+    );
+}
+
+#[test]
+fn test_fee_rate_or_min_relay() {
+    use bark::ark::bitcoin::FeeRate;
+
+    let configured = FeeRate::from_sat_per_vb(5).unwrap();
+    assert_eq!(
+        crate::onchain::fee_rate_or_min_relay(Some(configured)),
+        configured
+    );
+    assert_eq!(
+        crate::onchain::fee_rate_or_min_relay(None),
+        FeeRate::BROADCAST_MIN
+    );
+}
+
+#[test]
+fn test_estimate_exit_fees() {
+    use crate::utils::{EXIT_CPFP_CHILD_VSIZE, ExitPackage, estimate_exit_fees};
+    use bark::ark::bitcoin::FeeRate;
+
+    let packages = vec![
+        ExitPackage {
+            vtxo_id: "a".to_string(),
+            exit_tx_vsizes: vec![200, 150],
+            exit_delta: 144,
+        },
+        ExitPackage {
+            vtxo_id: "b".to_string(),
+            exit_tx_vsizes: vec![120],
+            exit_delta: 72,
+        },
+    ];
+    let fee_rate = FeeRate::from_sat_per_vb(10).unwrap();
+
+    let estimate = estimate_exit_fees(&packages, fee_rate);
+    assert_eq!(estimate.per_vtxo.len(), 2);
+    assert_eq!(estimate.per_vtxo[0].vtxo_id, "a");
+    assert_eq!(
+        estimate.per_vtxo[0].fee_sat,
+        (350 + 2 * EXIT_CPFP_CHILD_VSIZE) * 10
+    );
+    assert_eq!(estimate.per_vtxo[0].tx_count, 4);
+    assert_eq!(
+        estimate.per_vtxo[1].fee_sat,
+        (120 + EXIT_CPFP_CHILD_VSIZE) * 10
+    );
+    assert_eq!(estimate.per_vtxo[1].tx_count, 2);
+    assert_eq!(
+        estimate.total_fee_sat,
+        estimate.per_vtxo[0].fee_sat + estimate.per_vtxo[1].fee_sat
+    );
+    assert_eq!(estimate.tx_count, 6);
+    assert_eq!(estimate.claimable_after_blocks, 144);
+
+    let empty = estimate_exit_fees(&[], fee_rate);
+    assert_eq!(empty.total_fee_sat, 0);
+    assert_eq!(empty.tx_count, 0);
+    assert_eq!(empty.claimable_after_blocks, 0);
+}
+
+#[test]
+fn test_movement_kind_from_subsystem() {
+    use crate::utils::MovementKind;
+
+    let cases = [
+        ("bark.board", "board", MovementKind::Board),
+        ("bark.round", "offboard", MovementKind::Offboard),
+        ("bark.exit", "start", MovementKind::Exit),
+        ("bark.round", "refresh", MovementKind::Refresh),
+        ("bark.arkoor", "receive", MovementKind::Receive),
+        ("bark.arkoor", "send", MovementKind::Send),
+        ("bark.lightning_receive", "receive", MovementKind::Receive),
+        ("bark.lightning_send", "send", MovementKind::Send),
+        ("bark.lightning_send", "pay_invoice", MovementKind::Send),
+        ("something.else", "new", MovementKind::Unknown),
+    ];
+    for (name, kind, expected) in cases {
+        assert_eq!(
+            MovementKind::from_subsystem(name, kind),
+            expected,
+            "{} / {}",
+            name,
+            kind
+        );
+    }
+
+    assert!(
+        cxx::ffi::CxxMovementKind::from(MovementKind::Offboard)
+            == cxx::ffi::CxxMovementKind::Offboard
+    );
+}
+
+#[test]
+fn test_validate_onchain_outputs() {
+    use crate::utils::validate_onchain_outputs;
+    use bark::ark::bitcoin::{Address, Network};
+
+    let addr = |s: &str| {
+        Address::from_str(s)
+            .unwrap()
+            .require_network(Network::Regtest)
+            .unwrap()
+    };
+    let a = addr("bcrt1q6rz28mcfaxtmd6v789l9rrlrusdprr9pz3cppk");
+    let b = addr("bcrt1pqypqxpq9qcrsszg2pvxq6rs0zqg3yyc5z5tpwxqergd3c8g7rusq58nmtx");
+
+    let outputs = vec![
+        (a.clone(), Amount::from_sat(10_000)),
+        (b.clone(), Amount::from_sat(20_000)),
+    ];
+    validate_onchain_outputs(
+        &outputs,
+        Some(Amount::from_sat(50_000)),
+        Amount::from_sat(30_000),
+    )
+    .unwrap();
+    validate_onchain_outputs(&outputs, None, Amount::from_sat(30_000)).unwrap();
+
+    // Empty batch
+    assert!(validate_onchain_outputs(&[], None, Amount::from_sat(30_000)).is_err());
+    // Above the per-output maximum
+    assert!(
+        validate_onchain_outputs(
+            &outputs,
+            Some(Amount::from_sat(15_000)),
+            Amount::from_sat(30_000)
+        )
+        .is_err()
+    );
+    // Total above the spendable balance
+    assert!(validate_onchain_outputs(&outputs, None, Amount::from_sat(29_999)).is_err());
+    // Dust output
+    assert!(
+        validate_onchain_outputs(
+            &[(a, Amount::from_sat(100))],
+            None,
+            Amount::from_sat(30_000)
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn test_lifetime_stats() {
+    use crate::store::{get_daily_movement_changes, get_subsystem_totals, open_bark_db_read_only};
+    use crate::utils::{BalanceChange, MovementKind, WalletStats, lifetime_stats};
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("db.sqlite");
+    // Fixture of the table bark keeps movements in
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE bark_movement (
+            id INTEGER PRIMARY KEY,
+            subsystem_name TEXT NOT NULL,
+            subsystem_kind TEXT NOT NULL,
+            effective_balance INTEGER NOT NULL,
+            created_at DATETIME NOT NULL
+        );",
+    )
+    .unwrap();
+    let read = || open_bark_db_read_only(&path).unwrap();
+    let stats =
+        |from_ts, to_ts| lifetime_stats(&get_subsystem_totals(&read(), from_ts, to_ts).unwrap());
+
+    assert_eq!(stats(i64::MIN, i64::MAX), WalletStats::default());
+    assert!(get_daily_movement_changes(&read()).unwrap().is_empty());
+
+    conn.execute_batch(
+        "INSERT INTO bark_movement
+            (subsystem_name, subsystem_kind, effective_balance, created_at) VALUES
+            ('bark.arkoor', 'receive', 5000, '2023-11-14 22:14:10'),
+            ('bark.board', 'board', 100000, '2023-11-14 22:15:00'),
+            ('bark.arkoor', 'send', -20000, '2023-11-14 22:16:40'),
+            ('bark.lightning_send', 'send', -10000, '2023-11-14 22:18:20'),
+            -- A failed send doesn't change the balance
+            ('bark.lightning_send', 'send', 0, '2023-11-14 22:20:00'),
+            ('bark.offboard', 'offboard', -30000, '2023-11-16 02:00:00');",
+    )
+    .unwrap();
+
+    let all = stats(i64::MIN, i64::MAX);
+    assert_eq!(all.movement_count, 6);
+    assert_eq!(all.total_received_sat, 105_000);
+    assert_eq!(all.total_sent_sat, 60_000);
+    assert_eq!(all.first_movement_at, Some(1_700_000_050));
+
+    let kinds = all.per_kind.iter().map(|k| k.kind).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            MovementKind::Receive,
+            MovementKind::Board,
+            MovementKind::Send,
+            MovementKind::Offboard
+        ]
+    );
+    // Both send subsystems count as sends
+    let send = &all.per_kind[2];
+    assert_eq!(send.count, 3);
+    assert_eq!(send.sent_sat, 30_000);
+    assert_eq!(send.received_sat, 0);
+
+    // The range bounds are inclusive
+    let range = stats(1_700_000_100, 1_700_000_300);
+    assert_eq!(range.movement_count, 3);
+    assert_eq!(range.total_received_sat, 100_000);
+    assert_eq!(range.total_sent_sat, 30_000);
+    assert_eq!(range.first_movement_at, Some(1_700_000_100));
+
+    let change = |at, offchain_sat| BalanceChange {
+        at,
+        offchain_sat,
+        onchain_sat: 0,
+    };
+    assert_eq!(
+        get_daily_movement_changes(&read()).unwrap(),
+        vec![
+            change(1_700_000_050, 75_000),
+            change(1_700_100_000, -30_000)
+        ]
+    );
+}
+
+#[test]
+fn test_sync_log() {
+    let (temp_dir, store) = temp_store();
+    assert_eq!(store.last_successful_sync("wallet").unwrap(), None);
+
+    store.record_sync("wallet", Some(100), false).unwrap();
+    assert_eq!(store.last_successful_sync("wallet").unwrap(), None);
+
+    store.record_sync("wallet", Some(101), true).unwrap();
+    let last = store.last_successful_sync("wallet").unwrap().unwrap();
+    assert!((last - crate::store::now_timestamp()).abs() < 5);
+    assert_eq!(store.last_successful_sync("onchain").unwrap(), None);
+
+    // Old rows are pruned
+    for height in 0..150 {
+        store.record_sync("wallet", Some(height), true).unwrap();
+    }
+    assert!(store.last_successful_sync("wallet").unwrap().is_some());
+    let conn = crate::store::open_bark_db_read_only(&temp_dir.path().join("nitro.sqlite")).unwrap();
+    let (rows, oldest): (i64, u32) = conn
+        .query_row(
+            "SELECT COUNT(*), MIN(block_height) FROM bark_sync_log WHERE sync_type = 'wallet'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(rows, 100);
+    assert_eq!(oldest, 50);
+}
+
+#[test]
+fn test_vtxo_id_helpers() {
+    let txid = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+    let valid = format!("{}:0", txid);
+
+    assert!(cxx::is_valid_vtxo_id(&valid).unwrap());
+    assert!(cxx::is_valid_vtxo_id(&format!("{}:4294967295", txid)).unwrap());
+    assert_eq!(cxx::vtxo_id_to_string(&valid).unwrap(), valid);
+    // Hex is normalized to lowercase
+    assert_eq!(
+        cxx::vtxo_id_to_string(&format!("{}:7", txid.to_uppercase())).unwrap(),
+        format!("{}:7", txid)
+    );
+
+    let invalid = [
+        "".to_string(),
+        txid.to_string(),
+        format!("{}:", txid),
+        format!("{}:-1", txid),
+        format!("{}:4294967296", txid),
+        format!("{}:0:1", txid),
+        format!("{}:0", &txid[2..]),
+        format!("{}zz:0", &txid[2..]),
+        format!(" {}:0", txid),
+    ];
+    for id in invalid {
+        assert!(!cxx::is_valid_vtxo_id(&id).unwrap(), "{}", id);
+        assert!(cxx::vtxo_id_to_string(&id).is_err(), "{}", id);
+    }
+
+    assert_eq!(
+        crate::utils::parse_vtxo_ids(&[valid.clone(), valid.clone()])
+            .unwrap()
+            .len(),
+        2
+    );
+    assert!(crate::utils::parse_vtxo_ids(&[valid, "nope".to_string()]).is_err());
+}
+
+#[test]
+fn test_apply_live_config() {
+    use crate::utils::{apply_live_config, default_config};
+    use bark::ark::bitcoin::{FeeRate, Network};
+
+    let mut current = default_config(Network::Regtest);
+    let unchanged = apply_live_config(&mut current.clone(), &current.clone());
+    assert!(unchanged.applied.is_empty());
+    assert!(unchanged.requires_reload.is_empty());
+
+    let mut new = current.clone();
+    new.vtxo_refresh_expiry_threshold = 500;
+    new.fallback_fee_rate = Some(FeeRate::from_sat_per_vb(7).unwrap());
+    new.server_address = "http://127.0.0.1:4545".to_string();
+    new.vtxo_exit_margin = 20;
+
+    let result = apply_live_config(&mut current, &new);
+    assert_eq!(
+        result.applied,
+        vec!["vtxo_refresh_expiry_threshold", "fallback_fee_rate"]
+    );
+    assert_eq!(
+        result.requires_reload,
+        vec!["server_address", "vtxo_exit_margin"]
+    );
+    assert_eq!(current.vtxo_refresh_expiry_threshold, 500);
+    assert_eq!(current.fallback_fee_rate, new.fallback_fee_rate);
+    // Fields needing a reload keep the value the wallet runs with
+    assert_eq!(current.server_address, "http://127.0.0.1:3535");
+    assert_eq!(current.vtxo_exit_margin, 12);
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_apply_config_ffi() {
+    let _fixture = WalletTestFixture::new();
+    let (_temp_dir, mut opts) = setup_test_wallet_opts();
+    let before = cxx::get_next_required_refresh_blockheight().unwrap();
+
+    opts.config.vtxo_refresh_expiry_threshold = 100;
+    opts.config.fallback_fee_rate = 5_000;
+    let result = cxx::apply_config(opts.config).unwrap();
+    assert!(
+        result
+            .applied
+            .contains(&"vtxo_refresh_expiry_threshold".to_string())
+    );
+
+    let config = cxx::get_config().unwrap();
+    assert_eq!(config.vtxo_refresh_expiry_threshold, 100);
+    assert_eq!(config.fallback_fee_rate, 5_000);
+
+    let after = cxx::get_next_required_refresh_blockheight().unwrap();
+    let first = cxx::get_first_expiring_vtxo_blockheight().unwrap();
+    if !first.is_null() {
+        assert!(!before.is_null());
+        assert_eq!(unsafe { *after }, unsafe { *first } - 100);
+    }
+}
+
+#[test]
+fn test_exit_state_name() {
+    use crate::utils::exit_state_name;
+
+    assert_eq!(
+        exit_state_name(&serde_json::json!({ "type": "awaiting-delta", "tip_height": 100 })),
+        "awaiting-delta"
+    );
+    assert_eq!(exit_state_name(&serde_json::json!("claimed")), "unknown");
+    assert_eq!(
+        exit_state_name(&serde_json::json!({ "type": 1 })),
+        "unknown"
+    );
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_sync_exits_with_progress_ffi() {
+    let _fixture = WalletTestFixture::new();
+    let statuses = cxx::sync_exits_with_progress().unwrap();
+    assert!(statuses.is_empty());
+}
+
+#[test]
+fn test_metrics_aggregation() {
+    use crate::metrics::{MetricEntry, aggregate, classify_error, percentile, push_bounded};
+    use std::collections::VecDeque;
+
+    assert_eq!(percentile(&[], 50), 0);
+    assert_eq!(percentile(&[7], 95), 7);
+    let sorted = (1..=100).collect::<Vec<u64>>();
+    assert_eq!(percentile(&sorted, 50), 50);
+    assert_eq!(percentile(&sorted, 95), 95);
+    assert_eq!(percentile(&[10, 20, 30], 50), 20);
+
+    let entry = |operation, duration_ms, error_class| MetricEntry {
+        operation,
+        duration_ms,
+        success: error_class.is_none(),
+        error_class,
+        recorded_at: 0,
+    };
+    let entries = vec![
+        entry("sync", 300, None),
+        entry("board_amount", 2_000, Some("network")),
+        entry("sync", 100, None),
+        entry("sync", 200, Some("timeout")),
+    ];
+    let stats = aggregate(&entries);
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats[0].operation, "sync");
+    assert_eq!(stats[0].count, 3);
+    assert_eq!(stats[0].failure_count, 1);
+    assert_eq!(stats[0].p50_ms, 200);
+    assert_eq!(stats[0].p95_ms, 300);
+    assert_eq!(stats[0].max_ms, 300);
+    assert_eq!(stats[1].operation, "board_amount");
+    assert_eq!(stats[1].failure_count, 1);
+
+    // The ring buffer drops the oldest entries once full
+    let mut ring = VecDeque::new();
+    for i in 0..10 {
+        push_bounded(&mut ring, entry("sync", i, None), 4);
+    }
+    assert_eq!(ring.len(), 4);
+    assert_eq!(ring.front().unwrap().duration_ms, 6);
+    assert_eq!(ring.back().unwrap().duration_ms, 9);
+
+    assert_eq!(
+        classify_error(&anyhow::anyhow!("Wallet not loaded")),
+        "not_loaded"
+    );
+    assert_eq!(
+        classify_error(&anyhow::anyhow!("wallet is in offline mode")),
+        "offline"
+    );
+    assert_eq!(
+        classify_error(&anyhow::anyhow!("request timed out").context("Failed to sync")),
+        "timeout"
+    );
+    assert_eq!(classify_error(&anyhow::anyhow!("boom")), "other");
+}
+
+#[test]
+fn test_timed_records_metrics() {
+    let result: anyhow::Result<u32> = crate::TOKIO_RUNTIME
+        .block_on(crate::metrics::timed("test_timed_operation", async {
+            anyhow::bail!("Wallet not loaded")
+        }));
+    assert!(result.is_err());
+
+    let snapshot = cxx::get_metrics_snapshot().unwrap();
+    let entry = snapshot
+        .entries
+        .iter()
+        .rev()
+        .find(|e| e.operation == "test_timed_operation")
+        .expect("timing is recorded");
+    assert!(!entry.success);
+    assert_eq!(entry.error_class, "not_loaded");
+
+    // Public operations are timed under their own name, loaded or not
+    let _ = crate::TOKIO_RUNTIME.block_on(crate::get_ark_info());
+    let _ = crate::TOKIO_RUNTIME.block_on(crate::history());
+    let _ = crate::TOKIO_RUNTIME.block_on(crate::onchain::utxos());
+    let snapshot = cxx::get_metrics_snapshot().unwrap();
+    for operation in ["get_ark_info", "history", "onchain_utxos"] {
+        assert!(
+            snapshot.entries.iter().any(|e| e.operation == operation),
+            "{} is timed",
+            operation
+        );
+    }
+}
+
+#[test]
+fn test_count_unlocked_htlc_vtxos() {
+    use crate::utils::{CheckedHtlcSend, count_unlocked_htlc_vtxos};
+    use std::collections::HashSet;
+
+    let send = |hash: &str, htlc_vtxo_count, paid| CheckedHtlcSend {
+        payment_hash: hash.to_string(),
+        htlc_vtxo_count,
+        paid,
+    };
+    let checked = vec![
+        // Revoked: no longer pending and not paid
+        send("expired", 2, false),
+        // Still waiting on the recipient
+        send("in_flight", 1, false),
+        // Settled, its VTXOs went to the recipient
+        send("paid", 3, true),
+        send("also_expired", 1, false),
+    ];
+    let still_pending = HashSet::from(["in_flight".to_string()]);
+
+    assert_eq!(count_unlocked_htlc_vtxos(&checked, &still_pending), 3);
+    assert_eq!(count_unlocked_htlc_vtxos(&[], &still_pending), 0);
+}
+
+#[test]
+#[ignore = "requires live regtest backend and a funded wallet"]
+fn test_cancel_expired_htlcs_unlocks_vtxos() {
+    use bark::vtxo::VtxoStateKind;
+
+    let _fixture = WalletTestFixture::new();
+    let locked_count = || {
+        crate::TOKIO_RUNTIME
+            .block_on(crate::get_vtxo_count(&[VtxoStateKind::Locked]))
+            .unwrap()
+    };
+    let spendable_before = cxx::get_balance(false).unwrap().spendable;
+    assert!(spendable_before > 0);
+    assert_eq!(locked_count(), 0);
+
+    // Nobody can route to the invoice's node, so the send fails and its
+    // HTLC VTXOs stay locked until the HTLCs are revoked
+    let invoice = lnurl_test_invoice(10_000_000, "unroutable");
+    let _ = cxx::pay_lightning_invoice(&invoice.to_string(), std::ptr::null(), std::ptr::null());
+
+    cxx::cancel_expired_htlcs().unwrap();
+    // Locked -> Spendable: nothing is left locked and the funds of the
+    // failed send are spendable again
+    assert_eq!(locked_count(), 0);
+    assert_eq!(cxx::get_balance(false).unwrap().spendable, spendable_before);
+}
+
+#[test]
+fn test_lightning_fee_budget() {
+    use crate::store::LightningFeeCap;
+    use crate::utils::{
+        FeeBudgetExceeded, expected_lightning_fee, lightning_fee_ppm, lightning_send_fee,
+    };
+
+    let cap = LightningFeeCap::default();
+    assert_eq!(cap.cap_bps, 100);
+    // 1% of the amount, rounded up
+    assert_eq!(cap.budget_sat(100_000), 1_000);
+    assert_eq!(cap.budget_sat(1_050), 11);
+    // but never below the floor
+    assert_eq!(cap.budget_sat(100), 5);
+    assert_eq!(cap.budget_sat(0), 5);
+
+    let strict = LightningFeeCap {
+        cap_bps: 50,
+        floor_sat: 0,
+    };
+    assert_eq!(strict.budget_sat(10_000), 50);
+    assert_eq!(strict.budget_sat(0), 0);
+    assert!(
+        LightningFeeCap {
+            cap_bps: 10_001,
+            floor_sat: 0
+        }
+        .validate()
+        .is_err()
+    );
+
+    assert_eq!(
+        lightning_send_fee(Amount::from_sat(10_020), Amount::from_sat(10_000)),
+        Amount::from_sat(20)
+    );
+    assert_eq!(
+        lightning_send_fee(Amount::from_sat(9_000), Amount::from_sat(10_000)),
+        Amount::ZERO
+    );
+
+    // 20 sats on 10k is 0.2%, which expects 200 sats on 100k
+    let fee_ppm = lightning_fee_ppm(Amount::from_sat(20), Amount::from_sat(10_000)).unwrap();
+    assert_eq!(fee_ppm, 2_000);
+    assert_eq!(lightning_fee_ppm(Amount::from_sat(20), Amount::ZERO), None);
+    assert_eq!(
+        expected_lightning_fee(Amount::from_sat(100_000), fee_ppm),
+        Amount::from_sat(200)
+    );
+    assert_eq!(
+        expected_lightning_fee(Amount::from_sat(1), fee_ppm),
+        Amount::from_sat(1)
+    );
+    let err = FeeBudgetExceeded {
+        expected_fee_sat: 200,
+        budget_sat: 50,
+    };
+    assert!(err.to_string().contains("200 sats"));
+    assert!(err.to_string().contains("50 sats"));
+
+    let (_temp_dir, store) = temp_store();
+    assert_eq!(store.get_lightning_fee_cap().unwrap(), cap);
+    store.set_lightning_fee_cap(&strict).unwrap();
+    assert_eq!(store.get_lightning_fee_cap().unwrap(), strict);
+    assert_eq!(store.get_observed_lightning_fee_ppm().unwrap(), None);
+    store.set_observed_lightning_fee_ppm(fee_ppm).unwrap();
+    assert_eq!(
+        store.get_observed_lightning_fee_ppm().unwrap(),
+        Some(fee_ppm)
+    );
+}
+
+#[test]
+fn test_reset_wallet() {
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let datadir = temp_dir.path().join("wallet");
+    fs::create_dir_all(datadir.join("subdir")).unwrap();
+    fs::write(datadir.join("db.sqlite"), b"").unwrap();
+    fs::write(datadir.join("subdir").join("file"), b"data").unwrap();
+    crate::store::Store::open(&datadir.join("nitro.sqlite")).unwrap();
+    let datadir_str = datadir.to_str().unwrap();
+
+    // Nothing is deleted without confirmation
+    assert!(cxx::reset_wallet(datadir_str, false).is_err());
+    assert!(datadir.join("db.sqlite").exists());
+
+    cxx::reset_wallet(datadir_str, true).unwrap();
+    assert!(datadir.is_dir());
+    assert_eq!(fs::read_dir(&datadir).unwrap().count(), 0);
+
+    // A directory without a wallet in it is left alone
+    let other = temp_dir.path().join("other");
+    fs::create_dir_all(&other).unwrap();
+    fs::write(other.join("keep"), b"").unwrap();
+    assert!(cxx::reset_wallet(other.to_str().unwrap(), true).is_err());
+    assert!(other.join("keep").exists());
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_reset_other_datadir_keeps_wallet_loaded() {
+    let _fixture = WalletTestFixture::new();
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    crate::store::Store::open(&temp_dir.path().join("nitro.sqlite")).unwrap();
+    cxx::reset_wallet(temp_dir.path().to_str().unwrap(), true).unwrap();
+
+    assert!(cxx::is_wallet_loaded().unwrap());
+    assert!(cxx::get_wallet_state().unwrap() == ffi::CxxWalletState::Loaded);
+}
+
+#[test]
+fn test_event_journal() {
+    use crate::store::JournalEntry;
+
+    let (_temp_dir, store) = temp_store();
+
+    let entry = |created_at, operation: &str, error: Option<&str>| JournalEntry {
+        created_at,
+        operation: operation.to_string(),
+        params_hash: crate::utils::params_hash(operation),
+        result: if error.is_some() { "error" } else { "ok" }.to_string(),
+        error: error.map(String::from),
+    };
+    store
+        .append_journal_entry(&entry(100, "board_amount", None))
+        .unwrap();
+    store
+        .append_journal_entry(&entry(200, "send_onchain", Some("insufficient funds")))
+        .unwrap();
+    store
+        .append_journal_entry(&entry(300, "maintenance", None))
+        .unwrap();
+
+    let entries = store.get_journal_entries(150, 300).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].operation, "send_onchain");
+    assert_eq!(entries[0].params_hash.len(), 16);
+
+    let line: serde_json::Value = serde_json::from_str(&entries[0].to_json_line()).unwrap();
+    assert_eq!(line["timestamp"], 200);
+    assert_eq!(line["operation"], "send_onchain");
+    assert_eq!(line["result"], "error");
+    assert_eq!(line["error"], "insufficient funds");
+    let line: serde_json::Value = serde_json::from_str(&entries[1].to_json_line()).unwrap();
+    assert!(line["error"].is_null());
+
+    // Retention: by age first, then by count
+    assert_eq!(store.prune_journal(150, 10).unwrap(), 1);
+    assert_eq!(store.get_journal_entries(0, i64::MAX).unwrap().len(), 2);
+    assert_eq!(store.prune_journal(0, 1).unwrap(), 1);
+    let remaining = store.get_journal_entries(0, i64::MAX).unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].operation, "maintenance");
+}
+
+#[test]
+fn test_redact_sensitive() {
+    use crate::utils::redact_sensitive;
+
+    let preimage = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+    let invoice = "lnbcrt10u1p5abcdefpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypq";
+
+    let redacted = redact_sensitive(&format!(
+        "Failed to pay '{}' with preimage {}: route not found",
+        invoice, preimage
+    ));
+    assert!(!redacted.contains(invoice));
+    assert!(!redacted.contains(preimage));
+    assert!(redacted.contains("'lnbcrt10…[redacted]'"));
+    assert!(redacted.contains("e3b0c442…[redacted]:"));
+    assert!(redacted.ends_with("route not found"));
+
+    assert_eq!(redact_sensitive("insufficient funds"), "insufficient funds");
+
+    // Cut by characters, a multi-byte one in the first 8 must not panic
+    let garbled = "lnbcé1p5abcdefpp5qqqsyqcyq5rqwzqfqqqsyqcyq";
+    assert_eq!(
+        redact_sensitive(&format!("Invalid invoice {}", garbled)),
+        "Invalid invoice lnbcé1p5…[redacted]"
+    );
+}
+
+#[test]
+fn test_utxos_cover_amount() {
+    use crate::utils::utxos_cover_amount;
+    use bark::ark::bitcoin::FeeRate;
+
+    let utxos = [Amount::from_sat(30_000), Amount::from_sat(70_000)];
+    assert!(utxos_cover_amount(&utxos, Amount::from_sat(100_000), None));
+    assert!(!utxos_cover_amount(&utxos, Amount::from_sat(100_001), None));
+    assert!(!utxos_cover_amount(&[], Amount::from_sat(1), None));
+    assert!(utxos_cover_amount(&[], Amount::ZERO, None));
+
+    // 1 sat/vB: one input is 11 + 58 + 2 * 43 = 155 vB, two inputs 213 vB
+    let fee_rate = FeeRate::from_sat_per_vb_unchecked(1);
+    assert!(utxos_cover_amount(
+        &utxos,
+        Amount::from_sat(69_845),
+        Some(fee_rate)
+    ));
+    assert!(utxos_cover_amount(
+        &utxos,
+        Amount::from_sat(99_787),
+        Some(fee_rate)
+    ));
+    assert!(!utxos_cover_amount(
+        &utxos,
+        Amount::from_sat(99_788),
+        Some(fee_rate)
+    ));
+    assert!(!utxos_cover_amount(
+        &utxos,
+        Amount::from_sat(100_000),
+        Some(fee_rate)
+    ));
+}
+
+fn lnurl_test_invoice(
+    amount_msat: u64,
+    description_hash_of: &str,
+) -> bark::lightning_invoice::Bolt11Invoice {
+    use bark::ark::bitcoin::hashes::{Hash, sha256};
+    use bark::ark::bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use bark::lightning_invoice::{Currency, InvoiceBuilder, PaymentSecret};
+
+    let secp = Secp256k1::new();
+    let key = SecretKey::from_slice(&[0x42; 32]).unwrap();
+    InvoiceBuilder::new(Currency::Regtest)
+        .description_hash(sha256::Hash::hash(description_hash_of.as_bytes()))
+        .payment_hash(sha256::Hash::hash(b"preimage"))
+        .payment_secret(PaymentSecret([7; 32]))
+        .amount_milli_satoshis(amount_msat)
+        .current_timestamp()
+        .min_final_cltv_expiry_delta(144)
+        .build_signed(|msg| secp.sign_ecdsa_recoverable(msg, &key))
+        .unwrap()
+}
+
+#[test]
+fn test_lnurl_pay_validation() {
+    use crate::utils::{
+        check_lnurl_pay_request, lnurl_metadata_description, validate_lnurl_pay_invoice,
+    };
+    use bark::lnurllib::pay::PayResponse;
+
+    let metadata =
+        r#"[["text/plain","Pay to alice@example.com"],["text/identifier","alice@example.com"]]"#;
+    let pay: PayResponse = serde_json::from_value(serde_json::json!({
+        "callback": "https://example.com/lnurlp/alice/callback",
+        "maxSendable": 100_000_000u64,
+        "minSendable": 1_000u64,
+        "tag": "payRequest",
+        "metadata": metadata,
+        "commentAllowed": 10,
+    }))
+    .unwrap();
+
+    assert_eq!(
+        lnurl_metadata_description(metadata),
+        "Pay to alice@example.com"
+    );
+    assert_eq!(lnurl_metadata_description("not json"), "");
+
+    // Request limits
+    assert!(check_lnurl_pay_request(&pay, 50_000, Some("thanks")).is_ok());
+    assert!(check_lnurl_pay_request(&pay, 999, None).is_err());
+    assert!(check_lnurl_pay_request(&pay, 100_000_001, None).is_err());
+    assert!(check_lnurl_pay_request(&pay, 50_000, Some("way too long comment")).is_err());
+
+    // An honest service
+    let invoice = lnurl_test_invoice(50_000, metadata);
+    assert!(validate_lnurl_pay_invoice(&invoice, 50_000, metadata).is_ok());
+
+    // A service returning an invoice for more than was asked
+    let inflated = lnurl_test_invoice(5_000_000, metadata);
+    let err = validate_lnurl_pay_invoice(&inflated, 50_000, metadata).unwrap_err();
+    assert!(err.to_string().contains("does not match the requested"));
+
+    // An invoice that does not commit to the metadata, e.g. for someone else
+    let foreign = lnurl_test_invoice(50_000, "[[\"text/plain\",\"mallory\"]]");
+    let err = validate_lnurl_pay_invoice(&foreign, 50_000, metadata).unwrap_err();
+    assert!(err.to_string().contains("description hash"));
+}
+
+#[test]
+fn test_decode_lnurl_forms() {
+    use crate::utils::{LnurlDecoded, LnurlForm, lnurl_service_url};
+    use bark::lnurllib::LnUrlResponse;
+
+    let lnurl = "LNURL1DP68GURN8GHJ7UM9WFMXJCM99E3K7MF0V9CXJ0M385EKVCENXC6R2C35XVUKXEFCV5MKVV34X5EKZD3EV56NYD3HXQURZEPEXEJXXEPNXSCRVWFNV9NXZCN9XQ6XYEFHVGCXXCMYXYMNSERXFQ5FNS";
+    let url = "https://service.com/api?q=3fc3645b439ce8e7f2553a69e5267081d96dcd340693afabe04be7b0ccd178df";
+
+    assert_eq!(
+        lnurl_service_url(lnurl).unwrap(),
+        (LnurlForm::Bech32, url.to_string())
+    );
+    assert_eq!(
+        lnurl_service_url(&format!("lightning:{}", lnurl.to_lowercase())).unwrap(),
+        (LnurlForm::LightningUri, url.to_string())
+    );
+    assert_eq!(
+        lnurl_service_url(&format!("LIGHTNING:{}", lnurl)).unwrap(),
+        (LnurlForm::LightningUri, url.to_string())
+    );
+    let well_known = "https://example.com/.well-known/lnurlp/alice";
+    assert_eq!(
+        lnurl_service_url(well_known).unwrap(),
+        (LnurlForm::Url, well_known.to_string())
+    );
+    assert!(lnurl_service_url("http://example.com/lnurlp").is_err());
+    assert!(lnurl_service_url("lnurl1invalid").is_err());
+
+    // Login has no service document
+    let login =
+        LnurlDecoded::login("https://site.com/auth?tag=login&k1=abcd&action=login").unwrap();
+    assert_eq!(login.kind, "login");
+    assert!(LnurlDecoded::login(url).is_none());
+
+    let pay = serde_json::from_value(serde_json::json!({
+        "callback": "https://example.com/lnurlp/alice/callback",
+        "maxSendable": 100_000_000u64,
+        "minSendable": 1_000u64,
+        "tag": "payRequest",
+        "metadata": "[[\"text/plain\",\"alice\"]]",
+    }))
+    .unwrap();
+    let decoded =
+        LnurlDecoded::from_response(well_known.to_string(), LnUrlResponse::LnUrlPayResponse(pay));
+    assert_eq!(decoded.kind, "payRequest");
+    assert_eq!(decoded.min_amount_msat, Some(1_000));
+    assert_eq!(decoded.max_amount_msat, Some(100_000_000));
+    assert_eq!(decoded.metadata, "[[\"text/plain\",\"alice\"]]");
+}
+
+#[test]
+fn test_vtxo_state_history() {
+    use crate::store::{count_vtxos_in_states, get_vtxo_state_history, open_bark_db_read_only};
+    use crate::vtxo_transitions::{ALL_VTXO_STATES, vtxo_state_kind_name};
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("db.sqlite");
+    {
+        // Fixture of the table bark keeps VTXO states in
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE bark_vtxo_state (
+                id INTEGER PRIMARY KEY,
+                created_at DATETIME NOT NULL,
+                vtxo_id TEXT NOT NULL,
+                state_kind TEXT NOT NULL,
+                state BLOB NOT NULL
+            );
+            INSERT INTO bark_vtxo_state (created_at, vtxo_id, state_kind, state) VALUES
+                ('2025-01-02 10:00:00', 'a:0', 'Spent', x''),
+                ('2025-01-01 10:00:00', 'a:0', 'Spendable', x''),
+                ('2025-01-01 12:00:00', 'a:0', 'Locked', x''),
+                ('2025-01-01 11:00:00', 'b:1', 'Spendable', x'');",
+        )
+        .unwrap();
+    }
+
+    let conn = open_bark_db_read_only(&path).unwrap();
+    let history = get_vtxo_state_history(&conn, "a:0").unwrap();
+    let states = history.iter().map(|t| t.state.as_str()).collect::<Vec<_>>();
+    assert_eq!(states, vec!["Spendable", "Locked", "Spent"]);
+    assert_eq!(history[0].transitioned_at, "2025-01-01 10:00:00");
+
+    assert!(get_vtxo_state_history(&conn, "c:0").unwrap().is_empty());
+    assert!(open_bark_db_read_only(&temp_dir.path().join("missing.sqlite")).is_err());
+
+    // Counted by their latest state only
+    assert_eq!(count_vtxos_in_states(&conn, &["Spent"]).unwrap(), 1);
+    assert_eq!(count_vtxos_in_states(&conn, &["Spendable"]).unwrap(), 1);
+    assert_eq!(count_vtxos_in_states(&conn, &["Locked"]).unwrap(), 0);
+    let all = ALL_VTXO_STATES.map(vtxo_state_kind_name);
+    assert_eq!(count_vtxos_in_states(&conn, &all).unwrap(), 2);
+    assert_eq!(count_vtxos_in_states(&conn, &[]).unwrap(), 0);
+}
+
+#[test]
+fn test_datadir_lock() {
+    use crate::lock::{DatadirLock, LOCK_FILE, LockError};
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let lock = DatadirLock::acquire(temp_dir.path(), false).unwrap();
+
+    // A second load without closing the first must fail, even when stealing
+    for steal_lock in [false, true] {
+        let err = DatadirLock::acquire(temp_dir.path(), steal_lock).unwrap_err();
+        match err.downcast_ref::<LockError>() {
+            Some(LockError::WalletInUse { pid, .. }) => {
+                assert_eq!(*pid, Some(std::process::id()))
+            }
+            None => panic!("Expected WalletInUse, got {:#}", err),
+        }
+    }
+
+    drop(lock);
+    let contents = fs::read_to_string(temp_dir.path().join(LOCK_FILE)).unwrap();
+    assert!(contents.is_empty(), "Release must clear the holder");
+    drop(DatadirLock::acquire(temp_dir.path(), false).unwrap());
+}
+
+#[test]
+fn test_datadir_lock_stale_takeover() {
+    use crate::lock::{DatadirLock, LOCK_FILE, LockError, STALE_LOCK_SECS};
+    use crate::store::now_timestamp;
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let lock_path = temp_dir.path().join(LOCK_FILE);
+
+    // Left behind by a process that was killed an hour ago
+    fs::write(&lock_path, format!("4242 {}", now_timestamp() - 3600)).unwrap();
+    let err = DatadirLock::acquire(temp_dir.path(), false).unwrap_err();
+    match err.downcast_ref::<LockError>() {
+        Some(LockError::WalletInUse { pid, age_secs }) => {
+            assert_eq!(*pid, Some(4242));
+            assert!(age_secs.unwrap() >= 3600);
+        }
+        None => panic!("Expected WalletInUse, got {:#}", err),
+    }
+    let lock = DatadirLock::acquire(temp_dir.path(), true).unwrap();
+    let contents = fs::read_to_string(&lock_path).unwrap();
+    assert!(contents.starts_with(&format!("{} ", std::process::id())));
+    drop(lock);
+
+    // A recent record may belong to a process that is only just gone
+    fs::write(
+        &lock_path,
+        format!("4242 {}", now_timestamp() - STALE_LOCK_SECS + 10),
+    )
+    .unwrap();
+    assert!(DatadirLock::acquire(temp_dir.path(), true).is_err());
+}
+
+#[test]
+fn test_board_result() {
+    use crate::NitroBoardResult;
+    use bark::ark::VtxoId;
+    use bark::ark::bitcoin::{
+        OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness, absolute,
+        transaction,
+    };
+
+    let utxo = |n: u8, vout: u32, sat: u64| {
+        let txid = Txid::from_str(&format!("{:02x}", n).repeat(32)).unwrap();
+        (OutPoint::new(txid, vout), Amount::from_sat(sat))
+    };
+    let wallet_utxos = [utxo(1, 0, 60_000), utxo(2, 1, 50_000), utxo(3, 0, 10_000)];
+
+    // Boarding 100k from the first two outputs, with change
+    let funding_tx = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: [wallet_utxos[0].0, wallet_utxos[1].0]
+            .into_iter()
+            .map(|previous_output| TxIn {
+                previous_output,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: vec![
+            TxOut {
+                value: Amount::from_sat(100_000),
+                script_pubkey: ScriptBuf::new(),
+            },
+            TxOut {
+                value: Amount::from_sat(9_700),
+                script_pubkey: ScriptBuf::new(),
+            },
+        ],
+    };
+    let vtxo_id = VtxoId::from_str(&format!("{}:0", funding_tx.compute_txid())).unwrap();
+
+    let result = NitroBoardResult::new(
+        &funding_tx,
+        Amount::from_sat(100_000),
+        &[vtxo_id],
+        &wallet_utxos,
+    );
+    assert_eq!(result.txid, funding_tx.compute_txid().to_string());
+    assert_eq!(result.amount_sat, 100_000);
+    assert_eq!(result.fee_sat, 300);
+    assert_eq!(result.vtxo_ids, vec![vtxo_id.to_string()]);
+
+    // Inputs we don't know the value of can't be charged as fee
+    let fee = crate::utils::funding_tx_fee(&funding_tx, &wallet_utxos[1..]);
+    assert_eq!(fee, Amount::ZERO);
+}
+
+#[test]
+fn test_check_fee_bump() {
+    use crate::onchain::{BumpCandidate, FeeBumpError, check_fee_bump};
+    use bark::ark::bitcoin::{FeeRate, Txid};
+
+    let txid = Txid::from_str(&"ab".repeat(32)).unwrap();
+    let rate = FeeRate::from_sat_per_vb_unchecked;
+    let pending = BumpCandidate {
+        confirmed: false,
+        signals_rbf: true,
+        fee_rate: rate(2),
+        board_funding: false,
+    };
+
+    assert_eq!(check_fee_bump(txid, Some(&pending), rate(5)), Ok(()));
+    assert_eq!(
+        check_fee_bump(txid, None, rate(5)),
+        Err(FeeBumpError::UnknownTransaction(txid))
+    );
+    let confirmed = BumpCandidate {
+        confirmed: true,
+        ..pending.clone()
+    };
+    assert_eq!(
+        check_fee_bump(txid, Some(&confirmed), rate(5)),
+        Err(FeeBumpError::AlreadyConfirmed(txid))
+    );
+    let final_tx = BumpCandidate {
+        signals_rbf: false,
+        ..pending.clone()
+    };
+    assert_eq!(
+        check_fee_bump(txid, Some(&final_tx), rate(5)),
+        Err(FeeBumpError::NotReplaceable(txid))
+    );
+    let board = BumpCandidate {
+        board_funding: true,
+        ..pending.clone()
+    };
+    assert_eq!(
+        check_fee_bump(txid, Some(&board), rate(5)),
+        Err(FeeBumpError::BoardFunding(txid))
+    );
+    assert_eq!(
+        check_fee_bump(txid, Some(&pending), rate(2)),
+        Err(FeeBumpError::FeeRateTooLow {
+            current: rate(2),
+            requested: rate(2),
+        })
+    );
+}
+
+#[test]
+fn test_pending_payments() {
+    use crate::store::{PendingPayment, PendingPaymentStatus};
+
+    let (_temp_dir, store) = temp_store();
+
+    let payment = |hash: &str, started_at| PendingPayment {
+        payment_hash: hash.to_string(),
+        bolt11: format!("lnbcrt1{}", hash),
+        amount_msat: None,
+        destination: Some("02abcdef".to_string()),
+        started_at,
+        status: PendingPaymentStatus::Sending,
+    };
+    store.store_pending_payment(&payment("bb", 20)).unwrap();
+    store.store_pending_payment(&payment("aa", 10)).unwrap();
+
+    store
+        .update_pending_payment_status("aa", PendingPaymentStatus::InFlight, Some(21_000))
+        .unwrap();
+    assert!(
+        store
+            .update_pending_payment_status("cc", PendingPaymentStatus::InFlight, None)
+            .is_err()
+    );
+
+    let pending = store.get_pending_payments().unwrap();
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending[0].payment_hash, "aa");
+    assert_eq!(pending[0].status, PendingPaymentStatus::InFlight);
+    assert_eq!(pending[0].amount_msat, Some(21_000));
+    assert_eq!(pending[1].status, PendingPaymentStatus::Sending);
+    assert_eq!(pending[1].amount_msat, None);
+
+    // Retrying a payment replaces the earlier attempt
+    store.store_pending_payment(&payment("aa", 30)).unwrap();
+    let pending = store.get_pending_payments().unwrap();
+    assert_eq!(pending.len(), 2);
+    assert_eq!(pending[1].payment_hash, "aa");
+    assert_eq!(pending[1].status, PendingPaymentStatus::Sending);
+
+    assert!(store.remove_pending_payment("aa").unwrap());
+    assert!(!store.remove_pending_payment("aa").unwrap());
+    assert_eq!(store.get_pending_payments().unwrap().len(), 1);
+}
+
+#[test]
+fn test_receive_bundle() {
+    use crate::store::now_timestamp;
+    use crate::utils::{ReceiveBundle, can_reuse_receive_invoice};
+
+    let onchain = "bcrt1q6rz28mcfaxtmd6v789l9rrlrusdprr9pz3cppk";
+    let invoice = lnurl_test_invoice(21_000_000, "receive");
+
+    let bundle = ReceiveBundle::new(
+        "tark1address".to_string(),
+        onchain.to_string(),
+        Some(21_000),
+        Some(invoice.to_string()),
+    )
+    .unwrap();
+    assert_eq!(
+        bundle.unified_uri,
+        format!("bitcoin:{}?amount=0.00021&lightning={}", onchain, invoice)
+    );
+    assert_eq!(bundle.bolt11, Some(invoice.to_string()));
+
+    let bundle =
+        ReceiveBundle::new("tark1address".to_string(), onchain.to_string(), None, None).unwrap();
+    assert_eq!(bundle.unified_uri, format!("bitcoin:{}", onchain));
+    assert!(ReceiveBundle::new(String::new(), "nope".to_string(), None, None).is_err());
+
+    // The test invoice is valid for an hour
+    let now = now_timestamp() as u64;
+    assert!(can_reuse_receive_invoice(&invoice, 21_000, false, now));
+    assert!(!can_reuse_receive_invoice(&invoice, 21_001, false, now));
+    assert!(!can_reuse_receive_invoice(&invoice, 21_000, true, now));
+    assert!(!can_reuse_receive_invoice(
+        &invoice,
+        21_000,
+        false,
+        now + 3_300
+    ));
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_receive_bundle_ffi() {
+    let _fixture = WalletTestFixture::new();
+    let amount = 5_000u64;
+
+    let first = cxx::get_receive_bundle(&amount).unwrap();
+    let second = cxx::get_receive_bundle(&amount).unwrap();
+    assert_ne!(first.ark_address, second.ark_address);
+    assert_ne!(first.onchain_address, second.onchain_address);
+    assert_eq!(first.bolt11, second.bolt11);
+    assert!(second.unified_uri.contains(&second.onchain_address));
+
+    let without_amount = cxx::get_receive_bundle(std::ptr::null()).unwrap();
+    assert!(without_amount.bolt11.is_empty());
+    assert_ne!(without_amount.onchain_address, second.onchain_address);
+}
+
+#[test]
+fn test_retry_failed_payment_lookup() {
+    use crate::store::{PendingPayment, PendingPaymentStatus};
+    use crate::utils::retry_fee_budget;
+
+    assert_eq!(retry_fee_budget(100, 50), 150);
+    assert_eq!(retry_fee_budget(5, 0), 5);
+    assert_eq!(retry_fee_budget(5, 10), 6);
+
+    let (_temp_dir, store) = temp_store();
+    store
+        .store_pending_payment(&PendingPayment {
+            payment_hash: "aa".to_string(),
+            bolt11: "lnbcrt1aa".to_string(),
+            amount_msat: Some(5_000_000),
+            destination: None,
+            started_at: 10,
+            status: PendingPaymentStatus::Sending,
+        })
+        .unwrap();
+    store
+        .update_pending_payment_status("aa", PendingPaymentStatus::Failed, None)
+        .unwrap();
+
+    let failed = store.get_pending_payment("aa").unwrap().unwrap();
+    assert_eq!(failed.status, PendingPaymentStatus::Failed);
+    assert_eq!(failed.amount_msat, Some(5_000_000));
+    assert!(store.get_pending_payment("bb").unwrap().is_none());
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_totals_ffi() {
+    let _fixture = WalletTestFixture::new();
+    assert_eq!(cxx::get_total_received_sat().unwrap(), 0);
+    assert_eq!(cxx::get_total_sent_sat().unwrap(), 0);
+    assert_eq!(cxx::get_total_received_in_range(0, i64::MAX).unwrap(), 0);
+    assert_eq!(cxx::get_total_sent_in_range(i64::MAX, 0).unwrap(), 0);
+}
+
+#[test]
+fn test_daily_balances() {
+    use crate::utils::{BalanceChange, daily_balances};
+
+    // 2024-01-01T00:00:00Z
+    let jan1 = 1_704_067_200;
+    let day = 24 * 60 * 60;
+    let change = |at, offchain_sat, onchain_sat| BalanceChange {
+        at,
+        offchain_sat,
+        onchain_sat,
+    };
+    let changes = [
+        // Received onchain before the window starts
+        change(jan1 - 10 * day, 0, 100_000),
+        // Boarded on the 2nd
+        change(jan1 + day + 3_600, 60_000, -61_000),
+        // Received and spent offchain on the 3rd
+        change(jan1 + 2 * day + 10, 5_000, 0),
+        change(jan1 + 3 * day - 1, -20_000, 0),
+    ];
+
+    let history = daily_balances(&changes, 4, jan1 + 3 * day + 100);
+    let summary = history
+        .iter()
+        .map(|s| (s.date.as_str(), s.offchain_sat, s.onchain_sat))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            ("2024-01-01", 0, 100_000),
+            ("2024-01-02", 60_000, 39_000),
+            ("2024-01-03", 45_000, 39_000),
+            ("2024-01-04", 45_000, 39_000),
+        ]
+    );
+
+    assert!(daily_balances(&changes, 0, jan1).is_empty());
+    // Leap day and dates before the epoch format correctly
+    let feb29 = daily_balances(&[], 1, 1_709_164_800);
+    assert_eq!(feb29[0].date, "2024-02-29");
+    assert_eq!(daily_balances(&[], 1, -1)[0].date, "1969-12-31");
+}
+
+#[test]
+fn test_next_round_eta() {
+    use crate::utils::next_round_eta;
+
+    let interval = 30;
+    assert_eq!(next_round_eta(None, interval, 1_000), None);
+    assert_eq!(next_round_eta(Some(1_000), interval, 1_000), Some(0));
+    assert_eq!(next_round_eta(Some(1_000), interval, 1_001), Some(29));
+    assert_eq!(next_round_eta(Some(1_000), interval, 1_029), Some(1));
+    assert_eq!(next_round_eta(Some(1_000), interval, 1_030), Some(0));
+    // Several rounds later the schedule still lines up
+    assert_eq!(
+        next_round_eta(Some(1_000), interval, 1_000 + 10 * 30 + 12),
+        Some(18)
+    );
+    // A clock behind the last round waits a full interval at most
+    assert_eq!(next_round_eta(Some(1_000), interval, 990), Some(0));
+    assert_eq!(next_round_eta(Some(1_000), 0, 1_234), Some(0));
+}
+
+#[test]
+fn test_db_created_at() {
+    use crate::store::{get_db_created_at, open_bark_db_read_only};
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("db.sqlite");
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE migrations (
+            value INTEGER NOT NULL,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );",
+    )
+    .unwrap();
+    assert!(get_db_created_at(&open_bark_db_read_only(&path).unwrap()).is_err());
+
+    conn.execute_batch(
+        "INSERT INTO migrations (value, created_at) VALUES
+            (2, '2025-01-03 10:00:00'),
+            (0, '2025-01-01 10:00:00'),
+            (1, '2025-01-02 10:00:00');",
+    )
+    .unwrap();
+    let conn = open_bark_db_read_only(&path).unwrap();
+    assert_eq!(get_db_created_at(&conn).unwrap(), "2025-01-01 10:00:00");
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_wallet_id_ffi() {
+    let _fixture = WalletTestFixture::new();
+    let id = cxx::get_wallet_id().unwrap();
+    assert_eq!(id.len(), 8);
+    assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(cxx::get_wallet_id().unwrap(), id);
+    assert!(!cxx::get_wallet_created_at().unwrap().is_empty());
+}
+
+#[test]
+fn test_lightning_receive_state() {
+    use crate::utils::{LightningReceiveState, format_utc_timestamp};
+
+    let state = |revealed, finished, expired| {
+        LightningReceiveState::new(revealed, finished, expired).as_str()
+    };
+    assert_eq!(state(false, false, false), "Pending");
+    assert_eq!(state(false, false, true), "Expired");
+    assert_eq!(state(true, false, false), "Received");
+    // Once the preimage is out the payment arrived, even past the expiry
+    assert_eq!(state(true, false, true), "Received");
+    assert_eq!(state(true, true, true), "Claimed");
+    assert_eq!(state(false, true, false), "Expired");
+
+    assert_eq!(format_utc_timestamp(0), "1970-01-01T00:00:00+00:00");
+    assert_eq!(
+        format_utc_timestamp(1_709_210_096),
+        "2024-02-29T12:34:56+00:00"
+    );
+}
+
+#[test]
+fn test_export_descriptors_payload() {
+    use crate::onchain::{ensure_public_descriptor, import_descriptors_payload};
+    use bdk_wallet::bitcoin::secp256k1::Secp256k1;
+    use bdk_wallet::miniscript::{Descriptor, DescriptorPublicKey};
+
+    let secp = Secp256k1::new();
+    let tprv = "tprv8ZgxMBicQKsPeDgjzdC36fs6bMjGApWDNLR9erAXMs5skhMv36j9MV5ecvfavji5khqjWaWSFhN3YcCUUdiKH6isR4Pwy3U5y5egddBr16m";
+    let parse = |path: &str| {
+        Descriptor::<DescriptorPublicKey>::parse_descriptor(
+            &secp,
+            &format!("tr({}/86'/1'/0'/{}/*)", tprv, path),
+        )
+        .unwrap()
+    };
+    let (external, keymap) = parse("0");
+    let (internal, _) = parse("1");
+    let (external, internal) = (external.to_string(), internal.to_string());
+
+    ensure_public_descriptor(&external).unwrap();
+    ensure_public_descriptor(&internal).unwrap();
+    assert!(!external.contains("prv") && !internal.contains("prv"));
+    let with_secret = Descriptor::<DescriptorPublicKey>::from_str(&external)
+        .unwrap()
+        .to_string_with_secret(&keymap);
+    assert!(ensure_public_descriptor(&with_secret).is_err());
+
+    let payload: serde_json::Value = serde_json::from_str(&import_descriptors_payload(
+        &external,
+        &internal,
+        Some(1_700_000_000),
+    ))
+    .unwrap();
+    let requests = payload.as_array().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0]["internal"], false);
+    assert_eq!(requests[1]["internal"], true);
+    assert_eq!(requests[0]["timestamp"], 1_700_000_000);
+    for request in requests {
+        // Checksums are validated when parsing
+        let desc = request["desc"].as_str().unwrap();
+        assert!(desc.contains('#'));
+        Descriptor::<DescriptorPublicKey>::from_str(desc).unwrap();
+    }
+
+    let (body, _checksum) = external.split_once('#').unwrap();
+    assert!(Descriptor::<DescriptorPublicKey>::from_str(&format!("{}#00000000", body)).is_err());
+
+    let payload: serde_json::Value =
+        serde_json::from_str(&import_descriptors_payload(&external, &internal, None)).unwrap();
+    assert_eq!(payload[0]["timestamp"], "now");
+}
+
+#[test]
+fn test_backup_database() {
+    use crate::store::timestamped_backup_path;
+
+    let datadir = tempdir().expect("Failed to create temp dir");
+    {
+        let conn = rusqlite::Connection::open(datadir.path().join(crate::utils::DB_FILE)).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, v TEXT NOT NULL);
+            INSERT INTO t (v) VALUES ('a'), ('b');",
+        )
+        .unwrap();
+    }
+
+    let backups = tempdir().expect("Failed to create temp dir");
+    let requested = backups.path().join("wallet.sqlite");
+    assert_eq!(
+        timestamped_backup_path(&requested, 1_700_000_000).unwrap(),
+        backups.path().join("1700000000-wallet.sqlite")
+    );
+
+    // Backing up works while the wallet holds the database open
+    let _open = rusqlite::Connection::open(datadir.path().join(crate::utils::DB_FILE)).unwrap();
+    let backup = crate::backup_database(datadir.path(), &requested).unwrap();
+    assert!(
+        backup
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .ends_with("-wallet.sqlite")
+    );
+    assert!(!requested.exists());
+
+    let conn = rusqlite::Connection::open(&backup).unwrap();
+    let count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, 2);
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(integrity, "ok");
+
+    assert!(
+        crate::store::backup_sqlite_db(&datadir.path().join(crate::utils::DB_FILE), &backup)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_lock_diagnostics() {
+    use crate::metrics::lock_diagnostics;
+    use std::time::Duration;
+
+    crate::TOKIO_RUNTIME.block_on(async {
+        let holder = crate::lock_manager("test_lock_holder").await;
+        let waiter = tokio::spawn(async {
+            let _manager = crate::lock_manager("test_lock_waiter").await;
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let diagnostics = lock_diagnostics();
+        let current = diagnostics.current_holder.unwrap();
+        assert_eq!(current.operation, "test_lock_holder");
+        assert!(current.since > 0);
+
+        drop(holder);
+        waiter.await.unwrap();
+    });
+
+    let records = lock_diagnostics().records;
+    let find = |operation| {
+        records
+            .iter()
+            .rev()
+            .find(|r| r.operation == operation)
+            .cloned()
+            .unwrap()
+    };
+    let holder = find("test_lock_holder");
+    let waiter = find("test_lock_waiter");
+    assert!(holder.held_ms >= 200);
+    assert!(waiter.waited_ms >= 150);
+    assert!(waiter.acquired_at >= holder.acquired_at);
+}
+
+#[test]
+fn test_coin_control_selection() {
+    use crate::utils::{CoinControlError, check_coin_control};
+
+    let id = |n: u8| format!("{}:0", format!("{:02x}", n).repeat(32));
+    let (board, tip, tip2, locked) = (id(1), id(2), id(3), id(4));
+    let known = vec![board.clone(), tip.clone(), tip2.clone(), locked.clone()];
+    let spendable = vec![
+        (board.clone(), 50_000),
+        (tip.clone(), 1_000),
+        (tip2.clone(), 2_000),
+    ];
+
+    assert_eq!(
+        check_coin_control(&known, &spendable, &[], 1_000),
+        Err(CoinControlError::NoInputs)
+    );
+    assert_eq!(
+        check_coin_control(&known, &spendable, &[tip.clone(), tip.clone()], 1_000),
+        Err(CoinControlError::DuplicateInput(tip.clone()))
+    );
+    assert_eq!(
+        check_coin_control(&known, &spendable, &[id(9)], 1_000),
+        Err(CoinControlError::UnknownVtxo(id(9)))
+    );
+    assert_eq!(
+        check_coin_control(&known, &spendable, &[locked.clone()], 1_000),
+        Err(CoinControlError::NotSpendable(locked.clone()))
+    );
+    assert_eq!(
+        check_coin_control(&known, &spendable, &[tip.clone(), tip2.clone()], 5_000),
+        Err(CoinControlError::Shortfall {
+            selected_sat: 3_000,
+            missing_sat: 2_000
+        })
+    );
+
+    // Spending the tips must keep the board VTXO out of the payment
+    let to_lock =
+        check_coin_control(&known, &spendable, &[tip.clone(), tip2.clone()], 3_000).unwrap();
+    assert_eq!(to_lock, vec![board.clone()]);
+    assert!(
+        check_coin_control(&known, &spendable, &[board.clone()], 50_000)
+            .unwrap()
+            .len()
+            == 2
+    );
+}
+
+#[test]
+fn test_coin_control_lock_records() {
+    let (temp_dir, store) = temp_store();
+    assert!(store.get_coin_control_locks().unwrap().is_empty());
+
+    let locked = vec!["a:0".to_string(), "b:1".to_string()];
+    store.record_coin_control_locks(&locked).unwrap();
+    // Recording again, e.g. on a retry, doesn't duplicate
+    store.record_coin_control_locks(&locked[..1]).unwrap();
+    assert_eq!(store.get_coin_control_locks().unwrap(), locked);
+    drop(store);
+
+    // The records survive a restart so the locks can be undone on load
+    let store = crate::store::Store::open(&temp_dir.path().join("nitro.sqlite")).unwrap();
+    assert_eq!(store.get_coin_control_locks().unwrap(), locked);
+    for vtxo_id in &locked {
+        store.clear_coin_control_lock(vtxo_id).unwrap();
+    }
+    assert!(store.get_coin_control_locks().unwrap().is_empty());
+}
+
+#[test]
+fn test_lightning_receive_expiry() {
+    use crate::utils::lightning_receive_expired;
+
+    let invoice = lnurl_test_invoice(5_000_000, "receive");
+    let created = invoice.duration_since_epoch().as_secs();
+    let expires = created + invoice.expiry_time().as_secs();
+
+    assert!(!lightning_receive_expired(&invoice, false, false, created));
+    assert!(!lightning_receive_expired(
+        &invoice,
+        false,
+        false,
+        expires - 1
+    ));
+    assert!(lightning_receive_expired(
+        &invoice,
+        false,
+        false,
+        expires + 1
+    ));
+    // A payment that arrived in time still gets claimed after the expiry
+    assert!(!lightning_receive_expired(
+        &invoice,
+        true,
+        false,
+        expires + 1
+    ));
+    // Finished receives are no longer bark's to claim
+    assert!(!lightning_receive_expired(
+        &invoice,
+        false,
+        true,
+        expires + 1
+    ));
+
+    let (_temp_dir, store) = temp_store();
+    let hash = invoice.payment_hash().to_string();
+    assert!(
+        store
+            .mark_receive_expired(&hash, Some(5_000_000), expires as i64)
+            .unwrap()
+    );
+    // Only the first transition counts, so the event is emitted once
+    assert!(
+        !store
+            .mark_receive_expired(&hash, Some(5_000_000), expires as i64)
+            .unwrap()
+    );
+    assert!(store.get_expired_receives().unwrap().contains(&hash));
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_get_balance_sync_modes() {
+    let _fixture = WalletTestFixture::new();
+
+    // Reading local state only doesn't record a sync
+    let before = cxx::get_last_successful_sync_timestamp().unwrap();
+    let local = cxx::get_balance(true).unwrap();
+    assert_eq!(cxx::get_last_successful_sync_timestamp().unwrap(), before);
+
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let synced = cxx::get_balance(false).unwrap();
+    assert!(cxx::get_last_successful_sync_timestamp().unwrap() > before);
+    assert_eq!(synced.spendable, local.spendable);
+}
+
+#[test]
+fn test_unit_conversions() {
+    use crate::units::{
+        amount_from_sat, amount_to_msat, msat_to_sat_ceil, msat_to_sat_floor, sat_to_msat,
+    };
+
+    assert_eq!(amount_from_sat(1_234), Amount::from_sat(1_234));
+    assert_eq!(sat_to_msat(1_234), 1_234_000);
+    assert_eq!(sat_to_msat(u64::MAX), u64::MAX);
+    assert_eq!(amount_to_msat(Amount::from_sat(21)), 21_000);
+
+    assert_eq!(msat_to_sat_floor(1_234_999), (Amount::from_sat(1_234), 999));
+    assert_eq!(msat_to_sat_floor(1_234_000), (Amount::from_sat(1_234), 0));
+    assert_eq!(msat_to_sat_floor(999), (Amount::ZERO, 999));
+    assert_eq!(msat_to_sat_ceil(1_234_001), Amount::from_sat(1_235));
+    assert_eq!(msat_to_sat_ceil(1_234_000), Amount::from_sat(1_234));
+    assert_eq!(msat_to_sat_ceil(0), Amount::ZERO);
+}
+
+#[test]
+fn test_bridge_amount_round_trips() {
+    use crate::units::{amount_from_sat, amount_to_msat, msat_to_sat_ceil, msat_to_sat_floor};
+    use crate::utils::can_reuse_receive_invoice;
+
+    // Whole sats survive the trip to lightning and back without rounding
+    for sat in [0, 1, 21_000, 2_100_000_000_000_000] {
+        let msat = amount_to_msat(amount_from_sat(sat));
+        assert_eq!(msat_to_sat_floor(msat), (Amount::from_sat(sat), 0));
+        assert_eq!(msat_to_sat_ceil(msat), Amount::from_sat(sat));
+    }
+
+    // An invoice for a fractional sat amount never matches a sat amount
+    let invoice = lnurl_test_invoice(21_000_500, "fractional");
+    let now = crate::store::now_timestamp() as u64;
+    let (floor, remainder) = msat_to_sat_floor(21_000_500);
+    assert_eq!(remainder, 500);
+    assert!(!can_reuse_receive_invoice(
+        &invoice,
+        floor.to_sat(),
+        false,
+        now
+    ));
+    assert!(!can_reuse_receive_invoice(
+        &invoice,
+        msat_to_sat_ceil(21_000_500).to_sat(),
+        false,
+        now
+    ));
+}
+
+#[test]
+fn test_parse_onchain_address() {
+    use crate::utils::parse_onchain_address;
+    use bark::ark::bitcoin::Network;
+
+    let regtest = "bcrt1q6rz28mcfaxtmd6v789l9rrlrusdprr9pz3cppk";
+    let address = parse_onchain_address(regtest, Network::Regtest).unwrap();
+    assert_eq!(address.to_string(), regtest);
+
+    let err = parse_onchain_address(regtest, Network::Bitcoin).unwrap_err();
+    assert!(format!("{:#}", err).contains("not valid for configured network"));
+    let err = parse_onchain_address("not-an-address", Network::Regtest).unwrap_err();
+    assert!(format!("{:#}", err).contains("Invalid destination address format"));
+}
+
+#[test]
+fn test_onchain_rescan_helpers() {
+    use crate::onchain::{
+        RescanSource, RescanState, check_rescan_height, rescan_summary, scan_blocks,
+    };
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Hands out heights in order, cancelling the scan at `cancel_at`.
+    struct MockChain<'a> {
+        heights: std::ops::RangeInclusive<u32>,
+        cancel_at: Option<u32>,
+        cancelled: &'a AtomicBool,
+    }
+
+    impl RescanSource for MockChain<'_> {
+        type Block = ();
+
+        fn next_block(&mut self) -> anyhow::Result<Option<(u32, ())>> {
+            let next = self.heights.next();
+            if next.is_some() && next == self.cancel_at {
+                self.cancelled.store(true, Ordering::SeqCst);
+            }
+            Ok(next.map(|height| (height, ())))
+        }
+    }
+
+    assert!(check_rescan_height(0, 0).is_ok());
+    assert!(check_rescan_height(100, 100).is_ok());
+    assert!(check_rescan_height(101, 100).is_err());
+
+    let cancelled = AtomicBool::new(false);
+    let mut chain = MockChain {
+        heights: 100..=2600,
+        cancel_at: None,
+        cancelled: &cancelled,
+    };
+    let mut applied = Vec::new();
+    let mut progress = Vec::new();
+    let scanned = scan_blocks(
+        &mut chain,
+        &cancelled,
+        |height, ()| {
+            applied.push(height);
+            Ok(())
+        },
+        |height, scanned| progress.push((height, scanned)),
+    )
+    .unwrap();
+    assert_eq!(scanned, 2501);
+    assert_eq!(applied.first(), Some(&100));
+    assert_eq!(applied.last(), Some(&2600));
+    assert_eq!(progress, vec![(1099, 1000), (2099, 2000)]);
+
+    let mut chain = MockChain {
+        heights: 100..=200,
+        cancel_at: Some(150),
+        cancelled: &cancelled,
+    };
+    let mut applied = 0;
+    let err = scan_blocks(
+        &mut chain,
+        &cancelled,
+        |_, ()| {
+            applied += 1;
+            Ok(())
+        },
+        |_, _| {},
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("cancelled at height 150"));
+    assert_eq!(applied, 50);
+
+    let before = RescanState {
+        tx_count: 3,
+        balance: Amount::from_sat(50_000),
+    };
+    let after = RescanState {
+        tx_count: 5,
+        balance: Amount::from_sat(20_000),
+    };
+    let summary = rescan_summary(100, 2600, 2501, before, after, Some(120));
+    assert_eq!(summary.transactions_found, 2);
+    assert_eq!(summary.balance_delta_sat, -30_000);
+    assert_eq!(summary.birthday_height, Some(120));
+    assert_eq!(
+        rescan_summary(100, 100, 1, before, before, None).transactions_found,
+        0
+    );
+}
+
+#[test]
+fn test_rescan_records_birthday() {
+    use crate::onchain::record_birthday;
+
+    let (_temp_dir, store) = temp_store();
+    assert_eq!(store.get_onchain_birthday_height().unwrap(), None);
+    assert_eq!(record_birthday(&store, None), None);
+
+    assert_eq!(record_birthday(&store, Some(150)), Some(150));
+    assert_eq!(store.get_onchain_birthday_height().unwrap(), Some(150));
+
+    // Only earlier activity moves the birthday
+    assert_eq!(record_birthday(&store, Some(120)), Some(120));
+    assert_eq!(record_birthday(&store, Some(200)), Some(120));
+    assert_eq!(record_birthday(&store, None), Some(120));
+    assert_eq!(store.get_onchain_birthday_height().unwrap(), Some(120));
+}
+
+#[test]
+fn test_rescan_filter_keeps_wallet_transactions() {
+    use crate::onchain::WalletFilter;
+    use bark::ark::bitcoin::{
+        Network, OutPoint, ScriptBuf, Transaction, TxIn, TxOut, Txid, absolute,
+        blockdata::constants, transaction,
+    };
+
+    let script = |byte: u8| ScriptBuf::from_bytes(vec![byte; 22]);
+    let tx = |spends: OutPoint, pays_to: u8| Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: spends,
+            ..Default::default()
+        }],
+        output: vec![TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: script(pays_to),
+        }],
+    };
+    let owned = OutPoint::new(Txid::from_str(&"01".repeat(32)).unwrap(), 0);
+    let foreign = OutPoint::new(Txid::from_str(&"02".repeat(32)).unwrap(), 0);
+
+    let receive = tx(foreign, 1);
+    let receive_txid = receive.compute_txid();
+    // Spends the output received earlier in the same block
+    let spend_received = tx(OutPoint::new(receive_txid, 0), 9);
+    let spend_owned = tx(owned, 9);
+    let unrelated = tx(foreign, 9);
+
+    let mut block = constants::genesis_block(Network::Regtest);
+    block.txdata = vec![
+        unrelated.clone(),
+        receive.clone(),
+        spend_received.clone(),
+        spend_owned.clone(),
+    ];
+    let mut filter = WalletFilter::new([script(1)], [owned]);
+    let kept = filter.keep_relevant(block);
+    assert_eq!(kept.txdata, vec![receive, spend_received, spend_owned]);
+
+    // Outputs received in earlier blocks stay watched
+    let respend = tx(OutPoint::new(receive_txid, 0), 9);
+    let mut block = constants::genesis_block(Network::Regtest);
+    block.txdata = vec![unrelated, respend.clone()];
+    assert_eq!(filter.keep_relevant(block).txdata, vec![respend]);
+}
+
+#[test]
+fn test_split_arkoor_outputs() {
+    use crate::utils::split_arkoor_outputs;
+    use bark::ark::bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+
+    let secp = Secp256k1::new();
+    let pubkey =
+        |byte: u8| PublicKey::from_secret_key(&secp, &SecretKey::from_slice(&[byte; 32]).unwrap());
+    let key_store = [pubkey(1), pubkey(2)]
+        .into_iter()
+        .collect::<std::collections::HashSet<_>>();
+
+    // Recipient and change carry the same amount, so only the key tells them apart
+    let outputs = vec![
+        (pubkey(9), Amount::from_sat(5_000)),
+        (pubkey(1), Amount::from_sat(5_000)),
+        (pubkey(2), Amount::from_sat(1_200)),
+    ];
+    let (sent, change) = split_arkoor_outputs(outputs, |(key, _)| key_store.contains(key));
+    assert_eq!(sent, vec![(pubkey(9), Amount::from_sat(5_000))]);
+    assert_eq!(change.len(), 2);
+    assert_eq!(
+        change.iter().map(|(_, amount)| *amount).sum::<Amount>(),
+        Amount::from_sat(6_200)
+    );
+
+    let (sent, change) =
+        split_arkoor_outputs(vec![(pubkey(9), Amount::from_sat(100))], |(key, _)| {
+            key_store.contains(key)
+        });
+    assert_eq!(sent.len(), 1);
+    assert!(change.is_empty());
+}
+
+#[test]
+fn test_ark_purpose_index() {
+    use crate::{DEFAULT_ARK_PURPOSE_INDEX, check_ark_purpose_index, derive_ark_keypair};
+    use bark::ark::bitcoin::Network;
+
+    assert!(check_ark_purpose_index(DEFAULT_ARK_PURPOSE_INDEX).is_ok());
+    let err = check_ark_purpose_index(351).unwrap_err();
+    assert!(err.to_string().contains("not supported"));
+
+    let mnemonic = bip39::Mnemonic::from_str(
+        "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+    )
+    .unwrap();
+    let standard =
+        derive_ark_keypair(&mnemonic, Network::Regtest, DEFAULT_ARK_PURPOSE_INDEX, 0).unwrap();
+    let custom = derive_ark_keypair(&mnemonic, Network::Regtest, 351, 0).unwrap();
+    assert_ne!(standard.public_key(), custom.public_key());
+    assert_eq!(
+        standard.public_key(),
+        derive_ark_keypair(&mnemonic, Network::Regtest, DEFAULT_ARK_PURPOSE_INDEX, 0)
+            .unwrap()
+            .public_key()
+    );
+    let signature = crate::TOKIO_RUNTIME
+        .block_on(crate::sign_messsage_with_mnemonic(
+            "hello",
+            mnemonic.clone(),
+            Network::Regtest,
+            351,
+            0,
+        ))
+        .unwrap();
+    assert!(
+        crate::TOKIO_RUNTIME
+            .block_on(crate::verify_message(
+                "hello",
+                signature,
+                &custom.public_key()
+            ))
+            .unwrap()
+    );
+
+    let (_temp_dir, opts) = setup_test_wallet_opts();
+    assert_eq!(
+        crate::utils::ffi_config_opts_to_config_opts(opts.config)
+            .unwrap()
+            .ark_purpose_index,
+        None
+    );
+    let (_temp_dir, mut opts) = setup_test_wallet_opts();
+    opts.config.ark_purpose_index = 351;
+    assert_eq!(
+        crate::utils::ffi_config_opts_to_config_opts(opts.config)
+            .unwrap()
+            .ark_purpose_index,
+        Some(351)
+    );
+
+    let (_temp_dir, store) = temp_store();
+    assert_eq!(
+        store.get_ark_purpose_index().unwrap(),
+        DEFAULT_ARK_PURPOSE_INDEX
+    );
+    store.set_ark_purpose_index(351).unwrap();
+    assert_eq!(store.get_ark_purpose_index().unwrap(), 351);
+}
+
+#[test]
+fn test_vtxo_transition_matrix() {
+    use crate::vtxo_transitions::{
+        ALL_VTXO_STATES, IllegalVtxoTransition, check_vtxo_transition, vtxo_state_kind,
+    };
+    use bark::vtxo::{VtxoState, VtxoStateKind};
+
+    let legal = [
+        (VtxoStateKind::Spendable, VtxoStateKind::Locked),
+        (VtxoStateKind::Spendable, VtxoStateKind::Spent),
+        (VtxoStateKind::Locked, VtxoStateKind::Spendable),
+        (VtxoStateKind::Locked, VtxoStateKind::Spent),
+    ];
+    for from in ALL_VTXO_STATES {
+        for to in ALL_VTXO_STATES {
+            let result = check_vtxo_transition("vtxo", from, to);
+            match legal.contains(&(from, to)) {
+                true => assert!(result.is_ok(), "{:?} -> {:?} must be legal", from, to),
+                false => assert_eq!(
+                    result,
+                    Err(IllegalVtxoTransition {
+                        vtxo_id: "vtxo".to_string(),
+                        from,
+                        to,
+                    }),
+                    "{:?} -> {:?} must be illegal",
+                    from,
+                    to
+                ),
+            }
+        }
+    }
+
+    assert_eq!(
+        vtxo_state_kind(&VtxoState::Locked { movement_id: None }),
+        VtxoStateKind::Locked
+    );
+    assert_eq!(vtxo_state_kind(&VtxoState::Spent), VtxoStateKind::Spent);
+    let err =
+        check_vtxo_transition("abc:0", VtxoStateKind::Spent, VtxoStateKind::Spendable).unwrap_err();
+    assert!(err.to_string().contains("abc:0"));
+}
+
+#[test]
+fn test_query_vtxos() {
+    use crate::utils::{VtxoQuery, VtxoQueryFields, VtxoSortKey, query_vtxos};
+    use bark::vtxo::VtxoStateKind;
+
+    let vtxo = |name: &'static str, state, amount_sat, expiry_height| {
+        (
+            name,
+            VtxoQueryFields {
+                state,
+                amount_sat,
+                expiry_height,
+            },
+        )
+    };
+    let vtxos = vec![
+        vtxo("a", VtxoStateKind::Spendable, 5_000, 900),
+        vtxo("b", VtxoStateKind::Spendable, 20_000, 700),
+        vtxo("c", VtxoStateKind::Locked, 1_000, 800),
+        vtxo("d", VtxoStateKind::Spent, 50_000, 600),
+        vtxo("e", VtxoStateKind::Spendable, 10_000, 1_000),
+    ];
+    let all = VtxoQuery {
+        states: vec![],
+        min_amount_sat: None,
+        max_amount_sat: None,
+        max_expiry_height: None,
+        sort_key: VtxoSortKey::Expiry,
+        descending: false,
+        limit: None,
+        offset: 0,
+    };
+    let run = |query: &VtxoQuery| {
+        let (page, total) = query_vtxos(vtxos.clone(), |(_, fields)| *fields, query);
+        (
+            page.into_iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            total,
+        )
+    };
+
+    assert_eq!(run(&all), (vec!["d", "b", "c", "a", "e"], 5));
+
+    let spendable = VtxoQuery {
+        states: vec![VtxoStateKind::Spendable],
+        ..all.clone()
+    };
+    assert_eq!(run(&spendable), (vec!["b", "a", "e"], 3));
+
+    let amounts = VtxoQuery {
+        min_amount_sat: Some(5_000),
+        max_amount_sat: Some(20_000),
+        ..all.clone()
+    };
+    assert_eq!(run(&amounts), (vec!["b", "a", "e"], 3));
+
+    let expiring = VtxoQuery {
+        max_expiry_height: Some(800),
+        ..all.clone()
+    };
+    assert_eq!(run(&expiring), (vec!["d", "b", "c"], 3));
+
+    let by_amount_desc = VtxoQuery {
+        sort_key: VtxoSortKey::Amount,
+        descending: true,
+        ..spendable.clone()
+    };
+    assert_eq!(run(&by_amount_desc), (vec!["b", "e", "a"], 3));
+
+    // The total counts all matches, not just the page
+    let paged = VtxoQuery {
+        limit: Some(2),
+        offset: 1,
+        ..by_amount_desc.clone()
+    };
+    assert_eq!(run(&paged), (vec!["e", "a"], 3));
+    let past_end = VtxoQuery {
+        offset: 10,
+        ..paged
+    };
+    assert_eq!(run(&past_end), (vec![], 3));
+
+    let combined = VtxoQuery {
+        states: vec![VtxoStateKind::Spendable, VtxoStateKind::Locked],
+        max_amount_sat: Some(10_000),
+        max_expiry_height: Some(950),
+        sort_key: VtxoSortKey::Amount,
+        ..all
+    };
+    assert_eq!(run(&combined), (vec!["c", "a"], 2));
+}
+
+#[test]
+fn test_claim_retry_schedule() {
+    use crate::receive_claims::{
+        ClaimFailures, RECEIVE_AT_RISK_BLOCKS, claim_retry_interval, htlc_blocks_left,
+        note_claim_failure,
+    };
+    use crate::{WalletEventKind, take_events};
+    use std::time::Duration;
+
+    // Partial blocks count as mined, to err on the early side
+    assert_eq!(htlc_blocks_left(1_000, 144, 1_000), 144);
+    assert_eq!(htlc_blocks_left(1_000, 144, 1_001), 143);
+    assert_eq!(htlc_blocks_left(1_000, 144, 1_000 + 600 * 10), 134);
+    assert_eq!(htlc_blocks_left(1_000, 18, 1_000 + 600 * 20), -2);
+    assert_eq!(htlc_blocks_left(1_000, 144, 0), 144);
+
+    assert_eq!(claim_retry_interval(500), Some(Duration::from_secs(30)));
+    assert_eq!(claim_retry_interval(101), Some(Duration::from_secs(30)));
+    assert_eq!(claim_retry_interval(100), Some(Duration::from_secs(15)));
+    assert_eq!(claim_retry_interval(10), Some(Duration::from_secs(15)));
+    assert_eq!(claim_retry_interval(9), Some(Duration::from_secs(5)));
+    assert_eq!(claim_retry_interval(1), Some(Duration::from_secs(5)));
+    assert_eq!(claim_retry_interval(0), None);
+    assert_eq!(claim_retry_interval(-5), None);
+
+    // Failures far from expiry are tracked but not reported
+    let mut failures = ClaimFailures::default();
+    assert!(!note_claim_failure(
+        &mut failures,
+        "retry-far",
+        Some(1_000),
+        120,
+        "offline"
+    ));
+    assert!(failures.at_risk().is_empty());
+    assert_eq!(failures.next_retry(), Some(Duration::from_secs(30)));
+
+    assert!(note_claim_failure(
+        &mut failures,
+        "retry-near",
+        Some(2_000),
+        RECEIVE_AT_RISK_BLOCKS,
+        "server error"
+    ));
+    assert!(note_claim_failure(
+        &mut failures,
+        "retry-near",
+        Some(2_000),
+        8,
+        "timeout"
+    ));
+    assert!(!note_claim_failure(
+        &mut failures,
+        "retry-far",
+        Some(1_000),
+        119,
+        "offline"
+    ));
+    assert_eq!(failures.next_retry(), Some(Duration::from_secs(5)));
+
+    let at_risk = failures.at_risk();
+    assert_eq!(at_risk.len(), 1);
+    assert_eq!(at_risk[0].payment_hash, "retry-near");
+    assert_eq!(at_risk[0].failed_attempts, 2);
+    assert_eq!(at_risk[0].blocks_left, 8);
+    assert_eq!(at_risk[0].last_error, "timeout");
+
+    // Other tests may emit events concurrently, only look at ours
+    let events = take_events()
+        .into_iter()
+        .filter(|e| e.kind == WalletEventKind::LightningReceiveAtRisk)
+        .filter(|e| e.payload["payment_hash"] == "retry-near")
+        .collect::<Vec<_>>();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[1].payload["blocks_left"], 8);
+    assert_eq!(events[1].payload["failed_attempts"], 2);
+    assert_eq!(events[1].payload["amount_msat"], 2_000);
+
+    failures.record_success("retry-near");
+    assert!(failures.at_risk().is_empty());
+    let pending = std::collections::HashSet::new();
+    failures.retain_pending(&pending);
+    assert_eq!(failures.next_retry(), None);
+}
+
+#[test]
+fn test_mnemonic_languages() {
+    use crate::utils::parse_mnemonic;
+    use crate::{create_mnemonic_with_options, validate_mnemonic};
+    use bip39::{Language, Mnemonic};
+
+    // BIP39 test vectors for all-zero 128-bit entropy
+    let english = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+    let spanish = "ábaco ábaco ábaco ábaco ábaco ábaco ábaco ábaco ábaco ábaco ábaco abierto";
+    let japanese = ["あいこくしん"; 11].join("\u{3000}") + "\u{3000}あおぞら";
+
+    let zero_entropy = vec![0u8; 16];
+    for (phrase, language) in [
+        (english.to_string(), Language::English),
+        (spanish.to_string(), Language::Spanish),
+        (japanese.clone(), Language::Japanese),
+    ] {
+        let mnemonic = parse_mnemonic(&phrase).unwrap();
+        assert_eq!(mnemonic.language(), language);
+        assert_eq!(mnemonic.to_entropy(), zero_entropy);
+        assert_eq!(validate_mnemonic(&phrase).unwrap(), language);
+        assert_eq!(
+            Mnemonic::from_entropy_in(language, &zero_entropy).unwrap(),
+            mnemonic
+        );
+    }
+
+    assert_eq!(
+        hex::encode(parse_mnemonic(english).unwrap().to_seed("TREZOR")),
+        "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+    );
+
+    // Input is NFKD normalized, so the seed doesn't depend on how the
+    // accents or spaces were typed
+    let decomposed = spanish.replace('á', "a\u{301}");
+    assert_ne!(decomposed, spanish);
+    assert_eq!(
+        parse_mnemonic(&decomposed).unwrap().to_seed(""),
+        parse_mnemonic(spanish).unwrap().to_seed("")
+    );
+    assert_eq!(
+        parse_mnemonic(&japanese.replace('\u{3000}', " "))
+            .unwrap()
+            .to_seed(""),
+        parse_mnemonic(&japanese).unwrap().to_seed("")
+    );
+
+    // The seed comes from the words, the same entropy is another wallet in
+    // another language
+    assert_ne!(
+        parse_mnemonic(english).unwrap().to_seed(""),
+        parse_mnemonic(spanish).unwrap().to_seed("")
+    );
+
+    for language in [Language::English, Language::Spanish, Language::Japanese] {
+        let phrase = create_mnemonic_with_options(24, language).unwrap();
+        let mnemonic = parse_mnemonic(&phrase).unwrap();
+        assert_eq!(mnemonic.word_count(), 24);
+        assert_eq!(validate_mnemonic(&phrase).unwrap(), language);
+    }
+
+    assert!(
+        validate_mnemonic(
+            "ábaco abandon ábaco ábaco ábaco ábaco ábaco ábaco ábaco ábaco ábaco abierto"
+        )
+        .is_err()
+    );
+    assert!(validate_mnemonic("abandon abandon abandon").is_err());
+    assert!(create_mnemonic_with_options(13, Language::English).is_err());
+}
+
+#[test]
+fn test_payment_route() {
+    use crate::utils::{PaymentRoute, payment_route};
+    use crate::{LightningPaymentResult, PaymentResult};
+    use bark::ark::bitcoin::hashes::{Hash, sha256};
+    use bark::ark::bitcoin::secp256k1::{Secp256k1, SecretKey};
+    use bark::lightning_invoice::{Currency, InvoiceBuilder, PaymentSecret};
+
+    let secp = Secp256k1::new();
+    let key = SecretKey::from_slice(&[0x42; 32]).unwrap();
+    let amountless = InvoiceBuilder::new(Currency::Regtest)
+        .description("coffee".to_string())
+        .payment_hash(sha256::Hash::hash(b"preimage"))
+        .payment_secret(PaymentSecret([7; 32]))
+        .current_timestamp()
+        .min_final_cltv_expiry_delta(144)
+        .build_signed(|msg| secp.sign_ecdsa_recoverable(msg, &key))
+        .unwrap()
+        .to_string();
+    let fixed = lnurl_test_invoice(5_000_000, "coffee").to_string();
+
+    // Bolt11 invoices take the amount from the invoice if it has one
+    assert!(matches!(
+        payment_route(&fixed, None, None).unwrap(),
+        PaymentRoute::Bolt11(_, None)
+    ));
+    assert!(matches!(
+        payment_route(&fixed, Some(5_000), None).unwrap(),
+        PaymentRoute::Bolt11(_, Some(amount)) if amount.to_sat() == 5_000
+    ));
+    assert!(payment_route(&fixed, Some(4_000), None).is_err());
+    assert!(matches!(
+        payment_route(&amountless, Some(1_000), None).unwrap(),
+        PaymentRoute::Bolt11(_, Some(_))
+    ));
+    assert!(payment_route(&amountless, None, None).is_err());
+    assert!(payment_route(&fixed, None, Some("thanks".to_string())).is_err());
+
+    // Lightning addresses need an amount and are the only ones taking a comment
+    match payment_route(
+        " alice@example.com ",
+        Some(2_000),
+        Some("thanks".to_string()),
+    )
+    .unwrap()
+    {
+        PaymentRoute::LnAddress(address, amount, comment) => {
+            assert_eq!(address.to_string(), "alice@example.com");
+            assert_eq!(amount.to_sat(), 2_000);
+            assert_eq!(comment.as_deref(), Some("thanks"));
+        }
+        other => panic!("Expected a lightning address route, got {:?}", other),
+    }
+    assert!(payment_route("alice@example.com", None, None).is_err());
+
+    // Unsupported destinations are rejected before anything is paid
+    let onchain = payment_route(
+        "bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080",
+        Some(1_000),
+        None,
+    )
+    .unwrap_err();
+    assert!(onchain.to_string().contains("send_onchain"));
+    let pubkey = payment_route(
+        "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        Some(1_000),
+        None,
+    )
+    .unwrap_err();
+    assert!(pubkey.to_string().contains("ark address"));
+    let lnurl = "LNURL1DP68GURN8GHJ7UM9WFMXJCM99E3K7MF0V9CXJ0M385EKVCENXC6R2C35XVUKXEFCV5MKVV34X5EKZD3EV56NYD3HXQURZEPEXEJXXEPNXSCRVWFNV9NXZCN9XQ6XYEFHVGCXXCMYXYMNSERXFQ5FNS";
+    assert!(
+        payment_route(lnurl, None, None)
+            .unwrap_err()
+            .to_string()
+            .contains("process_lnurl_withdraw")
+    );
+    assert!(payment_route("not a destination", Some(1_000), None).is_err());
+    assert!(payment_route(&fixed, Some(0), None).is_err());
+
+    let arkoor = serde_json::to_value(PaymentResult::Arkoor {
+        destination: "tark1dest".to_string(),
+        amount_sat: 1_000,
+        sent_vtxo_ids: vec!["vtxo:0".to_string()],
+        change_amount_sat: 500,
+    })
+    .unwrap();
+    assert_eq!(arkoor["payment_type"], "arkoor");
+    assert_eq!(arkoor["sent_vtxo_ids"][0], "vtxo:0");
+    assert_eq!(arkoor["change_amount_sat"], 500);
+
+    let lnaddr = serde_json::to_value(PaymentResult::LightningAddress(LightningPaymentResult {
+        destination: "alice@example.com".to_string(),
+        amount_sat: 2_000,
+        invoice: fixed.clone(),
+        payment_hash: "hash".to_string(),
+        preimage: None,
+    }))
+    .unwrap();
+    assert_eq!(lnaddr["payment_type"], "lightning_address");
+    assert_eq!(lnaddr["destination"], "alice@example.com");
+    assert_eq!(lnaddr["invoice"], fixed);
+    assert!(lnaddr["preimage"].is_null());
+}
+
+#[test]
+fn test_pending_board_info() {
+    use crate::utils::{PendingBoardInfo, PendingBoardState};
+    use bark::ark::VtxoId;
+    use bark::ark::bitcoin::{OutPoint, Txid};
+    use std::collections::HashMap;
+
+    let txid = |byte: u8| Txid::from_str(&format!("{:02x}", byte).repeat(32)).unwrap();
+    let boards = [
+        (txid(1), 1, 50_000),
+        (txid(2), 0, 20_000),
+        (txid(3), 2, 10_000),
+    ];
+    // Mocked chain source: board 1 confirmed at 100, board 2 at 104, board 3 unconfirmed
+    let chain = HashMap::from([(txid(1), 100), (txid(2), 104)]);
+    let tip = 105;
+
+    let assemble = |boards: &[(Txid, u32, u64)]| {
+        boards
+            .iter()
+            .map(|&(txid, vout, amount_sat)| {
+                let anchor = OutPoint::new(txid, vout);
+                let vtxo_id = VtxoId::from_str(&anchor.to_string()).unwrap();
+                PendingBoardInfo::new(
+                    anchor,
+                    Amount::from_sat(amount_sat),
+                    &[vtxo_id],
+                    chain.get(&txid).copied(),
+                    tip,
+                    3,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let infos = assemble(&boards);
+    assert_eq!(infos.len(), 3);
+
+    assert_eq!(infos[0].funding_txid, txid(1).to_string());
+    assert_eq!(infos[0].vout, 1);
+    assert_eq!(infos[0].amount_sat, 50_000);
+    assert_eq!(infos[0].vtxo_ids, vec![format!("{}:1", txid(1))]);
+    assert_eq!(infos[0].confirmations, 6);
+    assert_eq!(infos[0].required_confirmations, 3);
+    assert_eq!(infos[0].state, PendingBoardState::AwaitingRegistration);
+
+    assert_eq!(infos[1].confirmations, 2);
+    assert_eq!(infos[1].state, PendingBoardState::AwaitingConfirmation);
+
+    assert_eq!(infos[2].confirmations, 0);
+    assert_eq!(infos[2].state, PendingBoardState::AwaitingConfirmation);
+    assert_eq!(infos[2].state.as_str(), "AwaitingConfirmation");
+
+    // Confirmed in the tip block counts as one confirmation
+    let at_tip = PendingBoardInfo::new(
+        OutPoint::new(txid(4), 0),
+        Amount::from_sat(1_000),
+        &[],
+        Some(tip),
+        tip,
+        1,
+    );
+    assert_eq!(at_tip.confirmations, 1);
+    assert_eq!(at_tip.state, PendingBoardState::AwaitingRegistration);
+
+    assert!(assemble(&[]).is_empty());
+}
+
+#[test]
+fn test_derivation_golden_vectors() {
+    use crate::test_vectors::{DERIVATION_VECTORS, SIGNED_MESSAGE};
+    use crate::{
+        DEFAULT_ARK_PURPOSE_INDEX, derive_ark_keypair, sign_messsage_with_mnemonic,
+        verify_derivation_integrity,
+    };
+    use bark::ark::bitcoin::Network;
+
+    for vectors in &DERIVATION_VECTORS {
+        let mnemonic = bip39::Mnemonic::from_str(vectors.mnemonic).unwrap();
+
+        // The network only changes the xpriv encoding, never the keys
+        for network in [Network::Bitcoin, Network::Signet, Network::Regtest] {
+            for (index, expected) in vectors.keys {
+                let keypair =
+                    derive_ark_keypair(&mnemonic, network, DEFAULT_ARK_PURPOSE_INDEX, index)
+                        .unwrap();
+                assert_eq!(
+                    keypair.public_key().to_string(),
+                    expected,
+                    "key {} of '{}' on {}",
+                    index,
+                    vectors.mnemonic,
+                    network
+                );
+            }
+            let custom = derive_ark_keypair(&mnemonic, network, 351, 0).unwrap();
+            assert_eq!(custom.public_key().to_string(), vectors.purpose_351_key);
+
+            let signature = crate::TOKIO_RUNTIME
+                .block_on(sign_messsage_with_mnemonic(
+                    SIGNED_MESSAGE,
+                    mnemonic.clone(),
+                    network,
+                    DEFAULT_ARK_PURPOSE_INDEX,
+                    0,
+                ))
+                .unwrap();
+            assert_eq!(signature.to_string(), vectors.signature);
+        }
+    }
+
+    assert!(verify_derivation_integrity().unwrap());
+}
+
+#[test]
+fn test_onchain_self_transfer() {
+    use crate::onchain::{check_consolidation, check_self_transfer_amount, fresh_internal_address};
+    use bdk_wallet::KeychainKind;
+    use bdk_wallet::bitcoin::Network;
+
+    // Public master key of the all-zero-entropy mnemonic
+    let tpub = "tpubD6NzVbkrYhZ4XYa9MoLt4BiMZ4gkt2faZ4BcmKu2a9te4LDpQmvEz2L2yDERivHxFPnxXXhqDRkUNnQCpZggCyEZLBktV7VaSmwayqMJy1s";
+    let mut wallet =
+        bdk_wallet::Wallet::create(format!("wpkh({}/0/*)", tpub), format!("wpkh({}/1/*)", tpub))
+            .network(Network::Regtest)
+            .create_wallet_no_persist()
+            .unwrap();
+
+    let first = fresh_internal_address(&mut wallet);
+    let second = fresh_internal_address(&mut wallet);
+    assert_ne!(first, second);
+    for (address, index) in [(&first, 0), (&second, 1)] {
+        assert_eq!(
+            wallet.derivation_of_spk(address.script_pubkey()),
+            Some((KeychainKind::Internal, index))
+        );
+    }
+    // Marked used, so the change of the transfer can't land on it
+    let change = wallet.next_unused_address(KeychainKind::Internal);
+    assert_eq!(change.index, 2);
+    // Receive addresses are untouched
+    assert_eq!(wallet.next_unused_address(KeychainKind::External).index, 0);
+
+    assert!(check_consolidation(0).is_err());
+    let single = check_consolidation(1).unwrap_err();
+    assert!(single.to_string().contains("single UTXO"));
+    assert!(check_consolidation(2).is_ok());
+
+    let spendable = Amount::from_sat(10_000);
+    assert!(check_self_transfer_amount(Amount::ZERO, spendable).is_err());
+    assert!(check_self_transfer_amount(Amount::from_sat(10_001), spendable).is_err());
+    assert!(check_self_transfer_amount(Amount::from_sat(10_000), spendable).is_ok());
+    assert!(check_self_transfer_amount(Amount::from_sat(1), spendable).is_ok());
+}
+
+#[test]
+fn test_accounting_csv_export() {
+    use crate::utils::{
+        ACCOUNTING_CSV_HEADER, AccountingCsvWriter, AccountingRow, MovementKind, csv_field,
+        write_accounting_csv,
+    };
+
+    assert_eq!(csv_field("plain"), "plain");
+    assert_eq!(csv_field("a,b"), "\"a,b\"");
+    assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+
+    let row =
+        |id: u32, at: i64, kind: MovementKind, effective: i64, fee: u64, to: &str| AccountingRow {
+            movement_id: id,
+            created_at: at,
+            kind,
+            status: "finished".to_string(),
+            effective_balance_sat: effective,
+            fee_sat: fee,
+            counterparty: to.to_string(),
+        };
+    // Out of order on purpose, the export replays them by time
+    let rows = vec![
+        row(
+            3,
+            3_000,
+            MovementKind::Send,
+            -2_100,
+            100,
+            "Bob, \"the builder\"",
+        ),
+        row(1, 1_000, MovementKind::Board, 10_000, 0, ""),
+        row(4, 4_000, MovementKind::Receive, 500, 0, "lnbcrt1invoice"),
+        row(
+            2,
+            2_000,
+            MovementKind::Send,
+            -1_050,
+            50,
+            "alice@example.com",
+        ),
+    ];
+
+    let mut out = Vec::new();
+    let written = write_accounting_csv(&mut out, rows.clone(), i64::MIN, i64::MAX).unwrap();
+    assert_eq!(written, 4);
+    let csv = String::from_utf8(out).unwrap();
+    let lines = csv.split_terminator("\r\n").collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        vec![
+            ACCOUNTING_CSV_HEADER,
+            "1970-01-01T00:16:40+00:00,1,board,finished,10000,0,0,,10000,",
+            "1970-01-01T00:33:20+00:00,2,send,finished,0,1000,50,alice@example.com,8950,",
+            "1970-01-01T00:50:00+00:00,3,send,finished,0,2000,100,\"Bob, \"\"the builder\"\"\",6850,",
+            "1970-01-01T01:06:40+00:00,4,receive,finished,500,0,0,lnbcrt1invoice,7350,",
+        ]
+    );
+    // Every row has the same number of columns as the header
+    assert!(lines[..3].iter().all(|line| line.matches(',').count() == 9));
+
+    // Streaming the rows page by page gives the same file
+    let mut sorted = rows.clone();
+    sorted.sort_by_key(|row| row.created_at);
+    let mut streamed = Vec::new();
+    let mut writer = AccountingCsvWriter::new(&mut streamed, i64::MIN, i64::MAX).unwrap();
+    for page in sorted.chunks(3) {
+        for row in page {
+            writer.push(row).unwrap();
+        }
+    }
+    assert_eq!(writer.finish().unwrap(), 4);
+    assert_eq!(String::from_utf8(streamed).unwrap(), csv);
+
+    // Earlier movements still count towards the balance
+    let mut out = Vec::new();
+    let written = write_accounting_csv(&mut out, rows, 2_500, 3_500).unwrap();
+    assert_eq!(written, 1);
+    let csv = String::from_utf8(out).unwrap();
+    assert!(csv.ends_with(",6850,\r\n"));
+    assert!(csv.contains("\r\n1970-01-01T00:50:00+00:00,3,"));
+
+    let mut out = Vec::new();
+    assert_eq!(write_accounting_csv(&mut out, vec![], 0, 10).unwrap(), 0);
+    assert_eq!(out, format!("{}\r\n", ACCOUNTING_CSV_HEADER).into_bytes());
+}
+
+#[test]
+fn test_unrefreshed_arkoor_trust() {
+    use crate::utils::{
+        InsufficientTrustedFunds, check_trusted_funds, split_trusted_amount,
+        untrusted_vtxos_to_exclude,
+    };
+    use std::collections::HashSet;
+
+    let id = |n: u8| format!("{}:0", format!("{:02x}", n).repeat(32));
+    let (board, refreshed, received, received2) = (id(1), id(2), id(3), id(4));
+    let spendable = vec![
+        (board.clone(), 40_000),
+        (refreshed.clone(), 10_000),
+        (received.clone(), 5_000),
+        (received2.clone(), 2_500),
+    ];
+    let untrusted: HashSet<String> = [received.clone(), received2.clone()].into_iter().collect();
+
+    assert_eq!(
+        split_trusted_amount(&spendable, &untrusted),
+        (Amount::from_sat(50_000), Amount::from_sat(7_500))
+    );
+    assert_eq!(
+        split_trusted_amount(&spendable, &HashSet::new()),
+        (Amount::from_sat(57_500), Amount::ZERO)
+    );
+    // Ids that aren't spendable don't count
+    let stale: HashSet<String> = [id(9)].into_iter().collect();
+    assert_eq!(split_trusted_amount(&spendable, &stale).1, Amount::ZERO);
+
+    // Trusted funds cover it
+    assert_eq!(check_trusted_funds(50_000, 50_000, 7_500), Ok(()));
+    // Only the unrefreshed arkoor funds would cover it
+    let err = check_trusted_funds(55_000, 50_000, 7_500).unwrap_err();
+    assert_eq!(
+        err,
+        InsufficientTrustedFunds {
+            amount_sat: 55_000,
+            trusted_sat: 50_000,
+            pending_trustless_sat: 7_500,
+        }
+    );
+    assert!(err.to_string().contains("7500 sats"));
+    assert_eq!(
+        check_trusted_funds(57_500, 50_000, 7_500)
+            .unwrap_err()
+            .amount_sat,
+        57_500
+    );
+    // Not even the total covers it, left to the regular insufficient funds error
+    assert_eq!(check_trusted_funds(57_501, 50_000, 7_500), Ok(()));
+
+    let mut excluded = untrusted_vtxos_to_exclude(&spendable, &untrusted, Some(30_000)).unwrap();
+    excluded.sort();
+    assert_eq!(excluded, vec![received.clone(), received2.clone()]);
+    // Without a known amount the VTXOs are still excluded
+    assert_eq!(
+        untrusted_vtxos_to_exclude(&spendable, &untrusted, None)
+            .unwrap()
+            .len(),
+        2
+    );
+    assert!(untrusted_vtxos_to_exclude(&spendable, &untrusted, Some(52_000)).is_err());
+    assert!(
+        untrusted_vtxos_to_exclude(&spendable, &HashSet::new(), Some(52_000))
+            .unwrap()
+            .is_empty()
+    );
+
+    let (temp_dir, store) = temp_store();
+    assert!(store.get_allow_spending_unrefreshed_arkoor().unwrap());
+    store.set_allow_spending_unrefreshed_arkoor(false).unwrap();
+    assert!(!store.get_allow_spending_unrefreshed_arkoor().unwrap());
+    drop(store);
+    let store = crate::store::Store::open(&temp_dir.path().join("nitro.sqlite")).unwrap();
+    assert!(!store.get_allow_spending_unrefreshed_arkoor().unwrap());
+}
+
+#[test]
+fn test_wallet_state_after_failed_load() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    use crate::{WalletManager, WalletState};
+
+    let (temp_dir, opts) = setup_test_wallet_opts();
+    let create_opts = crate::utils::ffi_config_to_config(opts).unwrap();
+    let mnemonic = create_opts.mnemonic.expose_secret().clone();
+    let (config, _) = crate::utils::merge_config_opts(create_opts).unwrap();
+
+    let state = Arc::new(AtomicU8::new(WalletState::NotLoaded as u8));
+    let mut manager = WalletManager::with_state(state.clone());
+    assert_eq!(manager.state(), WalletState::NotLoaded);
+
+    let missing = temp_dir.path().join("missing");
+    let loaded =
+        crate::TOKIO_RUNTIME.block_on(manager.load_wallet(&missing, mnemonic, config, false));
+    assert!(loaded.is_err());
+    assert_eq!(manager.state(), WalletState::Error);
+    // The state is shared, so it can be read without the manager
+    assert_eq!(state.load(Ordering::Acquire), WalletState::Error as u8);
+
+    // A failed create is reported the same way
+    manager.set_state(WalletState::NotLoaded);
+    let (_, mut opts) = setup_test_wallet_opts();
+    opts.regtest = false;
+    let no_network = crate::utils::ffi_config_to_config(opts).unwrap();
+    let created = crate::TOKIO_RUNTIME.block_on(manager.create_wallet(temp_dir.path(), no_network));
+    assert!(created.is_err());
+    assert_eq!(manager.state(), WalletState::Error);
+
+    // Resetting leaves a clean, unloaded datadir behind
+    fs::write(temp_dir.path().join(crate::utils::STORE_FILE), b"").unwrap();
+    crate::TOKIO_RUNTIME
+        .block_on(manager.reset_to_clean_state(temp_dir.path()))
+        .unwrap();
+    assert_eq!(manager.state(), WalletState::NotLoaded);
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_wallet_state_load_close_cycle() {
+    cxx::init_logger().unwrap();
+    let (temp_dir, opts) = setup_test_wallet_opts();
+    let datadir = temp_dir.path().to_str().unwrap();
+    let mnemonic = opts.mnemonic.clone();
+    let load_opts = || {
+        let (_, mut opts) = setup_test_wallet_opts();
+        opts.mnemonic = mnemonic.clone();
+        opts
+    };
+
+    if cxx::is_wallet_loaded().unwrap() {
+        cxx::close_wallet().unwrap();
+    }
+    cxx::create_wallet(datadir, opts).unwrap();
+    assert!(cxx::get_wallet_state().unwrap() == ffi::CxxWalletState::NotLoaded);
+
+    cxx::load_wallet(datadir, load_opts()).unwrap();
+    assert!(cxx::get_wallet_state().unwrap() == ffi::CxxWalletState::Loaded);
+
+    cxx::close_wallet().unwrap();
+    assert!(cxx::get_wallet_state().unwrap() == ffi::CxxWalletState::NotLoaded);
+
+    let missing = temp_dir.path().join("missing");
+    assert!(cxx::load_wallet(missing.to_str().unwrap(), load_opts()).is_err());
+    assert!(cxx::get_wallet_state().unwrap() == ffi::CxxWalletState::Error);
+
+    cxx::load_wallet(datadir, load_opts()).unwrap();
+    assert!(cxx::get_wallet_state().unwrap() == ffi::CxxWalletState::Loaded);
+    cxx::close_wallet().unwrap();
+    assert!(cxx::get_wallet_state().unwrap() == ffi::CxxWalletState::NotLoaded);
+}
+
+#[test]
+fn test_movement_cursor_pagination() {
+    use crate::utils::{MovementCursor, movements_page};
+
+    let cursor = |created_at: i64, id: u32| MovementCursor { created_at, id };
+    // Two movements share a timestamp, only the id tells them apart
+    let mut history = vec![
+        (cursor(100, 1), 1),
+        (cursor(200, 2), 2),
+        (cursor(200, 3), 3),
+        (cursor(300, 4), 4),
+        (cursor(400, 5), 5),
+    ];
+
+    let (page, next) = movements_page(history.clone(), None, 2);
+    assert_eq!(page, vec![5, 4]);
+    assert_eq!(next, Some(cursor(300, 4)));
+
+    // New movements land between fetching the pages
+    history.push((cursor(500, 6), 6));
+    history.push((cursor(500, 7), 7));
+
+    let (page, next) = movements_page(history.clone(), next, 2);
+    assert_eq!(page, vec![3, 2]);
+    assert_eq!(next, Some(cursor(200, 2)));
+
+    let (page, next) = movements_page(history.clone(), next, 2);
+    assert_eq!(page, vec![1]);
+    assert_eq!(next, None);
+
+    // An exactly full last page has no next cursor either
+    let (page, next) = movements_page(history.clone(), Some(cursor(200, 3)), 2);
+    assert_eq!(page, vec![2, 1]);
+    assert_eq!(next, None);
+
+    // Walking the whole history shows every movement exactly once
+    let mut seen = Vec::new();
+    let mut next = None;
+    loop {
+        let (page, cursor) = movements_page(history.clone(), next, 3);
+        seen.extend(page);
+        match cursor {
+            Some(cursor) => next = Some(cursor),
+            None => break,
+        }
+    }
+    assert_eq!(seen, vec![7, 6, 5, 4, 3, 2, 1]);
+
+    let encoded = cursor(1_700_000_000, 42).encode();
+    assert_eq!(
+        MovementCursor::decode(&encoded).unwrap(),
+        cursor(1_700_000_000, 42)
+    );
+    assert!(MovementCursor::decode("").is_err());
+    assert!(MovementCursor::decode("100").is_err());
+    assert!(MovementCursor::decode("100.x").is_err());
+}
+
+#[test]
+fn test_movement_page_keyset_with_interleaved_inserts() {
+    use crate::store::{get_movement_page, open_bark_db_read_only};
+    use crate::utils::MovementCursor;
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("db.sqlite");
+    let writer = rusqlite::Connection::open(&path).unwrap();
+    // Fixture of the table bark keeps movements in. Movements 2 and 3
+    // share a second, so the id breaks the tie.
+    writer
+        .execute_batch(
+            "CREATE TABLE bark_movement (
+                id INTEGER PRIMARY KEY,
+                created_at DATETIME NOT NULL
+            );
+            INSERT INTO bark_movement (id, created_at) VALUES
+                (1, '2025-01-01 10:00:00'),
+                (2, '2025-01-01 11:00:00'),
+                (3, '2025-01-01 11:00:00'),
+                (4, '2025-01-01 12:00:00'),
+                (5, '2025-01-01 13:00:00');",
+        )
+        .unwrap();
+
+    let ids = |page: &[MovementCursor]| page.iter().map(|c| c.id).collect::<Vec<_>>();
+    let conn = open_bark_db_read_only(&path).unwrap();
+
+    let first = get_movement_page(&conn, None, 2, true).unwrap();
+    assert_eq!(ids(&first), vec![5, 4]);
+    assert_eq!(first[0].created_at, 1_735_736_400);
+
+    // New movements arrive while the user scrolls
+    writer
+        .execute_batch(
+            "INSERT INTO bark_movement (id, created_at) VALUES
+                (6, '2025-01-01 14:00:00'),
+                (7, '2025-01-01 15:00:00');",
+        )
+        .unwrap();
+
+    let second = get_movement_page(&conn, first.last().copied(), 2, true).unwrap();
+    assert_eq!(ids(&second), vec![3, 2]);
+
+    writer
+        .execute(
+            "INSERT INTO bark_movement (id, created_at) VALUES (8, '2025-01-01 16:00:00')",
+            [],
+        )
+        .unwrap();
+
+    let third = get_movement_page(&conn, second.last().copied(), 2, true).unwrap();
+    assert_eq!(ids(&third), vec![1]);
+    assert!(
+        get_movement_page(&conn, third.last().copied(), 2, true)
+            .unwrap()
+            .is_empty()
+    );
+
+    // Oldest first, as the accounting export reads it
+    let oldest = get_movement_page(&conn, None, 3, false).unwrap();
+    assert_eq!(ids(&oldest), vec![1, 2, 3]);
+    let next = get_movement_page(&conn, oldest.last().copied(), 10, false).unwrap();
+    assert_eq!(ids(&next), vec![4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn test_bridge_panic_is_caught() {
+    use crate::panics::{PANIC_REPORT_FILE, set_panic_report_dir};
+
+    cxx::init_panic_handler().unwrap();
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    set_panic_report_dir(Some(temp_dir.path()));
+
+    let err = cxx::panic_for_test("deliberate test panic").unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<crate::InternalPanic>(),
+        Some(&crate::InternalPanic {
+            message: "deliberate test panic".to_string(),
+        })
+    );
+    assert_eq!(err.to_string(), "Internal panic: deliberate test panic");
+
+    let report = fs::read_to_string(temp_dir.path().join(PANIC_REPORT_FILE)).unwrap();
+    assert!(report.contains("message: deliberate test panic"));
+    assert!(report.contains("location: src/cxx.rs:"));
+    assert!(report.contains("thread: "));
+    assert!(
+        cxx::get_last_panic_report()
+            .unwrap()
+            .contains("deliberate test panic"),
+        "the report is kept in memory as well"
+    );
+
+    // Bridge calls that don't panic are unaffected
+    assert!(cxx::create_mnemonic().is_ok());
+    set_panic_report_dir(None);
+}
+
+#[test]
+fn test_store_recovers_from_poisoned_connection() {
+    let (_temp_dir, store) = temp_store();
+    store.add_address_book_entry("alice", "tark1alice").unwrap();
+
+    store.poison_for_test();
+
+    // Later calls keep working on the same connection
+    assert_eq!(store.get_address_book().unwrap().len(), 1);
+    store.add_address_book_entry("bob", "tark1bob").unwrap();
+    assert_eq!(store.get_address_book().unwrap().len(), 2);
+}
+
+#[test]
+fn test_database_size_and_row_counts() {
+    use crate::store::{database_size, open_bark_db_read_only, table_row_counts};
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("db.sqlite");
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE bark_vtxo (id TEXT PRIMARY KEY, raw BLOB NOT NULL);
+        CREATE TABLE \"odd \"\"name\" (value INTEGER);
+        CREATE INDEX bark_vtxo_raw ON bark_vtxo (raw);",
+    )
+    .unwrap();
+    let empty_size = database_size(&open_bark_db_read_only(&path).unwrap()).unwrap();
+    let page_size: u64 = conn
+        .query_row("PRAGMA page_size", [], |row| row.get(0))
+        .unwrap();
+    // Schema page plus a root page per table and index
+    assert!(empty_size >= 4 * page_size);
+    assert_eq!(empty_size, fs::metadata(&path).unwrap().len());
+
+    for i in 0..200 {
+        conn.execute(
+            "INSERT INTO bark_vtxo (id, raw) VALUES (?1, ?2)",
+            rusqlite::params![format!("vtxo-{}", i), vec![0u8; 512]],
+        )
+        .unwrap();
+    }
+    conn.execute("INSERT INTO \"odd \"\"name\" (value) VALUES (1)", [])
+        .unwrap();
+
+    let conn = open_bark_db_read_only(&path).unwrap();
+    let size = database_size(&conn).unwrap();
+    // 200 rows of 512 bytes, stored twice through the index
+    assert!(size > empty_size + 2 * 200 * 512);
+    assert!(size < 1024 * 1024);
+
+    let counts = table_row_counts(&conn).unwrap();
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts["bark_vtxo"], 200);
+    assert_eq!(counts["odd \"name"], 1);
+}
+
+#[test]
+fn test_board_requirements() {
+    use crate::utils::{BOARD_DUST_LIMIT_SAT, BoardAmountError, BoardRequirements};
+    use bark::ark::bitcoin::FeeRate;
+
+    let fee_rate = FeeRate::from_sat_per_vb(2).unwrap();
+    let requirements = BoardRequirements::new(None, Some(Amount::from_sat(100_000)), fee_rate);
+    assert_eq!(requirements.min_board_sat, BOARD_DUST_LIMIT_SAT);
+    assert_eq!(requirements.max_board_sat, Some(100_000));
+    // One input, recipient and change output at 2 sat/vB
+    assert_eq!(
+        requirements.estimated_onchain_fee_sat,
+        2 * (11 + 58 + 2 * 43)
+    );
+    assert_eq!(requirements.estimated_round_fee_sat, 0);
+
+    assert_eq!(
+        requirements.check(Amount::from_sat(329)),
+        Err(BoardAmountError::BelowMinimumBoard {
+            amount_sat: 329,
+            min_board_sat: 330,
+        })
+    );
+    assert!(requirements.check(Amount::from_sat(330)).is_ok());
+    assert!(requirements.check(Amount::from_sat(100_000)).is_ok());
+    assert_eq!(
+        requirements.check(Amount::from_sat(100_001)),
+        Err(BoardAmountError::AboveMaximumBoard {
+            amount_sat: 100_001,
+            max_board_sat: 100_000,
+        })
+    );
+
+    // A server minimum above dust wins, and no maximum means no limit
+    let requirements = BoardRequirements::new(Some(Amount::from_sat(10_000)), None, fee_rate);
+    assert_eq!(requirements.min_board_sat, 10_000);
+    assert!(requirements.check(Amount::from_sat(9_999)).is_err());
+    assert!(requirements.check(Amount::MAX_MONEY).is_ok());
+}
+
+#[test]
+fn test_schnorr_data_signatures() {
+    use crate::test_vectors::DERIVATION_VECTORS;
+    use crate::utils::{parse_mnemonic, sign_data_schnorr_with_keypair, verify_data_schnorr};
+    use crate::{DEFAULT_ARK_PURPOSE_INDEX, derive_ark_keypair};
+    use bark::ark::bitcoin::Network;
+
+    let mnemonic = parse_mnemonic(DERIVATION_VECTORS[0].mnemonic).unwrap();
+    let keypair =
+        derive_ark_keypair(&mnemonic, Network::Bitcoin, DEFAULT_ARK_PURPOSE_INDEX, 0).unwrap();
+    let compressed = keypair.public_key().to_string();
+    let x_only = keypair.x_only_public_key().0.to_string();
+    assert_eq!(compressed, DERIVATION_VECTORS[0].keys[0].1);
+
+    let data = "00ff10";
+    let signature = sign_data_schnorr_with_keypair(data, &keypair)
+        .unwrap()
+        .to_string();
+    assert_eq!(signature.len(), 128);
+    // Signing without aux randomness is deterministic
+    assert_eq!(
+        signature,
+        sign_data_schnorr_with_keypair(data, &keypair)
+            .unwrap()
+            .to_string()
+    );
+
+    assert!(verify_data_schnorr(data, &signature, &x_only).unwrap());
+    assert!(verify_data_schnorr(data, &signature, &compressed).unwrap());
+    assert!(!verify_data_schnorr("00ff11", &signature, &x_only).unwrap());
+
+    let other = derive_ark_keypair(&mnemonic, Network::Bitcoin, DEFAULT_ARK_PURPOSE_INDEX, 1)
+        .unwrap()
+        .public_key()
+        .to_string();
+    assert!(!verify_data_schnorr(data, &signature, &other).unwrap());
+
+    // Empty data is valid, malformed input is an error rather than false
+    assert!(sign_data_schnorr_with_keypair("", &keypair).is_ok());
+    assert!(sign_data_schnorr_with_keypair("0g", &keypair).is_err());
+    assert!(verify_data_schnorr(data, "00", &x_only).is_err());
+    assert!(verify_data_schnorr(data, &signature, "02").is_err());
+}
+
+/// A datadir with a bark database in WAL mode and a local store, without a
+/// live backend. The returned connection keeps the database open.
+fn seed_wallet_datadir(datadir: &std::path::Path) -> rusqlite::Connection {
+    use crate::store::Store;
+    use crate::utils::{DB_FILE, STORE_FILE};
+
+    fs::create_dir_all(datadir).unwrap();
+    let conn = rusqlite::Connection::open(datadir.join(DB_FILE)).unwrap();
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+        CREATE TABLE migrations (value INTEGER NOT NULL, created_at TEXT NOT NULL);
+        CREATE TABLE bark_properties (network TEXT NOT NULL, fingerprint TEXT NOT NULL);
+        CREATE TABLE bark_vtxo (id TEXT PRIMARY KEY, raw BLOB NOT NULL);
+        INSERT INTO migrations VALUES (1, '2025-01-01'), (2, '2025-01-02');
+        INSERT INTO bark_properties VALUES ('regtest', 'deadbeef');",
+    )
+    .unwrap();
+    for i in 0..50 {
+        conn.execute(
+            "INSERT INTO bark_vtxo (id, raw) VALUES (?1, ?2)",
+            rusqlite::params![format!("vtxo-{}", i), vec![1u8; 256]],
+        )
+        .unwrap();
+    }
+
+    let store = Store::open(&datadir.join(STORE_FILE)).unwrap();
+    store.add_address_book_entry("alice", "ark1alice").unwrap();
+    conn
+}
+
+#[test]
+fn test_move_wallet_datadir() {
+    use crate::datadir_move::{move_datadir, moved_to};
+    use crate::store::{Store, migration_registry};
+    use crate::utils::{DB_FILE, STORE_FILE};
+    use crate::{MOVED_TOMBSTONE_FILE, WalletManager};
+
+    let temp_dir = tempdir().expect("Failed to create temp dir");
+    let old = temp_dir.path().join("old");
+    let new = temp_dir.path().join("container").join("wallet");
+    let _conn = seed_wallet_datadir(&old);
+    assert!(old.join(format!("{}-wal", DB_FILE)).exists());
+
+    // Not a wallet, and not a datadir to move into
+    assert!(move_datadir(temp_dir.path(), &new).is_err());
+    fs::create_dir_all(&new).unwrap();
+    fs::write(new.join("other"), b"").unwrap();
+    assert!(move_datadir(&old, &new).is_err());
+    fs::remove_file(new.join("other")).unwrap();
+    assert!(move_datadir(&old, &old.join("nested")).is_err());
+
+    let report = move_datadir(&old, &new).unwrap();
+    assert_eq!(report.wallet_migration_version, 2);
+    assert_eq!(
+        report.store_schema_version,
+        migration_registry().latest_version()
+    );
+    assert!(report.files_copied >= 2);
+    assert!(report.bytes_copied > 50 * 256);
+    // 1 property, 2 migrations and 50 vtxos at least
+    assert!(report.rows_verified >= 53);
+    assert!(!new.join(crate::lock::LOCK_FILE).exists());
+    assert!(!temp_dir.path().join("container/wallet.moving").exists());
+
+    // The old datadir is kept, marked with where the wallet went
+    assert!(old.join(DB_FILE).exists());
+    assert!(old.join(MOVED_TOMBSTONE_FILE).exists());
+    assert_eq!(moved_to(&old), Some(new.clone()));
+    assert_eq!(moved_to(&new), None);
+    assert!(move_datadir(&old, &temp_dir.path().join("again")).is_err());
+
+    let (_, opts) = setup_test_wallet_opts();
+    let create_opts = crate::utils::ffi_config_to_config(opts).unwrap();
+    let mnemonic = create_opts.mnemonic.expose_secret().clone();
+    let (config, _) = crate::utils::merge_config_opts(create_opts).unwrap();
+    let mut manager = WalletManager::new();
+    let err = crate::TOKIO_RUNTIME
+        .block_on(manager.load_wallet(&old, mnemonic, config, false))
+        .unwrap_err();
+    assert!(err.to_string().contains("was moved to"));
+
+    // Everything is readable from the new location
+    let store = Store::open(&new.join(STORE_FILE)).unwrap();
+    assert_eq!(
+        store.get_address_book().unwrap()[0].destination,
+        "ark1alice"
+    );
+    let conn = crate::store::open_bark_db_read_only(&new.join(DB_FILE)).unwrap();
+    let vtxos: u64 = conn
+        .query_row("SELECT COUNT(*) FROM bark_vtxo", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(vtxos, 50);
+}
+
+#[test]
+#[ignore = "requires live regtest backend"]
+fn test_move_wallet_datadir_ffi() {
+    cxx::init_logger().unwrap();
+    let (temp_dir, opts) = setup_test_wallet_opts();
+    let old = temp_dir.path().join("old");
+    let new = temp_dir.path().join("new");
+    let mnemonic = opts.mnemonic.clone();
+    let load_opts = || {
+        let (_, mut opts) = setup_test_wallet_opts();
+        opts.mnemonic = mnemonic.clone();
+        opts
+    };
+
+    if cxx::is_wallet_loaded().unwrap() {
+        cxx::close_wallet().unwrap();
+    }
+    cxx::create_wallet(old.to_str().unwrap(), opts).unwrap();
+    let address = cxx::peak_address(0).unwrap();
+
+    // Refused while the wallet is loaded
+    assert!(cxx::move_wallet_datadir(old.to_str().unwrap(), new.to_str().unwrap()).is_err());
+    cxx::close_wallet().unwrap();
+
+    let report = cxx::move_wallet_datadir(old.to_str().unwrap(), new.to_str().unwrap()).unwrap();
+    assert!(report.bytes_copied > 0);
+    assert!(cxx::load_wallet(old.to_str().unwrap(), load_opts()).is_err());
+
+    cxx::load_wallet(new.to_str().unwrap(), load_opts()).unwrap();
+    assert_eq!(cxx::peak_address(0).unwrap().address, address.address);
+    cxx::close_wallet().unwrap();
+}
+
+#[test]
+fn test_round_failure_classification() {
+    use crate::round_failures::on_round_failure;
+    use crate::{RoundFailure, RoundFailureReason};
+    use bark::ark::VtxoId;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    let vtxo_id = "0d3c6b0fa4b5e0a1a0c6b0f0c0ddf1b8a7e9f7c6b1d2e3f4a5b6c7d8e9f0a1b2:1";
+    let classify = |err: anyhow::Error| RoundFailureReason::classify(&err);
+
+    let spent = anyhow::anyhow!(
+        "status: FailedPrecondition, message: \"vtxo {} already spent in round 42\"",
+        vtxo_id
+    )
+    .context("Failed to refresh vtxos");
+    assert_eq!(
+        classify(spent),
+        RoundFailureReason::InputAlreadySpent(Some(VtxoId::from_str(vtxo_id).unwrap()))
+    );
+    assert_eq!(
+        classify(anyhow::anyhow!("input was already forfeited")),
+        RoundFailureReason::InputAlreadySpent(None)
+    );
+    assert_eq!(
+        classify(anyhow::anyhow!("round is full, try again later")),
+        RoundFailureReason::RoundFull
+    );
+    assert_eq!(
+        classify(anyhow::anyhow!("transport error").context("Failed to connect to server")),
+        RoundFailureReason::ServerUnreachable
+    );
+    assert_eq!(
+        classify(anyhow::anyhow!("partial signature rejected by server")),
+        RoundFailureReason::SignatureRejected
+    );
+    assert_eq!(
+        classify(anyhow::anyhow!("round cancelled")),
+        RoundFailureReason::Other("round cancelled".to_string())
+    );
+
+    // Only a spent input triggers a resync
+    let resync_count = AtomicU32::new(0);
+    let resyncs = &resync_count;
+    let resync = move || async move {
+        resyncs.fetch_add(1, Ordering::SeqCst);
+    };
+    let err = crate::TOKIO_RUNTIME.block_on(on_round_failure(
+        anyhow::anyhow!("vtxo {} already spent", vtxo_id),
+        resync,
+    ));
+    assert_eq!(resyncs.load(Ordering::SeqCst), 1);
+    let failure = err.downcast_ref::<RoundFailure>().unwrap();
+    assert_eq!(failure.reason.code(), "input_already_spent");
+    assert!(
+        err.to_string()
+            .starts_with(&format!("Round failed [input_already_spent] {}", vtxo_id))
+    );
+
+    let err =
+        crate::TOKIO_RUNTIME.block_on(on_round_failure(anyhow::anyhow!("round is full"), resync));
+    assert_eq!(resyncs.load(Ordering::SeqCst), 1);
+    assert_eq!(err.to_string(), "Round failed [round_full]: round is full");
+}
+
+#[test]
+fn test_match_expected_payments() {
+    use crate::store::{ExpectedPayment, ExpectedPaymentStatus};
+    use crate::utils::{ReceivedVtxo, match_expected_payments};
+    use std::collections::HashSet;
+
+    let expected = |id: i64, pubkey: &str, amount_sat: Option<u64>| ExpectedPayment {
+        id,
+        pubkey_index: id as u32,
+        user_pubkey: pubkey.to_string(),
+        amount_sat,
+        memo: format!("order {}", id),
+        status: ExpectedPaymentStatus::Open,
+        vtxo_id: None,
+        created_at: 1_000 + id,
+        expires_at: 2_000,
+    };
+    let vtxo = |id: &str, pubkey: &str, amount_sat: u64| ReceivedVtxo {
+        vtxo_id: id.to_string(),
+        user_pubkey: pubkey.to_string(),
+        amount_sat,
+    };
+    let received = vec![
+        vtxo("a", "pk1", 1_000),
+        vtxo("b", "pk1", 5_000),
+        vtxo("c", "pk2", 7_000),
+        vtxo("d", "pk3", 100),
+    ];
+    let no_claims = HashSet::new();
+
+    // Amounts must match exactly, and any VTXO to the pubkey pays an
+    // amountless payment
+    let open = vec![
+        expected(1, "pk1", Some(5_000)),
+        expected(2, "pk2", Some(6_999)),
+        expected(3, "pk3", None),
+    ];
+    assert_eq!(
+        match_expected_payments(&open, &received, &no_claims),
+        vec![(1, "b".to_string()), (3, "d".to_string())]
+    );
+
+    // Several candidates: the older payment gets the first VTXO, and a VTXO
+    // never pays twice
+    let open = vec![expected(5, "pk1", None), expected(4, "pk1", None)];
+    assert_eq!(
+        match_expected_payments(&open, &received, &no_claims),
+        vec![(4, "a".to_string()), (5, "b".to_string())]
+    );
+    let open = vec![expected(6, "pk2", None), expected(7, "pk2", None)];
+    assert_eq!(
+        match_expected_payments(&open, &received, &no_claims),
+        vec![(6, "c".to_string())]
+    );
+
+    // VTXOs that paid earlier payments and payments no longer open are skipped
+    let claimed = HashSet::from(["a".to_string()]);
+    let mut cancelled = expected(8, "pk3", None);
+    cancelled.status = ExpectedPaymentStatus::Cancelled;
+    let open = vec![expected(9, "pk1", None), cancelled];
+    assert_eq!(
+        match_expected_payments(&open, &received, &claimed),
+        vec![(9, "b".to_string())]
+    );
+    assert!(match_expected_payments(&[], &received, &no_claims).is_empty());
+}
+
+#[test]
+fn test_expected_payment_store() {
+    use crate::store::ExpectedPaymentStatus;
+
+    let (_temp_dir, store) = temp_store();
+    let now = crate::store::now_timestamp();
+
+    let paid = store
+        .add_expected_payment(0, "pk0", Some(5_000), "coffee", now + 600)
+        .unwrap();
+    let stale = store
+        .add_expected_payment(1, "pk1", None, "tip", now - 1)
+        .unwrap();
+    let cancelled = store
+        .add_expected_payment(2, "pk2", None, "", now + 600)
+        .unwrap();
+    assert_eq!(paid.status, ExpectedPaymentStatus::Open);
+    assert_eq!(
+        store
+            .get_expected_payments(Some(ExpectedPaymentStatus::Open))
+            .unwrap(),
+        vec![paid.clone(), stale.clone(), cancelled.clone()]
+    );
+
+    assert!(store.fulfill_expected_payment(paid.id, "vtxo:0").unwrap());
+    assert!(!store.fulfill_expected_payment(paid.id, "vtxo:1").unwrap());
+    assert!(store.cancel_expected_payment(cancelled.id).unwrap());
+    assert!(!store.cancel_expected_payment(cancelled.id).unwrap());
+    assert_eq!(store.expire_expected_payments(now).unwrap(), 1);
+
+    let all = store.get_expected_payments(None).unwrap();
+    let statuses = all.iter().map(|p| p.status).collect::<Vec<_>>();
+    assert_eq!(
+        statuses,
+        vec![
+            ExpectedPaymentStatus::Fulfilled,
+            ExpectedPaymentStatus::Expired,
+            ExpectedPaymentStatus::Cancelled,
+        ]
+    );
+    assert_eq!(all[0].vtxo_id.as_deref(), Some("vtxo:0"));
+    assert_eq!(all[0].memo, "coffee");
+    assert_eq!(
+        "fulfilled".parse::<ExpectedPaymentStatus>().unwrap(),
+        ExpectedPaymentStatus::Fulfilled
+    );
+    assert!("paid".parse::<ExpectedPaymentStatus>().is_err());
+}
@@ -0,0 +1,32 @@
+//! Conversions between the amount units crossing the bridge.
+//!
+//! Lightning amounts are in millisatoshis, everything else in satoshis.
+//! Bridge code converts through these helpers only, so a lossy conversion
+//! is always explicit about its rounding.
+
+use bark::ark::bitcoin::Amount;
+
+pub const MSAT_PER_SAT: u64 = 1_000;
+
+pub fn amount_from_sat(sat: u64) -> Amount {
+    Amount::from_sat(sat)
+}
+
+/// Saturates instead of overflowing, no real amount comes near the limit.
+pub fn sat_to_msat(sat: u64) -> u64 {
+    sat.saturating_mul(MSAT_PER_SAT)
+}
+
+pub fn amount_to_msat(amount: Amount) -> u64 {
+    sat_to_msat(amount.to_sat())
+}
+
+/// Rounds down to whole sats, returning the msat that were cut off.
+pub fn msat_to_sat_floor(msat: u64) -> (Amount, u64) {
+    (Amount::from_sat(msat / MSAT_PER_SAT), msat % MSAT_PER_SAT)
+}
+
+/// Rounds up to whole sats, for amounts that must not fall short.
+pub fn msat_to_sat_ceil(msat: u64) -> Amount {
+    Amount::from_sat(msat.div_ceil(MSAT_PER_SAT))
+}
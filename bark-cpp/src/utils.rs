@@ -1,29 +1,69 @@
-use std::{path::Path, str::FromStr, sync::Arc};
+use std::{collections::HashSet, fmt, path::Path, str::FromStr, sync::Arc};
 
 use anyhow::{self, Context, bail};
 use bark::{
     Config, Wallet as BarkWallet, WalletVtxo,
     ark::{
         Vtxo, VtxoId,
-        bitcoin::{FeeRate, Network, secp256k1::PublicKey},
+        bitcoin::{
+            Amount, Denomination, FeeRate, Network, OutPoint, Transaction,
+            address::NetworkUnchecked,
+            secp256k1::{self, PublicKey},
+        },
     },
     lightning_invoice::Bolt11Invoice,
-    lnurllib::lightning_address::LightningAddress,
+    lnurllib::{
+        LnUrlResponse, lightning_address::LightningAddress, lnurl::LnUrl, pay::PayResponse,
+    },
     movement::{Movement, PaymentMethod},
     onchain::OnchainWallet,
     persist::sqlite::SqliteClient,
     round::RoundStatus,
-    vtxo::VtxoState,
+    vtxo::{VtxoState, VtxoStateKind},
 };
 
 use bitcoin_ext::FeeRateExt;
 use logger::log::{debug, info};
 use tokio::fs;
 use tonic::transport::Uri;
+use zeroize::Zeroize;
 
 use crate::cxx::ffi;
+use crate::store::{
+    BoardIntent, BoardIntentState, ExpectedPayment, ExpectedPaymentStatus, SubsystemTotals,
+};
 
 pub(crate) const DB_FILE: &str = "db.sqlite";
+pub(crate) const STORE_FILE: &str = "nitro.sqlite";
+
+/// Holds a secret value that must never end up in logs.
+///
+/// The value is redacted from `Debug` output and wiped from memory on drop.
+/// Use [`Secret::expose_secret`] where the actual value is needed.
+#[derive(Clone)]
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Secret(value)
+    }
+
+    pub fn expose_secret(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
 
 impl ConfigOpts {
     pub fn merge_into(self, cfg: &mut Config) -> anyhow::Result<()> {
@@ -46,7 +86,8 @@ impl ConfigOpts {
             cfg.bitcoind_user = if v.is_empty() { None } else { Some(v) };
         }
         if let Some(v) = self.bitcoind_pass {
-            cfg.bitcoind_pass = if v.is_empty() { None } else { Some(v) };
+            let v = v.expose_secret();
+            cfg.bitcoind_pass = if v.is_empty() { None } else { Some(v.clone()) };
         }
         cfg.vtxo_refresh_expiry_threshold = self.vtxo_refresh_expiry_threshold;
         cfg.fallback_fee_rate = self.fallback_fee_rate.map(FeeRate::from_sat_per_kvb_ceil);
@@ -91,12 +132,16 @@ pub struct ConfigOpts {
     pub bitcoind: Option<String>,
     pub bitcoind_cookie: Option<String>,
     pub bitcoind_user: Option<String>,
-    pub bitcoind_pass: Option<String>,
+    pub bitcoind_pass: Option<Secret<String>>,
     pub vtxo_refresh_expiry_threshold: u32,
     pub fallback_fee_rate: Option<u64>,
     pub htlc_recv_claim_delta: u16,
     pub vtxo_exit_margin: u16,
     pub round_tx_required_confirmations: u32,
+    /// BIP32 purpose index of the mnemonic-derived Ark keys, 350 if unset.
+    /// Only taken into account when creating a wallet, which refuses any
+    /// other value since bark derives the wallet's keys under 350.
+    pub ark_purpose_index: Option<u32>,
 }
 
 #[derive(Debug, Clone)]
@@ -110,7 +155,7 @@ pub struct CreateOpts {
 
     /// Recover a wallet with an existing mnemonic.
     /// This currently only works for on-chain funds.
-    pub mnemonic: bip39::Mnemonic,
+    pub mnemonic: Secret<bip39::Mnemonic>,
 
     /// The wallet/mnemonic's birthday blockheight to start syncing when recovering.
     pub birthday_height: Option<u32>,
@@ -139,20 +184,27 @@ pub(crate) async fn try_create_wallet(
     fs::create_dir_all(datadir)
         .await
         .context("can't create dir")?;
+    let _lock = crate::lock::DatadirLock::acquire(datadir, false)?;
 
     debug!("try_create_wallet datadir {:?} ", datadir);
     debug!("try_create_walletnetwork {:?}", net);
-    debug!("try_create_wallet config {:?}", config);
+    // The bark config carries the bitcoind password, so only log the endpoints
+    debug!(
+        "try_create_wallet server {} esplora {:?} bitcoind {:?}",
+        config.server_address, config.esplora_address, config.bitcoind_address
+    );
 
     // open db
     // generate seed
     let mnemonic = mnemonic.unwrap_or_else(|| bip39::Mnemonic::generate(12).expect("12 is valid"));
-    let seed = mnemonic.to_seed("");
+    let mut seed = mnemonic.to_seed("");
 
     // open db
     let db = Arc::new(SqliteClient::open(datadir.join(DB_FILE))?);
 
-    let bdk_wallet = OnchainWallet::load_or_create(net, seed, db.clone()).await?;
+    let bdk_wallet = OnchainWallet::load_or_create(net, seed, db.clone()).await;
+    seed.zeroize();
+    let bdk_wallet = bdk_wallet?;
     BarkWallet::create_with_onchain(&mnemonic, net, config, db, &bdk_wallet, false)
         .await
         .context("error creating wallet")?;
@@ -165,7 +217,177 @@ pub enum SendDestination {
     VtxoPubkey(PublicKey),
     Bolt11(Bolt11Invoice),
     LnAddress(LightningAddress),
-    // Potentially add LNURL string later if direct LNURL payment is supported
+    /// A bech32 `lnurl1...` string pointing at an LNURL-withdraw service
+    LnurlWithdraw(String),
+}
+
+/// Decodes a bech32 `lnurl1...` string into the service URL it encodes.
+pub fn decode_lnurl_url(lnurl: &str) -> anyhow::Result<String> {
+    let decoded = LnUrl::from_str(lnurl.trim())
+        .map_err(|err| anyhow::anyhow!("Failed to decode lnurl '{}': {:?}", lnurl, err))?;
+    Ok(decoded.url)
+}
+
+/// The form an LNURL was given in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LnurlForm {
+    /// A bare bech32 `lnurl1...` string
+    Bech32,
+    /// A `lightning:lnurl1...` URI
+    LightningUri,
+    /// A plain `https://` URL, like a `/.well-known/lnurlp/` endpoint
+    Url,
+}
+
+/// Finds the service URL an LNURL points at, in any of its [`LnurlForm`]s.
+pub fn lnurl_service_url(lnurl: &str) -> anyhow::Result<(LnurlForm, String)> {
+    let lnurl = lnurl.trim();
+    let lower = lnurl.to_ascii_lowercase();
+    if lower.starts_with("lightning:") {
+        let url = decode_lnurl_url(&lnurl["lightning:".len()..])?;
+        Ok((LnurlForm::LightningUri, url))
+    } else if lower.starts_with("lnurl1") {
+        Ok((LnurlForm::Bech32, decode_lnurl_url(lnurl)?))
+    } else if lower.starts_with("https://") {
+        Ok((LnurlForm::Url, lnurl.to_string()))
+    } else {
+        bail!("Not an lnurl: {}", lnurl)
+    }
+}
+
+/// An LNURL together with what its service offers.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct LnurlDecoded {
+    pub url: String,
+    /// `payRequest`, `withdrawRequest`, `channelRequest` or `login`
+    pub kind: String,
+    pub min_amount_msat: Option<u64>,
+    pub max_amount_msat: Option<u64>,
+    /// LNURL-pay metadata, or the default description of a withdrawal
+    pub metadata: String,
+}
+
+impl LnurlDecoded {
+    /// LNURL-auth is signalled in the URL itself and has no service document.
+    pub fn login(url: &str) -> Option<Self> {
+        let query = url.split_once('?')?.1;
+        query
+            .split('&')
+            .any(|p| p == "tag=login")
+            .then(|| LnurlDecoded {
+                url: url.to_string(),
+                kind: "login".to_string(),
+                min_amount_msat: None,
+                max_amount_msat: None,
+                metadata: String::new(),
+            })
+    }
+
+    pub fn from_response(url: String, response: LnUrlResponse) -> Self {
+        match response {
+            LnUrlResponse::LnUrlPayResponse(pay) => LnurlDecoded {
+                url,
+                kind: "payRequest".to_string(),
+                min_amount_msat: Some(pay.min_sendable),
+                max_amount_msat: Some(pay.max_sendable),
+                metadata: pay.metadata,
+            },
+            LnUrlResponse::LnUrlWithdrawResponse(withdraw) => LnurlDecoded {
+                url,
+                kind: "withdrawRequest".to_string(),
+                min_amount_msat: withdraw.min_withdrawable,
+                max_amount_msat: Some(withdraw.max_withdrawable),
+                metadata: withdraw.default_description,
+            },
+            LnUrlResponse::LnUrlChannelResponse(_) => LnurlDecoded {
+                url,
+                kind: "channelRequest".to_string(),
+                min_amount_msat: None,
+                max_amount_msat: None,
+                metadata: String::new(),
+            },
+        }
+    }
+}
+
+/// The fee budget for retrying a payment: `base_budget_sat` raised by
+/// `increase_pct` percent, rounded up.
+pub fn retry_fee_budget(base_budget_sat: u64, increase_pct: u32) -> u64 {
+    (base_budget_sat as u128 * (100 + increase_pct as u128)).div_ceil(100) as u64
+}
+
+/// An invoice fetched from a lightning address, not paid yet.
+#[derive(Debug, Clone)]
+pub struct ResolvedInvoice {
+    pub invoice: Bolt11Invoice,
+    /// The `text/plain` entry of the LNURL metadata the invoice commits to
+    pub description: String,
+    pub amount_sat: u64,
+    /// Unix timestamp after which the invoice can no longer be paid
+    pub expires_at: u64,
+}
+
+/// Checks an LNURL-pay request against the limits of the service before
+/// asking it for an invoice.
+pub fn check_lnurl_pay_request(
+    pay: &PayResponse,
+    amount_msat: u64,
+    comment: Option<&str>,
+) -> anyhow::Result<()> {
+    if amount_msat < pay.min_sendable || amount_msat > pay.max_sendable {
+        bail!(
+            "Amount of {} msat is outside the range accepted by the service ({} - {} msat)",
+            amount_msat,
+            pay.min_sendable,
+            pay.max_sendable
+        );
+    }
+    if let Some(comment) = comment {
+        let allowed = pay.comment_allowed.unwrap_or(0) as usize;
+        if comment.chars().count() > allowed {
+            bail!(
+                "Comment is longer than the {} characters the service accepts",
+                allowed
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Checks that an invoice returned by an LNURL-pay service is the one we
+/// asked for (LUD-06): it must be for exactly the requested amount and its
+/// description hash must commit to the service metadata.
+pub fn validate_lnurl_pay_invoice(
+    invoice: &Bolt11Invoice,
+    amount_msat: u64,
+    metadata: &str,
+) -> anyhow::Result<()> {
+    use bark::ark::bitcoin::hashes::{Hash, sha256};
+    use bark::lightning_invoice::Bolt11InvoiceDescriptionRef;
+
+    if invoice.amount_milli_satoshis() != Some(amount_msat) {
+        bail!(
+            "Invoice amount {:?} msat does not match the requested {} msat",
+            invoice.amount_milli_satoshis(),
+            amount_msat
+        );
+    }
+
+    let metadata_hash = sha256::Hash::hash(metadata.as_bytes());
+    match invoice.description() {
+        Bolt11InvoiceDescriptionRef::Hash(hash) if hash.0 == metadata_hash => Ok(()),
+        _ => bail!("Invoice description hash does not match the lnurl metadata"),
+    }
+}
+
+/// The `text/plain` description in LNURL-pay metadata, empty if there is none.
+pub fn lnurl_metadata_description(metadata: &str) -> String {
+    serde_json::from_str::<Vec<(String, serde_json::Value)>>(metadata)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|(kind, _)| kind == "text/plain")
+        .and_then(|(_, value)| value.as_str().map(String::from))
+        .unwrap_or_default()
 }
 
 /// Parses the destination string into a supported type.
@@ -177,15 +399,1170 @@ pub fn parse_send_destination(destination: &str) -> anyhow::Result<SendDestinati
         Ok(SendDestination::Bolt11(invoice))
     } else if let Ok(lnaddr) = LightningAddress::from_str(destination) {
         Ok(SendDestination::LnAddress(lnaddr))
+    } else if destination.to_lowercase().starts_with("lnurl1") {
+        // Only withdraw requests are supported, which is verified against the
+        // service document when the withdraw is processed.
+        decode_lnurl_url(destination)?;
+        Ok(SendDestination::LnurlWithdraw(destination.to_string()))
     } else {
-        // Could check for raw lnurl string here if needed
         bail!(
-            "Destination is not a valid VTXO pubkey, bolt11 invoice, or lightning address: {}",
+            "Destination is not a valid VTXO pubkey, bolt11 invoice, lightning address or lnurl: {}",
             destination
         )
     }
 }
 
+/// How [`crate::send_payment`] pays a destination.
+#[derive(Debug, Clone)]
+pub enum PaymentRoute {
+    Arkoor(bark::ark::Address, Amount),
+    Bolt11(Bolt11Invoice, Option<Amount>),
+    Offer(bark::ark::lightning::Offer, Option<Amount>),
+    LnAddress(LightningAddress, Amount, Option<String>),
+}
+
+/// Picks how to pay `destination`, checking the amount and comment fit it.
+pub fn payment_route(
+    destination: &str,
+    amount_sat: Option<u64>,
+    comment: Option<String>,
+) -> anyhow::Result<PaymentRoute> {
+    let destination = destination.trim();
+    if amount_sat == Some(0) {
+        bail!("Amount must be positive");
+    }
+    let amount = amount_sat.map(Amount::from_sat);
+    let require_amount =
+        || amount.with_context(|| format!("An amount is required to pay {}", destination));
+
+    let route = if let Ok(address) = bark::ark::Address::from_str(destination) {
+        PaymentRoute::Arkoor(address, require_amount()?)
+    } else if let Ok(offer) = bark::ark::lightning::Offer::from_str(destination) {
+        PaymentRoute::Offer(offer, amount)
+    } else if bark::ark::bitcoin::Address::<NetworkUnchecked>::from_str(destination).is_ok() {
+        bail!("Onchain addresses are not supported, use send_onchain instead")
+    } else {
+        match parse_send_destination(destination)? {
+            SendDestination::Bolt11(invoice) => {
+                match (invoice.amount_milli_satoshis(), amount) {
+                    (None, None) => bail!("An amount is required to pay an invoice without amount"),
+                    (Some(msat), Some(amount)) if msat != amount.to_sat() * 1000 => bail!(
+                        "Amount of {} sat doesn't match the invoice amount of {} msat",
+                        amount.to_sat(),
+                        msat
+                    ),
+                    _ => {}
+                }
+                PaymentRoute::Bolt11(invoice, amount)
+            }
+            SendDestination::LnAddress(address) => {
+                return Ok(PaymentRoute::LnAddress(address, require_amount()?, comment));
+            }
+            SendDestination::VtxoPubkey(_) => {
+                bail!("Sending to a bare VTXO pubkey is not supported, use an ark address")
+            }
+            SendDestination::LnurlWithdraw(_) => {
+                bail!("An lnurl withdraw receives funds, use process_lnurl_withdraw instead")
+            }
+        }
+    };
+    if comment.is_some() {
+        bail!("A comment can only be sent to a lightning address");
+    }
+    Ok(route)
+}
+
+/// Guesses the payment method of a destination string, using the same names
+/// as the movement history (`ark`, `bitcoin`, `invoice`, ...).
+pub fn guess_destination_type(destination: &str) -> &'static str {
+    if bark::ark::Address::from_str(destination).is_ok() {
+        return "ark";
+    }
+
+    match parse_send_destination(destination) {
+        Ok(SendDestination::VtxoPubkey(_)) => "vtxo-pubkey",
+        Ok(SendDestination::Bolt11(_)) => "invoice",
+        Ok(SendDestination::LnAddress(_)) => "lightning-address",
+        Ok(SendDestination::LnurlWithdraw(_)) => "lnurl",
+        Err(_) => {
+            if bark::ark::lightning::Offer::from_str(destination).is_ok() {
+                "offer"
+            } else if bark::ark::bitcoin::Address::<NetworkUnchecked>::from_str(destination).is_ok()
+            {
+                "bitcoin"
+            } else {
+                "unknown"
+            }
+        }
+    }
+}
+
+/// The payment details of a BIP21 `bitcoin:` URI.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Bip21Payment {
+    pub address: String,
+    pub amount_sat: Option<u64>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+    /// A bolt11 invoice to pay instead, as used by unified QR codes
+    pub lightning: Option<String>,
+}
+
+/// Parses a BIP21 payment URI like `bitcoin:ADDRESS?amount=0.001&label=Coffee`.
+pub fn parse_bip21_uri(uri: &str) -> anyhow::Result<Bip21Payment> {
+    let uri = uri.trim();
+    let rest = match uri.split_once(':') {
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("bitcoin") => rest,
+        _ => bail!("Not a bitcoin URI: {}", uri),
+    };
+    let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+    let address = percent_decode(address)?;
+    if address.is_empty() {
+        bail!("BIP21 URI is missing an address");
+    }
+    bark::ark::bitcoin::Address::<NetworkUnchecked>::from_str(&address)
+        .with_context(|| format!("Invalid address in BIP21 URI: {}", address))?;
+
+    let mut payment = Bip21Payment {
+        address,
+        amount_sat: None,
+        label: None,
+        message: None,
+        lightning: None,
+    };
+
+    for param in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        let value = percent_decode(value)?;
+        match key.to_lowercase().as_str() {
+            "amount" => {
+                let amount = Amount::from_str_in(&value, Denomination::Bitcoin)
+                    .with_context(|| format!("Invalid amount in BIP21 URI: {}", value))?;
+                payment.amount_sat = Some(amount.to_sat());
+            }
+            "label" => payment.label = Some(value),
+            "message" => payment.message = Some(value),
+            "lightning" => payment.lightning = Some(value),
+            key if key.starts_with("req-") => {
+                bail!("Unsupported required BIP21 parameter: {}", key)
+            }
+            _ => {}
+        }
+    }
+
+    Ok(payment)
+}
+
+/// Builds a BIP21 payment URI, optionally with a bolt11 invoice as fallback.
+pub fn generate_bip21_uri(
+    address: &str,
+    amount_sat: Option<u64>,
+    label: Option<&str>,
+    lightning_invoice: Option<&str>,
+) -> anyhow::Result<String> {
+    bark::ark::bitcoin::Address::<NetworkUnchecked>::from_str(address)
+        .with_context(|| format!("Invalid bitcoin address: {}", address))?;
+
+    let mut params = Vec::new();
+    if let Some(amount_sat) = amount_sat {
+        let amount = Amount::from_sat(amount_sat).to_string_in(Denomination::Bitcoin);
+        params.push(format!("amount={}", amount));
+    }
+    if let Some(label) = label {
+        params.push(format!("label={}", percent_encode(label)));
+    }
+    if let Some(invoice) = lightning_invoice {
+        Bolt11Invoice::from_str(invoice)
+            .map_err(|err| anyhow::anyhow!("Invalid lightning invoice: {}", err))?;
+        params.push(format!("lightning={}", invoice));
+    }
+
+    if params.is_empty() {
+        Ok(format!("bitcoin:{}", address))
+    } else {
+        Ok(format!("bitcoin:{}?{}", address, params.join("&")))
+    }
+}
+
+/// Everything the receive screen shows, generated in one go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceiveBundle {
+    pub ark_address: String,
+    pub onchain_address: String,
+    /// Only requested when an amount is given
+    pub bolt11: Option<String>,
+    /// BIP21 URI of the onchain address with the invoice as lightning fallback
+    pub unified_uri: String,
+}
+
+impl ReceiveBundle {
+    pub fn new(
+        ark_address: String,
+        onchain_address: String,
+        amount_sat: Option<u64>,
+        bolt11: Option<String>,
+    ) -> anyhow::Result<Self> {
+        let unified_uri =
+            generate_bip21_uri(&onchain_address, amount_sat, None, bolt11.as_deref())?;
+        Ok(ReceiveBundle {
+            ark_address,
+            onchain_address,
+            bolt11,
+            unified_uri,
+        })
+    }
+}
+
+/// How long an invoice must still be valid to be handed out again.
+pub const MIN_REUSED_INVOICE_VALIDITY_SECS: u64 = 10 * 60;
+
+/// Whether a receive invoice handed out before can be shown again for
+/// `amount_sat`: it must be for the same amount, nobody may have started
+/// paying it, and it must stay valid for a while.
+pub fn can_reuse_receive_invoice(
+    invoice: &Bolt11Invoice,
+    amount_sat: u64,
+    payment_started: bool,
+    now: u64,
+) -> bool {
+    let expires_at = invoice.expires_at().map(|at| at.as_secs()).unwrap_or(0);
+    invoice.amount_milli_satoshis() == Some(crate::units::sat_to_msat(amount_sat))
+        && !payment_started
+        && expires_at >= now + MIN_REUSED_INVOICE_VALIDITY_SECS
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(value: &str) -> anyhow::Result<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value
+                .get(i + 1..i + 3)
+                .context("Truncated percent encoding")?;
+            decoded.push(u8::from_str_radix(hex, 16).context("Invalid percent encoding")?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).context("Percent encoded value is not valid utf-8")
+}
+
+/// Parses a VTXO id in its `<txid>:<vout>` string form, as taken by every
+/// bridge function accepting VTXO ids.
+pub fn parse_vtxo_id(vtxo_id: &str) -> anyhow::Result<VtxoId> {
+    VtxoId::from_str(vtxo_id).with_context(|| format!("Invalid vtxo id format: '{}'", vtxo_id))
+}
+
+pub fn parse_vtxo_ids(vtxo_ids: &[String]) -> anyhow::Result<Vec<VtxoId>> {
+    vtxo_ids.iter().map(|id| parse_vtxo_id(id)).collect()
+}
+
+/// What the user should do with a quarantined VTXO: its funds can only be
+/// recovered by exiting unilaterally.
+pub const QUARANTINE_RECOMMENDED_ACTION: &str = "start_exit";
+
+/// Returns the `(vtxo_id, amount_sat)` of the locally spendable VTXOs that
+/// are missing from the server's listing.
+pub fn find_unknown_vtxos(
+    spendable: &[(String, u64)],
+    server_vtxo_ids: &HashSet<String>,
+) -> Vec<(String, u64)> {
+    spendable
+        .iter()
+        .filter(|(id, _)| !server_vtxo_ids.contains(id))
+        .cloned()
+        .collect()
+}
+
+/// Total amount of the spendable VTXOs that are quarantined, which must not
+/// be counted as spendable balance.
+pub fn quarantined_spendable_amount(
+    spendable: &[(String, u64)],
+    quarantined_ids: &HashSet<String>,
+) -> Amount {
+    let sats = spendable
+        .iter()
+        .filter(|(id, _)| quarantined_ids.contains(id))
+        .map(|(_, amount)| amount)
+        .sum();
+    Amount::from_sat(sats)
+}
+
+/// Parses an onchain address, which must be for `network`.
+pub fn parse_onchain_address(
+    destination: &str,
+    network: Network,
+) -> anyhow::Result<bark::ark::bitcoin::Address> {
+    bark::ark::bitcoin::Address::<NetworkUnchecked>::from_str(destination)
+        .with_context(|| format!("Invalid destination address format: '{}'", destination))?
+        .require_network(network)
+        .with_context(|| {
+            format!(
+                "Address '{}' is not valid for configured network {}",
+                destination, network
+            )
+        })
+}
+
+/// The `(vtxo_id, amount_sat)` of all spendable wallet VTXOs
+pub fn spendable_vtxo_ids(vtxos: &[WalletVtxo]) -> Vec<(String, u64)> {
+    vtxos
+        .iter()
+        .filter(|v| matches!(v.state, VtxoState::Spendable))
+        .map(|v| (v.vtxo.id().to_string(), v.vtxo.amount().to_sat()))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VtxoSortKey {
+    Expiry,
+    Amount,
+}
+
+/// Which VTXOs to list and in what order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VtxoQuery {
+    /// All states when empty
+    pub states: Vec<VtxoStateKind>,
+    pub min_amount_sat: Option<u64>,
+    pub max_amount_sat: Option<u64>,
+    /// Only VTXOs expiring at or before this height
+    pub max_expiry_height: Option<u32>,
+    pub sort_key: VtxoSortKey,
+    pub descending: bool,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+/// The properties of a VTXO a [`VtxoQuery`] looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VtxoQueryFields {
+    pub state: VtxoStateKind,
+    pub amount_sat: u64,
+    pub expiry_height: u32,
+}
+
+impl VtxoQuery {
+    pub fn matches(&self, vtxo: &VtxoQueryFields) -> bool {
+        (self.states.is_empty() || self.states.contains(&vtxo.state))
+            && self.min_amount_sat.is_none_or(|min| vtxo.amount_sat >= min)
+            && self.max_amount_sat.is_none_or(|max| vtxo.amount_sat <= max)
+            && self
+                .max_expiry_height
+                .is_none_or(|max| vtxo.expiry_height <= max)
+    }
+}
+
+/// Applies `query` to `items`, returning the requested page and the number
+/// of matching items across all pages.
+pub fn query_vtxos<T>(
+    items: Vec<T>,
+    fields: impl Fn(&T) -> VtxoQueryFields,
+    query: &VtxoQuery,
+) -> (Vec<T>, usize) {
+    let mut matching = items
+        .into_iter()
+        .filter(|item| query.matches(&fields(item)))
+        .collect::<Vec<_>>();
+    let total = matching.len();
+
+    matching.sort_by_key(|item| {
+        let fields = fields(item);
+        match query.sort_key {
+            VtxoSortKey::Expiry => (fields.expiry_height as u64, fields.amount_sat),
+            VtxoSortKey::Amount => (fields.amount_sat, fields.expiry_height as u64),
+        }
+    });
+    if query.descending {
+        matching.reverse();
+    }
+
+    let page = matching
+        .into_iter()
+        .skip(query.offset)
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
+    (page, total)
+}
+
+pub fn wallet_vtxo_query_fields(vtxo: &WalletVtxo) -> VtxoQueryFields {
+    VtxoQueryFields {
+        state: crate::vtxo_transitions::vtxo_state_kind(&vtxo.state),
+        amount_sat: vtxo.vtxo.amount().to_sat(),
+        expiry_height: vtxo.vtxo.expiry_height(),
+    }
+}
+
+/// Splits the outputs of an arkoor payment into `(sent, change)`, change
+/// being the outputs for which `is_ours` holds.
+pub fn split_arkoor_outputs<T>(
+    outputs: Vec<T>,
+    mut is_ours: impl FnMut(&T) -> bool,
+) -> (Vec<T>, Vec<T>) {
+    let (change, sent) = outputs.into_iter().partition(|output| is_ours(output));
+    (sent, change)
+}
+
+/// Why a set of VTXOs can't fund an arkoor payment on its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoinControlError {
+    NoInputs,
+    DuplicateInput(String),
+    UnknownVtxo(String),
+    NotSpendable(String),
+    Shortfall { selected_sat: u64, missing_sat: u64 },
+}
+
+impl fmt::Display for CoinControlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoinControlError::NoInputs => write!(f, "No VTXOs selected"),
+            CoinControlError::DuplicateInput(id) => write!(f, "VTXO {} is selected twice", id),
+            CoinControlError::UnknownVtxo(id) => write!(f, "Unknown vtxo {}", id),
+            CoinControlError::NotSpendable(id) => write!(f, "VTXO {} is not spendable", id),
+            CoinControlError::Shortfall {
+                selected_sat,
+                missing_sat,
+            } => write!(
+                f,
+                "Selected VTXOs hold {} sats, {} sats short of the amount",
+                selected_sat, missing_sat
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CoinControlError {}
+
+/// Checks that `selected` VTXOs can pay `amount_sat` by themselves and
+/// returns the other spendable VTXOs, which must be kept out of the payment.
+///
+/// `known` holds the ids of all wallet VTXOs, `spendable` the ones that may
+/// be spent.
+pub fn check_coin_control(
+    known: &[String],
+    spendable: &[(String, u64)],
+    selected: &[String],
+    amount_sat: u64,
+) -> Result<Vec<String>, CoinControlError> {
+    if selected.is_empty() {
+        return Err(CoinControlError::NoInputs);
+    }
+    let mut selected_sat = 0;
+    for (idx, id) in selected.iter().enumerate() {
+        if selected[..idx].contains(id) {
+            return Err(CoinControlError::DuplicateInput(id.clone()));
+        }
+        if !known.contains(id) {
+            return Err(CoinControlError::UnknownVtxo(id.clone()));
+        }
+        match spendable
+            .iter()
+            .find(|(spendable_id, _)| spendable_id == id)
+        {
+            Some((_, amount)) => selected_sat += amount,
+            None => return Err(CoinControlError::NotSpendable(id.clone())),
+        }
+    }
+    if selected_sat < amount_sat {
+        return Err(CoinControlError::Shortfall {
+            selected_sat,
+            missing_sat: amount_sat - selected_sat,
+        });
+    }
+
+    Ok(spendable
+        .iter()
+        .filter(|(id, _)| !selected.contains(id))
+        .map(|(id, _)| id.clone())
+        .collect())
+}
+
+/// Whether the VTXO was received out of round and not refreshed since, so
+/// it is only as final as the server's cosignature. Refreshing it in a
+/// round resets its arkoor depth.
+pub fn needs_refresh_for_trust(vtxo: &Vtxo) -> bool {
+    vtxo.arkoor_depth() > 0
+}
+
+/// Ids of the spendable VTXOs that [`needs_refresh_for_trust`].
+pub fn untrusted_vtxo_ids(vtxos: &[WalletVtxo]) -> HashSet<String> {
+    vtxos
+        .iter()
+        .filter(|v| matches!(v.state, VtxoState::Spendable) && needs_refresh_for_trust(&v.vtxo))
+        .map(|v| v.vtxo.id().to_string())
+        .collect()
+}
+
+/// Splits the amount of the `spendable` VTXOs into the trusted part and the
+/// part held by `untrusted_ids`.
+pub fn split_trusted_amount(
+    spendable: &[(String, u64)],
+    untrusted_ids: &HashSet<String>,
+) -> (Amount, Amount) {
+    let total: u64 = spendable.iter().map(|(_, amount)| amount).sum();
+    let untrusted: u64 = spendable
+        .iter()
+        .filter(|(id, _)| untrusted_ids.contains(id))
+        .map(|(_, amount)| amount)
+        .sum();
+    (
+        Amount::from_sat(total - untrusted),
+        Amount::from_sat(untrusted),
+    )
+}
+
+/// A payment that only unrefreshed arkoor VTXOs could cover, while spending
+/// them is not allowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InsufficientTrustedFunds {
+    pub amount_sat: u64,
+    pub trusted_sat: u64,
+    pub pending_trustless_sat: u64,
+}
+
+impl fmt::Display for InsufficientTrustedFunds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Insufficient trusted funds: {} sats needed but only {} sats are refreshed, \
+            {} sats received out of round must be refreshed before they can be spent",
+            self.amount_sat, self.trusted_sat, self.pending_trustless_sat
+        )
+    }
+}
+
+impl std::error::Error for InsufficientTrustedFunds {}
+
+/// The unrefreshed arkoor VTXOs to keep out of a payment of `amount_sat`,
+/// failing if only they could cover it.
+pub fn untrusted_vtxos_to_exclude(
+    spendable: &[(String, u64)],
+    untrusted_ids: &HashSet<String>,
+    amount_sat: Option<u64>,
+) -> Result<Vec<String>, InsufficientTrustedFunds> {
+    if let Some(amount_sat) = amount_sat {
+        let (trusted, pending_trustless) = split_trusted_amount(spendable, untrusted_ids);
+        check_trusted_funds(amount_sat, trusted.to_sat(), pending_trustless.to_sat())?;
+    }
+    Ok(spendable
+        .iter()
+        .filter(|(id, _)| untrusted_ids.contains(id))
+        .map(|(id, _)| id.clone())
+        .collect())
+}
+
+/// Fails if the trusted funds don't cover `amount_sat` but the unrefreshed
+/// arkoor funds would make up for it. A payment neither covers is left to
+/// fail on its own.
+pub fn check_trusted_funds(
+    amount_sat: u64,
+    trusted_sat: u64,
+    pending_trustless_sat: u64,
+) -> Result<(), InsufficientTrustedFunds> {
+    if trusted_sat >= amount_sat || trusted_sat + pending_trustless_sat < amount_sat {
+        return Ok(());
+    }
+    Err(InsufficientTrustedFunds {
+        amount_sat,
+        trusted_sat,
+        pending_trustless_sat,
+    })
+}
+
+/// A VTXO of the wallet, as matched against expected payments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceivedVtxo {
+    pub vtxo_id: String,
+    pub user_pubkey: String,
+    pub amount_sat: u64,
+}
+
+pub fn received_vtxos(vtxos: &[WalletVtxo]) -> Vec<ReceivedVtxo> {
+    vtxos
+        .iter()
+        .filter(|v| matches!(v.state, VtxoState::Spendable))
+        .map(|v| ReceivedVtxo {
+            vtxo_id: v.vtxo.id().to_string(),
+            user_pubkey: v.vtxo.user_pubkey().to_string(),
+            amount_sat: v.vtxo.amount().to_sat(),
+        })
+        .collect()
+}
+
+/// Pairs open expected payments with the `received` VTXOs that pay them,
+/// as (expected payment id, vtxo id).
+///
+/// A VTXO pays an expected payment if it is paid to its pubkey and, if the
+/// payment has an amount, is of exactly that amount. Older expected payments
+/// are matched first, each to the first fitting VTXO in `received`. A VTXO
+/// pays at most one expected payment, and none in `claimed`, which holds the
+/// VTXOs that already paid earlier ones.
+pub fn match_expected_payments(
+    open: &[ExpectedPayment],
+    received: &[ReceivedVtxo],
+    claimed: &HashSet<String>,
+) -> Vec<(i64, String)> {
+    let mut expected = open
+        .iter()
+        .filter(|p| p.status == ExpectedPaymentStatus::Open)
+        .collect::<Vec<_>>();
+    expected.sort_by_key(|p| (p.created_at, p.id));
+
+    let mut used = claimed.clone();
+    let mut matches = Vec::new();
+    for payment in expected {
+        let found = received.iter().find(|v| {
+            v.user_pubkey == payment.user_pubkey
+                && payment
+                    .amount_sat
+                    .is_none_or(|amount| amount == v.amount_sat)
+                && !used.contains(&v.vtxo_id)
+        });
+        if let Some(vtxo) = found {
+            used.insert(vtxo.vtxo_id.clone());
+            matches.push((payment.id, vtxo.vtxo_id.clone()));
+        }
+    }
+    matches
+}
+
+/// Virtual size of the child transaction that pays for an exit transaction
+/// by spending its fee anchor together with a wallet input (CPFP).
+pub const EXIT_CPFP_CHILD_VSIZE: u64 = 155;
+
+/// The transactions a unilateral exit of one VTXO puts on chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExitPackage {
+    pub vtxo_id: String,
+    /// Virtual size of each exit transaction, from the root down to the VTXO
+    pub exit_tx_vsizes: Vec<u64>,
+    pub exit_delta: u16,
+}
+
+impl ExitPackage {
+    pub fn from_vtxo(vtxo: &Vtxo) -> Self {
+        ExitPackage {
+            vtxo_id: vtxo.id().to_string(),
+            exit_tx_vsizes: vtxo
+                .transactions()
+                .map(|item| item.tx.vsize() as u64)
+                .collect(),
+            exit_delta: vtxo.exit_delta(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VtxoExitCost {
+    pub vtxo_id: String,
+    pub fee_sat: u64,
+    pub tx_count: u32,
+}
+
+/// What exiting a set of VTXOs will cost, shown before the user commits to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExitCostEstimate {
+    pub per_vtxo: Vec<VtxoExitCost>,
+    pub total_fee_sat: u64,
+    /// Exit transactions plus the CPFP child paying for each of them
+    pub tx_count: u32,
+    /// Worst-case number of blocks to wait after the exit confirms before
+    /// the funds can be claimed
+    pub claimable_after_blocks: u16,
+}
+
+/// Estimates the fees of exiting the given packages at `fee_rate`.
+///
+/// Exit transactions carry no fee themselves, so every one of them is paid
+/// for by a CPFP child of [`EXIT_CPFP_CHILD_VSIZE`].
+pub fn estimate_exit_fees(packages: &[ExitPackage], fee_rate: FeeRate) -> ExitCostEstimate {
+    let per_vtxo = packages
+        .iter()
+        .map(|package| {
+            let tx_count = package.exit_tx_vsizes.len() as u64;
+            let vsize =
+                package.exit_tx_vsizes.iter().sum::<u64>() + tx_count * EXIT_CPFP_CHILD_VSIZE;
+            VtxoExitCost {
+                vtxo_id: package.vtxo_id.clone(),
+                fee_sat: fee_rate.fee_vb(vsize).unwrap_or(Amount::MAX_MONEY).to_sat(),
+                tx_count: (tx_count * 2) as u32,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    ExitCostEstimate {
+        total_fee_sat: per_vtxo.iter().map(|c| c.fee_sat).sum(),
+        tx_count: per_vtxo.iter().map(|c| c.tx_count).sum(),
+        claimable_after_blocks: packages.iter().map(|p| p.exit_delta).max().unwrap_or(0),
+        per_vtxo,
+    }
+}
+
+/// The fee a transaction pays, given the values of the outputs it may
+/// spend. Inputs not among `spendable` are not counted.
+pub fn funding_tx_fee(tx: &Transaction, spendable: &[(OutPoint, Amount)]) -> Amount {
+    let input_value = tx
+        .input
+        .iter()
+        .filter_map(|input| {
+            spendable
+                .iter()
+                .find(|(outpoint, _)| *outpoint == input.previous_output)
+                .map(|(_, value)| *value)
+        })
+        .sum::<Amount>();
+    let output_value = tx.output.iter().map(|output| output.value).sum::<Amount>();
+    input_value
+        .checked_sub(output_value)
+        .unwrap_or(Amount::ZERO)
+}
+
+/// Virtual sizes used to estimate the fee of a plain onchain send from the
+/// wallet's taproot outputs: one recipient and one change output.
+pub const ONCHAIN_TX_OVERHEAD_VSIZE: u64 = 11;
+pub const ONCHAIN_P2TR_INPUT_VSIZE: u64 = 58;
+pub const ONCHAIN_P2TR_OUTPUT_VSIZE: u64 = 43;
+
+/// Whether the outputs worth `utxo_values` can pay `amount`.
+///
+/// With a `fee_rate`, outputs are picked largest first and the fee of
+/// spending them is added to what they need to cover.
+pub fn utxos_cover_amount(
+    utxo_values: &[Amount],
+    amount: Amount,
+    fee_rate: Option<FeeRate>,
+) -> bool {
+    let Some(fee_rate) = fee_rate else {
+        return utxo_values.iter().copied().sum::<Amount>() >= amount;
+    };
+
+    let mut values = utxo_values.to_vec();
+    values.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut selected = Amount::ZERO;
+    for (i, value) in values.into_iter().enumerate() {
+        selected += value;
+        let vsize = ONCHAIN_TX_OVERHEAD_VSIZE
+            + (i as u64 + 1) * ONCHAIN_P2TR_INPUT_VSIZE
+            + 2 * ONCHAIN_P2TR_OUTPUT_VSIZE;
+        let fee = fee_rate.fee_vb(vsize).unwrap_or(Amount::MAX_MONEY);
+        if amount
+            .checked_add(fee)
+            .is_some_and(|needed| selected >= needed)
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/// Dust limit of the taproot output a board creates.
+pub const BOARD_DUST_LIMIT_SAT: u64 = 330;
+
+/// What a board needs to succeed, for validating an amount up front.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardRequirements {
+    pub min_board_sat: u64,
+    /// `None` if the server doesn't limit the VTXO size
+    pub max_board_sat: Option<u64>,
+    /// Fee of a funding transaction spending one wallet output, with change
+    pub estimated_onchain_fee_sat: u64,
+    /// Boards are registered with the server directly instead of in a
+    /// round, so this is zero
+    pub estimated_round_fee_sat: u64,
+}
+
+impl BoardRequirements {
+    /// `server_min_board` is the server's own minimum, if it has one.
+    pub fn new(
+        server_min_board: Option<Amount>,
+        max_vtxo_amount: Option<Amount>,
+        fee_rate: FeeRate,
+    ) -> Self {
+        let vsize =
+            ONCHAIN_TX_OVERHEAD_VSIZE + ONCHAIN_P2TR_INPUT_VSIZE + 2 * ONCHAIN_P2TR_OUTPUT_VSIZE;
+        BoardRequirements {
+            min_board_sat: server_min_board
+                .map_or(0, |a| a.to_sat())
+                .max(BOARD_DUST_LIMIT_SAT),
+            max_board_sat: max_vtxo_amount.map(|a| a.to_sat()),
+            estimated_onchain_fee_sat: fee_rate.fee_vb(vsize).unwrap_or(Amount::MAX_MONEY).to_sat(),
+            estimated_round_fee_sat: 0,
+        }
+    }
+
+    pub fn check(&self, amount: Amount) -> Result<(), BoardAmountError> {
+        let amount_sat = amount.to_sat();
+        if amount_sat < self.min_board_sat {
+            return Err(BoardAmountError::BelowMinimumBoard {
+                amount_sat,
+                min_board_sat: self.min_board_sat,
+            });
+        }
+        if let Some(max_board_sat) = self.max_board_sat.filter(|max| amount_sat > *max) {
+            return Err(BoardAmountError::AboveMaximumBoard {
+                amount_sat,
+                max_board_sat,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Why an amount can't be boarded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardAmountError {
+    BelowMinimumBoard { amount_sat: u64, min_board_sat: u64 },
+    AboveMaximumBoard { amount_sat: u64, max_board_sat: u64 },
+}
+
+impl fmt::Display for BoardAmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardAmountError::BelowMinimumBoard {
+                amount_sat,
+                min_board_sat,
+            } => write!(
+                f,
+                "Board amount of {} sats is below the minimum of {} sats",
+                amount_sat, min_board_sat
+            ),
+            BoardAmountError::AboveMaximumBoard {
+                amount_sat,
+                max_board_sat,
+            } => write!(
+                f,
+                "Board amount of {} sats is above the maximum of {} sats",
+                amount_sat, max_board_sat
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BoardAmountError {}
+
+/// Outcome of one output of a batched onchain send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnchainOutputResult {
+    pub destination: String,
+    pub amount_sat: u64,
+    /// Txid of the round paying this output, if it was sent
+    pub txid: Option<String>,
+    /// Why the output was not sent. Outputs after the first failure are not
+    /// attempted.
+    pub error: Option<String>,
+}
+
+/// Checks a batch of onchain outputs against the server's per-output limit
+/// and the spendable balance before any of them is sent.
+pub fn validate_onchain_outputs(
+    outputs: &[(bark::ark::bitcoin::Address, Amount)],
+    max_output_amount: Option<Amount>,
+    spendable: Amount,
+) -> anyhow::Result<()> {
+    if outputs.is_empty() {
+        bail!("At least one output must be provided");
+    }
+
+    let mut total = Amount::ZERO;
+    for (address, amount) in outputs {
+        let dust = address.script_pubkey().minimal_non_dust();
+        if *amount < dust {
+            bail!(
+                "Amount {} to {} is below the dust limit of {}",
+                amount,
+                address,
+                dust
+            );
+        }
+        if let Some(max) = max_output_amount {
+            if *amount > max {
+                bail!(
+                    "Amount {} to {} exceeds the server maximum of {}",
+                    amount,
+                    address,
+                    max
+                );
+            }
+        }
+        total = total
+            .checked_add(*amount)
+            .context("Total amount overflows")?;
+    }
+
+    if total > spendable {
+        bail!(
+            "Total amount {} exceeds the spendable balance of {}",
+            total,
+            spendable
+        );
+    }
+    Ok(())
+}
+
+/// Where a VTXO's unilateral exit stands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExitStatusEntry {
+    pub vtxo_id: String,
+    pub amount_sat: u64,
+    /// bark's exit state, e.g. `start`, `processing`, `awaiting-delta`,
+    /// `claimable` or `claimed`
+    pub state: String,
+    pub claimable: bool,
+}
+
+/// Name of an exit state, taken from its serialized `type` tag
+pub fn exit_state_name<S: serde::Serialize>(state: &S) -> String {
+    serde_json::to_value(state)
+        .ok()
+        .and_then(|value| value.get("type")?.as_str().map(String::from))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// A pending lightning send after asking bark to check on it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckedHtlcSend {
+    pub payment_hash: String,
+    pub htlc_vtxo_count: usize,
+    pub paid: bool,
+}
+
+/// Number of HTLC VTXOs released by sends that were revoked by the check,
+/// i.e. that are no longer pending and weren't paid.
+pub fn count_unlocked_htlc_vtxos(
+    checked: &[CheckedHtlcSend],
+    still_pending: &HashSet<String>,
+) -> u32 {
+    checked
+        .iter()
+        .filter(|send| !send.paid && !still_pending.contains(&send.payment_hash))
+        .map(|send| send.htlc_vtxo_count as u32)
+        .sum()
+}
+
+/// The fee a lightning send actually paid: what was locked in its HTLCs
+/// beyond the amount paid.
+pub fn lightning_send_fee(htlc_amount: Amount, amount_paid: Amount) -> Amount {
+    htlc_amount.checked_sub(amount_paid).unwrap_or(Amount::ZERO)
+}
+
+/// `fee` as parts per million of `amount`, rounded up.
+pub fn lightning_fee_ppm(fee: Amount, amount: Amount) -> Option<u64> {
+    if amount == Amount::ZERO {
+        return None;
+    }
+    let ppm = (fee.to_sat() as u128 * 1_000_000).div_ceil(amount.to_sat() as u128);
+    Some(ppm.min(u64::MAX as u128) as u64)
+}
+
+/// The fee expected for paying `amount` at `fee_ppm`, rounded up.
+pub fn expected_lightning_fee(amount: Amount, fee_ppm: u64) -> Amount {
+    let fee = (amount.to_sat() as u128 * fee_ppm as u128).div_ceil(1_000_000);
+    Amount::from_sat(fee.min(u64::MAX as u128) as u64)
+}
+
+/// A lightning payment refused before paying because the fee it is
+/// expected to cost is above its budget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeeBudgetExceeded {
+    pub expected_fee_sat: u64,
+    pub budget_sat: u64,
+}
+
+impl fmt::Display for FeeBudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Fee budget exceeded: the payment is expected to cost {} sats in fees \
+            but its budget is {} sats",
+            self.expected_fee_sat, self.budget_sat
+        )
+    }
+}
+
+impl std::error::Error for FeeBudgetExceeded {}
+
+/// Shortens anything that looks like an invoice, offer, LNURL or a 32-byte
+/// hex value (a preimage or key), so the text can be shared for support.
+pub fn redact_sensitive(text: &str) -> String {
+    const LN_PREFIXES: &[&str] = &["lnbc", "lntb", "lnbcrt", "lntbs", "lno", "lni", "lnurl"];
+
+    text.split(' ')
+        .map(|word| {
+            let token = word.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+            let lower = token.to_ascii_lowercase();
+            let is_ln = token.len() > 20 && LN_PREFIXES.iter().any(|p| lower.starts_with(p));
+            let is_secret_hex = token.len() == 64 && token.chars().all(|c| c.is_ascii_hexdigit());
+            match is_ln || is_secret_hex {
+                true => {
+                    let prefix = token.chars().take(8).collect::<String>();
+                    word.replace(token, &format!("{}…[redacted]", prefix))
+                }
+                false => word.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Short hash identifying an operation's parameters without revealing them
+pub fn params_hash(params: &str) -> String {
+    use bark::ark::bitcoin::hashes::{Hash, sha256};
+
+    sha256::Hash::hash(params.as_bytes()).to_string()[..16].to_string()
+}
+
+/// A destination we sent to before, optionally named in the address book.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recipient {
+    pub name: Option<String>,
+    pub destination: String,
+    pub destination_type: String,
+    pub last_used_at: Option<i64>,
+    pub times_used: u32,
+}
+
+/// Onchain status of a board funding transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FundingTxStatus {
+    Confirmed,
+    Unconfirmed,
+    NotFound,
+}
+
+/// What to do with an open board intent when resuming interrupted boards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardResumeAction {
+    /// The funding tx confirmed, have bark register the board
+    Register,
+    /// The funding tx is still unconfirmed, or may be, try again later
+    Wait,
+    /// There is nothing left to follow up on
+    Abandon,
+}
+
+/// Decides how to resume a board intent given the status of its funding
+/// transaction, `None` when no funding txid was ever recorded.
+///
+/// Without a funding txid the board may still have been broadcast, so it is
+/// only abandoned once the onchain wallet has no unconfirmed spends left
+/// that could be its funding transaction.
+///
+/// Returns `None` for intents that already reached a final state.
+pub fn board_resume_action(
+    state: BoardIntentState,
+    funding: Option<FundingTxStatus>,
+    unconfirmed_spends: bool,
+) -> Option<BoardResumeAction> {
+    match (state, funding) {
+        (BoardIntentState::Started, _) | (BoardIntentState::Funded, None) => {
+            match unconfirmed_spends {
+                true => Some(BoardResumeAction::Wait),
+                false => Some(BoardResumeAction::Abandon),
+            }
+        }
+        (BoardIntentState::Funded, Some(FundingTxStatus::Confirmed)) => {
+            Some(BoardResumeAction::Register)
+        }
+        (BoardIntentState::Funded, Some(FundingTxStatus::Unconfirmed)) => {
+            Some(BoardResumeAction::Wait)
+        }
+        (BoardIntentState::Funded, Some(FundingTxStatus::NotFound)) => {
+            Some(BoardResumeAction::Abandon)
+        }
+        (
+            BoardIntentState::Registered | BoardIntentState::Failed | BoardIntentState::Abandoned,
+            _,
+        ) => None,
+    }
+}
+
+/// Outcome of resuming interrupted boards.
+#[derive(Debug, Default)]
+pub struct BoardResumeReport {
+    pub resumed: Vec<BoardIntent>,
+    pub abandoned: Vec<BoardIntent>,
+    pub pending: Vec<BoardIntent>,
+}
+
+/// Where a board stands on its way to spendable VTXOs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PendingBoardState {
+    /// The funding tx needs more confirmations
+    AwaitingConfirmation,
+    /// The funding tx is deep enough, the server has yet to register it
+    AwaitingRegistration,
+    Credited,
+}
+
+impl PendingBoardState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PendingBoardState::AwaitingConfirmation => "AwaitingConfirmation",
+            PendingBoardState::AwaitingRegistration => "AwaitingRegistration",
+            PendingBoardState::Credited => "Credited",
+        }
+    }
+}
+
+/// A board the server hasn't credited yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingBoardInfo {
+    pub funding_txid: String,
+    pub vout: u32,
+    pub amount_sat: u64,
+    pub vtxo_ids: Vec<String>,
+    pub confirmations: u32,
+    pub required_confirmations: u32,
+    pub state: PendingBoardState,
+}
+
+impl PendingBoardInfo {
+    /// `confirmed_at` is the height the funding tx confirmed at, if it did.
+    pub fn new(
+        funding_anchor: OutPoint,
+        amount: Amount,
+        vtxo_ids: &[VtxoId],
+        confirmed_at: Option<u32>,
+        tip: u32,
+        required_confirmations: u32,
+    ) -> Self {
+        let confirmations = confirmed_at.map_or(0, |height| tip.saturating_sub(height) + 1);
+        let state = if confirmations < required_confirmations {
+            PendingBoardState::AwaitingConfirmation
+        } else {
+            PendingBoardState::AwaitingRegistration
+        };
+        PendingBoardInfo {
+            funding_txid: funding_anchor.txid.to_string(),
+            vout: funding_anchor.vout,
+            amount_sat: amount.to_sat(),
+            vtxo_ids: vtxo_ids.iter().map(|id| id.to_string()).collect(),
+            confirmations,
+            required_confirmations,
+            state,
+        }
+    }
+}
+
 /// Configuration of the Bark wallet.
 /// Merge CreateOpts into ConfigOpts
 pub fn merge_config_opts(opts: CreateOpts) -> anyhow::Result<(Config, Network)> {
@@ -224,7 +1601,11 @@ pub fn merge_config_opts(opts: CreateOpts) -> anyhow::Result<(Config, Network)>
             _ => None,
         },
         bitcoind_pass: match net {
-            Network::Regtest => opts.config.bitcoind_pass.clone(),
+            Network::Regtest => opts
+                .config
+                .bitcoind_pass
+                .as_ref()
+                .map(|pass| pass.expose_secret().clone()),
             _ => None,
         },
         vtxo_refresh_expiry_threshold: opts.config.vtxo_refresh_expiry_threshold,
@@ -241,33 +1622,203 @@ pub fn merge_config_opts(opts: CreateOpts) -> anyhow::Result<(Config, Network)>
     Ok((config, net))
 }
 
-pub fn ffi_config_to_config(opts: ffi::CreateOpts) -> anyhow::Result<CreateOpts> {
-    let config_opts = ConfigOpts {
-        ark: Some(opts.config.ark),
-        esplora: Some(opts.config.esplora),
-        bitcoind: Some(opts.config.bitcoind),
-        bitcoind_cookie: Some(opts.config.bitcoind_cookie),
-        bitcoind_user: Some(opts.config.bitcoind_user),
-        bitcoind_pass: Some(opts.config.bitcoind_pass),
-        vtxo_refresh_expiry_threshold: opts.config.vtxo_refresh_expiry_threshold,
-        fallback_fee_rate: Some(opts.config.fallback_fee_rate),
-        htlc_recv_claim_delta: opts.config.htlc_recv_claim_delta,
-        vtxo_exit_margin: opts.config.vtxo_exit_margin,
-        round_tx_required_confirmations: opts.config.round_tx_required_confirmations,
+/// Our recommended settings for a new wallet on the given network.
+///
+/// There is no public ark server on mainnet yet, so the server address is
+/// left empty there and must be provided by the user.
+pub fn default_config(network: Network) -> Config {
+    let (server_address, esplora_address, fallback_fee_rate_kvb, required_confirmations) =
+        match network {
+            Network::Bitcoin => ("", Some("https://mempool.space/api"), 10_000, 6),
+            Network::Signet => (
+                "https://ark.signet.2nd.dev",
+                Some("https://esplora.signet.2nd.dev"),
+                100_000,
+                1,
+            ),
+            _ => ("http://127.0.0.1:3535", None, 10_000, 1),
+        };
+
+    Config {
+        server_address: server_address.to_string(),
+        esplora_address: esplora_address.map(String::from),
+        bitcoind_address: match network {
+            Network::Regtest => Some("http://127.0.0.1:18443".to_string()),
+            _ => None,
+        },
+        bitcoind_cookiefile: None,
+        bitcoind_user: None,
+        bitcoind_pass: None,
+        vtxo_refresh_expiry_threshold: 288,
+        fallback_fee_rate: Some(FeeRate::from_sat_per_kvb_ceil(fallback_fee_rate_kvb)),
+        htlc_recv_claim_delta: 18,
+        vtxo_exit_margin: 12,
+        round_tx_required_confirmations: required_confirmations,
+    }
+}
+
+/// Config fields the library reads on every use rather than bark keeping
+/// its own copy, so changing them takes effect without reloading the wallet.
+pub const LIVE_CONFIG_FIELDS: &[&str] = &["vtxo_refresh_expiry_threshold", "fallback_fee_rate"];
+
+/// Which changed config fields took effect right away and which only take
+/// effect once the wallet is reloaded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigApplyResult {
+    pub applied: Vec<String>,
+    pub requires_reload: Vec<String>,
+}
+
+/// Names of the fields that differ between the two configs
+pub fn changed_config_fields(old: &Config, new: &Config) -> Vec<&'static str> {
+    let fields = [
+        ("server_address", old.server_address != new.server_address),
+        (
+            "esplora_address",
+            old.esplora_address != new.esplora_address,
+        ),
+        (
+            "bitcoind_address",
+            old.bitcoind_address != new.bitcoind_address,
+        ),
+        (
+            "bitcoind_cookiefile",
+            old.bitcoind_cookiefile != new.bitcoind_cookiefile,
+        ),
+        ("bitcoind_user", old.bitcoind_user != new.bitcoind_user),
+        ("bitcoind_pass", old.bitcoind_pass != new.bitcoind_pass),
+        (
+            "vtxo_refresh_expiry_threshold",
+            old.vtxo_refresh_expiry_threshold != new.vtxo_refresh_expiry_threshold,
+        ),
+        (
+            "fallback_fee_rate",
+            old.fallback_fee_rate != new.fallback_fee_rate,
+        ),
+        (
+            "htlc_recv_claim_delta",
+            old.htlc_recv_claim_delta != new.htlc_recv_claim_delta,
+        ),
+        (
+            "vtxo_exit_margin",
+            old.vtxo_exit_margin != new.vtxo_exit_margin,
+        ),
+        (
+            "round_tx_required_confirmations",
+            old.round_tx_required_confirmations != new.round_tx_required_confirmations,
+        ),
+    ];
+    fields
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// Copies the live fields of `new` into `current` and reports what changed.
+pub fn apply_live_config(current: &mut Config, new: &Config) -> ConfigApplyResult {
+    let mut result = ConfigApplyResult::default();
+    for field in changed_config_fields(current, new) {
+        match LIVE_CONFIG_FIELDS.contains(&field) {
+            true => result.applied.push(field.to_string()),
+            false => result.requires_reload.push(field.to_string()),
+        }
+    }
+
+    current.vtxo_refresh_expiry_threshold = new.vtxo_refresh_expiry_threshold;
+    current.fallback_fee_rate = new.fallback_fee_rate;
+    result
+}
+
+/// Parses a mnemonic in any BIP39 language, detected from its words.
+///
+/// The phrase is NFKD normalized first as BIP39 requires, so composed and
+/// decomposed accents or ideographic spaces parse to the same mnemonic.
+pub fn parse_mnemonic(phrase: &str) -> anyhow::Result<bip39::Mnemonic> {
+    bip39::Mnemonic::parse(phrase).context("Invalid mnemonic format")
+}
+
+/// The message a Schnorr signature over hex encoded `data_hex` commits to:
+/// the SHA256 of the decoded bytes.
+pub fn schnorr_data_message(data_hex: &str) -> anyhow::Result<secp256k1::Message> {
+    use bark::ark::bitcoin::hashes::{Hash, sha256};
+
+    let data =
+        hex::decode(data_hex).with_context(|| format!("Invalid data hex: '{}'", data_hex))?;
+    Ok(secp256k1::Message::from_digest(
+        sha256::Hash::hash(&data).to_byte_array(),
+    ))
+}
+
+pub fn sign_data_schnorr_with_keypair(
+    data_hex: &str,
+    keypair: &secp256k1::Keypair,
+) -> anyhow::Result<secp256k1::schnorr::Signature> {
+    let msg = schnorr_data_message(data_hex)?;
+    let secp = secp256k1::Secp256k1::new();
+    Ok(secp.sign_schnorr_no_aux_rand(&msg, keypair))
+}
+
+/// Checks a BIP340 signature over `data_hex`. `pubkey_hex` may be x-only or
+/// compressed, so keys shown by [`crate::peak_keypair`] work as they are.
+pub fn verify_data_schnorr(
+    data_hex: &str,
+    signature_hex: &str,
+    pubkey_hex: &str,
+) -> anyhow::Result<bool> {
+    let msg = schnorr_data_message(data_hex)?;
+    let signature = secp256k1::schnorr::Signature::from_str(signature_hex)
+        .with_context(|| format!("Invalid signature format: '{}'", signature_hex))?;
+    let pubkey = match secp256k1::XOnlyPublicKey::from_str(pubkey_hex) {
+        Ok(pubkey) => pubkey,
+        Err(_) => {
+            PublicKey::from_str(pubkey_hex)
+                .with_context(|| format!("Invalid public key format: '{}'", pubkey_hex))?
+                .x_only_public_key()
+                .0
+        }
     };
+    let secp = secp256k1::Secp256k1::verification_only();
+    Ok(secp.verify_schnorr(&signature, &msg, &pubkey).is_ok())
+}
+
+pub fn ffi_config_to_config(mut opts: ffi::CreateOpts) -> anyhow::Result<CreateOpts> {
+    let mnemonic = parse_mnemonic(&opts.mnemonic);
+    opts.mnemonic.zeroize();
+    let mnemonic = Secret::new(mnemonic?);
 
     let create_opts = CreateOpts {
         regtest: opts.regtest,
         signet: opts.signet,
         bitcoin: opts.bitcoin,
-        mnemonic: bip39::Mnemonic::from_str(&opts.mnemonic)?,
+        mnemonic,
         birthday_height: unsafe { opts.birthday_height.as_ref().map(|r| *r) },
-        config: config_opts,
+        config: ffi_config_opts_to_config_opts(opts.config)?,
     };
 
     Ok(create_opts)
 }
 
+pub fn ffi_config_opts_to_config_opts(config: ffi::ConfigOpts) -> anyhow::Result<ConfigOpts> {
+    Ok(ConfigOpts {
+        ark: Some(config.ark),
+        esplora: Some(config.esplora),
+        bitcoind: Some(config.bitcoind),
+        bitcoind_cookie: Some(config.bitcoind_cookie),
+        bitcoind_user: Some(config.bitcoind_user),
+        bitcoind_pass: Some(Secret::new(config.bitcoind_pass)),
+        vtxo_refresh_expiry_threshold: config.vtxo_refresh_expiry_threshold,
+        fallback_fee_rate: Some(config.fallback_fee_rate),
+        htlc_recv_claim_delta: config.htlc_recv_claim_delta,
+        vtxo_exit_margin: config.vtxo_exit_margin,
+        round_tx_required_confirmations: config.round_tx_required_confirmations,
+        ark_purpose_index: match config.ark_purpose_index {
+            0 => None,
+            index => Some(index),
+        },
+    })
+}
+
 pub fn wallet_vtxo_to_bark_vtxo(wallet_vtxo: WalletVtxo) -> crate::cxx::ffi::BarkVtxo {
     let state_name = match &wallet_vtxo.state {
         VtxoState::Spendable => "Spendable",
@@ -292,6 +1843,7 @@ pub fn wallet_vtxo_to_bark_vtxo(wallet_vtxo: WalletVtxo) -> crate::cxx::ffi::Bar
             wallet_vtxo.vtxo.point().vout
         ),
         state: state_name,
+        needs_refresh_for_trust: needs_refresh_for_trust(&wallet_vtxo.vtxo),
     }
 }
 
@@ -304,6 +1856,7 @@ pub fn vtxo_to_bark_vtxo(vtxo: &Vtxo) -> crate::cxx::ffi::BarkVtxo {
         anchor_point: format!("{}:{}", vtxo.chain_anchor().txid, vtxo.chain_anchor().vout),
         point: format!("{}:{}", vtxo.point().txid, vtxo.point().vout),
         state: "unknown".to_string(),
+        needs_refresh_for_trust: needs_refresh_for_trust(vtxo),
     }
 }
 
@@ -325,6 +1878,238 @@ fn payment_method_to_ffi(pm: &PaymentMethod) -> (String, String) {
     }
 }
 
+/// What a movement did to the wallet, derived from the bark subsystem that
+/// recorded it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementKind {
+    Board,
+    Send,
+    Receive,
+    Offboard,
+    Exit,
+    Refresh,
+    Unknown,
+}
+
+impl MovementKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MovementKind::Board => "board",
+            MovementKind::Send => "send",
+            MovementKind::Receive => "receive",
+            MovementKind::Offboard => "offboard",
+            MovementKind::Exit => "exit",
+            MovementKind::Refresh => "refresh",
+            MovementKind::Unknown => "unknown",
+        }
+    }
+
+    pub fn from_subsystem(name: &str, kind: &str) -> MovementKind {
+        let name = name.to_ascii_lowercase();
+        let kind = kind.to_ascii_lowercase();
+        let is = |word: &str| kind.contains(word) || name.contains(word);
+
+        // Check offboard before board, and receive before send, as e.g.
+        // "offboard" contains "board".
+        if is("offboard") {
+            MovementKind::Offboard
+        } else if is("board") {
+            MovementKind::Board
+        } else if is("exit") {
+            MovementKind::Exit
+        } else if is("refresh") {
+            MovementKind::Refresh
+        } else if is("receive") {
+            MovementKind::Receive
+        } else if is("send") || is("pay") {
+            MovementKind::Send
+        } else {
+            MovementKind::Unknown
+        }
+    }
+}
+
+impl From<MovementKind> for crate::cxx::ffi::CxxMovementKind {
+    fn from(kind: MovementKind) -> Self {
+        match kind {
+            MovementKind::Board => crate::cxx::ffi::CxxMovementKind::Board,
+            MovementKind::Send => crate::cxx::ffi::CxxMovementKind::Send,
+            MovementKind::Receive => crate::cxx::ffi::CxxMovementKind::Receive,
+            MovementKind::Offboard => crate::cxx::ffi::CxxMovementKind::Offboard,
+            MovementKind::Exit => crate::cxx::ffi::CxxMovementKind::Exit,
+            MovementKind::Refresh => crate::cxx::ffi::CxxMovementKind::Refresh,
+            MovementKind::Unknown => crate::cxx::ffi::CxxMovementKind::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MovementKindStats {
+    pub kind: MovementKind,
+    pub count: u64,
+    pub received_sat: u64,
+    pub sent_sat: u64,
+}
+
+/// Aggregates over every movement since the wallet was created.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WalletStats {
+    pub movement_count: u64,
+    pub total_received_sat: u64,
+    pub total_sent_sat: u64,
+    /// Only kinds that occurred, in order of first occurrence
+    pub per_kind: Vec<MovementKindStats>,
+    /// Creation time of the earliest movement, if any
+    pub first_movement_at: Option<i64>,
+}
+
+/// Computes the lifetime stats from the movement totals of each subsystem,
+/// ordered by their first movement.
+pub fn lifetime_stats(totals: &[SubsystemTotals]) -> WalletStats {
+    let mut stats = WalletStats::default();
+    for subsystem in totals {
+        let kind =
+            MovementKind::from_subsystem(&subsystem.subsystem_name, &subsystem.subsystem_kind);
+
+        stats.movement_count += subsystem.count;
+        stats.total_received_sat += subsystem.received_sat;
+        stats.total_sent_sat += subsystem.sent_sat;
+        stats.first_movement_at = Some(match stats.first_movement_at {
+            Some(first) => first.min(subsystem.first_created_at),
+            None => subsystem.first_created_at,
+        });
+
+        match stats.per_kind.iter_mut().find(|k| k.kind == kind) {
+            Some(kind_stats) => {
+                kind_stats.count += subsystem.count;
+                kind_stats.received_sat += subsystem.received_sat;
+                kind_stats.sent_sat += subsystem.sent_sat;
+            }
+            None => stats.per_kind.push(MovementKindStats {
+                kind,
+                count: subsystem.count,
+                received_sat: subsystem.received_sat,
+                sent_sat: subsystem.sent_sat,
+            }),
+        }
+    }
+    stats
+}
+
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// The wallet balance at the end of a UTC day.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct BalanceSnapshot {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub offchain_sat: u64,
+    pub onchain_sat: u64,
+}
+
+/// A change of the offchain or onchain balance at unix time `at`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BalanceChange {
+    pub at: i64,
+    pub offchain_sat: i64,
+    pub onchain_sat: i64,
+}
+
+/// Replays `changes` into the balance at the end of each of the last `days`
+/// days up to and including the day of `now`, oldest first.
+pub fn daily_balances(changes: &[BalanceChange], days: u32, now: i64) -> Vec<BalanceSnapshot> {
+    let today = now.div_euclid(SECS_PER_DAY);
+    (0..days as i64)
+        .rev()
+        .map(|days_ago| {
+            let day = today - days_ago;
+            let (offchain, onchain) = changes
+                .iter()
+                .filter(|c| c.at.div_euclid(SECS_PER_DAY) <= day)
+                .fold((0i64, 0i64), |(off, on), c| {
+                    (off + c.offchain_sat, on + c.onchain_sat)
+                });
+            BalanceSnapshot {
+                date: format_utc_date(day),
+                offchain_sat: offchain.max(0) as u64,
+                onchain_sat: onchain.max(0) as u64,
+            }
+        })
+        .collect()
+}
+
+/// Formats days since the unix epoch as a `YYYY-MM-DD` date.
+fn format_utc_date(days_since_epoch: i64) -> String {
+    // Civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days_since_epoch + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Whether a lightning receive gave up: its invoice expired at `now` without
+/// a payment arriving.
+pub fn lightning_receive_expired(
+    invoice: &Bolt11Invoice,
+    preimage_revealed: bool,
+    finished: bool,
+    now: u64,
+) -> bool {
+    !preimage_revealed && !finished && invoice.would_expire(std::time::Duration::from_secs(now))
+}
+
+/// Formats unix seconds as an RFC 3339 UTC timestamp.
+pub fn format_utc_timestamp(secs: i64) -> String {
+    let secs_of_day = secs.rem_euclid(86_400);
+    format!(
+        "{}T{:02}:{:02}:{:02}+00:00",
+        format_utc_date(secs.div_euclid(86_400)),
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60
+    )
+}
+
+/// Where a lightning receive is at, as shown to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightningReceiveState {
+    /// Waiting for the payer
+    Pending,
+    /// The payment arrived and the preimage was revealed, the claim is not
+    /// finished yet
+    Received,
+    Claimed,
+    /// The invoice expired or the receive was given up without a payment
+    Expired,
+}
+
+impl LightningReceiveState {
+    pub fn new(preimage_revealed: bool, finished: bool, invoice_expired: bool) -> Self {
+        match (preimage_revealed, finished) {
+            (true, true) => LightningReceiveState::Claimed,
+            (true, false) => LightningReceiveState::Received,
+            (false, true) => LightningReceiveState::Expired,
+            (false, false) if invoice_expired => LightningReceiveState::Expired,
+            (false, false) => LightningReceiveState::Pending,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LightningReceiveState::Pending => "Pending",
+            LightningReceiveState::Received => "Received",
+            LightningReceiveState::Claimed => "Claimed",
+            LightningReceiveState::Expired => "Expired",
+        }
+    }
+}
+
 pub fn movement_to_bark_movement(
     movement: &Movement,
 ) -> anyhow::Result<crate::cxx::ffi::BarkMovement> {
@@ -379,6 +2164,8 @@ pub fn movement_to_bark_movement(
     Ok(crate::cxx::ffi::BarkMovement {
         id: movement.id.0,
         status: movement.status.as_str().to_string(),
+        kind: MovementKind::from_subsystem(&movement.subsystem.name, &movement.subsystem.kind)
+            .into(),
         subsystem_name: movement.subsystem.name.clone(),
         subsystem_kind: movement.subsystem.kind.clone(),
         metadata_json,
@@ -396,6 +2183,225 @@ pub fn movement_to_bark_movement(
     })
 }
 
+/// Position in the movement history, which is paged newest first by
+/// creation time and then id. Handed to apps as an opaque string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MovementCursor {
+    pub created_at: i64,
+    pub id: u32,
+}
+
+impl MovementCursor {
+    pub fn of(movement: &Movement) -> Self {
+        MovementCursor {
+            created_at: movement.time.created_at.timestamp(),
+            id: movement.id.0,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        format!("{}.{}", self.created_at, self.id)
+    }
+
+    pub fn decode(cursor: &str) -> anyhow::Result<Self> {
+        let (created_at, id) = cursor
+            .split_once('.')
+            .with_context(|| format!("Invalid movement cursor '{}'", cursor))?;
+        Ok(MovementCursor {
+            created_at: created_at
+                .parse()
+                .with_context(|| format!("Invalid movement cursor '{}'", cursor))?,
+            id: id
+                .parse()
+                .with_context(|| format!("Invalid movement cursor '{}'", cursor))?,
+        })
+    }
+}
+
+/// The first `limit` items that come after `after` in the history, newest
+/// first, or from the start without a cursor. Also returns the cursor for
+/// the next page, `None` once the history is exhausted.
+///
+/// Unlike an offset, the cursor stays put when new movements are added
+/// between two page fetches, so no item is shown twice or skipped.
+pub fn movements_page<T>(
+    mut items: Vec<(MovementCursor, T)>,
+    after: Option<MovementCursor>,
+    limit: usize,
+) -> (Vec<T>, Option<MovementCursor>) {
+    items.retain(|(cursor, _)| after.is_none_or(|after| *cursor < after));
+    items.sort_by(|(a, _), (b, _)| b.cmp(a));
+    let next = match items.len() > limit {
+        true => limit.checked_sub(1).map(|last| items[last].0),
+        false => None,
+    };
+    items.truncate(limit);
+    (items.into_iter().map(|(_, item)| item).collect(), next)
+}
+
+/// A movement as it appears in the accounting export.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountingRow {
+    pub movement_id: u32,
+    pub created_at: i64,
+    pub kind: MovementKind,
+    pub status: String,
+    /// Change of the offchain balance, fees included
+    pub effective_balance_sat: i64,
+    pub fee_sat: u64,
+    /// Destinations paid to, or received on for incoming movements
+    pub counterparty: String,
+}
+
+impl AccountingRow {
+    pub fn from_movement(movement: &Movement) -> Self {
+        let destinations = if movement.sent_to.is_empty() {
+            &movement.received_on
+        } else {
+            &movement.sent_to
+        };
+        AccountingRow {
+            movement_id: movement.id.0,
+            created_at: movement.time.created_at.timestamp(),
+            kind: MovementKind::from_subsystem(&movement.subsystem.name, &movement.subsystem.kind),
+            status: movement.status.as_str().to_string(),
+            effective_balance_sat: movement.effective_balance.to_sat(),
+            fee_sat: movement.offchain_fee.to_sat(),
+            counterparty: destinations
+                .iter()
+                .map(|dest| payment_method_to_ffi(&dest.destination).1)
+                .collect::<Vec<_>>()
+                .join("; "),
+        }
+    }
+
+    /// Amount received and amount sent, the latter without the fee.
+    pub fn amounts_in_out(&self) -> (u64, u64) {
+        match self.effective_balance_sat {
+            gained if gained >= 0 => (gained as u64, 0),
+            lost => (0, lost.unsigned_abs().saturating_sub(self.fee_sat)),
+        }
+    }
+}
+
+/// Columns of the accounting export. `fiat_value` is always empty, there is
+/// no exchange rate module to fill it from.
+pub const ACCOUNTING_CSV_HEADER: &str = "date,movement_id,kind,status,amount_in_sat,amount_out_sat,fee_sat,counterparty,balance_sat,fiat_value";
+
+/// Quotes a CSV field as RFC 4180 requires when it contains a separator,
+/// quote or line break.
+pub fn csv_field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+/// Writes accounting rows as CSV one at a time, so an export never holds
+/// the whole history. Rows must be pushed oldest first.
+///
+/// Only rows created in `[from_ts, to_ts]` are written, with the offchain
+/// balance after each one. Earlier rows only count towards the opening
+/// balance.
+pub struct AccountingCsvWriter<W: std::io::Write> {
+    out: W,
+    from_ts: i64,
+    to_ts: i64,
+    balance: i64,
+    written: u64,
+}
+
+impl<W: std::io::Write> AccountingCsvWriter<W> {
+    pub fn new(mut out: W, from_ts: i64, to_ts: i64) -> std::io::Result<Self> {
+        write!(out, "{}\r\n", ACCOUNTING_CSV_HEADER)?;
+        Ok(AccountingCsvWriter {
+            out,
+            from_ts,
+            to_ts,
+            balance: 0,
+            written: 0,
+        })
+    }
+
+    pub fn push(&mut self, row: &AccountingRow) -> std::io::Result<()> {
+        self.balance += row.effective_balance_sat;
+        if row.created_at < self.from_ts || row.created_at > self.to_ts {
+            return Ok(());
+        }
+        let (amount_in, amount_out) = row.amounts_in_out();
+        // There is no exchange rate source in this library, so the fiat
+        // column is always empty
+        write!(
+            self.out,
+            "{},{},{},{},{},{},{},{},{},\r\n",
+            format_utc_timestamp(row.created_at),
+            row.movement_id,
+            row.kind.as_str(),
+            csv_field(&row.status),
+            amount_in,
+            amount_out,
+            row.fee_sat,
+            csv_field(&row.counterparty),
+            self.balance
+        )?;
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Flushes the output and returns the number of rows written.
+    pub fn finish(mut self) -> std::io::Result<u64> {
+        self.out.flush()?;
+        Ok(self.written)
+    }
+}
+
+/// Writes the rows created in `[from_ts, to_ts]` as CSV, oldest first, see
+/// [`AccountingCsvWriter`]. Returns the number of rows written.
+pub fn write_accounting_csv<W: std::io::Write>(
+    out: W,
+    mut rows: Vec<AccountingRow>,
+    from_ts: i64,
+    to_ts: i64,
+) -> std::io::Result<u64> {
+    rows.sort_by_key(|row| (row.created_at, row.movement_id));
+    let mut csv = AccountingCsvWriter::new(out, from_ts, to_ts)?;
+    for row in &rows {
+        csv.push(row)?;
+    }
+    csv.finish()
+}
+
+/// A round we took part in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObservedRound {
+    pub funding_txid: String,
+    /// When the round completed, as unix seconds
+    pub at: i64,
+    /// How long we waited for the round to complete
+    pub wait_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NextRoundEta {
+    /// Seconds until the next round, `None` without a round to anchor on,
+    /// in which case it is up to one `round_interval_secs`
+    pub eta_secs: Option<u64>,
+    pub last_round: Option<ObservedRound>,
+    pub round_interval_secs: u64,
+}
+
+/// Seconds until the next round, assuming rounds keep happening every
+/// `interval_secs` after the one at `last_round_at`.
+pub fn next_round_eta(last_round_at: Option<i64>, interval_secs: u64, now: i64) -> Option<u64> {
+    let last_round_at = last_round_at?;
+    if interval_secs == 0 {
+        return Some(0);
+    }
+    let elapsed = (now - last_round_at).max(0) as u64;
+    Some((interval_secs - elapsed % interval_secs) % interval_secs)
+}
+
 pub fn round_status_to_ffi(status: RoundStatus) -> crate::cxx::ffi::RoundStatus {
     let is_final = status.is_final();
     let is_success = status.is_success();
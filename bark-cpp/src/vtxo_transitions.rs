@@ -0,0 +1,117 @@
+use std::fmt;
+
+use anyhow::Context;
+use bark::ark::VtxoId;
+use bark::persist::BarkPersister;
+use bark::persist::sqlite::SqliteClient;
+use bark::vtxo::{VtxoState, VtxoStateKind};
+use logger::log::warn;
+
+/// All VTXO states, for callers that may move a VTXO out of any of them.
+pub const ALL_VTXO_STATES: [VtxoStateKind; 3] = [
+    VtxoStateKind::Spendable,
+    VtxoStateKind::Locked,
+    VtxoStateKind::Spent,
+];
+
+pub fn vtxo_state_kind(state: &VtxoState) -> VtxoStateKind {
+    match state {
+        VtxoState::Spendable => VtxoStateKind::Spendable,
+        VtxoState::Locked { .. } => VtxoStateKind::Locked,
+        VtxoState::Spent => VtxoStateKind::Spent,
+    }
+}
+
+/// The name bark persists `kind` under in its `state_kind` column.
+pub fn vtxo_state_kind_name(kind: VtxoStateKind) -> &'static str {
+    match kind {
+        VtxoStateKind::Spendable => "Spendable",
+        VtxoStateKind::Locked => "Locked",
+        VtxoStateKind::Spent => "Spent",
+    }
+}
+
+/// The states a VTXO in state `from` may move to. Spent is final.
+pub fn allowed_vtxo_transitions(from: VtxoStateKind) -> &'static [VtxoStateKind] {
+    match from {
+        VtxoStateKind::Spendable => &[VtxoStateKind::Locked, VtxoStateKind::Spent],
+        VtxoStateKind::Locked => &[VtxoStateKind::Spendable, VtxoStateKind::Spent],
+        VtxoStateKind::Spent => &[],
+    }
+}
+
+/// A VTXO state change the transition table doesn't allow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IllegalVtxoTransition {
+    pub vtxo_id: String,
+    pub from: VtxoStateKind,
+    pub to: VtxoStateKind,
+}
+
+impl fmt::Display for IllegalVtxoTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Illegal transition of vtxo {} from {:?} to {:?}",
+            self.vtxo_id, self.from, self.to
+        )
+    }
+}
+
+impl std::error::Error for IllegalVtxoTransition {}
+
+pub fn check_vtxo_transition(
+    vtxo_id: &str,
+    from: VtxoStateKind,
+    to: VtxoStateKind,
+) -> Result<(), IllegalVtxoTransition> {
+    if !allowed_vtxo_transitions(from).contains(&to) {
+        return Err(IllegalVtxoTransition {
+            vtxo_id: vtxo_id.to_string(),
+            from,
+            to,
+        });
+    }
+    Ok(())
+}
+
+/// Moves the VTXO to `state` if the transition table allows it from the
+/// state it is in.
+///
+/// Only the current state is passed on as allowed old state, so the update
+/// fails if the VTXO changed state in between.
+pub async fn transition_vtxo_state(
+    db: &SqliteClient,
+    vtxo_id: VtxoId,
+    state: VtxoState,
+) -> anyhow::Result<()> {
+    let current = db
+        .get_wallet_vtxo(vtxo_id)
+        .await?
+        .with_context(|| format!("Unknown vtxo {}", vtxo_id))?;
+    let from = vtxo_state_kind(&current.state);
+    if let Err(err) = check_vtxo_transition(&vtxo_id.to_string(), from, vtxo_state_kind(&state)) {
+        warn!("{}", err);
+        return Err(err.into());
+    }
+    db.update_vtxo_state_checked(vtxo_id, state, &[from])
+        .await?;
+    Ok(())
+}
+
+/// Sets the VTXO's state whatever state it is in, bypassing the transition
+/// table. For repairing a wallet database only.
+pub async fn force_vtxo_state_unchecked(
+    db: &SqliteClient,
+    vtxo_id: VtxoId,
+    state: VtxoState,
+) -> anyhow::Result<()> {
+    warn!(
+        "Forcing vtxo {} into state {:?}",
+        vtxo_id,
+        vtxo_state_kind(&state)
+    );
+    db.update_vtxo_state_checked(vtxo_id, state, &ALL_VTXO_STATES)
+        .await?;
+    Ok(())
+}
@@ -1,42 +1,225 @@
 // Re-export the log crate for consumers of this library
 pub extern crate log;
 
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
 #[cfg(target_os = "android")]
-use android_logger::Config;
-use log::LevelFilter;
+use android_logger::{AndroidLogger, Config};
+use log::{Level, LevelFilter, Log, Metadata, Record};
 #[cfg(target_os = "ios")]
 use oslog::OsLogger;
 
-pub struct Logger {}
+/// Where and how much to log.
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    pub level: LevelFilter,
+    /// Also write to this file, next to the platform sink
+    pub file_path: Option<PathBuf>,
+    /// The file is rotated once it grows past this size
+    pub max_file_size_bytes: u64,
+    /// Number of files kept, counting the one being written
+    pub max_files: usize,
+}
 
-impl Logger {
-    pub fn new(level: LevelFilter) -> Self {
-        #[cfg(target_os = "android")]
-        {
-            android_logger::init_once(Config::default().with_max_level(level).with_tag("NitroArk"));
-            log::info!("Android logger initialized.");
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: LevelFilter::Debug,
+            file_path: None,
+            max_file_size_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+/// A buffered log file that is rotated by size.
+///
+/// When `path` grows past the maximum size it is renamed to `path.1`, the
+/// previous `path.1` to `path.2` and so on, dropping the oldest file once
+/// `max_files` exist.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    size: u64,
+    writer: BufWriter<File>,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, max_size: u64, max_files: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
         }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            max_size,
+            max_files: max_files.max(1),
+            size: file.metadata()?.len(),
+            writer: BufWriter::new(file),
+        })
+    }
 
-        #[cfg(target_os = "ios")]
-        {
-            OsLogger::new("com.nitro.ark")
-                .level_filter(level)
-                .init()
-                .unwrap();
-            log::info!("oslog initialized.");
+    /// The path of the `index`th rotated file, `0` being the live one.
+    pub fn rotated_path(path: &Path, index: usize) -> PathBuf {
+        match index {
+            0 => path.to_path_buf(),
+            _ => {
+                let mut name = path.as_os_str().to_owned();
+                name.push(format!(".{}", index));
+                PathBuf::from(name)
+            }
         }
+    }
 
-        #[cfg(not(any(target_os = "android", target_os = "ios")))]
-        {
-            env_logger::builder()
-                .filter_level(level)
-                .parse_default_env()
-                .init();
-            log::info!("Standard (env_logger) initialized.");
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 + 1 > self.max_size {
+            self.rotate()?;
         }
+        writeln!(self.writer, "{}", line)?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
 
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        let oldest = Self::rotated_path(&self.path, self.max_files - 1);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for index in (0..self.max_files - 1).rev() {
+            let from = Self::rotated_path(&self.path, index);
+            if from.exists() {
+                fs::rename(&from, Self::rotated_path(&self.path, index + 1))?;
+            }
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.writer = BufWriter::new(file);
+        self.size = 0;
+        Ok(())
+    }
+}
+
+/// Sends records to the platform sink and, if configured, a log file.
+struct TeeLogger {
+    platform: Box<dyn Log>,
+    file: Option<Mutex<RotatingFile>>,
+}
+
+impl Log for TeeLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        self.platform.log(record);
+        if let Some(file) = &self.file {
+            let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+            let line = format!(
+                "{} {:<5} {}: {}",
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |d| d.as_millis()),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+            let _ = file.write_line(&line);
+            // Errors often precede a crash, don't leave them in the buffer
+            if record.level() == Level::Error {
+                let _ = file.flush();
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.platform.flush();
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap_or_else(|e| e.into_inner()).flush();
+        }
+    }
+}
+
+/// The platform sink and the level it logs at.
+fn platform_logger(level: LevelFilter) -> (Box<dyn Log>, LevelFilter) {
+    #[cfg(target_os = "android")]
+    {
+        let logger =
+            AndroidLogger::new(Config::default().with_max_level(level).with_tag("NitroArk"));
+        (Box::new(logger), level)
+    }
+
+    #[cfg(target_os = "ios")]
+    {
+        let logger = OsLogger::new("com.nitro.ark").level_filter(level);
+        (Box::new(logger), level)
+    }
+
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    {
+        // RUST_LOG may ask for more than `level`
+        let logger = env_logger::builder()
+            .filter_level(level)
+            .parse_default_env()
+            .build();
+        let filter = logger.filter();
+        (Box::new(logger), filter)
+    }
+}
+
+pub struct Logger {}
+
+impl Logger {
+    pub fn new(level: LevelFilter) -> Self {
+        // Without a file this only fails if a logger is already installed
+        let _ = Logger::with_config(LogConfig {
+            level,
+            ..LogConfig::default()
+        });
         Logger {}
     }
+
+    /// Installs the global logger. Fails if the log file can't be opened
+    /// or a logger is already installed.
+    pub fn with_config(config: LogConfig) -> io::Result<Self> {
+        let file = match &config.file_path {
+            Some(path) => Some(Mutex::new(RotatingFile::open(
+                path,
+                config.max_file_size_bytes,
+                config.max_files,
+            )?)),
+            None => None,
+        };
+        let (platform, max_level) = platform_logger(config.level);
+        let logger = TeeLogger { platform, file };
+        log::set_logger(Box::leak(Box::new(logger)))
+            .map_err(|e| io::Error::new(io::ErrorKind::AlreadyExists, e.to_string()))?;
+        log::set_max_level(max_level);
+
+        match &config.file_path {
+            Some(path) => log::info!("Logger initialized, also writing to {}.", path.display()),
+            None => log::info!("Logger initialized."),
+        }
+        Ok(Logger {})
+    }
+
+    /// Writes out buffered log lines.
+    pub fn flush() {
+        log::logger().flush();
+    }
 }
 
 impl Default for Logger {
@@ -44,3 +227,54 @@ impl Default for Logger {
         Logger::new(LevelFilter::Debug)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_log_file_rotation() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("logs").join("bark.log");
+        let line = "x".repeat(99);
+
+        // 100 bytes per line, 10 lines per file, 25 lines overflow twice
+        let mut file = RotatingFile::open(&path, 1_000, 3).unwrap();
+        for _ in 0..25 {
+            file.write_line(&line).unwrap();
+        }
+        file.flush().unwrap();
+
+        let size = |index| {
+            fs::metadata(RotatingFile::rotated_path(&path, index))
+                .unwrap()
+                .len()
+        };
+        assert_eq!(size(0), 500);
+        assert_eq!(size(1), 1_000);
+        assert_eq!(size(2), 1_000);
+        assert!(!RotatingFile::rotated_path(&path, 3).exists());
+
+        // The oldest file is dropped once max_files exist
+        for _ in 0..10 {
+            file.write_line(&line).unwrap();
+        }
+        file.flush().unwrap();
+        assert_eq!(size(0), 500);
+        assert_eq!(size(1), 1_000);
+        assert_eq!(size(2), 1_000);
+        assert!(!RotatingFile::rotated_path(&path, 3).exists());
+
+        // Reopening appends to the live file
+        drop(file);
+        let mut file = RotatingFile::open(&path, 1_000, 3).unwrap();
+        file.write_line(&line).unwrap();
+        file.flush().unwrap();
+        assert_eq!(size(0), 600);
+        assert_eq!(
+            RotatingFile::rotated_path(&path, 2),
+            dir.path().join("logs").join("bark.log.2")
+        );
+    }
+}
@@ -38,6 +38,8 @@ private:
   // Helper function to create ConfigOpts from BarkConfigOpts
   static bark_cxx::ConfigOpts createConfigOpts(const std::optional<BarkConfigOpts>& config) {
     bark_cxx::ConfigOpts config_opts;
+    // 0 selects the default Ark purpose index
+    config_opts.ark_purpose_index = 0;
     if (config.has_value()) {
       config_opts.ark = config->ark.value_or("");
       config_opts.esplora = config->esplora.value_or("");
@@ -58,7 +60,11 @@ private:
 public:
   NitroArk() : HybridObject(TAG) {
     // Initialize the Rust logger once when a NitroArk object is created.
-    bark_cxx::init_logger();
+    try {
+      bark_cxx::init_logger();
+    } catch (const rust::Error&) {
+      // Wallet calls still work without logs, so don't fail construction
+    }
   }
 
   // --- Management ---
@@ -147,7 +153,13 @@ public:
   }
 
   std::shared_ptr<Promise<bool>> isWalletLoaded() override {
-    return Promise<bool>::async([]() { return bark_cxx::is_wallet_loaded(); });
+    return Promise<bool>::async([]() {
+      try {
+        return bark_cxx::is_wallet_loaded();
+      } catch (const rust::Error& e) {
+        throw std::runtime_error(e.what());
+      }
+    });
   }
 
   std::shared_ptr<Promise<void>> syncPendingBoards() override {
@@ -651,16 +663,15 @@ public:
   // --- Lightning Operations ---
 
   std::shared_ptr<Promise<LightningSendResult>> payLightningInvoice(const std::string& destination,
-                                                                    std::optional<double> amountSat) override {
-    return Promise<LightningSendResult>::async([destination, amountSat]() {
+                                                                    std::optional<double> amountSat,
+                                                                    std::optional<double> maxFeeSat) override {
+    return Promise<LightningSendResult>::async([destination, amountSat, maxFeeSat]() {
       try {
-        bark_cxx::LightningSend rust_result;
-        if (amountSat.has_value()) {
-          uint64_t amountSat_val = static_cast<uint64_t>(amountSat.value());
-          rust_result = bark_cxx::pay_lightning_invoice(destination, &amountSat_val);
-        } else {
-          rust_result = bark_cxx::pay_lightning_invoice(destination, nullptr);
-        }
+        uint64_t amountSat_val = amountSat.has_value() ? static_cast<uint64_t>(amountSat.value()) : 0;
+        uint64_t maxFeeSat_val = maxFeeSat.has_value() ? static_cast<uint64_t>(maxFeeSat.value()) : 0;
+        bark_cxx::LightningSend rust_result =
+            bark_cxx::pay_lightning_invoice(destination, amountSat.has_value() ? &amountSat_val : nullptr,
+                                            maxFeeSat.has_value() ? &maxFeeSat_val : nullptr);
 
         LightningSendResult result;
         result.invoice = std::string(rust_result.invoice.data(), rust_result.invoice.length());
@@ -711,11 +722,13 @@ public:
   }
 
   std::shared_ptr<Promise<LightningSendResult>> payLightningAddress(const std::string& addr, double amountSat,
-                                                                    const std::string& comment) override {
-    return Promise<LightningSendResult>::async([addr, amountSat, comment]() {
+                                                                    const std::string& comment,
+                                                                    std::optional<double> maxFeeSat) override {
+    return Promise<LightningSendResult>::async([addr, amountSat, comment, maxFeeSat]() {
       try {
-        bark_cxx::LightningSend rust_result =
-            bark_cxx::pay_lightning_address(addr, static_cast<uint64_t>(amountSat), comment);
+        uint64_t maxFeeSat_val = maxFeeSat.has_value() ? static_cast<uint64_t>(maxFeeSat.value()) : 0;
+        bark_cxx::LightningSend rust_result = bark_cxx::pay_lightning_address(
+            addr, static_cast<uint64_t>(amountSat), comment, maxFeeSat.has_value() ? &maxFeeSat_val : nullptr);
 
         LightningSendResult result;
         result.invoice = std::string(rust_result.invoice.data(), rust_result.invoice.length());
@@ -735,10 +748,10 @@ public:
     });
   }
 
-  std::shared_ptr<Promise<Bolt11Invoice>> bolt11Invoice(double amountMsat) override {
-    return Promise<Bolt11Invoice>::async([amountMsat]() {
+  std::shared_ptr<Promise<Bolt11Invoice>> bolt11Invoice(double amountSat) override {
+    return Promise<Bolt11Invoice>::async([amountSat]() {
       try {
-        bark_cxx::Bolt11Invoice invoice_rs = bark_cxx::bolt11_invoice(static_cast<uint64_t>(amountMsat));
+        bark_cxx::Bolt11Invoice invoice_rs = bark_cxx::bolt11_invoice(static_cast<uint64_t>(amountSat));
         return Bolt11Invoice(std::string(invoice_rs.bolt11_invoice.data(), invoice_rs.bolt11_invoice.length()),
                              std::string(invoice_rs.payment_secret.data(), invoice_rs.payment_secret.length()),
                              std::string(invoice_rs.payment_hash.data(), invoice_rs.payment_hash.length()));
@@ -851,11 +864,11 @@ public:
   std::shared_ptr<Promise<BoardResult>> boardAmount(double amountSat) override {
     return Promise<BoardResult>::async([amountSat]() {
       try {
-        bark_cxx::BoardResult result_rs = bark_cxx::board_amount(static_cast<uint64_t>(amountSat));
+        bark_cxx::CxxBoardResult result_rs = bark_cxx::board_amount(static_cast<uint64_t>(amountSat));
         BoardResult result;
-        result.funding_txid = std::string(result_rs.funding_txid.data(), result_rs.funding_txid.length());
+        result.funding_txid = std::string(result_rs.txid.data(), result_rs.txid.length());
         std::vector<std::string> vtxos_vec;
-        for (const auto& vtxo : result_rs.vtxos) {
+        for (const auto& vtxo : result_rs.vtxo_ids) {
           vtxos_vec.push_back(std::string(vtxo.data(), vtxo.length()));
         }
         result.vtxos = vtxos_vec;
@@ -869,11 +882,11 @@ public:
   std::shared_ptr<Promise<BoardResult>> boardAll() override {
     return Promise<BoardResult>::async([]() {
       try {
-        bark_cxx::BoardResult result_rs = bark_cxx::board_all();
+        bark_cxx::CxxBoardResult result_rs = bark_cxx::board_all();
         BoardResult result;
-        result.funding_txid = std::string(result_rs.funding_txid.data(), result_rs.funding_txid.length());
+        result.funding_txid = std::string(result_rs.txid.data(), result_rs.txid.length());
         std::vector<std::string> vtxos_vec;
-        for (const auto& vtxo : result_rs.vtxos) {
+        for (const auto& vtxo : result_rs.vtxo_ids) {
           vtxos_vec.push_back(std::string(vtxo.data(), vtxo.length()));
         }
         result.vtxos = vtxos_vec;
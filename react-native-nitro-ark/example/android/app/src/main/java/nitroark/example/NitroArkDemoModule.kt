@@ -135,9 +135,9 @@ class NitroArkDemoModule(reactContext: ReactApplicationContext) :
   }
 
   @ReactMethod
-  fun bolt11Invoice(amountMsat: Double, promise: Promise) {
+  fun bolt11Invoice(amountSat: Double, promise: Promise) {
     try {
-      val result = NitroArkNative.bolt11Invoice(amountMsat.toLong())
+      val result = NitroArkNative.bolt11Invoice(amountSat.toLong())
       promise.resolve(bolt11InvoiceToMap(result))
     } catch (e: Exception) {
       promise.reject("ERR_BOLT11_INVOICE_JNI", e)
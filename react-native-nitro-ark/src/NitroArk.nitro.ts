@@ -237,7 +237,8 @@ export interface NitroArk extends HybridObject<{ ios: 'c++'; android: 'c++' }> {
   ): Promise<ArkoorPaymentResult>;
   payLightningInvoice(
     destination: string,
-    amountSat?: number
+    amountSat?: number,
+    maxFeeSat?: number
   ): Promise<LightningSendResult>;
   payLightningOffer(
     offer: string,
@@ -246,12 +247,13 @@ export interface NitroArk extends HybridObject<{ ios: 'c++'; android: 'c++' }> {
   payLightningAddress(
     addr: string,
     amountSat: number,
-    comment: string
+    comment: string,
+    maxFeeSat?: number
   ): Promise<LightningSendResult>;
   sendOnchain(destination: string, amountSat: number): Promise<string>;
 
   // --- Lightning Invoicing ---
-  bolt11Invoice(amountMsat: number): Promise<Bolt11Invoice>;
+  bolt11Invoice(amountSat: number): Promise<Bolt11Invoice>;
   lightningReceiveStatus(
     paymentHash: string
   ): Promise<LightningReceive | undefined>;
@@ -433,11 +433,11 @@ export function onchainSendMany(
 
 /**
  * Creates a Bolt 11 invoice.
- * @param amountMsat The amount in millisatoshis for the invoice.
+ * @param amountSat The amount in satoshis for the invoice.
  * @returns A promise resolving to Bolt11Invoice object.
  */
-export function bolt11Invoice(amountMsat: number): Promise<Bolt11Invoice> {
-  return NitroArkHybridObject.bolt11Invoice(amountMsat);
+export function bolt11Invoice(amountSat: number): Promise<Bolt11Invoice> {
+  return NitroArkHybridObject.bolt11Invoice(amountSat);
 }
 
 /**
@@ -496,13 +496,19 @@ export function tryClaimAllLightningReceives(wait: boolean): Promise<void> {
  * Pays a Bolt11 Lightning invoice.
  * @param destination The Lightning invoice.
  * @param amountSat The amount in satoshis to send. Use 0 for invoice amount.
+ * @param maxFeeSat The most the payment may cost in fees. Defaults to the stored lightning fee cap.
  * @returns A promise resolving to a LightningSendResult object
  */
 export function payLightningInvoice(
   destination: string,
-  amountSat?: number
+  amountSat?: number,
+  maxFeeSat?: number
 ): Promise<LightningSendResult> {
-  return NitroArkHybridObject.payLightningInvoice(destination, amountSat);
+  return NitroArkHybridObject.payLightningInvoice(
+    destination,
+    amountSat,
+    maxFeeSat
+  );
 }
 
 /**
@@ -523,14 +529,21 @@ export function payLightningOffer(
  * @param addr The Lightning Address.
  * @param amountSat The amount in satoshis to send.
  * @param comment An optional comment.
+ * @param maxFeeSat The most the payment may cost in fees. Defaults to the stored lightning fee cap.
  * @returns A promise resolving to a LightningSendResult object
  */
 export function payLightningAddress(
   addr: string,
   amountSat: number,
-  comment: string
+  comment: string,
+  maxFeeSat?: number
 ): Promise<LightningSendResult> {
-  return NitroArkHybridObject.payLightningAddress(addr, amountSat, comment);
+  return NitroArkHybridObject.payLightningAddress(
+    addr,
+    amountSat,
+    comment,
+    maxFeeSat
+  );
 }
 
 // --- Ark Operations ---